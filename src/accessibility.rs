@@ -0,0 +1,249 @@
+//! Accessibility tree for assistive technologies.
+
+use std::ops::Range;
+
+use accesskit::{
+    Action, ActionData, ActionHandler, ActionRequest, ActivationHandler, DeactivationHandler, Node,
+    NodeId, Role, TextPosition, TextSelection, Tree, TreeUpdate,
+};
+use accesskit_unix::Adapter;
+use calloop::LoopHandle;
+use calloop::channel::{self, Event, Sender};
+use tracing::error;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::State;
+use crate::header::HeaderAction;
+
+/// Node ID of the window root.
+const WINDOW_ID: NodeId = NodeId(0);
+
+/// Node ID of the header's title button.
+const TITLE_ID: NodeId = NodeId(1);
+
+/// Node ID of the header's new note button.
+const NEW_NOTE_ID: NodeId = NodeId(2);
+
+/// Node ID of the header's overflow menu button.
+const OVERFLOW_ID: NodeId = NodeId(3);
+
+/// Node ID of the note's text input.
+const TEXT_ID: NodeId = NodeId(4);
+
+/// Node ID of the first text run, followed by one run for every line.
+const FIRST_RUN_ID: u64 = 5;
+
+/// Accessibility tree adapter.
+pub struct Accessibility {
+    adapter: Adapter,
+    content: Option<Content>,
+}
+
+impl Accessibility {
+    pub fn new(event_loop: &LoopHandle<'static, State>) -> Self {
+        // Forward adapter requests to the event loop, which owns all UI state.
+        let (tx, rx) = channel::channel();
+        let _ = event_loop
+            .insert_source(rx, |event, _, state| match event {
+                Event::Msg(Request::Activate) => state.window.refresh_accessibility(),
+                Event::Msg(Request::Action(request)) => {
+                    state.window.accessibility_action(&state.config, request)
+                },
+                Event::Closed => (),
+            })
+            .inspect_err(|err| error!("Failed to insert accessibility source: {err}"));
+
+        let adapter = Adapter::new(RequestHandler(tx.clone()), RequestHandler(tx), NoopHandler);
+
+        Self { adapter, content: None }
+    }
+
+    /// Update the window's accessibility tree.
+    ///
+    /// This is a noop unless an assistive technology is connected, or if the
+    /// content did not change since the last update.
+    pub fn update(&mut self, text: &str, selection: Range<usize>, header: Option<&str>) {
+        let unchanged = self.content.as_ref().is_some_and(|content| {
+            content.text == text
+                && content.selection == selection
+                && content.header.as_deref() == header
+        });
+        if unchanged {
+            return;
+        }
+
+        let content = Content {
+            header: header.map(str::to_owned),
+            text: text.into(),
+            runs: text_runs(text),
+            selection,
+        };
+        self.adapter.update_if_active(|| content.tree());
+        self.content = Some(content);
+    }
+
+    /// Force the next update to submit the entire tree.
+    pub fn invalidate(&mut self) {
+        self.content = None;
+    }
+
+    /// Update the window's keyboard focus state.
+    pub fn set_focused(&mut self, focused: bool) {
+        self.adapter.update_window_focus_state(focused);
+    }
+
+    /// Get the action requested by an assistive technology.
+    pub fn action(&self, request: ActionRequest) -> Option<AccessibleAction> {
+        let action = match (request.action, request.target, request.data) {
+            (Action::Click, TITLE_ID, _) => HeaderAction::EditTitle,
+            (Action::Click, NEW_NOTE_ID, _) => HeaderAction::NewNote,
+            (Action::Click, OVERFLOW_ID, _) => HeaderAction::Overflow,
+            (Action::SetTextSelection, _, Some(ActionData::SetTextSelection(selection))) => {
+                let content = self.content.as_ref()?;
+                let anchor = content.offset(selection.anchor)?;
+                let focus = content.offset(selection.focus)?;
+                return Some(AccessibleAction::Select(anchor.min(focus)..anchor.max(focus)));
+            },
+            _ => return None,
+        };
+        Some(AccessibleAction::Header(action))
+    }
+}
+
+/// Action requested by an assistive technology.
+pub enum AccessibleAction {
+    Header(HeaderAction),
+    Select(Range<usize>),
+}
+
+/// Content exposed in the accessibility tree.
+struct Content {
+    header: Option<String>,
+    text: String,
+    runs: Vec<Range<usize>>,
+    selection: Range<usize>,
+}
+
+impl Content {
+    /// Build the entire accessibility tree.
+    fn tree(&self) -> TreeUpdate {
+        let mut nodes = Vec::new();
+        let mut window = Node::new(Role::Window);
+
+        if let Some(title) = &self.header {
+            let buttons = [
+                (TITLE_ID, title.as_str(), Some("Rename note")),
+                (NEW_NOTE_ID, "New note", None),
+                (OVERFLOW_ID, "More options", None),
+            ];
+            for (id, label, description) in buttons {
+                let mut button = Node::new(Role::Button);
+                button.add_action(Action::Click);
+                button.set_label(label);
+                if let Some(description) = description {
+                    button.set_description(description);
+                }
+                window.push_child(id);
+                nodes.push((id, button));
+            }
+        }
+
+        let mut text_input = Node::new(Role::MultilineTextInput);
+        text_input.add_action(Action::Focus);
+        text_input.add_action(Action::SetTextSelection);
+        text_input.set_label("Note");
+        text_input.set_value(self.text.as_str());
+        text_input.set_text_selection(TextSelection {
+            anchor: self.position(self.selection.start),
+            focus: self.position(self.selection.end),
+        });
+
+        for (i, run) in self.runs.iter().enumerate() {
+            let line = &self.text[run.clone()];
+            let lengths = line.graphemes(true).map(|grapheme| grapheme.len().min(255) as u8);
+
+            let mut text_run = Node::new(Role::TextRun);
+            text_run.set_value(line);
+            text_run.set_character_lengths(lengths.collect::<Vec<_>>());
+
+            let id = NodeId(FIRST_RUN_ID + i as u64);
+            text_input.push_child(id);
+            nodes.push((id, text_run));
+        }
+
+        window.push_child(TEXT_ID);
+        nodes.push((TEXT_ID, text_input));
+        nodes.push((WINDOW_ID, window));
+
+        TreeUpdate { nodes, tree: Some(Tree::new(WINDOW_ID)), focus: TEXT_ID }
+    }
+
+    /// Convert a byte offset to a position within a text run.
+    fn position(&self, offset: usize) -> TextPosition {
+        let index = self.runs.iter().position(|run| offset < run.end);
+        let index = index.unwrap_or(self.runs.len() - 1);
+        let run = &self.runs[index];
+
+        let character_index = self.text[run.start..offset.max(run.start)].graphemes(true).count();
+        TextPosition { node: NodeId(FIRST_RUN_ID + index as u64), character_index }
+    }
+
+    /// Convert a position within a text run to a byte offset.
+    fn offset(&self, position: TextPosition) -> Option<usize> {
+        let index = position.node.0.checked_sub(FIRST_RUN_ID)?;
+        let run = self.runs.get(index as usize)?;
+
+        let mut graphemes = self.text[run.clone()].grapheme_indices(true);
+        let offset = graphemes.nth(position.character_index).map(|(i, _)| run.start + i);
+        Some(offset.unwrap_or(run.end))
+    }
+}
+
+/// Split text into one run per line, including its trailing newline.
+///
+/// An empty run is added after trailing newlines, to allow placing the cursor
+/// on the empty last line.
+fn text_runs(text: &str) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        runs.push(start..start + line.len());
+        start += line.len();
+    }
+
+    if text.is_empty() || text.ends_with('\n') {
+        runs.push(text.len()..text.len());
+    }
+
+    runs
+}
+
+/// Adapter request forwarded to the event loop.
+enum Request {
+    Activate,
+    Action(ActionRequest),
+}
+
+/// Handler forwarding adapter requests to the event loop.
+struct RequestHandler(Sender<Request>);
+
+impl ActivationHandler for RequestHandler {
+    fn request_initial_tree(&mut self) -> Option<TreeUpdate> {
+        // The tree is submitted from the event loop once it handled the request.
+        let _ = self.0.send(Request::Activate);
+        None
+    }
+}
+
+impl ActionHandler for RequestHandler {
+    fn do_action(&mut self, request: ActionRequest) {
+        let _ = self.0.send(Request::Action(request));
+    }
+}
+
+/// Handler ignoring accessibility deactivation.
+struct NoopHandler;
+
+impl DeactivationHandler for NoopHandler {
+    fn deactivate_accessibility(&mut self) {}
+}
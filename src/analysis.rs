@@ -0,0 +1,344 @@
+//! Incremental analysis of the text's structure.
+//!
+//! The text is analyzed separately for every independently shaped layout
+//! block, so edits only require analyzing the blocks they touch again. Blocks
+//! end after the newline terminating an empty line, so every list item starts
+//! a new block.
+
+use std::fmt::{self, Display, Formatter};
+use std::ops::{Add, Range};
+use std::path::PathBuf;
+
+use crate::editor::{self, ColorSpan, ListItem, TextChange};
+use crate::spellcheck::Spellcheck;
+use crate::text::Text;
+use crate::thumbnail;
+
+/// Structure of a text, cached for every layout block.
+#[derive(Default)]
+pub struct TextAnalysis {
+    blocks: Vec<Block>,
+    revision: Option<u64>,
+}
+
+impl TextAnalysis {
+    /// Update the analysis for the current content of `text`.
+    ///
+    /// Only blocks changed since the last update are analyzed again, unless
+    /// the analysis was invalidated.
+    ///
+    /// Returns `false` if the text is unchanged since the last update.
+    pub fn update(&mut self, text: &Text, spellcheck: Option<&Spellcheck>) -> bool {
+        let revision = self.revision.replace(text.revision());
+        if revision == Some(text.revision()) {
+            return false;
+        }
+
+        // Combine all edits since the last update into a single change.
+        let change = revision.and_then(|revision| text.edits_since(revision)).and_then(|edits| {
+            edits.map(TextChange::from).reduce(|change, next| change.then(&next))
+        });
+
+        match change {
+            Some(change) if !self.blocks.is_empty() => self.update_blocks(text, spellcheck, change),
+            _ => self.blocks = analyze_range(text, spellcheck, 0..text.len()),
+        }
+
+        true
+    }
+
+    /// Analyze the entire text again on the next update.
+    ///
+    /// This is necessary after changes affecting the analysis of unchanged
+    /// text, like updates to the spellchecking dictionary.
+    pub fn invalidate(&mut self) {
+        self.revision = None;
+    }
+
+    /// Get the byte ranges of all layout blocks.
+    pub fn text_blocks(&self) -> Vec<Range<usize>> {
+        self.blocks.iter().map(|block| block.range.clone()).collect()
+    }
+
+    /// Get all list items.
+    pub fn list_items(&self) -> Vec<ListItem> {
+        self.collect(|block| &block.items, |item, offset| item.offset(offset))
+    }
+
+    /// Get the byte ranges of all completed list items, with their content.
+    pub fn completed_items(&self) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+        self.collect(
+            |block| &block.completed,
+            |(item, content), offset| (shift(item, offset), shift(content, offset)),
+        )
+        .into_iter()
+        .unzip()
+    }
+
+    /// Get all color spans.
+    pub fn color_spans(&self) -> Vec<ColorSpan> {
+        self.collect(
+            |block| &block.color_spans,
+            |span, offset| ColorSpan {
+                color: span.color.clone(),
+                start_marker: shift(&span.start_marker, offset),
+                end_marker: shift(&span.end_marker, offset),
+            },
+        )
+    }
+
+    /// Get the byte ranges of all tags, with their normalized names.
+    pub fn tags(&self) -> Vec<(Range<usize>, String)> {
+        self.collect(
+            |block| &block.tags,
+            |(range, name), offset| (shift(range, offset), name.clone()),
+        )
+    }
+
+    /// Get the ranges of all code fences, see [`editor::code_fences`].
+    pub fn code_fences(&self) -> Vec<Range<usize>> {
+        self.collect(|block| &block.code_fences, shift)
+    }
+
+    /// Get all lines referencing an image, see [`thumbnail::image_lines`].
+    pub fn image_lines(&self) -> Vec<(usize, PathBuf)> {
+        self.collect(
+            |block| &block.image_lines,
+            |(newline, path), offset| (newline + offset, path.clone()),
+        )
+    }
+
+    /// Get the byte ranges of all misspelled words.
+    pub fn misspellings(&self) -> Vec<Range<usize>> {
+        self.collect(|block| &block.misspellings, shift)
+    }
+
+    /// Get statistics of the entire text.
+    pub fn stats(&self) -> TextStats {
+        self.blocks.iter().fold(TextStats::default(), |stats, block| stats + block.stats)
+    }
+
+    /// Analyze the blocks touched by a change again.
+    fn update_blocks(&mut self, text: &Text, spellcheck: Option<&Spellcheck>, change: TextChange) {
+        // Include adjacent blocks, since edits at their edges can merge blocks.
+        let first = self.blocks.iter().position(|block| block.range.end >= change.start);
+        let last = self.blocks.iter().rposition(|block| block.range.start <= change.old_end);
+        let (Some(first), Some(mut last)) = (first, last) else {
+            self.blocks = analyze_range(text, spellcheck, 0..text.len());
+            return;
+        };
+
+        // Blocks ending after the change are only moved by it.
+        let new_offset = |offset: usize| offset - change.old_end + change.new_end;
+
+        // Extend the range until it ends at a block boundary of the new text,
+        // including the empty last block at the end of the text.
+        let start = self.blocks[first].range.start;
+        let mut end = new_offset(self.blocks[last].range.end);
+        while last + 1 < self.blocks.len() && (end == text.len() || !is_block_end(text, end)) {
+            last += 1;
+            end = new_offset(self.blocks[last].range.end);
+        }
+
+        for block in &mut self.blocks[last + 1..] {
+            block.range = new_offset(block.range.start)..new_offset(block.range.end);
+        }
+
+        let blocks = analyze_range(text, spellcheck, start..end);
+        self.blocks.splice(first..=last, blocks);
+    }
+
+    /// Collect a property of all blocks, moving it from block-relative offsets
+    /// to offsets within the text.
+    fn collect<'a, T: 'a, U>(
+        &'a self,
+        property: impl Fn(&'a Block) -> &'a [T],
+        offset: impl Fn(&T, usize) -> U,
+    ) -> Vec<U> {
+        self.blocks
+            .iter()
+            .flat_map(|block| property(block).iter().map(|value| offset(value, block.range.start)))
+            .collect()
+    }
+}
+
+/// Structure of a single layout block.
+///
+/// All offsets are relative to the start of the block.
+struct Block {
+    range: Range<usize>,
+    items: Vec<ListItem>,
+    completed: Vec<(Range<usize>, Range<usize>)>,
+    color_spans: Vec<ColorSpan>,
+    tags: Vec<(Range<usize>, String)>,
+    code_fences: Vec<Range<usize>>,
+    image_lines: Vec<(usize, PathBuf)>,
+    misspellings: Vec<Range<usize>>,
+    stats: TextStats,
+}
+
+impl Block {
+    fn new(text: &Text, spellcheck: Option<&Spellcheck>, range: Range<usize>) -> Self {
+        let block = text.slice(range.clone());
+
+        let items = ListItem::find_all(&block);
+        let completed = items
+            .iter()
+            .map(|item| (item.range.clone(), editor::item_content(&block, item.range.clone())))
+            .filter(|(_, content)| editor::is_completed(&block[content.clone()]))
+            .collect();
+        let tags = editor::find_tags(&block)
+            .map(|(range, name)| (range, editor::normalize_tag(name)))
+            .collect();
+
+        Self {
+            stats: TextStats::with_items(&block, items.len()),
+            misspellings: spellcheck
+                .map(|spellcheck| spellcheck.misspellings(&block))
+                .unwrap_or_default(),
+            color_spans: editor::color_spans(&block),
+            code_fences: editor::code_fences(&block),
+            image_lines: thumbnail::image_lines(&block),
+            completed,
+            range,
+            items,
+            tags,
+        }
+    }
+}
+
+/// Text content statistics.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Debug)]
+pub struct TextStats {
+    pub chars: usize,
+    pub words: usize,
+    pub lines: usize,
+    pub items: usize,
+}
+
+impl TextStats {
+    /// Count the content of `text`.
+    pub fn new(text: &str) -> Self {
+        Self::with_items(text, editor::list_items(text).len())
+    }
+
+    /// Count the content of `text`, with an already known number of list items.
+    fn with_items(text: &str, items: usize) -> Self {
+        Self {
+            chars: text.chars().count(),
+            words: text.split_whitespace().count(),
+            lines: text.lines().count(),
+            items,
+        }
+    }
+}
+
+/// Statistics of adjacent blocks can be combined, since words and lines never
+/// span multiple blocks.
+impl Add for TextStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            chars: self.chars + other.chars,
+            words: self.words + other.words,
+            lines: self.lines + other.lines,
+            items: self.items + other.items,
+        }
+    }
+}
+
+impl Display for TextStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let Self { chars, words, lines, items } = self;
+        write!(f, "{chars} chars · {words} words · {lines} lines · {items} items")
+    }
+}
+
+/// Analyze all blocks within a range of `text`.
+///
+/// The range must start and end at block boundaries.
+fn analyze_range(text: &Text, spellcheck: Option<&Spellcheck>, range: Range<usize>) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut start = range.start;
+    let mut offset = range.start;
+    let mut previous = range.start.checked_sub(1).and_then(|offset| text.byte(offset));
+    for chunk in text.chunks_in(range.clone()) {
+        for byte in chunk.bytes() {
+            offset += 1;
+            if byte == b'\n' && previous == Some(b'\n') {
+                blocks.push(Block::new(text, spellcheck, start..offset));
+                start = offset;
+            }
+            previous = Some(byte);
+        }
+    }
+
+    // The last block of the text is always present, even if it is empty.
+    if start < range.end || range.end == text.len() {
+        blocks.push(Block::new(text, spellcheck, start..range.end));
+    }
+
+    blocks
+}
+
+/// Check if a block ends at `offset`.
+fn is_block_end(text: &Text, offset: usize) -> bool {
+    offset >= 2 && text.byte(offset - 1) == Some(b'\n') && text.byte(offset - 2) == Some(b'\n')
+}
+
+/// Move a range by `offset` bytes.
+fn shift(range: &Range<usize>, offset: usize) -> Range<usize> {
+    range.start + offset..range.end + offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_blocks(text: &Text) -> Vec<String> {
+        let mut analysis = TextAnalysis::default();
+        analysis.update(text, None);
+        analysis.text_blocks().into_iter().map(|block| text.slice(block).into_owned()).collect()
+    }
+
+    #[test]
+    fn text_blocks_split_after_blank_lines() {
+        let text = Text::from(String::from("one\ntwo\n\nthree\n\n\nfour"));
+        assert_eq!(text_blocks(&text), ["one\ntwo\n\n", "three\n\n", "\n", "four"]);
+        assert_eq!(text_blocks(&Text::default()), [""]);
+    }
+
+    #[test]
+    fn incremental_analysis_matches_full_analysis() {
+        let mut text = Text::from(String::from("a #tag\n\n    code\n\n```\nb\n\nc:\n\n  d"));
+        let mut analysis = TextAnalysis::default();
+        analysis.update(&text, None);
+
+        // Merge, split and append blocks.
+        text.remove(6..8);
+        text.insert(0, "{red}x{/}\n\n");
+        text.insert(text.len(), "\n\n```");
+        assert!(analysis.update(&text, None));
+        assert!(!analysis.update(&text, None));
+
+        let mut full = TextAnalysis::default();
+        full.update(&text, None);
+        assert_eq!(analysis.text_blocks(), full.text_blocks());
+        assert_eq!(analysis.list_items(), full.list_items());
+        assert_eq!(analysis.color_spans(), full.color_spans());
+        assert_eq!(analysis.tags(), full.tags());
+        assert_eq!(analysis.code_fences(), full.code_fences());
+        assert_eq!(analysis.stats(), TextStats::new(text.as_str()));
+    }
+
+    #[test]
+    fn block_stats_match_text_stats() {
+        for text in ["", "one\ntwo\n\nthree\n\n\nfour", "- a\n\n- b c\n\n", "\n\n\n"] {
+            let text = Text::from(String::from(text));
+            let mut analysis = TextAnalysis::default();
+            analysis.update(&text, None);
+            assert_eq!(analysis.stats(), TextStats::new(text.as_str()));
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Window background rendering.
+
+use std::fs;
+use std::path::Path;
+
+use skia_safe::{
+    BlendMode, Canvas as SkiaCanvas, Data, FilterMode, Image, Matrix, MipmapMode, Paint, Point,
+    SamplingOptions, Shader, TileMode,
+};
+use tracing::error;
+
+use crate::config;
+use crate::geometry::Size;
+
+/// Window background.
+///
+/// Gradients and images depend on the window size, so their paint is cached
+/// for the last drawn physical size.
+pub struct Background {
+    config: config::Background,
+    image: Option<Image>,
+    paint: Option<(Size, Paint)>,
+}
+
+impl Background {
+    /// Create a new background, loading its image from disk.
+    pub fn new(background: &config::Background) -> Self {
+        let image = match background {
+            config::Background::Image(path) => load_image(path),
+            _ => None,
+        };
+
+        Self { image, config: background.clone(), paint: None }
+    }
+
+    /// Update the background configuration.
+    ///
+    /// Returns `true` if the background has changed.
+    pub fn set(&mut self, background: &config::Background) -> bool {
+        if &self.config == background {
+            return false;
+        }
+
+        *self = Self::new(background);
+
+        true
+    }
+
+    /// Check if the background is fully opaque.
+    pub fn is_opaque(&self) -> bool {
+        match (&self.config, &self.image) {
+            (config::Background::Color(color), _) => color.is_opaque(),
+            (config::Background::Gradient(top, bottom), _) => top.is_opaque() && bottom.is_opaque(),
+            (config::Background::Image(_), Some(image)) => image.is_opaque(),
+            // Images which failed to load are replaced by an opaque color.
+            (config::Background::Image(_), None) => true,
+        }
+    }
+
+    /// Draw the background, replacing the canvas content.
+    pub fn draw(&mut self, canvas: &SkiaCanvas, size: Size) {
+        if self.paint.as_ref().is_none_or(|(paint_size, _)| *paint_size != size) {
+            self.paint = Some((size, self.create_paint(size)));
+        }
+
+        if let Some((_, paint)) = &self.paint {
+            canvas.draw_paint(paint);
+        }
+    }
+
+    /// Create the background paint for a physical window size.
+    fn create_paint(&self, size: Size) -> Paint {
+        let mut paint = Paint::default();
+        paint.set_color4f(self.config.color().as_color4f(), None);
+        paint.set_blend_mode(BlendMode::Src);
+
+        let shader = match (&self.config, &self.image) {
+            (config::Background::Gradient(top, bottom), _) => {
+                let points = (Point::new(0., 0.), Point::new(0., size.height as f32));
+                let colors = [top.as_color4f().to_color(), bottom.as_color4f().to_color()];
+                Shader::linear_gradient(points, &colors[..], None, TileMode::Clamp, None, None)
+            },
+            (config::Background::Image(_), Some(image)) => image_shader(image, size),
+            _ => None,
+        };
+        if let Some(shader) = shader {
+            paint.set_shader(shader);
+        }
+
+        paint
+    }
+}
+
+/// Create a shader scaling an image to cover the entire window.
+///
+/// The image is centered, cropping the edges which exceed the window size.
+fn image_shader(image: &Image, size: Size) -> Option<Shader> {
+    let (width, height) = (size.width as f32, size.height as f32);
+    let (image_width, image_height) = (image.width() as f32, image.height() as f32);
+    let scale = (width / image_width).max(height / image_height);
+
+    let x = (width - image_width * scale) / 2.;
+    let y = (height - image_height * scale) / 2.;
+    let mut matrix = Matrix::translate((x, y));
+    matrix.pre_scale((scale, scale), None);
+
+    let sampling = SamplingOptions::new(FilterMode::Linear, MipmapMode::Linear);
+    image.to_shader((TileMode::Clamp, TileMode::Clamp), sampling, &matrix)
+}
+
+/// Load a background image from disk.
+fn load_image(path: &Path) -> Option<Image> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            error!("Failed to read background image {path:?}: {err}");
+            return None;
+        },
+    };
+
+    let image = Image::from_encoded(Data::new_copy(&data));
+    if image.is_none() {
+        error!("Failed to decode background image {path:?}");
+    }
+    image
+}
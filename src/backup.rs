@@ -0,0 +1,42 @@
+//! Backups of text replaced by external changes and item edits.
+
+use std::collections::VecDeque;
+
+use tracing::error;
+
+use crate::storage::Storage;
+
+/// Maximum number of backups kept in memory and in storage.
+pub const MAX_BACKUPS: usize = 10;
+
+/// Ring buffer of text content replaced by external file changes or item edits.
+#[derive(Default)]
+pub struct Backups {
+    entries: VecDeque<String>,
+}
+
+impl Backups {
+    /// Stash text before it is replaced.
+    ///
+    /// A snapshot of the text is also saved to the storage backend.
+    pub fn push(&mut self, storage: &dyn Storage, text: String) {
+        if let Err(err) = storage.write_snapshot(&text) {
+            error!("Failed to save replaced notes: {err}");
+        }
+
+        self.stash(text);
+    }
+
+    /// Stash text before it is replaced, without saving it to storage.
+    pub fn stash(&mut self, text: String) {
+        if self.entries.len() >= MAX_BACKUPS {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(text);
+    }
+
+    /// Take the most recent backup.
+    pub fn pop(&mut self) -> Option<String> {
+        self.entries.pop_back()
+    }
+}
@@ -1,18 +1,23 @@
 //! Configuration options.
 
-use std::fmt::{self, Display, Formatter};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display, Formatter, Write as _};
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::time::Duration;
+use std::{fs, io};
 
 use calloop::LoopHandle;
 use calloop::channel::{self, Event, Sender};
+use chrono::{DateTime, Local};
 use configory::EventHandler;
 use configory::docgen::{DocType, Docgen, Leaf};
-use serde::de::Visitor;
-use serde::{Deserialize, Deserializer};
-use skia_safe::Color4f;
-use tracing::{error, info};
+use serde::de::{IntoDeserializer, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use skia_safe::font_style::{Slant, Weight, Width};
+use skia_safe::textlayout::{TextAlign, TextDirection};
+use skia_safe::{Color4f, FontStyle as SkiaFontStyle};
+use tracing::{error, info, warn};
 
 use crate::State;
 
@@ -29,7 +34,7 @@ use crate::State;
 /// <br> `${XDG_CONFIG_HOME:-$HOME/.config}/pinax/pinax.toml`.
 ///
 /// ## Fields
-#[derive(Docgen, Deserialize, Default, Debug)]
+#[derive(Docgen, Deserialize, Serialize, Default, Clone, Debug)]
 #[serde(default, deny_unknown_fields)]
 pub struct Config {
     /// This section documents the `[general]` table.
@@ -40,67 +45,380 @@ pub struct Config {
     pub colors: Colors,
     /// This section documents the `[input]` table.
     pub input: Input,
+    /// This section documents the `[editing]` table.
+    pub editing: Editing,
+    /// This section documents the `[spellcheck]` table.
+    pub spellcheck: Spellcheck,
+    /// This section documents the `[hooks]` table.
+    pub hooks: Hooks,
+    /// This section documents the `[print]` table.
+    pub print: Print,
+    /// This section documents the `[layout]` table.
+    pub layout: Layout,
+    /// This section documents the `[notes.<NAME>]` tables, applied to the note named `<NAME>`.
+    pub notes: NoteOverrides,
+}
+
+impl Config {
+    /// Read the configuration file, without monitoring it for changes.
+    ///
+    /// This is used by subcommands which do not start Pinax.
+    pub fn load() -> Self {
+        let path = match dirs::config_dir() {
+            Some(config_dir) => config_dir.join("pinax/pinax.toml"),
+            None => return Self::default(),
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(err) => {
+                error!("Failed to read config at {path:?}: {err}");
+                return Self::default();
+            },
+        };
+
+        toml::from_str(&content)
+            .inspect_err(|err| error!("Config error: {err}"))
+            .unwrap_or_default()
+    }
+
+    /// Get the configuration with the overrides of a note applied.
+    pub fn for_note(&self, name: &str) -> Self {
+        let mut config = self.clone();
+        let Some(note) = self.notes.get(name) else { return config };
+
+        if let Some(size) = note.font_size {
+            config.font.size = size;
+        }
+        if let Some(foreground) = note.foreground {
+            config.colors.foreground = foreground;
+        }
+        if let Some(background) = &note.background {
+            config.colors.background = background.clone();
+        }
+        if let Some(highlight) = note.highlight {
+            config.colors.highlight = highlight;
+        }
+        if let Some(anchor) = note.anchor {
+            config.general.anchor = anchor;
+        }
+        if let Some(content_hints) = &note.content_hints {
+            config.input.content_hints = content_hints.clone();
+        }
+        if let Some(list_style) = note.list_style {
+            config.layout.list_style = list_style;
+        }
+
+        config
+    }
+
+    /// Get the TOML representation of the value at a dotted config `key`.
+    ///
+    /// Without a key, the entire configuration is returned.
+    pub fn value(&self, key: Option<&str>) -> Result<String, String> {
+        // Report the storage path in use, rather than omitting the default.
+        let mut config = self.clone();
+        config.general.path = Some(config.general.storage_path());
+
+        let mut value = toml::Value::try_from(&config).map_err(|err| err.to_string())?;
+        if let Some(key) = key {
+            for segment in key.split('.') {
+                value = match value {
+                    toml::Value::Table(mut table) => table.remove(segment),
+                    _ => None,
+                }
+                .ok_or_else(|| format!("unknown or unset config key {key:?}"))?;
+            }
+        }
+
+        match value {
+            toml::Value::Table(table) => toml::to_string(&table).map_err(|err| err.to_string()),
+            value => Ok(value.to_string()),
+        }
+    }
 }
 
 /// General configuration.
-#[derive(Docgen, Deserialize, Default, Debug)]
+#[derive(Docgen, Deserialize, Serialize, Clone, Debug)]
 #[serde(default, deny_unknown_fields)]
 pub struct General {
-    /// Location the notes are saved to.
+    /// Location the notes are saved to, with strftime placeholders in the file name.
     #[docgen(default = "${XDG_DATA_HOME:-$HOME/.local/share}/pinax/notes")]
     path: Option<PathBuf>,
+    /// Keep running in the background when the window is closed.
+    pub keep_running: bool,
+    /// Vertical position of text shorter than the window (top or bottom).
+    pub anchor: Anchor,
+    /// Horizontal text alignment (left, center or right).
+    pub align: Align,
+    /// Base text direction (auto, ltr or rtl).
+    pub direction: Direction,
+    /// Show the header bar with the note title and actions.
+    pub header: bool,
+    /// Rendering backend (auto, gl, vulkan or software).
+    pub renderer: RendererBackend,
+    /// Prefix new list items with the current time.
+    pub auto_timestamp: bool,
+    /// Format of inserted timestamps, using strftime syntax.
+    pub timestamp_format: String,
+    /// Format of expanded relative dates, using strftime syntax.
+    pub date_format: String,
+    /// Window width used when no previous size was saved.
+    pub width: u32,
+    /// Window height used when no previous size was saved.
+    pub height: u32,
+    /// Maximum width of text lines, centering the text in wider windows.
+    #[docgen(default = "none")]
+    pub max_text_width: Option<u32>,
+    /// Minimum width of text columns, splitting wide windows into columns.
+    #[docgen(default = "none")]
+    pub column_width: Option<u32>,
+    /// When to save changes (debounced, immediate or manual).
+    pub save_mode: SaveMode,
+    /// Delay after the last edit before changes are saved in debounced mode.
+    pub save_debounce_ms: u64,
+    /// Maximum delay before changes are saved in debounced mode.
+    pub save_max_delay_ms: u64,
+    /// Flush saved notes to the storage device, protecting them from crashes.
+    pub fsync: bool,
+    /// Note storage backend (file or sqlite), applied on restart.
+    pub backend: StorageBackend,
+    /// Days deleted notes are kept in the trash, keeping them forever if zero.
+    pub trash_retention_days: u32,
+    /// Seconds without user input before the window is dimmed.
+    #[docgen(default = "none")]
+    pub idle_dim_secs: Option<u32>,
+}
+
+impl Default for General {
+    fn default() -> Self {
+        Self {
+            header: true,
+            fsync: true,
+            timestamp_format: "%Y-%m-%d %H:%M".into(),
+            date_format: "%Y-%m-%d".into(),
+            width: 360,
+            height: 720,
+            save_debounce_ms: 1000,
+            save_max_delay_ms: 5000,
+            trash_retention_days: 30,
+            max_text_width: Default::default(),
+            save_mode: Default::default(),
+            backend: Default::default(),
+            column_width: Default::default(),
+            idle_dim_secs: Default::default(),
+            auto_timestamp: Default::default(),
+            keep_running: Default::default(),
+            anchor: Default::default(),
+            align: Default::default(),
+            direction: Default::default(),
+            renderer: Default::default(),
+            path: Default::default(),
+        }
+    }
 }
 
 impl General {
     /// Get the storage path.
+    ///
+    /// Placeholders in the file name are expanded using the current time.
     pub fn storage_path(&self) -> PathBuf {
+        self.storage_path_at(Local::now())
+    }
+
+    /// Get the storage path with placeholders expanded for `time`.
+    pub fn storage_path_at(&self, time: DateTime<Local>) -> PathBuf {
+        let path = self.raw_storage_path();
+        let template = match path.file_name().and_then(|name| name.to_str()) {
+            Some(template) if template.contains('%') => template,
+            _ => return path,
+        };
+
+        // Fall back to the literal path if the format is invalid.
+        let mut file_name = String::new();
+        if write!(file_name, "{}", time.format(template)).is_err()
+            || file_name.is_empty()
+            || file_name.contains('/')
+        {
+            warn!("Invalid storage path format: {template:?}");
+            return path;
+        }
+
+        path.with_file_name(file_name)
+    }
+
+    /// Check if the storage path changes over time.
+    pub fn is_path_template(&self) -> bool {
+        self.raw_storage_path()
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.contains('%'))
+    }
+
+    /// Get the storage path without expanding placeholders.
+    fn raw_storage_path(&self) -> PathBuf {
         self.path.clone().unwrap_or_else(|| dirs::data_dir().unwrap().join("pinax/notes"))
     }
 }
 
 /// Font configuration.
-#[derive(Docgen, Deserialize, Debug)]
+#[derive(Docgen, Deserialize, Serialize, Clone, Debug)]
 #[serde(default, deny_unknown_fields)]
 pub struct Font {
-    /// Font family.
-    pub family: String,
+    /// Font family, or list of families in order of preference.
+    pub family: FontFamily,
+    /// Font family of code blocks, or list of families in order of preference.
+    pub monospace_family: FontFamily,
+    /// Directory with additional font files.
+    #[docgen(default = "none")]
+    pub directory: Option<PathBuf>,
     /// Font size.
     pub size: f64,
+    /// Line height relative to the font size.
+    pub line_height: f64,
+    /// Extra spacing between list items.
+    pub paragraph_spacing: f64,
+    /// Font weight, from 100 (thin) to 900 (black).
+    pub weight: u32,
+    /// Font style (normal or italic).
+    pub style: FontStyle,
+    /// OpenType features, like `tnum` or `liga=0`.
+    #[docgen(doc_type = "list of text", default = "[]")]
+    pub features: Vec<String>,
+}
+
+impl Font {
+    /// Get the Skia font style.
+    pub fn skia_style(&self) -> SkiaFontStyle {
+        let slant = match self.style {
+            FontStyle::Normal => Slant::Upright,
+            FontStyle::Italic => Slant::Italic,
+        };
+        SkiaFontStyle::new(Weight::from(self.weight as i32), Width::NORMAL, slant)
+    }
 }
 
 impl Default for Font {
     fn default() -> Self {
-        Self { family: String::from("sans"), size: 18. }
+        Self {
+            family: FontFamily(vec![String::from("sans")]),
+            monospace_family: FontFamily(vec![String::from("monospace")]),
+            size: 18.,
+            line_height: 1.,
+            paragraph_spacing: 0.,
+            weight: 400,
+            style: Default::default(),
+            directory: Default::default(),
+            features: Default::default(),
+        }
     }
 }
 
+/// Minimum contrast ratio between selected text and its background.
+pub const MIN_SELECTION_CONTRAST: f64 = 3.;
+
+/// Default background color.
+const DEFAULT_BACKGROUND: Color = Color::new(24, 24, 24);
+
 /// Color configuration.
-#[derive(Docgen, Deserialize, Copy, Clone, Hash, PartialEq, Eq, Debug)]
+#[derive(Docgen, Deserialize, Serialize, Clone, Hash, PartialEq, Eq, Debug)]
 #[serde(default, deny_unknown_fields)]
 pub struct Colors {
     /// Primary foreground color.
     #[serde(alias = "fg")]
     pub foreground: Color,
-    /// Primary background color.
+    /// Primary background color, gradient from top to bottom, or image path.
     #[serde(alias = "bg")]
-    pub background: Color,
+    pub background: Background,
     /// Primary accent color.
     #[serde(alias = "hl")]
     pub highlight: Color,
+    /// Header bar background color.
+    pub header: Color,
+    /// Text color of selected text, defaulting to the background color.
+    #[docgen(default = "none")]
+    pub selection_foreground: Option<Color>,
+    /// Background color of selected text, defaulting to the highlight color.
+    #[docgen(default = "none")]
+    pub selection_background: Option<Color>,
+    /// Blur content behind translucent backgrounds, if supported by the compositor.
+    pub blur: bool,
+    /// Named colors for text spans like `{red}text{/}`.
+    pub accents: Accents,
 }
 
 impl Default for Colors {
     fn default() -> Self {
         Self {
             foreground: Color::new(255, 255, 255),
-            background: Color::new(24, 24, 24),
+            background: Background::Color(DEFAULT_BACKGROUND),
             highlight: Color::new(117, 42, 42),
+            header: Color::new(36, 36, 36),
+            selection_foreground: Default::default(),
+            selection_background: Default::default(),
+            blur: Default::default(),
+            accents: Default::default(),
         }
     }
 }
 
+impl Colors {
+    /// Get the foreground and background color of selected text.
+    ///
+    /// If the selection colors are hard to tell apart, the primary foreground
+    /// and background colors are used inverted instead.
+    pub fn selection(&self) -> (Color, Color) {
+        let foreground = self.selection_foreground.unwrap_or(self.background.color());
+        let background = self.selection_background.unwrap_or(self.highlight);
+
+        if foreground.contrast(background) < MIN_SELECTION_CONTRAST {
+            (self.background.color(), self.foreground)
+        } else {
+            (foreground, background)
+        }
+    }
+}
+
+/// Named accent colors for text spans.
+#[derive(Deserialize, Serialize, Clone, Hash, PartialEq, Eq, Debug)]
+#[serde(transparent)]
+pub struct Accents(BTreeMap<String, Color>);
+
+impl Default for Accents {
+    fn default() -> Self {
+        Self(BTreeMap::from([
+            ("red".into(), Color::new(224, 82, 82)),
+            ("orange".into(), Color::new(224, 150, 60)),
+            ("green".into(), Color::new(92, 176, 92)),
+            ("blue".into(), Color::new(82, 132, 224)),
+            ("purple".into(), Color::new(164, 100, 224)),
+        ]))
+    }
+}
+
+impl Deref for Accents {
+    type Target = BTreeMap<String, Color>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Docgen for Accents {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("table of colors"))
+    }
+
+    fn format(&self) -> String {
+        let colors: Vec<_> =
+            self.0.iter().map(|(name, color)| format!("{name} = {}", color.format())).collect();
+        format!("{{ {} }}", colors.join(", "))
+    }
+}
+
 /// Input configuration.
-#[derive(Docgen, Deserialize, Debug)]
+#[derive(Docgen, Deserialize, Serialize, Clone, Debug)]
 #[serde(default, deny_unknown_fields)]
 pub struct Input {
     /// Square of the maximum distance before touch input is considered a drag.
@@ -108,29 +426,794 @@ pub struct Input {
     /// Maximum interval between taps to be considered a double/trible-tap.
     #[docgen(doc_type = "integer (milliseconds)", default = "300")]
     pub max_multi_tap: MillisDuration,
+    /// Minimum duration of a touch to be considered a long-press.
+    #[docgen(doc_type = "integer (milliseconds)", default = "500")]
+    pub long_press: MillisDuration,
+    /// Height of the on-screen keyboard, reserved at the bottom while the IME
+    /// is active.
+    pub keyboard_height: u32,
+    /// Normalize pasted text, removing carriage returns, invisible characters,
+    /// trailing whitespace and excess blank lines.
+    pub normalize_paste: bool,
+    /// Split pasted text into separate list items for every line.
+    pub paste_lines_as_items: bool,
+    /// IME content hints for text lines (completion, spellcheck,
+    /// auto_capitalization, lowercase, uppercase, titlecase or latin).
+    pub content_hints: ContentHints,
 }
 
 impl Default for Input {
     fn default() -> Self {
-        Self { max_multi_tap: Duration::from_millis(300).into(), max_tap_distance: 400. }
+        Self {
+            max_multi_tap: Duration::from_millis(300).into(),
+            long_press: Duration::from_millis(500).into(),
+            max_tap_distance: 400.,
+            keyboard_height: 0,
+            normalize_paste: true,
+            paste_lines_as_items: false,
+            content_hints: Default::default(),
+        }
+    }
+}
+
+/// IME content hints for text lines.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+#[serde(transparent)]
+pub struct ContentHints(Vec<ContentHint>);
+
+impl Default for ContentHints {
+    fn default() -> Self {
+        Self(vec![
+            ContentHint::Completion,
+            ContentHint::Spellcheck,
+            ContentHint::AutoCapitalization,
+        ])
+    }
+}
+
+impl Deref for ContentHints {
+    type Target = [ContentHint];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Docgen for ContentHints {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("list of text"))
+    }
+
+    fn format(&self) -> String {
+        let hints: Vec<_> = self.0.iter().map(ContentHint::format).collect();
+        format!("[{}]", hints.join(", "))
+    }
+}
+
+/// IME content hint.
+#[derive(Deserialize, Serialize, Copy, Clone, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentHint {
+    Completion,
+    Spellcheck,
+    AutoCapitalization,
+    Lowercase,
+    Uppercase,
+    Titlecase,
+    Latin,
+}
+
+impl ContentHint {
+    fn format(&self) -> String {
+        let name = match self {
+            Self::Completion => "completion",
+            Self::Spellcheck => "spellcheck",
+            Self::AutoCapitalization => "auto_capitalization",
+            Self::Lowercase => "lowercase",
+            Self::Uppercase => "uppercase",
+            Self::Titlecase => "titlecase",
+            Self::Latin => "latin",
+        };
+        format!("{name:?}")
+    }
+}
+
+/// Per-note configuration overrides, keyed by note name.
+#[derive(Deserialize, Serialize, Default, Clone, Debug)]
+#[serde(transparent)]
+pub struct NoteOverrides(HashMap<String, NoteConfig>);
+
+impl Deref for NoteOverrides {
+    type Target = HashMap<String, NoteConfig>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Document overrides like a single note's table.
+impl Docgen for NoteOverrides {
+    fn doc_type() -> DocType {
+        NoteConfig::doc_type()
+    }
+
+    fn format(&self) -> String {
+        NoteConfig::default().format()
+    }
+}
+
+/// Configuration overrides of a single note.
+#[derive(Docgen, Deserialize, Serialize, Default, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoteConfig {
+    /// Font size, replacing `font.size`.
+    #[docgen(default = "none")]
+    pub font_size: Option<f64>,
+    /// Primary foreground color, replacing `colors.foreground`.
+    #[docgen(default = "none")]
+    pub foreground: Option<Color>,
+    /// Primary background color, replacing `colors.background`.
+    #[docgen(default = "none")]
+    pub background: Option<Background>,
+    /// Primary accent color, replacing `colors.highlight`.
+    #[docgen(default = "none")]
+    pub highlight: Option<Color>,
+    /// Vertical position of short text, replacing `general.anchor`.
+    #[docgen(default = "none")]
+    pub anchor: Option<Anchor>,
+    /// IME content hints for text lines, replacing `input.content_hints`.
+    #[docgen(default = "none")]
+    pub content_hints: Option<ContentHints>,
+    /// Style of list item markers, replacing `layout.list_style`.
+    #[docgen(default = "none")]
+    pub list_style: Option<ListStyle>,
+}
+
+/// Font family fallback list.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FontFamily(Vec<String>);
+
+impl Deref for FontFamily {
+    type Target = [String];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Docgen for FontFamily {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("text or list of text"))
+    }
+
+    fn format(&self) -> String {
+        match &self.0[..] {
+            [family] => format!("{family:?}"),
+            families => format!("{families:?}"),
+        }
+    }
+}
+
+/// Deserialize font families from a string or an array of strings.
+impl<'de> Deserialize<'de> for FontFamily {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct FontFamilyVisitor;
+
+        impl<'de> Visitor<'de> for FontFamilyVisitor {
+            type Value = FontFamily;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                f.write_str("font family or list of font families")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<FontFamily, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(FontFamily(vec![value.into()]))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<FontFamily, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut families = Vec::new();
+                while let Some(family) = seq.next_element()? {
+                    families.push(family);
+                }
+
+                if families.is_empty() {
+                    return Err(serde::de::Error::custom("font family list is empty"));
+                }
+
+                Ok(FontFamily(families))
+            }
+        }
+
+        deserializer.deserialize_any(FontFamilyVisitor)
+    }
+}
+
+/// Serialize single font families as string.
+impl Serialize for FontFamily {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match &self.0[..] {
+            [family] => serializer.serialize_str(family),
+            families => families.serialize(serializer),
+        }
+    }
+}
+
+/// Font style.
+#[derive(Deserialize, Serialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FontStyle {
+    #[default]
+    Normal,
+    Italic,
+}
+
+impl Docgen for FontStyle {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("text"))
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Normal => "\"normal\"".into(),
+            Self::Italic => "\"italic\"".into(),
+        }
+    }
+}
+
+/// Vertical text anchor.
+#[derive(Deserialize, Serialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Anchor {
+    Top,
+    #[default]
+    Bottom,
+}
+
+impl Docgen for Anchor {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("text"))
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Top => "\"top\"".into(),
+            Self::Bottom => "\"bottom\"".into(),
+        }
+    }
+}
+
+/// Horizontal text alignment.
+#[derive(Deserialize, Serialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+impl Align {
+    pub const fn as_text_align(&self) -> TextAlign {
+        match self {
+            Self::Left => TextAlign::Left,
+            Self::Center => TextAlign::Center,
+            Self::Right => TextAlign::Right,
+        }
+    }
+}
+
+impl Docgen for Align {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("text"))
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Left => "\"left\"".into(),
+            Self::Center => "\"center\"".into(),
+            Self::Right => "\"right\"".into(),
+        }
+    }
+}
+
+/// Base text direction.
+#[derive(Deserialize, Serialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    #[default]
+    Auto,
+    Ltr,
+    Rtl,
+}
+
+impl Direction {
+    /// Resolve the paragraph direction for text consisting of `chunks`.
+    ///
+    /// Automatic detection uses the first strongly directional character.
+    pub fn text_direction<'a>(&self, chunks: impl IntoIterator<Item = &'a str>) -> TextDirection {
+        match self {
+            Self::Ltr => TextDirection::LTR,
+            Self::Rtl => TextDirection::RTL,
+            Self::Auto => chunks
+                .into_iter()
+                .flat_map(str::chars)
+                .find_map(|c| match c {
+                    c if is_rtl(c) => Some(TextDirection::RTL),
+                    c if c.is_alphabetic() => Some(TextDirection::LTR),
+                    _ => None,
+                })
+                .unwrap_or(TextDirection::LTR),
+        }
+    }
+}
+
+impl Docgen for Direction {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("text"))
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Auto => "\"auto\"".into(),
+            Self::Ltr => "\"ltr\"".into(),
+            Self::Rtl => "\"rtl\"".into(),
+        }
+    }
+}
+
+/// Rendering backend.
+#[derive(Deserialize, Serialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum RendererBackend {
+    #[default]
+    Auto,
+    Gl,
+    Vulkan,
+    Software,
+}
+
+impl Docgen for RendererBackend {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("text"))
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Auto => "\"auto\"".into(),
+            Self::Gl => "\"gl\"".into(),
+            Self::Vulkan => "\"vulkan\"".into(),
+            Self::Software => "\"software\"".into(),
+        }
+    }
+}
+
+/// Text persistence strategy.
+#[derive(Deserialize, Serialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SaveMode {
+    /// Save after a short period without edits.
+    #[default]
+    Debounced,
+    /// Save after every edit.
+    Immediate,
+    /// Save only on request, when switching notes, or on exit.
+    Manual,
+}
+
+impl Docgen for SaveMode {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("text"))
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Debounced => "\"debounced\"".into(),
+            Self::Immediate => "\"immediate\"".into(),
+            Self::Manual => "\"manual\"".into(),
+        }
+    }
+}
+
+/// Note storage backend.
+#[derive(Deserialize, Serialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// One plain text file per note.
+    #[default]
+    File,
+    /// Single SQLite database for all notes.
+    Sqlite,
+}
+
+impl Docgen for StorageBackend {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("text"))
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::File => "\"file\"".into(),
+            Self::Sqlite => "\"sqlite\"".into(),
+        }
+    }
+}
+
+/// Check if a character belongs to a right-to-left script.
+fn is_rtl(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0590}'..='\u{08FF}'
+            | '\u{FB1D}'..='\u{FDFF}'
+            | '\u{FE70}'..='\u{FEFF}'
+            | '\u{10800}'..='\u{10FFF}'
+            | '\u{1E800}'..='\u{1EFFF}'
+    )
+}
+
+/// Smart input transforms applied while typing.
+#[derive(Docgen, Deserialize, Serialize, Default, Copy, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Editing {
+    /// Close brackets and quotes automatically.
+    pub auto_pair: bool,
+    /// Replace `--` with an en dash.
+    pub smart_dashes: bool,
+    /// Replace straight quotes with curly quotes.
+    pub smart_quotes: bool,
+}
+
+/// Spellcheck configuration.
+#[derive(Docgen, Deserialize, Serialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Spellcheck {
+    /// Underline misspelled words.
+    pub enabled: bool,
+    /// Hunspell dictionary or plain word list used for spellchecking.
+    pub dictionary: PathBuf,
+}
+
+impl Default for Spellcheck {
+    fn default() -> Self {
+        Self { dictionary: PathBuf::from("/usr/share/hunspell/en_US.dic"), enabled: false }
+    }
+}
+
+/// External command hooks.
+///
+/// Commands are given as a program followed by its arguments, like
+/// `["git", "-C", "/notes", "add"]`. Notes changed by a `pre_load` command
+/// are reloaded once it has finished.
+#[derive(Docgen, Deserialize, Serialize, Default, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    /// Command run after saving a note, with its path appended.
+    pub post_save: Vec<String>,
+    /// Command run before loading a note, with its path appended.
+    pub pre_load: Vec<String>,
+}
+
+/// Printing and PDF export.
+#[derive(Docgen, Deserialize, Serialize, Clone, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Print {
+    /// Command printing the PDF document passed on its stdin.
+    #[docgen(default = "[\"lp\"]")]
+    pub command: Vec<String>,
+    /// Paper size (a4 or letter).
+    pub paper: Paper,
+    /// Page margin in millimeters.
+    pub margin: f64,
+}
+
+impl Default for Print {
+    fn default() -> Self {
+        Self { command: vec!["lp".into()], paper: Default::default(), margin: 20. }
+    }
+}
+
+/// Printed page size.
+#[derive(Deserialize, Serialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Paper {
+    #[default]
+    A4,
+    Letter,
+}
+
+impl Paper {
+    /// Page size in PDF points.
+    pub const fn size(&self) -> (f32, f32) {
+        match self {
+            Self::A4 => (595.28, 841.89),
+            Self::Letter => (612., 792.),
+        }
+    }
+}
+
+impl Docgen for Paper {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("text"))
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::A4 => "\"a4\"".into(),
+            Self::Letter => "\"letter\"".into(),
+        }
+    }
+}
+
+/// Minimum spacing between bullet points and their text at scale 1.
+const BULLET_SPACING: f64 = 10.;
+
+/// Text layout configuration.
+#[derive(Docgen, Deserialize, Serialize, Copy, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Layout {
+    /// Padding around the text.
+    pub padding: f64,
+    /// Size of list item bullet points.
+    pub bullet_size: f64,
+    /// Shape of list item bullet points (square, circle or dash).
+    pub bullet_shape: BulletShape,
+    /// Color of list item bullet points, defaulting to the foreground color.
+    #[docgen(default = "none")]
+    pub bullet_color: Option<Color>,
+    /// Style of list item markers (bullets or numbers).
+    pub list_style: ListStyle,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            padding: 15.,
+            bullet_size: 5.,
+            bullet_shape: Default::default(),
+            bullet_color: Default::default(),
+            list_style: Default::default(),
+        }
+    }
+}
+
+impl Layout {
+    /// Horizontal padding reserved for bullet points at scale 1.
+    ///
+    /// This includes the space of the bullet point itself, which is aligned to
+    /// the left of this area rather than centered in it.
+    pub fn bullet_padding(&self) -> f64 {
+        self.bullet_shape.width(self.bullet_size) + BULLET_SPACING
     }
 }
 
-/// RGB color.
+/// List item bullet point shape.
+#[derive(Deserialize, Serialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum BulletShape {
+    #[default]
+    Square,
+    Circle,
+    Dash,
+}
+
+impl BulletShape {
+    /// Horizontal extent of a bullet point with the specified size.
+    pub fn width(&self, size: f64) -> f64 {
+        match self {
+            Self::Square | Self::Circle => size,
+            Self::Dash => size * 2.,
+        }
+    }
+}
+
+impl Docgen for BulletShape {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("text"))
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Square => "\"square\"".into(),
+            Self::Circle => "\"circle\"".into(),
+            Self::Dash => "\"dash\"".into(),
+        }
+    }
+}
+
+/// List item marker style.
+#[derive(Deserialize, Serialize, Copy, Clone, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ListStyle {
+    #[default]
+    Bullets,
+    Numbers,
+}
+
+impl ListStyle {
+    /// Get the style used for sections switched to the other style.
+    pub fn toggled(self) -> Self {
+        match self {
+            Self::Bullets => Self::Numbers,
+            Self::Numbers => Self::Bullets,
+        }
+    }
+}
+
+impl Docgen for ListStyle {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("text"))
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Bullets => "\"bullets\"".into(),
+            Self::Numbers => "\"numbers\"".into(),
+        }
+    }
+}
+
+/// Window background.
+#[derive(Clone, Hash, PartialEq, Eq, Debug)]
+pub enum Background {
+    /// Solid color.
+    Color(Color),
+    /// Vertical gradient from the top to the bottom color.
+    Gradient(Color, Color),
+    /// Image scaled to cover the entire window.
+    Image(PathBuf),
+}
+
+impl Background {
+    /// Get a solid color representing the background.
+    ///
+    /// This is used for UI elements which cannot be drawn with a gradient or
+    /// image, like menus and the selected text's foreground.
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Color(color) => *color,
+            Self::Gradient(top, bottom) => {
+                let mix = |top: u8, bottom: u8| ((top as u16 + bottom as u16) / 2) as u8;
+                Color {
+                    r: mix(top.r, bottom.r),
+                    g: mix(top.g, bottom.g),
+                    b: mix(top.b, bottom.b),
+                    a: mix(top.a, bottom.a),
+                }
+            },
+            Self::Image(_) => DEFAULT_BACKGROUND,
+        }
+    }
+}
+
+impl Docgen for Background {
+    fn doc_type() -> DocType {
+        DocType::Leaf(Leaf::new("color, list of two colors or image path"))
+    }
+
+    fn format(&self) -> String {
+        match self {
+            Self::Color(color) => color.format(),
+            Self::Gradient(top, bottom) => format!("[{}, {}]", top.format(), bottom.format()),
+            Self::Image(path) => format!("{path:?}"),
+        }
+    }
+}
+
+/// Deserialize backgrounds from a color, an array of two colors, or a path.
+impl<'de> Deserialize<'de> for Background {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BackgroundVisitor;
+
+        impl<'de> Visitor<'de> for BackgroundVisitor {
+            type Value = Background;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
+                f.write_str("hex color, list of two hex colors or image path")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Background, E>
+            where
+                E: serde::de::Error,
+            {
+                if value.starts_with('#') {
+                    Color::deserialize(value.into_deserializer()).map(Background::Color)
+                } else {
+                    Ok(Background::Image(PathBuf::from(value)))
+                }
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Background, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut colors = Vec::new();
+                while let Some(color) = seq.next_element::<Color>()? {
+                    colors.push(color);
+                }
+
+                match colors[..] {
+                    [top, bottom] => Ok(Background::Gradient(top, bottom)),
+                    _ => {
+                        let msg = format!("gradient has {} colors; expected 2", colors.len());
+                        Err(serde::de::Error::custom(msg))
+                    },
+                }
+            }
+        }
+
+        deserializer.deserialize_any(BackgroundVisitor)
+    }
+}
+
+/// Serialize backgrounds in the format they are deserialized from.
+impl Serialize for Background {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Color(color) => color.serialize(serializer),
+            Self::Gradient(top, bottom) => [top, bottom].serialize(serializer),
+            Self::Image(path) => path.serialize(serializer),
+        }
+    }
+}
+
+/// RGBA color.
 #[derive(Copy, Clone, Hash, PartialEq, Eq, Debug)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
     pub b: u8,
+    pub a: u8,
 }
 
 impl Color {
     pub const fn new(r: u8, g: u8, b: u8) -> Self {
-        Self { r, g, b }
+        Self { r, g, b, a: u8::MAX }
     }
 
     pub const fn as_color4f(&self) -> Color4f {
-        Color4f { r: self.r as f32 / 255., g: self.g as f32 / 255., b: self.b as f32 / 255., a: 1. }
+        Color4f {
+            r: self.r as f32 / 255.,
+            g: self.g as f32 / 255.,
+            b: self.b as f32 / 255.,
+            a: self.a as f32 / 255.,
+        }
+    }
+
+    /// Check if the color is fully opaque.
+    pub const fn is_opaque(&self) -> bool {
+        self.a == u8::MAX
+    }
+
+    /// Get the contrast ratio between two colors, ranging from 1 to 21.
+    pub fn contrast(&self, other: Self) -> f64 {
+        let (l1, l2) = (self.luminance(), other.luminance());
+        (l1.max(l2) + 0.05) / (l1.min(l2) + 0.05)
+    }
+
+    /// Get the color's relative luminance, as defined by WCAG.
+    fn luminance(&self) -> f64 {
+        let linear = |channel: u8| {
+            let channel = channel as f64 / 255.;
+            if channel <= 0.03928 { channel / 12.92 } else { ((channel + 0.055) / 1.055).powf(2.4) }
+        };
+        0.2126 * linear(self.r) + 0.7152 * linear(self.g) + 0.0722 * linear(self.b)
     }
 }
 
@@ -140,7 +1223,7 @@ impl Docgen for Color {
     }
 
     fn format(&self) -> String {
-        format!("\"#{:0>2x}{:0>2x}{:0>2x}\"", self.r, self.g, self.b)
+        format!("\"{self}\"")
     }
 }
 
@@ -156,7 +1239,7 @@ impl<'de> Deserialize<'de> for Color {
             type Value = Color;
 
             fn expecting(&self, f: &mut Formatter<'_>) -> fmt::Result {
-                f.write_str("hex color like #ff00ff")
+                f.write_str("hex color like #ff00ff or #ff00ff80")
             }
 
             fn visit_str<E>(self, value: &str) -> Result<Color, E>
@@ -170,21 +1253,28 @@ impl<'de> Deserialize<'de> for Color {
                     },
                 };
 
+                // Default to opaque colors without alpha channel.
                 let digits = channels.len();
-                if digits != 6 {
-                    let msg = format!("color {value:?} has {digits} digits; expected 6");
-                    return Err(E::custom(msg));
-                }
+                let alpha = match digits {
+                    6 => "ff",
+                    8 => "",
+                    _ => {
+                        let msg = format!("color {value:?} has {digits} digits; expected 6 or 8");
+                        return Err(E::custom(msg));
+                    },
+                };
 
-                match u32::from_str_radix(channels, 16) {
+                match u32::from_str_radix(&format!("{channels}{alpha}"), 16) {
                     Ok(mut color) => {
+                        let a = (color & 0xFF) as u8;
+                        color >>= 8;
                         let b = (color & 0xFF) as u8;
                         color >>= 8;
                         let g = (color & 0xFF) as u8;
                         color >>= 8;
                         let r = color as u8;
 
-                        Ok(Color::new(r, g, b))
+                        Ok(Color { r, g, b, a })
                     },
                     Err(_) => Err(E::custom(format!("color {value:?} contains non-hex digits"))),
                 }
@@ -195,9 +1285,23 @@ impl<'de> Deserialize<'de> for Color {
     }
 }
 
+/// Serialize rgb color as hex string.
+impl Serialize for Color {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
 impl Display for Color {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(f, "#{:0>2x}{:0>2x}{:0>2x}", self.r, self.g, self.b)
+        write!(f, "#{:0>2x}{:0>2x}{:0>2x}", self.r, self.g, self.b)?;
+        if !self.is_opaque() {
+            write!(f, "{:0>2x}", self.a)?;
+        }
+        Ok(())
     }
 }
 
@@ -223,6 +1327,15 @@ impl<'de> Deserialize<'de> for MillisDuration {
     }
 }
 
+impl Serialize for MillisDuration {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(self.0.as_millis() as u64)
+    }
+}
+
 impl From<Duration> for MillisDuration {
     fn from(duration: Duration) -> Self {
         Self(duration)
@@ -248,6 +1361,7 @@ impl ConfigEventHandler {
             .insert_source(rx, |event, _, state| {
                 if let Event::Msg(config) = event {
                     state.window.update_config(&config);
+                    state.config = config;
                 }
             })
             .inspect_err(|err| error!("Failed to insert config source: {err}"));
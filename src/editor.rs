@@ -0,0 +1,1446 @@
+//! Renderer-agnostic text editing logic.
+//!
+//! Everything in this module operates on plain text and abstract layout
+//! metrics, so it can be tested without a Wayland connection or Skia.
+
+use std::cmp;
+use std::collections::BTreeMap;
+use std::ops::{Bound, Range, RangeBounds};
+use std::time::Duration;
+
+use chrono::{Datelike, Days, Months, NaiveDate, Weekday};
+use unicode_segmentation::GraphemeCursor;
+
+use crate::config::Editing;
+use crate::text::{Text, TextEdit};
+
+/// Prefix marking pinned list items.
+pub const PIN_MARKER: char = '!';
+
+/// Marker wrapping completed list items.
+pub const COMPLETED_MARKER: &str = "~~";
+
+/// Prefix of item tags, like `#groceries`.
+pub const TAG_MARKER: char = '#';
+
+/// Marker fencing code blocks.
+pub const CODE_FENCE: &str = "```";
+
+/// Marker ending a color span.
+pub const COLOR_END_MARKER: &str = "{/}";
+
+/// Characters closed automatically, with their closing counterpart.
+const AUTO_PAIRS: [(char, char); 7] = [
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('"', '"'),
+    ('\'', '\''),
+    ('\u{201c}', '\u{201d}'),
+    ('\u{2018}', '\u{2019}'),
+];
+
+/// Fraction of the kinetic scroll velocity retained after one second.
+const SCROLL_FRICTION: f32 = 0.05;
+
+/// Minimum kinetic scroll velocity in pixels per second.
+pub const MIN_SCROLL_VELOCITY: f32 = 20.;
+
+/// Invisible characters removed from pasted text.
+///
+/// Zero-width joiners are kept, since they are required for emoji sequences.
+const INVISIBLE_CHARS: [char; 4] = ['\u{200B}', '\u{2060}', '\u{FEFF}', '\u{00AD}'];
+
+/// Type of content in a single line of text.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum LineKind {
+    Text,
+    Url,
+    Number,
+}
+
+impl LineKind {
+    /// Determine the content type of a line.
+    pub fn classify(line: &str) -> Self {
+        let line = line.trim();
+
+        // Consider everything that looks like a URI scheme or `www.` domain a URL.
+        let is_url = !line.contains(char::is_whitespace)
+            && (line.starts_with("www.")
+                || line.split_once("://").is_some_and(|(scheme, _)| {
+                    !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric())
+                }));
+        if is_url {
+            return Self::Url;
+        }
+
+        // Allow common separators, to support things like prices or phone numbers.
+        let is_number = line.contains(|c: char| c.is_ascii_digit())
+            && line.chars().all(|c| c.is_ascii_digit() || "+-.,/() ".contains(c));
+        if is_number {
+            return Self::Number;
+        }
+
+        Self::Text
+    }
+}
+
+/// Single contiguous change between two texts.
+pub struct TextChange {
+    /// Start of the changed region.
+    pub start: usize,
+    /// End of the changed region in the old text.
+    pub old_end: usize,
+    /// End of the changed region in the new text.
+    pub new_end: usize,
+}
+
+impl TextChange {
+    pub fn new(old: &str, new: &str) -> Self {
+        // Find common prefix, stopping at the last shared char boundary.
+        let mut start = old.bytes().zip(new.bytes()).take_while(|(a, b)| a == b).count();
+        while !old.is_char_boundary(start) {
+            start -= 1;
+        }
+
+        // Find common suffix, without overlapping the prefix.
+        let max_suffix = cmp::min(old.len(), new.len()) - start;
+        let mut suffix =
+            old.bytes().rev().zip(new.bytes().rev()).take_while(|(a, b)| a == b).count();
+        suffix = cmp::min(suffix, max_suffix);
+        while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix)
+        {
+            suffix -= 1;
+        }
+
+        Self { start, old_end: old.len() - suffix, new_end: new.len() - suffix }
+    }
+
+    /// Map an offset in the old text to the new text.
+    ///
+    /// Offsets inside the changed region are moved to its end.
+    pub fn map(&self, offset: usize) -> usize {
+        if offset <= self.start {
+            offset
+        } else if offset >= self.old_end {
+            offset - self.old_end + self.new_end
+        } else {
+            self.new_end
+        }
+    }
+
+    /// Map a range in the old text to the new text.
+    pub fn map_range(&self, range: Range<usize>) -> Range<usize> {
+        self.map(range.start)..self.map(range.end)
+    }
+
+    /// Combine this change with a subsequent change of the new text.
+    ///
+    /// The combined change covers both changes, mapping the old text directly
+    /// to the text after the subsequent change.
+    pub fn then(&self, next: &Self) -> Self {
+        let start = cmp::min(self.start, next.start);
+        let new_end = cmp::max(next.map(self.new_end), next.new_end);
+
+        // Text after the combined change is only shifted by both changes.
+        let old_end = new_end + self.old_end + next.old_end - self.new_end - next.new_end;
+
+        Self { start, old_end, new_end }
+    }
+}
+
+impl From<&TextEdit> for TextChange {
+    fn from(edit: &TextEdit) -> Self {
+        let new_end = edit.range.start + edit.text.len();
+        Self { start: edit.range.start, old_end: edit.range.end, new_end }
+    }
+}
+
+/// Vertical scroll state of a text area.
+#[derive(Default, Copy, Clone, PartialEq, Debug)]
+pub struct Scroll {
+    /// Offset of the content, negative when scrolled towards its end.
+    pub offset: f32,
+    /// Kinetic scroll velocity in pixels per second.
+    pub velocity: f32,
+}
+
+impl Scroll {
+    /// Advance kinetic scrolling by `interval`.
+    ///
+    /// Returns `true` if the offset was changed.
+    pub fn animate(&mut self, interval: Duration) -> bool {
+        if self.velocity == 0. {
+            return false;
+        }
+
+        // Decelerate exponentially.
+        let seconds = interval.as_secs_f32();
+        self.offset += self.velocity * seconds;
+        self.velocity *= SCROLL_FRICTION.powf(seconds);
+        if self.velocity.abs() < MIN_SCROLL_VELOCITY {
+            self.velocity = 0.;
+        }
+
+        true
+    }
+
+    /// Scroll the minimum distance necessary for `line_end` to be visible.
+    pub fn reveal(&mut self, line_end: f32, viewport_height: f32) {
+        let delta = line_end + self.offset - viewport_height;
+        if delta > 0. {
+            self.offset -= delta;
+        } else if line_end + self.offset < 0. {
+            self.offset = -line_end;
+        }
+    }
+
+    /// Clamp the offset to the content's limits.
+    ///
+    /// Kinetic scrolling is stopped when a limit is reached.
+    pub fn clamp(&mut self, content_height: f32, viewport_height: f32) {
+        let min_offset = -(content_height - viewport_height).max(0.);
+        let offset = self.offset.min(0.).max(min_offset);
+
+        if offset != self.offset {
+            self.velocity = 0.;
+            self.offset = offset;
+        }
+    }
+}
+
+/// Geometry of laid out text.
+///
+/// All coordinates are relative to the top of the content, ignoring the
+/// scroll offset.
+pub trait LayoutMetrics {
+    /// Get the visual line containing the cursor at `offset`.
+    fn visual_line(&self, offset: usize) -> Option<usize>;
+
+    /// Get the vertical center of a visual line.
+    fn line_center_y(&self, line: usize) -> Option<f32>;
+
+    /// Get the horizontal start of the column containing a visual line.
+    fn column_x(&self, line: usize) -> Option<f32>;
+
+    /// Get the text offset closest to a point.
+    fn offset_at_point(&self, x: f32, y: f32) -> Option<usize>;
+}
+
+/// Get the cursor position after moving one visual line up or down.
+///
+/// The cursor keeps its horizontal position `x` within the column, even if the
+/// adjacent line is in another column.
+pub fn vertical_motion(
+    metrics: &impl LayoutMetrics,
+    offset: usize,
+    x: f32,
+    up: bool,
+) -> Option<usize> {
+    let line = metrics.visual_line(offset)?;
+    let target = if up { line.checked_sub(1)? } else { line + 1 };
+
+    let x = x - metrics.column_x(line)? + metrics.column_x(target)?;
+    let y = metrics.line_center_y(target)?;
+    metrics.offset_at_point(x, y)
+}
+
+/// Get byte ranges of all list items in `text`.
+///
+/// List items are separated by at least one empty line.
+pub fn list_items(text: &str) -> Vec<Range<usize>> {
+    let mut items: Vec<Range<usize>> = Vec::new();
+    let mut consecutive_newlines = 2;
+    for (i, c) in text.char_indices() {
+        if c == '\n' {
+            consecutive_newlines += 1;
+            continue;
+        } else if c.is_whitespace() {
+            continue;
+        }
+
+        if consecutive_newlines >= 2 {
+            items.push(i..i);
+        }
+        consecutive_newlines = 0;
+
+        if let Some(item) = items.last_mut() {
+            item.end = i + c.len_utf8();
+        }
+    }
+    items
+}
+
+/// Move the list item at index `from` to index `to`.
+///
+/// The `items` must be the list items of `text`, see [`list_items`]. Text
+/// between items stays in place, which preserves separators and indentation.
+///
+/// Returns the new text and the start of the moved item within it.
+pub fn move_list_item(
+    text: &str,
+    items: &[Range<usize>],
+    from: usize,
+    to: usize,
+) -> (String, usize) {
+    let mut order: Vec<_> = (0..items.len()).filter(|&i| i != from).collect();
+    order.insert(to, from);
+
+    let mut moved = String::with_capacity(text.len());
+    let mut moved_start = 0;
+    let mut last_end = 0;
+    for (slot, &item) in items.iter().zip(&order) {
+        moved.push_str(&text[last_end..slot.start]);
+        if item == from {
+            moved_start = moved.len();
+        }
+        moved.push_str(&text[items[item].clone()]);
+        last_end = slot.end;
+    }
+    moved.push_str(&text[last_end..]);
+
+    (moved, moved_start)
+}
+
+/// List item ending with a colon, followed by more deeply indented items.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Section {
+    /// Byte range of the header item.
+    pub header: Range<usize>,
+    /// Byte range from the start of the first to the end of the last indented item.
+    pub items: Range<usize>,
+}
+
+/// Find all collapsible sections formed by list items.
+///
+/// Sections can be nested, with every section containing all of its nested
+/// sections' items. The `items` must be all list items of a text, see
+/// [`ListItem::find_all`].
+pub fn item_sections(items: &[ListItem]) -> Vec<Section> {
+    let mut sections = Vec::new();
+    for (i, header) in items.iter().enumerate() {
+        if !header.header {
+            continue;
+        }
+
+        let count = items[i + 1..].iter().take_while(|item| item.indent > header.indent).count();
+        if count > 0 {
+            let items = items[i + 1].range.start..items[i + count].range.end;
+            sections.push(Section { header: header.range.clone(), items });
+        }
+    }
+    sections
+}
+
+/// Get the number of every list item within its list.
+///
+/// Items are numbered separately for every indentation level, restarting after
+/// every less indented item.
+pub fn item_numbers<'a>(items: impl IntoIterator<Item = &'a ListItem>) -> Vec<usize> {
+    let mut levels: Vec<(usize, usize)> = Vec::new();
+    items
+        .into_iter()
+        .map(|item| {
+            while levels.last().is_some_and(|(level, _)| *level > item.indent) {
+                levels.pop();
+            }
+
+            match levels.last_mut() {
+                Some((level, number)) if *level == item.indent => *number += 1,
+                _ => levels.push((item.indent, 1)),
+            }
+            levels.last().map_or(1, |(_, number)| *number)
+        })
+        .collect()
+}
+
+/// List item with the properties of its lines.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ListItem {
+    /// Byte range of the item, see [`list_items`].
+    pub range: Range<usize>,
+    /// Indentation of the item's first line, in bytes.
+    pub indent: usize,
+    /// Whether the item ends with a colon, making it a section header.
+    pub header: bool,
+    /// Whether all lines of the item are indented by at least four spaces.
+    pub indented: bool,
+}
+
+impl ListItem {
+    /// Find all list items in `text`.
+    pub fn find_all(text: &str) -> Vec<Self> {
+        list_items(text).into_iter().map(|range| Self::new(text, range)).collect()
+    }
+
+    fn new(text: &str, range: Range<usize>) -> Self {
+        let line_start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        let indented = text[line_start..range.end].split('\n').all(|line| line.starts_with("    "));
+        Self {
+            header: text[range.clone()].ends_with(':'),
+            indent: range.start - line_start,
+            range,
+            indented,
+        }
+    }
+
+    /// Move the item by `offset` bytes.
+    pub fn offset(&self, offset: usize) -> Self {
+        let range = self.range.start + offset..self.range.end + offset;
+        Self { range, ..*self }
+    }
+}
+
+/// Find all lines starting with a code fence.
+///
+/// Returns the ranges from the start of every fence to the end of its line.
+pub fn code_fences(text: &str) -> Vec<Range<usize>> {
+    let mut fences = Vec::new();
+    let mut line_start = 0;
+    for line in text.split('\n') {
+        let content = line.trim_start();
+        let content_start = line_start + line.len() - content.len();
+        let line_end = line_start + line.len();
+        line_start = line_end + 1;
+
+        if content.starts_with(CODE_FENCE) {
+            fences.push(content_start..line_end);
+        }
+    }
+    fences
+}
+
+/// Find all code blocks of a text with length `len`.
+///
+/// Code blocks are either enclosed by lines starting with a code fence, which
+/// may include empty lines, or list items with all lines indented by at least
+/// four spaces. The `fences` and `items` must be all code fences and list items
+/// of the text, see [`code_fences`] and [`ListItem::find_all`].
+pub fn item_code_blocks(
+    fences: &[Range<usize>],
+    items: &[ListItem],
+    len: usize,
+) -> Vec<Range<usize>> {
+    let mut blocks = Vec::new();
+
+    // Pair fences, extending unterminated fences to the end of the text.
+    let mut fence_start = None;
+    for fence in fences {
+        match fence_start.take() {
+            Some(start) => blocks.push(start..fence.end),
+            None => fence_start = Some(fence.start),
+        }
+    }
+    if let Some(start) = fence_start {
+        blocks.push(start..len);
+    }
+
+    // Add indented list items outside of fenced blocks.
+    let fenced = blocks.len();
+    for item in items.iter().filter(|item| item.indented) {
+        if !blocks[..fenced].iter().any(|block| block.contains(&item.range.start)) {
+            blocks.push(item.range.clone());
+        }
+    }
+    blocks.sort_unstable_by_key(|block| block.start);
+
+    blocks
+}
+
+/// Text span rendered in a named accent color, like `{red}text{/}`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ColorSpan {
+    /// Name of the accent color.
+    pub color: String,
+    /// Byte range of the marker starting the span.
+    pub start_marker: Range<usize>,
+    /// Byte range of the marker ending the span.
+    pub end_marker: Range<usize>,
+}
+
+impl ColorSpan {
+    /// Byte range of the colored text between both markers.
+    pub fn content(&self) -> Range<usize> {
+        self.start_marker.end..self.end_marker.start
+    }
+
+    /// Byte range of the span, including both markers.
+    pub fn range(&self) -> Range<usize> {
+        self.start_marker.start..self.end_marker.end
+    }
+}
+
+/// Find all color spans in `text`.
+///
+/// Spans start with a color name in braces and end with the next end marker
+/// within the same list item. Color names consist of letters, digits, `-` and
+/// `_`, and spans cannot be nested.
+pub fn color_spans(text: &str) -> Vec<ColorSpan> {
+    let mut spans = Vec::new();
+    for item in list_items(text) {
+        let mut start = item.start;
+        while let Some(offset) = text[start..item.end].find('{') {
+            let marker_start = start + offset;
+            start = marker_start + 1;
+
+            // Validate the color name.
+            let name = &text[marker_start + 1..item.end];
+            let name = match name.split_once('}') {
+                Some((name, _)) => name,
+                None => break,
+            };
+            let is_name_char = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+            if name.is_empty() || !name.chars().all(is_name_char) {
+                continue;
+            }
+
+            // Find the end of the span.
+            let marker_end = marker_start + name.len() + 2;
+            let end_start = match text[marker_end..item.end].find(COLOR_END_MARKER) {
+                Some(offset) => marker_end + offset,
+                None => continue,
+            };
+            let end_marker = end_start..end_start + COLOR_END_MARKER.len();
+            start = end_marker.end;
+
+            spans.push(ColorSpan {
+                color: name.into(),
+                start_marker: marker_start..marker_end,
+                end_marker,
+            });
+        }
+    }
+    spans
+}
+
+/// Tags used in a note, with the locations of all occurrences.
+#[derive(Default, Debug)]
+pub struct TagIndex {
+    /// Byte ranges of all tags, including their marker, in text order.
+    ranges: Vec<Range<usize>>,
+    /// Indices into `ranges` for every normalized tag name.
+    names: BTreeMap<String, Vec<usize>>,
+}
+
+impl TagIndex {
+    /// Find all tags in `text`.
+    pub fn new(text: &str) -> Self {
+        Self::from_tags(find_tags(text).map(|(range, name)| (range, normalize_tag(name))))
+    }
+
+    /// Create an index from tag ranges with their normalized names, in text order.
+    pub fn from_tags(tags: impl IntoIterator<Item = (Range<usize>, String)>) -> Self {
+        let mut index = Self::default();
+        for (range, name) in tags {
+            index.names.entry(name).or_default().push(index.ranges.len());
+            index.ranges.push(range);
+        }
+        index
+    }
+
+    /// Byte ranges of all tags, in text order.
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    /// Get all occurrences of a tag.
+    ///
+    /// Tags are matched case-insensitively, with an optional tag marker.
+    pub fn occurrences(&self, tag: &str) -> impl Iterator<Item = &Range<usize>> {
+        let indices = self.names.get(&normalize_tag(tag)).map_or(&[][..], Vec::as_slice);
+        indices.iter().map(|index| &self.ranges[*index])
+    }
+
+    /// Check if a tag is used anywhere in the note.
+    pub fn contains(&self, tag: &str) -> bool {
+        self.names.contains_key(&normalize_tag(tag))
+    }
+}
+
+/// Find all tags in `text`, with their names.
+///
+/// Tags start with the tag marker at the beginning of a word, followed by
+/// letters, digits, `-` or `_`, with at least one letter.
+pub fn find_tags(text: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    text.match_indices(TAG_MARKER).filter_map(|(start, _)| {
+        if text[..start].chars().next_back().is_some_and(|c| !c.is_whitespace()) {
+            return None;
+        }
+
+        let name = &text[start + TAG_MARKER.len_utf8()..];
+        let len =
+            name.find(|c: char| !c.is_alphanumeric() && c != '-' && c != '_').unwrap_or(name.len());
+        let name = &name[..len];
+        if !name.chars().any(char::is_alphabetic) {
+            return None;
+        }
+
+        Some((start..start + TAG_MARKER.len_utf8() + len, name))
+    })
+}
+
+/// Get the canonical form of a tag name, used for comparisons.
+pub fn normalize_tag(tag: &str) -> String {
+    tag.strip_prefix(TAG_MARKER).unwrap_or(tag).to_lowercase()
+}
+
+/// Get the length of the markup and indentation before a text block's content.
+pub fn content_prefix_len(block: &str) -> usize {
+    let item = block.trim_end_matches('\n');
+    let line = item.split('\n').next().unwrap_or_default();
+    let content = line.strip_prefix(PIN_MARKER).unwrap_or(line).trim_start();
+
+    // Skip the completion marker, if the entire item is completed.
+    let remainder = &item[line.len() - content.len()..];
+    let content = match content.strip_prefix(COMPLETED_MARKER) {
+        Some(completed) if is_completed(remainder) => completed,
+        _ => content,
+    };
+
+    line.len() - content.len()
+}
+
+/// Get the content range of a list item, excluding its pin marker.
+pub fn item_content(text: &str, item: Range<usize>) -> Range<usize> {
+    let item_text = &text[item.clone()];
+    let content = item_text.strip_prefix(PIN_MARKER).unwrap_or(item_text).trim_start();
+    item.end - content.len()..item.end
+}
+
+/// Check if list item content is marked as completed.
+pub fn is_completed(content: &str) -> bool {
+    content.len() >= 2 * COMPLETED_MARKER.len()
+        && content.starts_with(COMPLETED_MARKER)
+        && content.ends_with(COMPLETED_MARKER)
+}
+
+/// Convert list item contents to a Markdown list.
+pub fn markdown_list(items: &[&str]) -> String {
+    let mut markdown = String::new();
+    for item in items {
+        for (i, line) in item.lines().enumerate() {
+            // Indent continuation lines to keep them within the item.
+            markdown.push_str(if i == 0 { "- " } else { "  " });
+            markdown.push_str(line);
+            markdown.push('\n');
+        }
+    }
+    markdown
+}
+
+/// Convert list item contents to an HTML list.
+///
+/// Completed items are struck through, matching their Markdown markup.
+pub fn html_list(items: &[&str]) -> String {
+    let mut html = String::from("<ul>");
+    for item in items {
+        let completed = is_completed(item);
+        let content = if completed {
+            &item[COMPLETED_MARKER.len()..item.len() - COMPLETED_MARKER.len()]
+        } else {
+            *item
+        };
+
+        let escaped = content.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+        let lines = escaped.lines().collect::<Vec<_>>().join("<br>");
+
+        if completed {
+            html.push_str(&format!("<li><s>{lines}</s></li>"));
+        } else {
+            html.push_str(&format!("<li>{lines}</li>"));
+        }
+    }
+    html.push_str("</ul>");
+    html
+}
+
+/// Clean up text pasted from other applications.
+///
+/// Line endings are converted to `\n`, tabs are replaced by spaces, and
+/// invisible characters, trailing whitespace and runs of more than one blank
+/// line are removed. With `lines_as_items`, every line becomes a separate list
+/// item.
+pub fn normalize_paste(text: &str, lines_as_items: bool) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+    let mut normalized = String::with_capacity(text.len());
+    let mut blank_lines = 0;
+    for (i, line) in text.split('\n').enumerate() {
+        let line: String = line
+            .chars()
+            .map(|c| if c == '\t' { ' ' } else { c })
+            .filter(|c| !c.is_control() && !INVISIBLE_CHARS.contains(c))
+            .collect();
+        let line = line.trim_end();
+
+        // Skip all but the first of consecutive blank lines.
+        if line.is_empty() {
+            blank_lines += 1;
+            if blank_lines > 1 {
+                continue;
+            }
+        } else {
+            blank_lines = 0;
+        }
+
+        if i > 0 {
+            normalized.push('\n');
+        }
+        normalized.push_str(line);
+    }
+
+    if lines_as_items {
+        let lines: Vec<_> = normalized.lines().filter(|line| !line.trim().is_empty()).collect();
+        return lines.join("\n\n");
+    }
+
+    normalized
+}
+
+/// Get the start of the grapheme cluster before `offset`.
+pub fn previous_grapheme(text: &str, offset: usize) -> usize {
+    let mut cursor = GraphemeCursor::new(offset, text.len(), true);
+    cursor.prev_boundary(text, 0).ok().flatten().unwrap_or(0)
+}
+
+/// Get the end of the grapheme cluster after `offset`.
+pub fn next_grapheme(text: &str, offset: usize) -> usize {
+    let mut cursor = GraphemeCursor::new(offset, text.len(), true);
+    cursor.next_boundary(text, 0).ok().flatten().unwrap_or(text.len())
+}
+
+/// Get the range of the word around `offset`.
+///
+/// Words are delimited by non-alphanumeric characters.
+pub fn word_at(text: &str, offset: usize) -> Range<usize> {
+    let mut word_start = 0;
+    let mut word_end = text.len();
+    for (i, c) in text.char_indices() {
+        let c_end = i + c.len_utf8();
+        if c_end < offset && !c.is_alphanumeric() {
+            word_start = c_end;
+        } else if i > offset && !c.is_alphanumeric() {
+            word_end = i;
+            break;
+        }
+    }
+    word_start..word_end
+}
+
+/// Find the natural-language date around `offset`, like `tomorrow` or `next fri`.
+///
+/// Returns the range of the longest matching phrase and the date it refers
+/// to, relative to `today`.
+pub fn relative_date_at(
+    text: &str,
+    offset: usize,
+    today: NaiveDate,
+) -> Option<(Range<usize>, NaiveDate)> {
+    // Split the line around the offset into words.
+    let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[offset..].find('\n').map_or(text.len(), |i| offset + i);
+    let mut words = Vec::new();
+    let mut word_start = None;
+    for (i, c) in text[line_start..line_end].char_indices() {
+        match (word_start, c.is_alphanumeric()) {
+            (None, true) => word_start = Some(line_start + i),
+            (Some(start), false) => {
+                words.push(start..line_start + i);
+                word_start = None;
+            },
+            _ => (),
+        }
+    }
+    if let Some(start) = word_start {
+        words.push(start..line_end);
+    }
+
+    for len in (1..=3).rev() {
+        for phrase in words.windows(len) {
+            let range = phrase[0].start..phrase[len - 1].end;
+            if offset < range.start || offset > range.end {
+                continue;
+            }
+
+            // Only combine words separated by whitespace.
+            let separated = phrase
+                .windows(2)
+                .all(|pair| text[pair[0].end..pair[1].start].chars().all(char::is_whitespace));
+            if !separated {
+                continue;
+            }
+
+            let phrase: Vec<_> =
+                phrase.iter().map(|word| text[word.clone()].to_lowercase()).collect();
+            if let Some(date) = parse_relative_date(&phrase, today) {
+                return Some((range, date));
+            }
+        }
+    }
+
+    None
+}
+
+/// Parse the lowercase words of a natural-language date.
+fn parse_relative_date(words: &[String], today: NaiveDate) -> Option<NaiveDate> {
+    let words: Vec<_> = words.iter().map(String::as_str).collect();
+    match words.as_slice() {
+        ["today"] => Some(today),
+        ["tomorrow"] => today.succ_opt(),
+        ["yesterday"] => today.pred_opt(),
+        ["next", "week"] => today.checked_add_days(Days::new(7)),
+        ["next", "month"] => today.checked_add_months(Months::new(1)),
+        ["next", weekday] => {
+            let days = days_until(today.weekday(), parse_weekday(weekday)?);
+            today.checked_add_days(Days::new(if days == 0 { 7 } else { days }))
+        },
+        ["last", weekday] => {
+            let days = days_until(parse_weekday(weekday)?, today.weekday());
+            today.checked_sub_days(Days::new(if days == 0 { 7 } else { days }))
+        },
+        [weekday] => {
+            today.checked_add_days(Days::new(days_until(today.weekday(), parse_weekday(weekday)?)))
+        },
+        ["in", count, unit] => {
+            let count: u32 = count.parse().ok()?;
+            match *unit {
+                "day" | "days" => today.checked_add_days(Days::new(count.into())),
+                "week" | "weeks" => today.checked_add_days(Days::new(u64::from(count) * 7)),
+                "month" | "months" => today.checked_add_months(Months::new(count)),
+                _ => None,
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Parse a full or abbreviated weekday name.
+fn parse_weekday(name: &str) -> Option<Weekday> {
+    match name {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thur" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Days from one weekday until the next occurrence of another, or zero if equal.
+fn days_until(from: Weekday, to: Weekday) -> u64 {
+    u64::from((7 + to.num_days_from_monday() - from.num_days_from_monday()) % 7)
+}
+
+/// Replacement of typed text by smart input transforms.
+#[derive(PartialEq, Debug)]
+pub struct InputTransform {
+    /// Range of existing text replaced by the input.
+    pub range: Range<usize>,
+    /// Text inserted in place of the range.
+    pub text: String,
+    /// Cursor position within the inserted text.
+    pub cursor: usize,
+}
+
+/// Apply the enabled smart input transforms to a character typed at `cursor`.
+///
+/// Returns `None` if the input should be inserted unchanged.
+pub fn transform_input(
+    text: &Text,
+    cursor: usize,
+    input: &str,
+    editing: &Editing,
+) -> Option<InputTransform> {
+    let mut chars = input.chars();
+    let (typed, None) = (chars.next()?, chars.next()) else {
+        return None;
+    };
+    let previous = text.char_before(cursor);
+    let next = text.char_at(cursor);
+
+    // Replace `--` with an en dash.
+    if editing.smart_dashes && typed == '-' && previous == Some('-') {
+        let text = String::from('\u{2013}');
+        return Some(InputTransform { range: cursor - 1..cursor, cursor: text.len(), text });
+    }
+
+    // Quotes after whitespace or opening characters start a quotation.
+    let opening = previous.is_none_or(|previous| {
+        previous.is_whitespace() || AUTO_PAIRS.iter().any(|&(open, _)| open == previous)
+    });
+
+    let c = match typed {
+        '"' if editing.smart_quotes && opening => '\u{201c}',
+        '"' if editing.smart_quotes => '\u{201d}',
+        '\'' if editing.smart_quotes && opening => '\u{2018}',
+        '\'' if editing.smart_quotes => '\u{2019}',
+        c => c,
+    };
+
+    if editing.auto_pair {
+        // Step over closing characters instead of duplicating them.
+        let closing = AUTO_PAIRS.iter().any(|&(_, close)| close == c);
+        if closing && next == Some(c) {
+            let range = cursor..cursor + c.len_utf8();
+            return Some(InputTransform { range, text: c.to_string(), cursor: c.len_utf8() });
+        }
+
+        // Close opening characters, unless they are directly followed by text.
+        let is_quote = matches!(c, '"' | '\'' | '\u{201c}' | '\u{2018}');
+        let followed = next.is_some_and(|next| {
+            !next.is_whitespace() && !AUTO_PAIRS.iter().any(|&(_, close)| close == next)
+        });
+        let pair = AUTO_PAIRS.iter().find(|&&(open, _)| open == c);
+        if let Some(&(open, close)) = pair
+            && (opening || !is_quote)
+            && !followed
+        {
+            let text = format!("{open}{close}");
+            return Some(InputTransform { range: cursor..cursor, text, cursor: open.len_utf8() });
+        }
+    }
+
+    // Insert curly quotes in place of straight ones.
+    (c != typed).then(|| InputTransform {
+        range: cursor..cursor,
+        text: c.to_string(),
+        cursor: c.len_utf8(),
+    })
+}
+
+/// Get the end of an empty pair of auto-closed characters around `cursor`.
+///
+/// This allows removing both characters of a pair like `(|)` at once.
+pub fn empty_pair_end(text: &Text, cursor: usize) -> Option<usize> {
+    let previous = text.char_before(cursor)?;
+    let next = text.char_at(cursor)?;
+    AUTO_PAIRS.contains(&(previous, next)).then(|| cursor + next.len_utf8())
+}
+
+/// Clamp a selection to a text of length `len`.
+///
+/// Returns `None` if the resulting selection is empty.
+pub fn selection_range(range: impl RangeBounds<usize>, len: usize) -> Option<Range<usize>> {
+    let start = match range.start_bound() {
+        Bound::Included(start) => *start,
+        Bound::Excluded(start) => *start + 1,
+        Bound::Unbounded => usize::MIN,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(end) => *end + 1,
+        Bound::Excluded(end) => *end,
+        Bound::Unbounded => usize::MAX,
+    };
+    let end = end.min(len);
+
+    (start < end).then_some(start..end)
+}
+
+/// Insert a newline at `cursor`, following list semantics.
+///
+/// Inside a list item the new line continues the item, while a newline on an
+/// empty line separates the following text into a new item. On an empty
+/// item, the item is removed instead of inserting more blank lines.
+///
+/// Returns the new cursor position.
+pub fn insert_newline(text: &mut Text, cursor: usize) -> usize {
+    let line = text.line_range(cursor);
+    let line_empty = text.get(line.clone()).is_some_and(|line| line.trim().is_empty());
+    let previous_empty =
+        line.start == 1 || (line.start > 1 && text.byte(line.start - 2) == Some(b'\n'));
+
+    if line_empty && previous_empty {
+        // Remove the empty item, together with its separating newline.
+        text.remove(line.start - 1..line.end);
+        line.start - 1
+    } else {
+        text.insert(cursor, "\n");
+        cursor + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Width of a character in the test layout.
+    const CELL_WIDTH: f32 = 10.;
+
+    /// Height of a line in the test layout.
+    const LINE_HEIGHT: f32 = 20.;
+
+    /// Horizontal distance between columns in the test layout.
+    const COLUMN_STRIDE: f32 = 1000.;
+
+    /// Monospace layout without wrapping, flowing lines into fixed-size columns.
+    struct Grid {
+        lines: Vec<Range<usize>>,
+        lines_per_column: usize,
+    }
+
+    impl Grid {
+        fn new(text: &str, lines_per_column: usize) -> Self {
+            let mut lines = Vec::new();
+            let mut start = 0;
+            for line in text.split('\n') {
+                lines.push(start..start + line.len());
+                start += line.len() + 1;
+            }
+            Self { lines, lines_per_column }
+        }
+
+        /// Get the cursor's horizontal position at `offset`.
+        fn x(&self, offset: usize) -> f32 {
+            let line = self.visual_line(offset).unwrap();
+            let column = self.column_x(line).unwrap();
+            column + (offset - self.lines[line].start) as f32 * CELL_WIDTH
+        }
+    }
+
+    impl LayoutMetrics for Grid {
+        fn visual_line(&self, offset: usize) -> Option<usize> {
+            self.lines.iter().position(|line| line.contains(&offset) || line.end == offset)
+        }
+
+        fn line_center_y(&self, line: usize) -> Option<f32> {
+            (line < self.lines.len())
+                .then(|| ((line % self.lines_per_column) as f32 + 0.5) * LINE_HEIGHT)
+        }
+
+        fn column_x(&self, line: usize) -> Option<f32> {
+            (line < self.lines.len()).then(|| (line / self.lines_per_column) as f32 * COLUMN_STRIDE)
+        }
+
+        fn offset_at_point(&self, x: f32, y: f32) -> Option<usize> {
+            let column = (x / COLUMN_STRIDE).floor() as usize;
+            let row = (y / LINE_HEIGHT).floor() as usize;
+            let line = self.lines.get(column * self.lines_per_column + row)?;
+
+            let x = x - column as f32 * COLUMN_STRIDE;
+            let index = (x / CELL_WIDTH).round() as usize;
+            Some(line.start + index.min(line.len()))
+        }
+    }
+
+    #[test]
+    fn list_items_separated_by_blank_lines() {
+        let text = "one\ntwo\n\nthree\n \n\n  four  ";
+        let items: Vec<_> = list_items(text).into_iter().map(|item| &text[item]).collect();
+        assert_eq!(items, ["one\ntwo", "three", "four"]);
+    }
+
+    #[test]
+    fn list_items_empty() {
+        assert!(list_items("").is_empty());
+        assert!(list_items("\n \n\t\n").is_empty());
+    }
+
+    #[test]
+    fn sections_contain_indented_items() {
+        let text = "Groceries:\n\n  milk\n\n  Fruit:\n\n    apples\n\n  eggs\n\nLater:\n\nnot:";
+        let sections: Vec<_> = item_sections(&ListItem::find_all(text))
+            .into_iter()
+            .map(|section| (&text[section.header], &text[section.items]))
+            .collect();
+        assert_eq!(
+            sections,
+            [("Groceries:", "milk\n\n  Fruit:\n\n    apples\n\n  eggs"), ("Fruit:", "apples"),]
+        );
+    }
+
+    #[test]
+    fn move_items_between_separators() {
+        let text = "a\n\n  bb\n\n\nc";
+        let items = list_items(text);
+        assert_eq!(move_list_item(text, &items, 0, 2), ("bb\n\n  c\n\n\na".into(), 10));
+        assert_eq!(move_list_item(text, &items, 2, 0), ("c\n\n  a\n\n\nbb".into(), 0));
+        assert_eq!(move_list_item(text, &items, 1, 1), (text.into(), 5));
+    }
+
+    #[test]
+    fn numbers_per_indentation_level() {
+        let text = "a\n\nb\n\n  c\n\n  d\n\n    e\n\n  f\n\ng\n\n  h";
+        let items = ListItem::find_all(text);
+        assert_eq!(item_numbers(&items), [1, 2, 1, 2, 1, 3, 3, 1]);
+    }
+
+    #[test]
+    fn color_spans_within_items() {
+        let text = "{red}urgent{/} {x y}\n{blue}a {green}b{/} c{/}\n\n{red}open\n\nnext{/}";
+        let spans: Vec<_> = color_spans(text)
+            .into_iter()
+            .map(|span| (span.color.clone(), &text[span.content()]))
+            .collect();
+        assert_eq!(spans, [("red".into(), "urgent"), ("blue".into(), "a {green}b")]);
+    }
+
+    #[test]
+    fn fenced_and_indented_code() {
+        let text = "a\n\n    let x;\n    x\n\n  y\n\n```\nfn f()\n\n    {}\n```\n\n```\nopen";
+        let items = ListItem::find_all(text);
+        let blocks: Vec<_> = item_code_blocks(&code_fences(text), &items, text.len())
+            .into_iter()
+            .map(|block| &text[block])
+            .collect();
+        assert_eq!(blocks, ["let x;\n    x", "```\nfn f()\n\n    {}\n```", "```\nopen"]);
+    }
+
+    #[test]
+    fn tags_at_word_start() {
+        let text = "#buy milk #Errands\nissue#4 #42 ##x #to-do, # none #ünï_code";
+        let index = TagIndex::new(text);
+        let tags: Vec<_> = index.ranges().iter().map(|tag| &text[tag.clone()]).collect();
+        assert_eq!(tags, ["#buy", "#Errands", "#to-do", "#ünï_code"]);
+
+        assert!(index.contains("errands"));
+        assert!(index.contains("#ERRANDS"));
+        assert!(!index.contains("42"));
+        assert_eq!(index.occurrences("#errands").collect::<Vec<_>>(), [&(10..18)]);
+        assert_eq!(index.occurrences("missing").count(), 0);
+    }
+
+    #[test]
+    fn content_prefix_skips_markup() {
+        assert_eq!(content_prefix_len("item"), 0);
+        assert_eq!(content_prefix_len("  item"), 2);
+        assert_eq!(content_prefix_len("! item\n\n"), 2);
+        assert_eq!(content_prefix_len("!~~done~~"), 3);
+        assert_eq!(content_prefix_len("~~done\nstill~~\n\n"), 2);
+
+        // Only completed items have their marker skipped.
+        assert_eq!(content_prefix_len("~~partial"), 0);
+    }
+
+    #[test]
+    fn item_content_excludes_pin() {
+        let text = "first\n\n!  pinned";
+        let items = list_items(text);
+        assert_eq!(&text[item_content(text, items[0].clone())], "first");
+        assert_eq!(&text[item_content(text, items[1].clone())], "pinned");
+    }
+
+    #[test]
+    fn completed_items() {
+        assert!(is_completed("~~done~~"));
+        assert!(is_completed("~~~~"));
+        assert!(!is_completed("~~~"));
+        assert!(!is_completed("~~open"));
+        assert!(!is_completed("open~~"));
+    }
+
+    #[test]
+    fn markdown_list_indents_continuation_lines() {
+        let markdown = markdown_list(&["one", "two\nlines", "~~done~~"]);
+        assert_eq!(markdown, "- one\n- two\n  lines\n- ~~done~~\n");
+    }
+
+    #[test]
+    fn html_list_escapes_and_strikes_through() {
+        let html = html_list(&["a < b & c", "two\nlines", "~~done~~"]);
+        assert_eq!(
+            html,
+            "<ul><li>a &lt; b &amp; c</li><li>two<br>lines</li><li><s>done</s></li></ul>"
+        );
+    }
+
+    #[test]
+    fn normalize_paste_cleans_up_text() {
+        let text = "one\r\ntwo\t \rthree\u{200B}\n\n\n\nfour\u{7}";
+        assert_eq!(normalize_paste(text, false), "one\ntwo\nthree\n\nfour");
+
+        // Zero-width joiners are required for emoji sequences.
+        let emoji = "\u{1F469}\u{200D}\u{1F4BB}";
+        assert_eq!(normalize_paste(emoji, false), emoji);
+    }
+
+    #[test]
+    fn normalize_paste_lines_as_items() {
+        let text = "one\ntwo\n\n\nthree\n";
+        assert_eq!(normalize_paste(text, true), "one\n\ntwo\n\nthree");
+    }
+
+    #[test]
+    fn grapheme_movement() {
+        let text = "ae\u{301}\u{1F469}\u{200D}\u{1F4BB}z";
+        let accent_end = 1 + "e\u{301}".len();
+        let emoji_end = accent_end + "\u{1F469}\u{200D}\u{1F4BB}".len();
+
+        assert_eq!(next_grapheme(text, 0), 1);
+        assert_eq!(next_grapheme(text, 1), accent_end);
+        assert_eq!(next_grapheme(text, accent_end), emoji_end);
+        assert_eq!(next_grapheme(text, text.len()), text.len());
+
+        assert_eq!(previous_grapheme(text, text.len()), emoji_end);
+        assert_eq!(previous_grapheme(text, emoji_end), accent_end);
+        assert_eq!(previous_grapheme(text, accent_end), 1);
+        assert_eq!(previous_grapheme(text, 0), 0);
+    }
+
+    #[test]
+    fn rope_grapheme_movement() {
+        // Span multiple rope chunks, to cover graphemes crossing chunk boundaries.
+        let grapheme = "e\u{301}\u{1F469}\u{200D}\u{1F4BB}";
+        let text = format!("a{}", grapheme.repeat(500));
+        let rope = Text::from(text.clone());
+
+        let mut offset = 0;
+        while offset < text.len() {
+            let next = next_grapheme(&text, offset);
+            assert_eq!(rope.next_grapheme(offset), next);
+            assert_eq!(rope.previous_grapheme(next), offset);
+            offset = next;
+        }
+        assert_eq!(rope.next_grapheme(text.len()), text.len());
+        assert_eq!(rope.previous_grapheme(0), 0);
+    }
+
+    #[test]
+    fn classify_lines() {
+        assert_eq!(LineKind::classify("https://example.org/path"), LineKind::Url);
+        assert_eq!(LineKind::classify("  www.example.org "), LineKind::Url);
+        assert_eq!(LineKind::classify("+1 (555) 123-4567"), LineKind::Number);
+        assert_eq!(LineKind::classify("12.50"), LineKind::Number);
+        assert_eq!(LineKind::classify("see https://example.org"), LineKind::Text);
+        assert_eq!(LineKind::classify("://missing"), LineKind::Text);
+        assert_eq!(LineKind::classify("- ."), LineKind::Text);
+        assert_eq!(LineKind::classify(""), LineKind::Text);
+    }
+
+    #[test]
+    fn text_change_region() {
+        let change = TextChange::new("hello world", "hello brave world");
+        assert_eq!((change.start, change.old_end, change.new_end), (6, 6, 12));
+
+        let change = TextChange::new("abcabc", "abc");
+        assert_eq!((change.start, change.old_end, change.new_end), (3, 6, 3));
+
+        let change = TextChange::new("same", "same");
+        assert_eq!((change.start, change.old_end, change.new_end), (4, 4, 4));
+    }
+
+    #[test]
+    fn text_change_char_boundaries() {
+        // Both strings share the first byte of `ä` and `ö`.
+        let change = TextChange::new("xäy", "xöy");
+        assert_eq!((change.start, change.old_end, change.new_end), (1, 3, 3));
+    }
+
+    #[test]
+    fn combined_text_changes() {
+        let old = "abcdef";
+        let replace = TextEdit { range: 1..2, text: "XY".into() };
+        let remove = TextEdit { range: 5..6, text: String::new() };
+        let change = TextChange::from(&replace).then(&TextChange::from(&remove));
+
+        let new = "aXYcdf";
+        assert_eq!((&old[..change.start], &new[..change.start]), ("a", "a"));
+        assert_eq!((&old[change.old_end..], &new[change.new_end..]), ("f", "f"));
+
+        let insert = TextEdit { range: 0..0, text: "12".into() };
+        let change = change.then(&TextChange::from(&insert));
+        assert_eq!((change.start, change.old_end, change.new_end), (0, 5, 7));
+    }
+
+    #[test]
+    fn text_change_keeps_position_after_earlier_edit() {
+        let old = "first\nsecond line\nthird";
+        let new = "first line\nsecond line\nthird";
+        let change = TextChange::new(old, new);
+
+        // Cursor and selection in a later line move with the inserted text.
+        let cursor = old.find("line").unwrap();
+        assert_eq!(&new[change.map(cursor)..], "line\nthird");
+        let selection = change.map_range(old.find("third").unwrap()..old.len());
+        assert_eq!(&new[selection], "third");
+
+        // Offsets before the edit are unchanged.
+        assert_eq!(change.map(3), 3);
+    }
+
+    #[test]
+    fn text_change_map_offsets() {
+        let change = TextChange::new("one two three", "one 2 three");
+        assert_eq!(change.map(2), 2);
+        assert_eq!(change.map(4), 4);
+        assert_eq!(change.map(5), 5);
+        assert_eq!(change.map(7), 5);
+        assert_eq!(change.map(13), 11);
+    }
+
+    #[test]
+    fn word_selection() {
+        let text = "one two-three four";
+        assert_eq!(word_at(text, 0), 0..3);
+        assert_eq!(word_at(text, 5), 4..7);
+        assert_eq!(word_at(text, 9), 8..13);
+        assert_eq!(word_at(text, text.len()), 14..18);
+        assert_eq!(word_at("", 0), 0..0);
+    }
+
+    #[test]
+    fn relative_dates() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 15).unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2024, 5, day).unwrap();
+
+        assert_eq!(relative_date_at("call mom tomorrow", 12, today), Some((9..17, date(16))));
+        assert_eq!(relative_date_at("due next Fri!", 8, today), Some((4..12, date(17))));
+        assert_eq!(relative_date_at("fri", 0, today), Some((0..3, date(17))));
+        assert_eq!(relative_date_at("wed", 0, today), Some((0..3, today)));
+        assert_eq!(relative_date_at("next wed", 0, today), Some((0..8, date(22))));
+        assert_eq!(relative_date_at("last wed", 0, today), Some((0..8, date(8))));
+        assert_eq!(relative_date_at("in 3 days", 4, today), Some((0..9, date(18))));
+        assert_eq!(relative_date_at("a\nin 2 weeks", 3, today), Some((2..12, date(29))));
+        assert_eq!(relative_date_at("today, fri", 2, today), Some((0..5, today)));
+        assert_eq!(relative_date_at("next\nfri", 0, today), None);
+        assert_eq!(relative_date_at("no date", 3, today), None);
+    }
+
+    #[test]
+    fn smart_input_transforms() {
+        let all = Editing { auto_pair: true, smart_dashes: true, smart_quotes: true };
+        let transform = |text: &str, cursor, input, editing: &Editing| {
+            let transform = transform_input(&Text::from(text.to_owned()), cursor, input, editing)?;
+            let mut text = text.to_owned();
+            text.replace_range(transform.range.clone(), &transform.text);
+            Some((text, transform.range.start + transform.cursor))
+        };
+
+        assert_eq!(transform("a-", 2, "-", &all), Some(("a\u{2013}".into(), 4)));
+        assert_eq!(transform("a ", 2, "(", &all), Some(("a ()".into(), 3)));
+        assert_eq!(transform("()", 1, ")", &all), Some(("()".into(), 2)));
+        assert_eq!(transform("a", 0, "(", &all), None);
+        assert_eq!(transform("", 0, "\"", &all), Some(("\u{201c}\u{201d}".into(), 3)));
+        assert_eq!(transform("don", 3, "'", &all), Some(("don\u{2019}".into(), 6)));
+        assert_eq!(transform("(a", 2, "b", &all), None);
+
+        let pairs = Editing { auto_pair: true, ..Default::default() };
+        assert_eq!(transform("", 0, "\"", &pairs), Some(("\"\"".into(), 1)));
+        assert_eq!(transform("\"a\"", 2, "\"", &pairs), Some(("\"a\"".into(), 3)));
+        assert_eq!(transform("don", 3, "'", &pairs), None);
+        assert_eq!(transform("a-", 2, "-", &pairs), None);
+
+        assert_eq!(empty_pair_end(&Text::from(String::from("a()")), 2), Some(3));
+        assert_eq!(empty_pair_end(&Text::from(String::from("(a)")), 2), None);
+    }
+
+    #[test]
+    fn selection_clamping() {
+        assert_eq!(selection_range(2..5, 10), Some(2..5));
+        assert_eq!(selection_range(2..=5, 10), Some(2..6));
+        assert_eq!(selection_range(.., 10), Some(0..10));
+        assert_eq!(selection_range(8..20, 10), Some(8..10));
+        assert_eq!(selection_range(5..5, 10), None);
+        assert_eq!(selection_range(12.., 10), None);
+    }
+
+    #[test]
+    fn newline_continues_item() {
+        let mut text = Text::from(String::from("one"));
+        assert_eq!(insert_newline(&mut text, 3), 4);
+        assert_eq!(text.as_str(), "one\n");
+    }
+
+    #[test]
+    fn newline_starts_new_item() {
+        let mut text = Text::from(String::from("one\n"));
+        assert_eq!(insert_newline(&mut text, 4), 5);
+        assert_eq!(text.as_str(), "one\n\n");
+    }
+
+    #[test]
+    fn newline_removes_empty_item() {
+        let mut text = Text::from(String::from("one\n\n"));
+        assert_eq!(insert_newline(&mut text, 5), 4);
+        assert_eq!(text.as_str(), "one\n");
+
+        let mut text = Text::from(String::from("\n"));
+        assert_eq!(insert_newline(&mut text, 1), 0);
+        assert_eq!(text.as_str(), "");
+    }
+
+    #[test]
+    fn kinetic_scrolling_decelerates() {
+        let mut scroll = Scroll { offset: 0., velocity: -1000. };
+
+        assert!(scroll.animate(Duration::from_millis(100)));
+        assert_eq!(scroll.offset, -100.);
+        assert!(scroll.velocity > -1000. && scroll.velocity < 0.);
+
+        while scroll.animate(Duration::from_millis(100)) {}
+        assert_eq!(scroll.velocity, 0.);
+        assert!(!scroll.animate(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn reveal_scrolls_minimal_distance() {
+        // Lines below the viewport are scrolled to its bottom.
+        let mut scroll = Scroll::default();
+        scroll.reveal(150., 100.);
+        assert_eq!(scroll.offset, -50.);
+
+        // Visible lines do not scroll.
+        scroll.reveal(120., 100.);
+        assert_eq!(scroll.offset, -50.);
+
+        // Lines above the viewport are scrolled to its top.
+        scroll.reveal(20., 100.);
+        assert_eq!(scroll.offset, -20.);
+    }
+
+    #[test]
+    fn clamp_stops_kinetic_scrolling() {
+        let mut scroll = Scroll { offset: 10., velocity: 500. };
+        scroll.clamp(300., 100.);
+        assert_eq!(scroll, Scroll { offset: 0., velocity: 0. });
+
+        let mut scroll = Scroll { offset: -250., velocity: -500. };
+        scroll.clamp(300., 100.);
+        assert_eq!(scroll, Scroll { offset: -200., velocity: 0. });
+
+        let mut scroll = Scroll { offset: -50., velocity: -500. };
+        scroll.clamp(300., 100.);
+        assert_eq!(scroll, Scroll { offset: -50., velocity: -500. });
+
+        // Content shorter than the viewport cannot be scrolled.
+        let mut scroll = Scroll { offset: -50., velocity: 0. };
+        scroll.clamp(50., 100.);
+        assert_eq!(scroll.offset, 0.);
+    }
+
+    #[test]
+    fn vertical_motion_keeps_column() {
+        let grid = Grid::new("abcdef\nab\nabcdef", usize::MAX);
+
+        // Move down from the middle of the first line, clamping to the shorter line.
+        let down = vertical_motion(&grid, 4, grid.x(4), false);
+        assert_eq!(down, Some(9));
+
+        let up = vertical_motion(&grid, 15, grid.x(15), true);
+        assert_eq!(up, Some(9));
+
+        // Keeping the original position allows returning to it.
+        assert_eq!(vertical_motion(&grid, 9, grid.x(4), false), Some(14));
+    }
+
+    #[test]
+    fn vertical_motion_at_boundaries() {
+        let grid = Grid::new("one\ntwo", usize::MAX);
+        assert_eq!(vertical_motion(&grid, 1, grid.x(1), true), None);
+        assert_eq!(vertical_motion(&grid, 5, grid.x(5), false), None);
+    }
+
+    #[test]
+    fn vertical_motion_across_columns() {
+        let grid = Grid::new("abc\nabc\nabc\nabc", 2);
+
+        // Moving down from the bottom of the first column enters the second.
+        let down = vertical_motion(&grid, 6, grid.x(6), false);
+        assert_eq!(down, Some(10));
+        assert_eq!(grid.visual_line(10), Some(2));
+
+        let up = vertical_motion(&grid, 10, grid.x(10), true);
+        assert_eq!(up, Some(6));
+    }
+}
@@ -1,9 +1,97 @@
 //! Shared geometry types.
 
-use std::ops::{Mul, Sub, SubAssign};
+use std::ops::{Add, AddAssign, Deref, DerefMut, Div, Mul, Sub, SubAssign};
 
+use serde::{Deserialize, Serialize};
 use skia_safe::Point;
 
+/// Geometry in logical pixels, independent of the output's scale factor.
+#[derive(Serialize, Deserialize, PartialEq, Eq, Copy, Clone, Default, Debug)]
+#[serde(transparent)]
+pub struct Logical<T>(pub T);
+
+impl<T: Scale> Logical<T> {
+    /// Convert to physical pixels.
+    pub fn to_physical(self, scale: f64) -> Physical<T> {
+        Physical(self.0.scale(scale))
+    }
+}
+
+/// Geometry in physical pixels, as used for rendering.
+#[derive(PartialEq, Eq, Copy, Clone, Default, Debug)]
+pub struct Physical<T>(pub T);
+
+impl<T: Scale> Physical<T> {
+    /// Convert to logical pixels.
+    pub fn to_logical(self, scale: f64) -> Logical<T> {
+        Logical(self.0.scale(1. / scale))
+    }
+}
+
+/// Implement shared traits for a coordinate space wrapper.
+macro_rules! impl_space {
+    ($space:ident) => {
+        impl<T> Deref for $space<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        impl<T> DerefMut for $space<T> {
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        }
+
+        impl<T: Add<Output = T>> Add for $space<T> {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                Self(self.0 + other.0)
+            }
+        }
+
+        impl<T: Sub<Output = T>> Sub for $space<T> {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                Self(self.0 - other.0)
+            }
+        }
+    };
+}
+
+impl_space!(Logical);
+impl_space!(Physical);
+
+/// Conversion between coordinate spaces.
+pub trait Scale {
+    /// Multiply all dimensions by a scale factor.
+    fn scale(self, scale: f64) -> Self;
+}
+
+impl Scale for f64 {
+    fn scale(self, scale: f64) -> Self {
+        self * scale
+    }
+}
+
+impl Scale for Position<f64> {
+    fn scale(self, scale: f64) -> Self {
+        self * scale
+    }
+}
+
+impl Scale for Size {
+    fn scale(self, scale: f64) -> Self {
+        let width = (self.width as f64 * scale).round() as u32;
+        let height = (self.height as f64 * scale).round() as u32;
+        Self { width, height }
+    }
+}
+
 /// 2D object position.
 #[derive(PartialEq, Eq, Copy, Clone, Default, Debug)]
 pub struct Position<T = i32> {
@@ -17,6 +105,15 @@ impl<T> Position<T> {
     }
 }
 
+impl Position<f64> {
+    /// Clamp the position to the area between the origin and `size`.
+    pub fn clamp(self, size: Size) -> Self {
+        let x = self.x.clamp(0., size.width as f64);
+        let y = self.y.clamp(0., size.height as f64);
+        Self { x, y }
+    }
+}
+
 impl<T> From<(T, T)> for Position<T> {
     fn from((x, y): (T, T)) -> Self {
         Self { x, y }
@@ -29,6 +126,23 @@ impl From<Position<f64>> for Point {
     }
 }
 
+impl<T: Add<Output = T>> Add<Position<T>> for Position<T> {
+    type Output = Self;
+
+    fn add(mut self, other: Position<T>) -> Self {
+        self.x = self.x + other.x;
+        self.y = self.y + other.y;
+        self
+    }
+}
+
+impl<T: AddAssign> AddAssign<Position<T>> for Position<T> {
+    fn add_assign(&mut self, other: Position<T>) {
+        self.x += other.x;
+        self.y += other.y;
+    }
+}
+
 impl<T: Sub<Output = T>> Sub<Position<T>> for Position<T> {
     type Output = Self;
 
@@ -56,8 +170,18 @@ impl Mul<f64> for Position<f64> {
     }
 }
 
+impl Div<f64> for Position<f64> {
+    type Output = Position<f64>;
+
+    fn div(mut self, scale: f64) -> Self {
+        self.x /= scale;
+        self.y /= scale;
+        self
+    }
+}
+
 /// 2D object size.
-#[derive(PartialEq, Eq, Copy, Clone, Default, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Copy, Clone, Default, Debug)]
 pub struct Size<T = u32> {
     pub width: T,
     pub height: T,
@@ -81,12 +205,12 @@ impl From<Size> for Size<f32> {
     }
 }
 
-impl Mul<f64> for Size {
+impl<T: Add<Output = T>> Add<Size<T>> for Size<T> {
     type Output = Self;
 
-    fn mul(mut self, scale: f64) -> Self {
-        self.width = (self.width as f64 * scale).round() as u32;
-        self.height = (self.height as f64 * scale).round() as u32;
+    fn add(mut self, other: Self) -> Self {
+        self.width = self.width + other.width;
+        self.height = self.height + other.height;
         self
     }
 }
@@ -0,0 +1,393 @@
+//! Note header bar.
+
+use std::path::PathBuf;
+
+use skia_safe::textlayout::{
+    FontCollection, Paragraph, ParagraphBuilder, ParagraphStyle, TextStyle,
+};
+use skia_safe::{Canvas as SkiaCanvas, Color4f, Paint, Point, Rect};
+
+use crate::config::{Colors, Config, FontFamily};
+use crate::skia;
+
+/// Height of the header bar at scale 1.
+const HEADER_HEIGHT: f64 = 40.;
+
+/// Label of the new note button.
+const NEW_NOTE_LABEL: &str = "+";
+
+/// Label of the overflow menu button.
+const OVERFLOW_LABEL: &str = "⋮";
+
+/// Title suffix indicating active overwrite mode.
+const OVERWRITE_LABEL: &str = " · OVR";
+
+/// Title font size relative to the configured font size.
+const TITLE_FONT_SCALE: f64 = 0.8;
+
+/// Header bar showing the note title, or a temporary notice, and actions.
+pub struct Header {
+    font_collection: FontCollection,
+    paragraphs: Option<[Paragraph; 3]>,
+
+    title: String,
+    notice: Option<&'static str>,
+    stats: Option<String>,
+    editing: Option<String>,
+    unsaved: bool,
+    overwrite: bool,
+
+    font_family: FontFamily,
+    font_directory: Option<PathBuf>,
+    font_size: f64,
+    padding: f64,
+
+    background: Color4f,
+    foreground: Color4f,
+    highlight: Color4f,
+
+    focus: Option<HeaderAction>,
+    visible: bool,
+    width: u32,
+    scale: f64,
+}
+
+impl Header {
+    pub fn new(config: &Config, colors: &Colors, title: String) -> Self {
+        let font_collection = skia::font_collection(config.font.directory.as_deref());
+
+        Self {
+            font_collection,
+            title,
+            font_family: config.font.family.clone(),
+            font_directory: config.font.directory.clone(),
+            font_size: config.font.size,
+            padding: config.layout.padding,
+            background: colors.header.as_color4f(),
+            foreground: colors.foreground.as_color4f(),
+            highlight: colors.highlight.as_color4f(),
+            visible: config.general.header,
+            scale: 1.,
+            paragraphs: Default::default(),
+            editing: Default::default(),
+            notice: Default::default(),
+            stats: Default::default(),
+            unsaved: Default::default(),
+            overwrite: Default::default(),
+            focus: Default::default(),
+            width: Default::default(),
+        }
+    }
+
+    /// Render the header to the canvas.
+    pub fn draw(&mut self, canvas: &SkiaCanvas, width: u32) {
+        self.width = width;
+        if !self.visible {
+            return;
+        }
+
+        let height = self.height() as f32;
+
+        // Draw header background.
+        let mut paint = Paint::default();
+        paint.set_color4f(self.background, None);
+        canvas.draw_rect(Rect::new(0., 0., width as f32, height), &paint);
+
+        let paragraphs = match self.paragraphs.take() {
+            Some(paragraphs) => paragraphs,
+            None => {
+                let mut title = match (&self.editing, self.notice, &self.stats) {
+                    (Some(editing), ..) => format!("{editing}_"),
+                    (None, Some(notice), _) => notice.into(),
+                    (None, None, Some(stats)) => stats.clone(),
+                    (None, None, None) if self.unsaved => format!("• {}", self.title),
+                    (None, None, None) => self.title.clone(),
+                };
+                if self.overwrite && self.editing.is_none() {
+                    title.push_str(OVERWRITE_LABEL);
+                }
+                [
+                    self.build_paragraph(&title),
+                    self.build_paragraph(NEW_NOTE_LABEL),
+                    self.build_paragraph(OVERFLOW_LABEL),
+                ]
+            },
+        };
+        let [title, new_note, overflow] = self.paragraphs.insert(paragraphs);
+
+        // Layout title, truncating it to fit into a single line.
+        let padding = (self.padding * self.scale).round() as f32;
+        title.layout((width as f32 - 2. * padding - 2. * height).max(0.));
+
+        // Draw title vertically centered.
+        let y = ((height - title.height()) / 2.).round();
+        title.paint(canvas, Point::new(padding, y));
+
+        // Draw action buttons, right-aligned.
+        for (i, button) in [new_note, overflow].into_iter().enumerate() {
+            button.layout(height);
+
+            let x = width as f32 - (2 - i) as f32 * height;
+            let x = x + ((height - button.max_intrinsic_width()) / 2.).round();
+            let y = ((height - button.height()) / 2.).round();
+            button.paint(canvas, Point::new(x, y));
+        }
+
+        // Outline the element focused by keyboard navigation.
+        if let Some(focus) = self.focus {
+            skia::draw_focus_outline(canvas, self.button_rect(focus), self.highlight, self.scale);
+        }
+    }
+
+    /// Physical height of the header.
+    pub fn height(&self) -> f64 {
+        if self.visible { (HEADER_HEIGHT * self.scale).round() } else { 0. }
+    }
+
+    /// Get the note title, if the header is visible.
+    pub fn visible_title(&self) -> Option<&str> {
+        self.visible.then_some(self.title.as_str())
+    }
+
+    /// Get the action at a physical position.
+    pub fn action_at(&self, position: Point) -> Option<HeaderAction> {
+        if !self.visible || position.y >= self.height() as f32 {
+            return None;
+        }
+
+        [HeaderAction::Overflow, HeaderAction::NewNote]
+            .into_iter()
+            .find(|action| self.button_rect(*action).contains(position))
+            .or(Some(HeaderAction::EditTitle))
+    }
+
+    /// Get the physical bounds of an action's button.
+    ///
+    /// For [`HeaderAction::EditTitle`] this is the area of the title.
+    pub fn button_rect(&self, action: HeaderAction) -> Rect {
+        let height = self.height() as f32;
+        let width = self.width as f32;
+        match action {
+            HeaderAction::EditTitle => Rect::new(0., 0., width - 2. * height, height),
+            HeaderAction::NewNote => Rect::new(width - 2. * height, 0., width - height, height),
+            HeaderAction::Overflow => Rect::new(width - height, 0., width, height),
+        }
+    }
+
+    /// Move keyboard focus to a header action.
+    ///
+    /// Returns `true` if the focus was changed.
+    pub fn set_focus(&mut self, focus: Option<HeaderAction>) -> bool {
+        let focus = focus.filter(|_| self.visible);
+        if self.focus == focus {
+            return false;
+        }
+
+        self.focus = focus;
+
+        true
+    }
+
+    /// Get the header action focused by keyboard navigation.
+    pub fn focus(&self) -> Option<HeaderAction> {
+        self.focus
+    }
+
+    /// Start editing the note title.
+    pub fn start_editing(&mut self) {
+        self.editing = Some(self.title.clone());
+        self.paragraphs = None;
+    }
+
+    /// Check if the title is being edited.
+    pub fn editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    /// Append text to the edited title.
+    pub fn push_str(&mut self, text: &str) {
+        if let Some(editing) = &mut self.editing {
+            editing.push_str(text);
+            self.paragraphs = None;
+        }
+    }
+
+    /// Delete the last character of the edited title.
+    pub fn pop_char(&mut self) {
+        if let Some(editing) = &mut self.editing {
+            editing.pop();
+            self.paragraphs = None;
+        }
+    }
+
+    /// Stop editing, returning the new title.
+    ///
+    /// Returns `None` if the title was not being edited.
+    pub fn finish_editing(&mut self) -> Option<String> {
+        let title = self.editing.take()?;
+        self.paragraphs = None;
+        Some(title)
+    }
+
+    /// Create a single-line paragraph with the header's text style.
+    fn build_paragraph(&self, text: &str) -> Paragraph {
+        let mut paint = Paint::default();
+        paint.set_color4f(self.foreground, None);
+        paint.set_anti_alias(true);
+
+        let mut text_style = TextStyle::new();
+        text_style.set_foreground_paint(&paint);
+        text_style.set_font_size((self.font_size * TITLE_FONT_SCALE * self.scale) as f32);
+        text_style.set_font_families(&self.font_family);
+
+        let mut paragraph_style = ParagraphStyle::new();
+        paragraph_style.set_text_style(&text_style);
+        paragraph_style.set_max_lines(1);
+        paragraph_style.set_ellipsis("…");
+
+        let mut builder = ParagraphBuilder::new(&paragraph_style, &self.font_collection);
+        builder.add_text(text);
+        builder.build()
+    }
+
+    /// Update the note title.
+    ///
+    /// Returns `true` if the title was changed.
+    pub fn set_title(&mut self, title: String) -> bool {
+        if self.title == title {
+            return false;
+        }
+
+        self.title = title;
+        self.paragraphs = None;
+
+        true
+    }
+
+    /// Mark the title as having unsaved changes.
+    ///
+    /// Returns `true` if the indicator was changed.
+    pub fn set_unsaved(&mut self, unsaved: bool) -> bool {
+        if self.unsaved == unsaved {
+            return false;
+        }
+
+        self.unsaved = unsaved;
+        self.paragraphs = None;
+
+        true
+    }
+
+    /// Indicate whether overwrite mode is active.
+    ///
+    /// Returns `true` if the indicator was changed.
+    pub fn set_overwrite(&mut self, overwrite: bool) -> bool {
+        if self.overwrite == overwrite {
+            return false;
+        }
+
+        self.overwrite = overwrite;
+        self.paragraphs = None;
+
+        true
+    }
+
+    /// Show a notice in place of the title.
+    ///
+    /// Returns `true` if the notice was changed.
+    pub fn set_notice(&mut self, notice: Option<&'static str>) -> bool {
+        if self.notice == notice {
+            return false;
+        }
+
+        self.notice = notice;
+        self.paragraphs = None;
+
+        true
+    }
+
+    /// Show text statistics in place of the title.
+    ///
+    /// Returns `true` if the statistics were changed.
+    pub fn set_stats(&mut self, stats: Option<String>) -> bool {
+        if self.stats == stats {
+            return false;
+        }
+
+        self.stats = stats;
+        self.paragraphs = None;
+
+        true
+    }
+
+    /// Update the header's color palette.
+    ///
+    /// Returns `true` if any color was changed.
+    pub fn set_colors(&mut self, colors: &Colors) -> bool {
+        let background = colors.header.as_color4f();
+        let foreground = colors.foreground.as_color4f();
+        let highlight = colors.highlight.as_color4f();
+        if self.background == background
+            && self.foreground == foreground
+            && self.highlight == highlight
+        {
+            return false;
+        }
+
+        self.background = background;
+        self.foreground = foreground;
+        self.highlight = highlight;
+        self.paragraphs = None;
+
+        true
+    }
+
+    /// Drop cached text layouts until the next redraw.
+    pub fn release_layout(&mut self) {
+        self.paragraphs = None;
+    }
+
+    /// Set the header's font scale.
+    pub fn set_scale_factor(&mut self, scale: f64) {
+        if self.scale != scale {
+            self.scale = scale;
+            self.paragraphs = None;
+        }
+    }
+
+    /// Handle config updates.
+    ///
+    /// Returns `true` if a redraw is required.
+    pub fn update_config(&mut self, config: &Config) -> bool {
+        if self.font_size == config.font.size
+            && self.font_family == config.font.family
+            && self.font_directory == config.font.directory
+            && self.visible == config.general.header
+            && self.padding == config.layout.padding
+        {
+            return false;
+        }
+
+        self.visible = config.general.header;
+        self.padding = config.layout.padding;
+
+        if self.font_directory != config.font.directory {
+            self.font_collection = skia::font_collection(config.font.directory.as_deref());
+            self.font_directory = config.font.directory.clone();
+        }
+
+        self.font_family = config.font.family.clone();
+        self.font_size = config.font.size;
+        self.paragraphs = None;
+
+        true
+    }
+}
+
+/// Header bar actions.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HeaderAction {
+    EditTitle,
+    NewNote,
+    Overflow,
+}
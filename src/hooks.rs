@@ -0,0 +1,158 @@
+//! External command hooks.
+
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::{io, iter, thread};
+
+use calloop::LoopHandle;
+use calloop::channel::{self, Event, Sender};
+use tracing::{error, info};
+
+use crate::State;
+use crate::config::Config;
+use crate::text_box::TextBox;
+
+/// Runner for the configured hook commands.
+pub struct HookRunner {
+    jobs: Option<mpsc::Sender<HookJob>>,
+    post_save: Vec<String>,
+    pre_load: Vec<String>,
+}
+
+impl HookRunner {
+    pub fn new(event_loop: &LoopHandle<'static, State>, config: &Config) -> Self {
+        // Create calloop channel to report hook results on the main thread.
+        let (tx, rx) = channel::channel();
+        let _ = event_loop
+            .insert_source(rx, |event, _, state| {
+                if let Event::Msg(result) = event {
+                    Self::handle_result(state, result);
+                }
+            })
+            .inspect_err(|err| error!("Failed to insert hook source: {err}"));
+
+        // Run all hooks on a single worker thread.
+        let (jobs_tx, jobs_rx) = mpsc::channel();
+        let jobs = thread::Builder::new()
+            .name("hooks".into())
+            .spawn(move || Self::work(jobs_rx, tx))
+            .inspect_err(|err| error!("Failed to spawn hook thread: {err}"))
+            .ok()
+            .map(|_| jobs_tx);
+
+        Self {
+            jobs,
+            post_save: config.hooks.post_save.clone(),
+            pre_load: config.hooks.pre_load.clone(),
+        }
+    }
+
+    /// Run a hook in the background.
+    ///
+    /// This is a noop if no command is configured for the hook.
+    pub fn run(&self, kind: HookKind, path: &Path) {
+        let command = match kind {
+            HookKind::PostSave => &self.post_save,
+            HookKind::PreLoad => &self.pre_load,
+        };
+        let Some((program, args)) = command.split_first() else { return };
+        let Some(jobs) = &self.jobs else { return };
+
+        let mut command = Command::new(program);
+        command.args(args).arg(path).stdin(Stdio::null());
+
+        let job = HookJob { kind, path: path.to_path_buf(), command };
+        if jobs.send(job).is_err() {
+            error!("Failed to queue {kind} hook, hook thread is gone");
+        }
+    }
+
+    /// Run queued hooks one at a time.
+    ///
+    /// Hooks queued for the same note while another hook is running are
+    /// coalesced, so a burst of saves only runs the hook once more.
+    fn work(jobs: Receiver<HookJob>, results: Sender<HookResult>) {
+        while let Ok(job) = jobs.recv() {
+            let mut queue: Vec<HookJob> = Vec::new();
+            for job in iter::once(job).chain(jobs.try_iter()) {
+                match queue
+                    .iter_mut()
+                    .find(|queued| queued.kind == job.kind && queued.path == job.path)
+                {
+                    Some(queued) => *queued = job,
+                    None => queue.push(job),
+                }
+            }
+
+            for HookJob { kind, path, mut command } in queue {
+                let status = command.status();
+                if results.send(HookResult { kind, path, status }).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Handle a finished hook on the main thread.
+    fn handle_result(state: &mut State, result: HookResult) {
+        let HookResult { kind, path, status } = result;
+        match status {
+            Ok(status) if status.success() => {
+                info!("Successfully ran {kind} hook for {path:?}");
+
+                // Pick up changes made by the hook to the current note.
+                if kind == HookKind::PreLoad {
+                    TextBox::reload_note(state, &path);
+                }
+            },
+            Ok(status) => {
+                error!("The {kind} hook for {path:?} failed: {status}");
+                state.window.toast(format!("The {kind} hook failed ({status})"));
+            },
+            Err(err) => {
+                error!("Failed to run {kind} hook for {path:?}: {err}");
+                state.window.toast(format!("Failed to run {kind} hook: {err}"));
+            },
+        }
+    }
+
+    /// Handle config updates.
+    pub fn update_config(&mut self, config: &Config) {
+        self.post_save.clone_from(&config.hooks.post_save);
+        self.pre_load.clone_from(&config.hooks.pre_load);
+    }
+}
+
+/// Hook waiting for execution.
+struct HookJob {
+    kind: HookKind,
+    path: PathBuf,
+    command: Command,
+}
+
+/// Completed hook execution.
+pub struct HookResult {
+    pub kind: HookKind,
+    pub path: PathBuf,
+    pub status: io::Result<ExitStatus>,
+}
+
+/// Available hooks.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HookKind {
+    /// After a note was written to storage.
+    PostSave,
+    /// Before a note is read from storage.
+    PreLoad,
+}
+
+impl Display for HookKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PostSave => write!(f, "post-save"),
+            Self::PreLoad => write!(f, "pre-load"),
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! Conversion of notes from other applications.
+//!
+//! Google Keep takeout JSON, Markdown and plain text files are converted into
+//! Pinax notes, with list items separated by blank lines and completed tasks
+//! wrapped in completion markers.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::Error;
+use crate::editor::{COMPLETED_MARKER, normalize_paste};
+use crate::storage::Storage;
+
+/// Name of notes without a usable title or file name.
+const FALLBACK_NAME: &str = "imported";
+
+/// File in the state directory marking that the first-run prompt was shown.
+const PROMPTED_MARKER: &str = "pinax/import-prompted";
+
+/// Location of the marker for the first-run import prompt.
+pub fn prompted_marker() -> Option<PathBuf> {
+    Some(dirs::state_dir()?.join(PROMPTED_MARKER))
+}
+
+/// Check if the first-run import prompt should be shown.
+///
+/// This is the case when the storage directory contains no notes and the prompt
+/// was never shown before.
+pub fn is_first_run(storage: &dyn Storage, storage_dir: &Path, marker: &Path) -> bool {
+    !marker.exists() && storage.list(storage_dir).unwrap_or_default().is_empty()
+}
+
+/// Import all supported notes at `source` into the storage directory.
+///
+/// The `source` can be a single file or a directory, like the `Keep` folder
+/// of a Google takeout archive. Returns the paths of all imported notes.
+pub fn import(
+    storage: &dyn Storage,
+    storage_dir: &Path,
+    source: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    let mut files = if source.is_dir() {
+        fs::read_dir(source)?.filter_map(|entry| Some(entry.ok()?.path())).collect()
+    } else {
+        vec![source.to_path_buf()]
+    };
+    files.sort();
+
+    let mut imported = Vec::new();
+    for file in files {
+        // Skip hidden files and attachments.
+        let file_name = file.file_name().and_then(OsStr::to_str).unwrap_or_default();
+        if file_name.starts_with('.') || !file.is_file() {
+            continue;
+        }
+        let format = match Format::from_path(&file) {
+            Some(format) => format,
+            None => continue,
+        };
+
+        let content = fs::read_to_string(&file)?;
+        let (name, text) = match format.convert(&file, &content) {
+            Ok(Some(note)) => note,
+            Ok(None) => continue,
+            Err(err) => {
+                warn!("Skipping {file:?}: {err}");
+                continue;
+            },
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+
+        let path = unique_path(storage, storage_dir, &name);
+        storage.write(&path, &mut [text.as_str()].into_iter())?;
+        imported.push(path);
+    }
+
+    Ok(imported)
+}
+
+/// Supported note formats.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Format {
+    Keep,
+    Markdown,
+    Text,
+}
+
+impl Format {
+    /// Determine the format based on the file extension.
+    fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(OsStr::to_str)? {
+            "json" => Some(Self::Keep),
+            "md" | "markdown" => Some(Self::Markdown),
+            "txt" => Some(Self::Text),
+            _ => None,
+        }
+    }
+
+    /// Convert a file to the name and text of a Pinax note.
+    ///
+    /// Returns `None` for notes which should not be imported.
+    fn convert(&self, path: &Path, content: &str) -> Result<Option<(String, String)>, String> {
+        let stem = path.file_stem().and_then(OsStr::to_str).unwrap_or_default();
+        match self {
+            Self::Keep => {
+                let note: KeepNote =
+                    serde_json::from_str(content).map_err(|err| err.to_string())?;
+                if note.is_trashed {
+                    return Ok(None);
+                }
+
+                let name = if note.title.trim().is_empty() { stem } else { &note.title };
+                Ok(Some((name.into(), note.text())))
+            },
+            Self::Markdown => {
+                let (title, content) = front_matter(content);
+                Ok(Some((title.unwrap_or(stem).into(), markdown(content))))
+            },
+            Self::Text => Ok(Some((stem.into(), normalize_paste(content, false)))),
+        }
+    }
+}
+
+/// Note in a Google Keep takeout archive.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "camelCase")]
+struct KeepNote {
+    title: String,
+    text_content: String,
+    list_content: Vec<KeepItem>,
+    is_trashed: bool,
+}
+
+impl KeepNote {
+    /// Convert the note's content to Pinax list items.
+    fn text(&self) -> String {
+        if self.list_content.is_empty() {
+            return normalize_paste(&self.text_content, false);
+        }
+
+        let items: Vec<_> = self
+            .list_content
+            .iter()
+            .filter_map(|keep_item| {
+                // Remove blank lines, which would split the item.
+                let text = normalize_paste(keep_item.text.trim(), false).replace("\n\n", "\n");
+                (!text.is_empty()).then(|| item(&text, keep_item.is_checked))
+            })
+            .collect();
+        items.join("\n\n")
+    }
+}
+
+/// List item of a Google Keep checklist.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default, rename_all = "camelCase")]
+struct KeepItem {
+    text: String,
+    is_checked: bool,
+}
+
+/// Split YAML front matter from Markdown, returning its title and the content.
+fn front_matter(text: &str) -> (Option<&str>, &str) {
+    let header = match text.strip_prefix("---\n").or_else(|| text.strip_prefix("---\r\n")) {
+        Some(header) => header,
+        None => return (None, text),
+    };
+
+    let mut offset = 0;
+    for line in header.split_inclusive('\n') {
+        let end = offset + line.len();
+        if matches!(line.trim_end(), "---" | "...") {
+            let title = header[..offset]
+                .lines()
+                .find_map(|line| line.strip_prefix("title:"))
+                .map(|title| title.trim().trim_matches(['"', '\'']))
+                .filter(|title| !title.is_empty());
+            return (title, &header[end..]);
+        }
+        offset = end;
+    }
+
+    // Treat unterminated front matter as regular content.
+    (None, text)
+}
+
+/// Convert Markdown to Pinax list items.
+///
+/// Every list item, heading and paragraph becomes a separate item, with
+/// checked task list items marked as completed.
+fn markdown(text: &str) -> String {
+    let mut items: Vec<(String, bool)> = Vec::new();
+    let mut continued = false;
+    for line in normalize_paste(text, false).lines() {
+        let line = line.trim();
+        if line.is_empty() || is_markdown_rule(line) {
+            continued = false;
+            continue;
+        }
+
+        if let Some((content, completed)) = markdown_list_item(line) {
+            items.push((content.into(), completed));
+            continued = true;
+        } else if let Some(heading) = markdown_heading(line) {
+            items.push((heading.into(), false));
+            continued = false;
+        } else if let Some((content, _)) = items.last_mut().filter(|_| continued) {
+            // Join continuation lines with their list item or paragraph.
+            content.push('\n');
+            content.push_str(line);
+        } else {
+            items.push((line.into(), false));
+            continued = true;
+        }
+    }
+
+    let items: Vec<_> =
+        items.iter().map(|(content, completed)| item(content, *completed)).collect();
+    items.join("\n\n")
+}
+
+/// Parse a Markdown list item, returning its content and completion state.
+fn markdown_list_item(line: &str) -> Option<(&str, bool)> {
+    let content = match line.strip_prefix(['-', '*', '+']) {
+        Some(content) => content,
+        // Strip ordered list markers like `1.` or `1)`.
+        None => {
+            let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            line[digits..].strip_prefix(['.', ')']).filter(|_| digits > 0)?
+        },
+    };
+    let content = content.strip_prefix(' ')?.trim_start();
+
+    if let Some(task) = content.strip_prefix("[ ]") {
+        Some((task.trim_start(), false))
+    } else if let Some(task) = content.strip_prefix("[x]").or_else(|| content.strip_prefix("[X]")) {
+        Some((task.trim_start(), true))
+    } else {
+        Some((content, false))
+    }
+}
+
+/// Parse a Markdown ATX heading, returning its title.
+fn markdown_heading(line: &str) -> Option<&str> {
+    let title = line.trim_start_matches('#');
+    let level = line.len() - title.len();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    Some(title.strip_prefix(' ')?.trim())
+}
+
+/// Check if a line is a Markdown thematic break, like `---`.
+fn is_markdown_rule(line: &str) -> bool {
+    let line: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    line.len() >= 3 && ['-', '*', '_'].iter().any(|marker| line.chars().all(|c| c == *marker))
+}
+
+/// Format the content of a list item.
+fn item(content: &str, completed: bool) -> String {
+    if completed {
+        format!("{COMPLETED_MARKER}{content}{COMPLETED_MARKER}")
+    } else {
+        content.into()
+    }
+}
+
+/// Get a path for a new note which does not replace any existing notes.
+fn unique_path(storage: &dyn Storage, storage_dir: &Path, name: &str) -> PathBuf {
+    // Ensure the name is a valid, visible file name.
+    let name = name.trim().replace('/', "-");
+    let name = name.trim_start_matches('.');
+    let name = if name.is_empty() { FALLBACK_NAME } else { name };
+
+    let mut path = storage_dir.join(name);
+    let mut suffix = 2;
+    while storage.exists(&path) {
+        path = storage_dir.join(format!("{name}-{suffix}"));
+        suffix += 1;
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::storage::file::FileStorage;
+
+    /// Import all files in `files` into an empty storage directory.
+    ///
+    /// Returns the file name and content of every imported note.
+    fn import_files(files: &[(&str, &str)], existing: &[&str]) -> Vec<(String, String)> {
+        let source = tempfile::tempdir().unwrap();
+        for (name, content) in files {
+            fs::write(source.path().join(name), content).unwrap();
+        }
+
+        let storage_dir = tempfile::tempdir().unwrap();
+        for name in existing {
+            fs::write(storage_dir.path().join(name), "existing").unwrap();
+        }
+
+        let storage = FileStorage::new(&Config::default());
+        let imported = import(&storage, storage_dir.path(), source.path()).unwrap();
+        imported
+            .iter()
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, storage.read(path).unwrap().unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn markdown_front_matter() {
+        let imported = import_files(
+            &[
+                (
+                    "tasks.md",
+                    "---\ntitle: \"Groceries\"\ntags: [food]\n---\n- [ ] Milk\n- [x] Eggs\n",
+                ),
+                ("plain.md", "---\nauthor: me\n---\n# Heading\ntext\n"),
+                ("unterminated.md", "---\ntitle: Broken\n"),
+            ],
+            &[],
+        );
+
+        let imported: Vec<_> =
+            imported.iter().map(|(name, text)| (name.as_str(), text.as_str())).collect();
+        assert_eq!(
+            imported,
+            [
+                ("plain", "Heading\n\ntext"),
+                ("Groceries", "Milk\n\n~~Eggs~~"),
+                ("unterminated", "title: Broken"),
+            ]
+        );
+    }
+
+    #[test]
+    fn file_names() {
+        let imported = import_files(
+            &[
+                (".secret.txt", "hidden"),
+                ("dots.json", r#"{"title": "...", "textContent": "Dots"}"#),
+                ("image.png", "binary"),
+                (
+                    "list.json",
+                    r#"{"title": " ", "listContent": [{"text": "Done", "isChecked": true}]}"#,
+                ),
+                ("notes.txt", "Text"),
+                ("trashed.json", r#"{"title": "Trash", "textContent": "Gone", "isTrashed": true}"#),
+                ("work.json", r#"{"title": "Work/Home", "textContent": "Call Bob"}"#),
+            ],
+            &["notes"],
+        );
+
+        let imported: Vec<_> =
+            imported.iter().map(|(name, text)| (name.as_str(), text.as_str())).collect();
+        assert_eq!(
+            imported,
+            [
+                ("imported", "Dots"),
+                ("list", "~~Done~~"),
+                ("notes-2", "Text"),
+                ("Work-Home", "Call Bob"),
+            ]
+        );
+    }
+
+    #[test]
+    fn first_run() {
+        let storage_dir = tempfile::tempdir().unwrap();
+        let marker = storage_dir.path().join(".prompted");
+        let storage = FileStorage::new(&Config::default());
+
+        assert!(is_first_run(&storage, storage_dir.path(), &marker));
+
+        fs::write(storage_dir.path().join("note"), "text").unwrap();
+        assert!(!is_first_run(&storage, storage_dir.path(), &marker));
+
+        fs::remove_file(storage_dir.path().join("note")).unwrap();
+        fs::write(&marker, "").unwrap();
+        assert!(!is_first_run(&storage, storage_dir.path(), &marker));
+    }
+}
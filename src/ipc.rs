@@ -0,0 +1,313 @@
+//! Inter-process communication.
+
+use std::fmt::{self, Display, Formatter};
+use std::io::{self, BufRead, BufReader, ErrorKind as IoErrorKind, Write};
+use std::net::Shutdown;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use std::{env, fs};
+
+use calloop::generic::Generic;
+use calloop::{Interest, LoopHandle, Mode, PostAction};
+use tracing::{error, warn};
+
+use crate::window::Action;
+use crate::{Error, State};
+
+/// Maximum time waiting for a client to send its message.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// IPC message.
+#[derive(PartialEq, Eq, Debug)]
+pub enum Message {
+    /// Bring the window to the front.
+    Show { token: Option<String> },
+    /// Query the effective value of a config `key`, or the entire config.
+    Get { key: Option<String> },
+    /// Run an action, like the matching key binding.
+    Action(Action),
+}
+
+impl Message {
+    /// Parse a message from its command line arguments.
+    ///
+    /// Arguments are percent-encoded, allowing them to contain whitespace.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let (command, args) = args.split_first().ok_or("missing command")?;
+        let mut text = command.clone();
+        for arg in args {
+            text.push(' ');
+            text.push_str(&percent_encode(arg));
+        }
+        text.parse()
+    }
+}
+
+impl FromStr for Message {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut words = text.split_whitespace();
+        match words.next() {
+            Some("show") => Ok(Self::Show { token: decode_arg(words.next())? }),
+            Some("get") => Ok(Self::Get { key: decode_arg(words.next())? }),
+            Some(command) => parse_action(command, words).map(Self::Action),
+            None => Err("empty message".into()),
+        }
+    }
+}
+
+impl Display for Message {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Show { token: Some(token) } => write!(f, "show {}", percent_encode(token)),
+            Self::Show { token: None } => write!(f, "show"),
+            Self::Get { key: Some(key) } => write!(f, "get {}", percent_encode(key)),
+            Self::Get { key: None } => write!(f, "get"),
+            Self::Action(action) => write_action(f, action),
+        }
+    }
+}
+
+/// Parse an action from its IPC command and percent-encoded arguments.
+fn parse_action<'a>(
+    command: &str,
+    mut args: impl Iterator<Item = &'a str>,
+) -> Result<Action, String> {
+    let mut decoded_arg = || decode_arg(args.next());
+
+    match command {
+        "archive" => Ok(Action::Archive),
+        "save" => Ok(Action::Save),
+        "fullscreen" => Ok(Action::Fullscreen),
+        "maximize" => Ok(Action::Maximize),
+        "paste-items" => Ok(Action::PasteItems),
+        "append" => Ok(Action::Append { text: decoded_arg()?.unwrap_or_default() }),
+        "print" => Ok(Action::Print { path: decoded_arg()?.map(PathBuf::from) }),
+        "new-note" => Ok(Action::NewNote { name: decoded_arg()?.ok_or("missing note name")? }),
+        "filter-tag" => Ok(Action::FilterTag { tag: decoded_arg()? }),
+        "search" => Ok(Action::Search),
+        "toggle-stats" => Ok(Action::ToggleStats),
+        "zoom-in" => Ok(Action::ZoomIn),
+        "zoom-out" => Ok(Action::ZoomOut),
+        "reset-zoom" => Ok(Action::ResetZoom),
+        "toggle-profiler" => Ok(Action::ToggleProfiler),
+        command => Err(format!("unknown command {command:?}")),
+    }
+}
+
+/// Decode an optional percent-encoded argument.
+fn decode_arg(arg: Option<&str>) -> Result<Option<String>, String> {
+    match arg {
+        Some(arg) => percent_decode(arg).ok_or_else(|| "invalid percent-encoding".into()).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Write an action as IPC command with percent-encoded arguments.
+fn write_action(f: &mut Formatter<'_>, action: &Action) -> fmt::Result {
+    match action {
+        Action::Archive => write!(f, "archive"),
+        Action::Save => write!(f, "save"),
+        Action::Fullscreen => write!(f, "fullscreen"),
+        Action::Maximize => write!(f, "maximize"),
+        Action::PasteItems => write!(f, "paste-items"),
+        Action::Append { text } => write!(f, "append {}", percent_encode(text)),
+        Action::Print { path: Some(path) } => {
+            write!(f, "print {}", percent_encode(&path.to_string_lossy()))
+        },
+        Action::Print { path: None } => write!(f, "print"),
+        Action::NewNote { name } => write!(f, "new-note {}", percent_encode(name)),
+        Action::FilterTag { tag: Some(tag) } => write!(f, "filter-tag {}", percent_encode(tag)),
+        Action::FilterTag { tag: None } => write!(f, "filter-tag"),
+        Action::Search => write!(f, "search"),
+        Action::ToggleStats => write!(f, "toggle-stats"),
+        Action::ZoomIn => write!(f, "zoom-in"),
+        Action::ZoomOut => write!(f, "zoom-out"),
+        Action::ResetZoom => write!(f, "reset-zoom"),
+        Action::ToggleProfiler => write!(f, "toggle-profiler"),
+    }
+}
+
+/// Send a message to the running Pinax instance.
+pub fn send(message: &Message) -> io::Result<()> {
+    let path = socket_path().ok_or_else(|| io::Error::from(IoErrorKind::NotFound))?;
+    let mut stream = UnixStream::connect(path)?;
+    writeln!(stream, "{message}")
+}
+
+/// Send a message to the running Pinax instance and wait for its reply.
+///
+/// Returns `Err` with the server's error message if the request failed.
+pub fn request(message: &Message) -> io::Result<Result<String, String>> {
+    let path = socket_path().ok_or_else(|| io::Error::from(IoErrorKind::NotFound))?;
+    let mut stream = UnixStream::connect(path)?;
+    writeln!(stream, "{message}")?;
+    stream.shutdown(Shutdown::Write)?;
+
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+
+    let invalid_reply = || io::Error::new(IoErrorKind::InvalidData, "invalid reply");
+    let (status, text) = reply.trim_end().split_once(' ').unwrap_or((reply.trim_end(), ""));
+    let text = percent_decode(text).ok_or_else(invalid_reply)?;
+    match status {
+        "ok" => Ok(Ok(text)),
+        "error" => Ok(Err(text)),
+        _ => Err(invalid_reply()),
+    }
+}
+
+/// IPC socket server.
+///
+/// The socket file is removed once the server is dropped.
+pub struct IpcServer {
+    path: PathBuf,
+}
+
+impl IpcServer {
+    /// Start listening for IPC messages.
+    pub fn new(event_loop: &LoopHandle<'static, State>) -> Result<Option<Self>, Error> {
+        let path = match socket_path() {
+            Some(path) => path,
+            None => {
+                warn!("Missing runtime directory, IPC is disabled");
+                return Ok(None);
+            },
+        };
+
+        // Remove stale sockets from previous instances, without taking over the
+        // socket of another running instance.
+        match UnixStream::connect(&path) {
+            Ok(_) => {
+                warn!("IPC socket {path:?} is used by another instance, IPC is disabled");
+                return Ok(None);
+            },
+            Err(err) if err.kind() == IoErrorKind::ConnectionRefused => {
+                let _ = fs::remove_file(&path);
+            },
+            Err(_) => (),
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+
+        let source = Generic::new(listener, Interest::READ, Mode::Level);
+        event_loop.insert_source(source, |_, listener, state| {
+            loop {
+                match listener.as_ref().accept() {
+                    Ok((stream, _)) => Self::handle_client(state, stream),
+                    Err(err) if err.kind() == IoErrorKind::WouldBlock => break,
+                    Err(err) => {
+                        error!("Failed to accept IPC connection: {err}");
+                        break;
+                    },
+                }
+            }
+
+            Ok(PostAction::Continue)
+        })?;
+
+        Ok(Some(Self { path }))
+    }
+
+    /// Read and dispatch all messages from a client.
+    fn handle_client(state: &mut State, stream: UnixStream) {
+        if let Err(err) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+            error!("Failed to configure IPC connection: {err}");
+            return;
+        }
+
+        for line in BufReader::new(&stream).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(err) => {
+                    error!("Failed to read IPC message: {err}");
+                    return;
+                },
+            };
+
+            match line.parse::<Message>() {
+                Ok(message) => Self::dispatch(state, &stream, message),
+                Err(err) => warn!("Ignoring invalid IPC message: {err}"),
+            }
+        }
+    }
+
+    /// Send the result of a request back to the client.
+    fn reply(mut stream: &UnixStream, result: Result<String, String>) {
+        let reply = match result {
+            Ok(text) => format!("ok {}", percent_encode(&text)),
+            Err(text) => format!("error {}", percent_encode(&text)),
+        };
+
+        if let Err(err) = writeln!(stream, "{reply}") {
+            error!("Failed to send IPC reply: {err}");
+        }
+    }
+
+    /// Handle an IPC message.
+    fn dispatch(state: &mut State, stream: &UnixStream, message: Message) {
+        match message {
+            Message::Show { token } => {
+                state.window.show();
+
+                match token {
+                    Some(token) => state.window.activate(&state.protocol_states, token),
+                    None => warn!("Ignoring activation request without activation token"),
+                }
+            },
+            Message::Get { key } => {
+                Self::reply(stream, state.window.config().value(key.as_deref()))
+            },
+            Message::Action(action) => state.window.run_action(&state.config, action),
+        }
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Encode all bytes except unreserved URI characters as `%XX`.
+pub fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Decode `%XX` escapes and `+` as space.
+///
+/// Returns `None` if an escape is malformed or the text is not valid UTF-8.
+pub fn percent_decode(text: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(text.len());
+    let mut bytes = text.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'%' => {
+                let hex = [bytes.next()?, bytes.next()?];
+                let hex = std::str::from_utf8(&hex).ok()?;
+                decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            },
+            b'+' => decoded.push(b' '),
+            byte => decoded.push(byte),
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// Location of the IPC socket.
+fn socket_path() -> Option<PathBuf> {
+    let display = env::var("WAYLAND_DISPLAY").unwrap_or_else(|_| "wayland-0".into());
+    Some(dirs::runtime_dir()?.join(format!("pinax-{display}.sock")))
+}
@@ -0,0 +1,500 @@
+//! Text layout composed of independently shaped blocks.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use skia_safe::textlayout::{
+    GlyphClusterInfo, LineMetrics, Paragraph, RectHeightStyle, RectWidthStyle, TextBox,
+};
+use skia_safe::{Canvas as SkiaCanvas, Point, Rect};
+
+/// Vertically stacked text blocks.
+///
+/// Each block is a separately shaped paragraph, allowing unchanged blocks to
+/// be reused when the text is modified. Blocks outside of the viewport can be
+/// left unshaped, reserving space based on their estimated size instead.
+///
+/// All text offsets, line numbers and positions are relative to the entire
+/// layout. Queries for unshaped blocks return `None`.
+///
+/// Blocks can optionally be flowed into multiple side-by-side columns, see
+/// [`Self::flow_columns`].
+///
+/// All lines of a block after its first line can be shifted to the right by a
+/// hanging indent, to align them with the first line's content.
+///
+/// Paragraphs may omit hidden parts of their text, see [`DisplayMap`]. Entire
+/// blocks can be hidden as well, taking up no space and ignoring all queries.
+#[derive(Default)]
+pub struct TextLayout {
+    blocks: Vec<Block>,
+    height: f32,
+
+    column_count: usize,
+    column_stride: f32,
+}
+
+impl TextLayout {
+    /// Append a block below all existing blocks.
+    ///
+    /// The `range` must start at the end of the previous block's range. When
+    /// `trim` is set, the empty line following the paragraph's trailing
+    /// newline is excluded from the layout.
+    ///
+    /// The `indent` is the horizontal offset of all lines after the first one,
+    /// while `display` maps offsets within `range` to the paragraph's text.
+    pub fn push(
+        &mut self,
+        paragraph: Paragraph,
+        range: Range<usize>,
+        hash: u64,
+        trim: bool,
+        indent: f32,
+        display: DisplayMap,
+    ) {
+        let hanging_indent = paragraph
+            .get_line_metrics_at(0)
+            .filter(|_| indent > 0. && paragraph.line_number() > 1)
+            .map(|metrics| HangingIndent {
+                width: indent,
+                first_line_bottom: (metrics.baseline + metrics.descent) as f32,
+            });
+
+        let mut size =
+            BlockSize { height: paragraph.height(), line_count: paragraph.line_number() };
+        if trim
+            && size.line_count > 1
+            && let Some(metrics) = paragraph.get_line_metrics_at(size.line_count - 1)
+        {
+            size.height -= metrics.height as f32;
+            size.line_count -= 1;
+        }
+
+        self.push_block(Some(paragraph), range, hash, size, true);
+        if let Some(block) = self.blocks.last_mut() {
+            block.indent = hanging_indent;
+            block.display = display;
+        }
+    }
+
+    /// Append an unshaped block below all existing blocks.
+    ///
+    /// The `exact` flag indicates that `size` is known from a previous layout,
+    /// rather than estimated.
+    pub fn push_unshaped(&mut self, range: Range<usize>, hash: u64, size: BlockSize, exact: bool) {
+        self.push_block(None, range, hash, size, exact);
+    }
+
+    /// Append a hidden block, which is neither rendered nor shaped.
+    pub fn push_hidden(&mut self, range: Range<usize>) {
+        let size = BlockSize { height: 0., line_count: 0 };
+        self.push_block(None, range, 0, size, false);
+        if let Some(block) = self.blocks.last_mut() {
+            block.hidden = true;
+        }
+    }
+
+    fn push_block(
+        &mut self,
+        paragraph: Option<Paragraph>,
+        range: Range<usize>,
+        hash: u64,
+        size: BlockSize,
+        exact: bool,
+    ) {
+        let first_line =
+            self.blocks.last().map_or(0, |block| block.first_line + block.size.line_count);
+        let top = self.height;
+        self.height += size.height;
+
+        self.blocks.push(Block {
+            paragraph,
+            range,
+            hash,
+            first_line,
+            exact,
+            size,
+            top,
+            x: Default::default(),
+            column: Default::default(),
+            indent: Default::default(),
+            display: Default::default(),
+            hidden: Default::default(),
+        });
+    }
+
+    /// Distribute all blocks across multiple columns.
+    ///
+    /// Blocks are kept in text order, filling each column top to bottom before
+    /// moving on to the next one, while balancing the column heights. The
+    /// `stride` is the horizontal distance between the start of two columns.
+    pub fn flow_columns(&mut self, count: usize, stride: f32) {
+        self.column_count = count;
+        self.column_stride = stride;
+        if count <= 1 {
+            return;
+        }
+
+        let target_height = self.height / count as f32;
+        let mut column = 0;
+        let mut top = 0.;
+        let mut height = 0.;
+        for block in &mut self.blocks {
+            // Move to the next column once the block is mostly past the target height.
+            if column + 1 < count && top > 0. && top + block.size.height / 2. > target_height {
+                column += 1;
+                top = 0.;
+            }
+
+            block.column = column;
+            block.x = column as f32 * stride;
+            block.top = top;
+
+            top += block.size.height;
+            height = f32::max(height, top);
+        }
+
+        self.height = height;
+    }
+
+    /// Horizontal offset of the column containing a line.
+    pub fn line_column_x(&self, line_number: usize) -> Option<f32> {
+        let index = self
+            .blocks
+            .partition_point(|block| block.first_line + block.size.line_count <= line_number);
+        self.blocks.get(index).map(|block| block.x)
+    }
+
+    /// Convert the layout into a cache for the next layout.
+    pub fn into_cache(self) -> BlockCache {
+        let mut cache = BlockCache::default();
+        for block in self.blocks.into_iter().filter(|block| block.exact) {
+            cache.sizes.insert(block.hash, block.size);
+            if let Some(paragraph) = block.paragraph {
+                cache.paragraphs.insert(block.hash, paragraph);
+            }
+        }
+        cache
+    }
+
+    /// Check if all blocks within a vertical range are shaped.
+    pub fn is_shaped(&self, y_range: Range<f32>) -> bool {
+        self.blocks
+            .iter()
+            .filter(|block| {
+                !block.hidden
+                    && block.top < y_range.end
+                    && block.top + block.size.height > y_range.start
+            })
+            .all(|block| block.paragraph.is_some())
+    }
+
+    /// Total height of all blocks.
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+
+    /// Render all shaped blocks within the canvas' clip bounds.
+    pub fn paint(&self, canvas: &SkiaCanvas, point: Point) {
+        for block in &self.blocks {
+            self.paint_block(canvas, point, block);
+        }
+    }
+
+    /// Render only the block containing a byte offset.
+    pub fn paint_at(&self, canvas: &SkiaCanvas, point: Point, index: usize) {
+        if let Some(block) = self.block_at_index(index) {
+            self.paint_block(canvas, point, block);
+        }
+    }
+
+    fn paint_block(&self, canvas: &SkiaCanvas, point: Point, block: &Block) {
+        let clip = canvas.local_clip_bounds();
+        let top = point.y + block.top;
+        if clip.is_some_and(|clip| top >= clip.bottom || top + block.size.height <= clip.top) {
+            return;
+        }
+
+        let paragraph = match &block.paragraph {
+            Some(paragraph) => paragraph,
+            None => return,
+        };
+
+        let origin = Point::new(point.x + block.x, top);
+        let (indent, clip) = match (&block.indent, clip) {
+            (Some(indent), Some(clip)) => (indent, clip),
+            _ => {
+                paragraph.paint(canvas, origin);
+                return;
+            },
+        };
+
+        // Paint the first line and the indented lines separately.
+        let split = top + indent.first_line_bottom;
+        let first_line = Rect::new(clip.left, clip.top, clip.right, split.min(clip.bottom));
+        let other_lines = Rect::new(clip.left, split.max(clip.top), clip.right, clip.bottom);
+        for (rect, x) in [(first_line, 0.), (other_lines, indent.width)] {
+            canvas.save();
+            canvas.clip_rect(rect, None, None);
+            paragraph.paint(canvas, origin + Point::new(x, 0.));
+            canvas.restore();
+        }
+    }
+
+    /// Vertical bounds of the visible block containing a byte offset.
+    pub fn block_bounds(&self, index: usize) -> Option<Range<f32>> {
+        let block = self.block_at_index(index).filter(|block| !block.hidden)?;
+        Some(block.top..block.top + block.size.height)
+    }
+
+    /// Move the block containing a byte offset vertically.
+    ///
+    /// Only the block itself is moved, without making room for it, which
+    /// allows animating blocks without laying out the text again.
+    pub fn shift_block(&mut self, index: usize, delta: f32) {
+        let block_index = self.blocks.partition_point(|block| block.range.end <= index);
+        let block_index = block_index.min(self.blocks.len().saturating_sub(1));
+        if let Some(block) = self.blocks.get_mut(block_index) {
+            block.top += delta;
+        }
+    }
+
+    /// Get the line containing a byte offset.
+    pub fn get_line_number_at(&self, index: usize) -> Option<usize> {
+        let block = self.block_at_index(index)?;
+        let index = block.display.to_display(index - block.range.start);
+        let line = block.paragraph.as_ref()?.get_line_number_at(index)?;
+        Some(block.first_line + line)
+    }
+
+    /// Get the metrics of a line.
+    pub fn get_line_metrics_at(&self, line_number: usize) -> Option<LineMetrics<'_>> {
+        let index = self
+            .blocks
+            .partition_point(|block| block.first_line + block.size.line_count <= line_number);
+        let block = self.blocks.get(index)?;
+        let paragraph = block.paragraph.as_ref()?;
+        let mut metrics = paragraph.get_line_metrics_at(line_number - block.first_line)?;
+
+        let to_layout = |index| block.range.start + block.display.to_stored(index);
+        metrics.start_index = to_layout(metrics.start_index);
+        metrics.end_index = to_layout(metrics.end_index);
+        metrics.end_excluding_whitespaces = to_layout(metrics.end_excluding_whitespaces);
+        metrics.end_including_newline = to_layout(metrics.end_including_newline);
+        metrics.baseline += block.top as f64;
+        metrics.left += block.x as f64;
+        if line_number > block.first_line {
+            metrics.left += block.indent.as_ref().map_or(0., |indent| indent.width) as f64;
+        }
+        metrics.line_number = line_number;
+
+        Some(metrics)
+    }
+
+    /// Get the glyph cluster containing a byte offset.
+    pub fn get_glyph_cluster_at(&self, index: usize) -> Option<GlyphClusterInfo> {
+        let block = self.block_at_index(index)?;
+        let paragraph = block.paragraph.as_ref()?;
+        let cluster =
+            paragraph.get_glyph_cluster_at(block.display.to_display(index - block.range.start))?;
+        Some(block.offset_cluster(cluster))
+    }
+
+    /// Get the glyph cluster closest to a position.
+    pub fn get_closest_glyph_cluster_at(
+        &self,
+        point: impl Into<Point>,
+    ) -> Option<GlyphClusterInfo> {
+        let point = point.into();
+
+        // Limit the search to the column below the point.
+        let column = match self.column_count {
+            0 | 1 => 0,
+            count => ((point.x / self.column_stride).floor().max(0.) as usize).min(count - 1),
+        };
+        let start = self.blocks.partition_point(|block| block.column < column);
+        let end = self.blocks.partition_point(|block| block.column <= column);
+        let blocks = &self.blocks[start..end];
+
+        // Use the closest visible block, falling back to the last one.
+        let index = blocks.partition_point(|block| block.top + block.size.height <= point.y);
+        let block = blocks[index..]
+            .iter()
+            .chain(blocks[..index].iter().rev())
+            .chain(self.blocks.iter().rev())
+            .find(|block| !block.hidden)?;
+
+        let paragraph = block.paragraph.as_ref()?;
+        let y = point.y - block.top;
+        let x = point.x - block.x - block.indent_at(y);
+        let cluster = paragraph.get_closest_glyph_cluster_at((x, y))?;
+        Some(block.offset_cluster(cluster))
+    }
+
+    /// Get the bounding boxes of a text range.
+    pub fn get_rects_for_range(
+        &self,
+        range: Range<usize>,
+        rect_height_style: RectHeightStyle,
+        rect_width_style: RectWidthStyle,
+    ) -> Vec<TextBox> {
+        let mut rects = Vec::new();
+        for block in &self.blocks {
+            let paragraph = match &block.paragraph {
+                Some(paragraph) => paragraph,
+                None => continue,
+            };
+
+            let start = range.start.max(block.range.start);
+            let end = range.end.min(block.range.end);
+            if start >= end {
+                continue;
+            }
+
+            let display = &block.display;
+            let block_range = display.to_display(start - block.range.start)
+                ..display.to_display(end - block.range.start);
+            if block_range.is_empty() {
+                continue;
+            }
+
+            let block_rects =
+                paragraph.get_rects_for_range(block_range, rect_height_style, rect_width_style);
+            rects.extend(block_rects.into_iter().map(|mut text_box| {
+                let indent = block.indent_at(text_box.rect.center_y());
+                text_box.rect.offset((block.x + indent, block.top));
+                text_box
+            }));
+        }
+        rects
+    }
+
+    /// Get the block containing a byte offset.
+    ///
+    /// Offsets at the boundary between two blocks belong to the second block.
+    fn block_at_index(&self, index: usize) -> Option<&Block> {
+        let block_index = self.blocks.partition_point(|block| block.range.end <= index);
+        self.blocks.get(block_index).or(self.blocks.last())
+    }
+}
+
+/// Shaped blocks and sizes of a previous layout.
+#[derive(Default)]
+pub struct BlockCache {
+    paragraphs: HashMap<u64, Paragraph>,
+    sizes: HashMap<u64, BlockSize>,
+}
+
+impl BlockCache {
+    /// Take the shaped paragraph of a block.
+    pub fn take_paragraph(&mut self, hash: u64) -> Option<Paragraph> {
+        self.paragraphs.remove(&hash)
+    }
+
+    /// Get the exact size of a block which was shaped before.
+    pub fn size(&self, hash: u64) -> Option<BlockSize> {
+        self.sizes.get(&hash).copied()
+    }
+}
+
+/// Dimensions of a text block.
+#[derive(Copy, Clone, Debug)]
+pub struct BlockSize {
+    pub height: f32,
+    pub line_count: usize,
+}
+
+/// Paragraph within a layout.
+struct Block {
+    /// Shaped paragraph, `None` for blocks outside the viewport.
+    paragraph: Option<Paragraph>,
+
+    /// Byte range within the layout's text.
+    range: Range<usize>,
+    /// Hash of all content affecting the paragraph's shaping.
+    hash: u64,
+
+    /// Number of lines before this block.
+    first_line: usize,
+
+    /// Position within the layout.
+    x: f32,
+    top: f32,
+    /// Index of the column containing the block.
+    column: usize,
+    /// Offset of all lines after the first one.
+    indent: Option<HangingIndent>,
+    /// Mapping from offsets within `range` to the paragraph's text.
+    display: DisplayMap,
+    size: BlockSize,
+    /// Whether the size is known, rather than estimated.
+    exact: bool,
+    /// Whether the block is excluded from the layout.
+    hidden: bool,
+}
+
+impl Block {
+    /// Horizontal indent of the line at a vertical paragraph position.
+    fn indent_at(&self, y: f32) -> f32 {
+        match &self.indent {
+            Some(indent) if y >= indent.first_line_bottom => indent.width,
+            _ => 0.,
+        }
+    }
+
+    /// Convert a cluster from paragraph to layout coordinates.
+    fn offset_cluster(&self, mut cluster: GlyphClusterInfo) -> GlyphClusterInfo {
+        let indent = self.indent_at(cluster.bounds.center_y());
+        cluster.bounds.offset((self.x + indent, self.top));
+        let to_layout = |index| self.range.start + self.display.to_stored(index);
+        cluster.text_range = to_layout(cluster.text_range.start)..to_layout(cluster.text_range.end);
+        cluster
+    }
+}
+
+/// Indent of a block's lines after its first line.
+struct HangingIndent {
+    width: f32,
+    /// Bottom of the first line, relative to the block's top.
+    first_line_bottom: f32,
+}
+
+/// Mapping between stored text and the text rendered in its place.
+///
+/// Rendered text omits hidden ranges of the stored text, like markup which is
+/// represented by styling instead. Offsets are relative to the block's start.
+#[derive(Default, Debug)]
+pub struct DisplayMap {
+    /// Sorted, non-overlapping ranges of hidden text.
+    hidden: Vec<Range<usize>>,
+}
+
+impl DisplayMap {
+    pub fn new(hidden: Vec<Range<usize>>) -> Self {
+        Self { hidden }
+    }
+
+    /// Convert a stored text offset to a rendered text offset.
+    ///
+    /// Offsets within hidden text are moved to the start of the hidden range.
+    pub fn to_display(&self, offset: usize) -> usize {
+        let mut removed = 0;
+        for range in self.hidden.iter().take_while(|range| range.start < offset) {
+            removed += offset.min(range.end) - range.start;
+        }
+        offset - removed
+    }
+
+    /// Convert a rendered text offset to a stored text offset.
+    ///
+    /// Offsets at the start of hidden text are placed before it.
+    pub fn to_stored(&self, mut offset: usize) -> usize {
+        for range in &self.hidden {
+            if range.start >= offset {
+                break;
+            }
+            offset += range.len();
+        }
+        offset
+    }
+}
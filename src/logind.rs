@@ -0,0 +1,115 @@
+//! Logind suspend and session lock notifications.
+
+use std::{process, thread};
+
+use calloop::LoopHandle;
+use calloop::channel::{self, Event, Sender};
+use tracing::{error, info, warn};
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedFd, OwnedObjectPath};
+
+use crate::State;
+
+/// Logind D-Bus service name.
+const LOGIND_DESTINATION: &str = "org.freedesktop.login1";
+
+/// Watch for system suspend and session lock.
+///
+/// All pending changes are written to disk immediately before either happens,
+/// since the system might not resume before debounced writes are due. Suspend
+/// is delayed with an inhibitor lock until the changes have been written.
+pub fn watch(event_loop: &LoopHandle<'static, State>) {
+    // Create calloop channel to flush changes on the main thread.
+    let (tx, rx) = channel::channel();
+    let _ = event_loop
+        .insert_source(rx, |event, _, state| {
+            // Inhibitor locks are released once the changes have been written.
+            if let Event::Msg((reason, _inhibitor)) = event {
+                info!("Writing pending changes before {reason}");
+                state.window.flush();
+            }
+        })
+        .inspect_err(|err| error!("Failed to insert logind source: {err}"));
+
+    let _ = thread::Builder::new()
+        .name("logind".into())
+        .spawn(move || {
+            if let Err(err) = watch_suspend(tx) {
+                warn!("Failed to watch logind signals: {err}");
+            }
+        })
+        .inspect_err(|err| error!("Failed to spawn logind thread: {err}"));
+}
+
+/// Forward logind's `PrepareForSleep` signals.
+fn watch_suspend(tx: Sender<(&'static str, Option<OwnedFd>)>) -> zbus::Result<()> {
+    let connection = Connection::system()?;
+    let manager = Proxy::new(
+        &connection,
+        LOGIND_DESTINATION,
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+    )?;
+
+    // Watch for locks of our session, if we're running inside of one.
+    match manager.call::<_, _, OwnedObjectPath>("GetSessionByPID", &(process::id(),)) {
+        Ok(session_path) => {
+            let (connection, tx) = (connection.clone(), tx.clone());
+            let _ = thread::Builder::new()
+                .name("logind-session".into())
+                .spawn(move || {
+                    if let Err(err) = watch_lock(&connection, session_path, tx) {
+                        warn!("Failed to watch logind session signals: {err}");
+                    }
+                })
+                .inspect_err(|err| error!("Failed to spawn logind session thread: {err}"));
+        },
+        Err(err) => info!("Not running inside a logind session: {err}"),
+    }
+
+    let signals = manager.receive_signal("PrepareForSleep")?;
+    let mut inhibitor = inhibit(&manager);
+
+    for message in signals {
+        // Reacquire the inhibitor lock after resuming.
+        let suspending: bool = message.body().deserialize()?;
+        if !suspending {
+            inhibitor = inhibit(&manager);
+            continue;
+        }
+
+        if tx.send(("suspend", inhibitor.take())).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Forward logind's session `Lock` signals.
+fn watch_lock(
+    connection: &Connection,
+    session_path: OwnedObjectPath,
+    tx: Sender<(&'static str, Option<OwnedFd>)>,
+) -> zbus::Result<()> {
+    let session =
+        Proxy::new(connection, LOGIND_DESTINATION, session_path, "org.freedesktop.login1.Session")?;
+
+    for _ in session.receive_signal("Lock")? {
+        if tx.send(("session lock", None)).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Take a delay inhibitor lock for system suspend.
+///
+/// Suspend is postponed until the returned file descriptor is closed.
+fn inhibit(manager: &Proxy<'_>) -> Option<OwnedFd> {
+    manager
+        .call("Inhibit", &("sleep", "Pinax", "Saving notes", "delay"))
+        .inspect_err(|err| warn!("Failed to take logind inhibitor lock: {err}"))
+        .ok()
+}
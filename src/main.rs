@@ -1,6 +1,12 @@
+use std::collections::HashMap;
+use std::io::{IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
-use std::{env, process};
+use std::{env, fs, io, path, process};
 
+use _cursor_shape::wp_cursor_shape_device_v1::{Shape, WpCursorShapeDeviceV1};
+use _data_control::zwlr_data_control_source_v1::ZwlrDataControlSourceV1;
+use calloop::signals::{Signal, Signals};
 use calloop::timer::{TimeoutAction, Timer};
 use calloop::{EventLoop, LoopHandle, RegistrationToken};
 use calloop_wayland_source::WaylandSource;
@@ -11,23 +17,61 @@ use smithay_client_toolkit::reexports::client::globals::{
 };
 use smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard;
 use smithay_client_toolkit::reexports::client::protocol::wl_pointer::WlPointer;
+use smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat;
 use smithay_client_toolkit::reexports::client::protocol::wl_touch::WlTouch;
 use smithay_client_toolkit::reexports::client::{
     ConnectError, Connection, DispatchError, QueueHandle,
 };
+use smithay_client_toolkit::reexports::protocols::wp::cursor_shape::v1::client as _cursor_shape;
+use smithay_client_toolkit::reexports::protocols_wlr::data_control::v1::client as _data_control;
 use smithay_client_toolkit::seat::keyboard::{Keysym, Modifiers, RepeatInfo};
 use tracing::{error, info};
+use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
 use crate::config::{Config, ConfigEventHandler};
+use crate::geometry::{Logical, Position};
+use crate::ipc::{IpcServer, Message};
+use crate::notify::Notifier;
+use crate::recovery::Replay;
 use crate::wayland::{ProtocolStates, TextInput};
-use crate::window::Window;
+use crate::window::{Action, KeyboardFocus, Window};
 
+mod accessibility;
+mod analysis;
+mod background;
+mod backup;
 mod config;
+mod editor;
 mod geometry;
+mod header;
+mod hooks;
+mod import;
+mod ipc;
+mod layout;
+mod logind;
+mod menu;
+mod metadata;
+mod notify;
+mod print;
+mod profile;
+mod quick;
+mod recovery;
+mod reminder;
 mod renderer;
+mod search;
+mod search_view;
+mod session;
 mod skia;
+mod snapshot;
+mod spellcheck;
+mod storage;
+mod text;
 mod text_box;
+mod text_scale;
+mod thumbnail;
+mod trash_view;
+mod vulkan;
 mod wayland;
 mod window;
 
@@ -36,39 +80,318 @@ mod gl {
     include!(concat!(env!("OUT_DIR"), "/gl_bindings.rs"));
 }
 
+/// URI prefix for text shared with Pinax, like `pinax://add?text=Hello`.
+const SHARE_URI: &str = "pinax://add?";
+
 fn main() {
+    // Log the duration of frame timing spans while profiling.
+    let profile = env::args().nth(1).as_deref() == Some("--profile");
+    let span_events = if profile { FmtSpan::CLOSE } else { FmtSpan::NONE };
+
     // Setup logging.
     let directives = env::var("RUST_LOG").unwrap_or("warn,pinax=info,configory=info".into());
     let env_filter = EnvFilter::builder().parse_lossy(directives);
-    FmtSubscriber::builder().with_env_filter(env_filter).with_line_number(true).init();
+    FmtSubscriber::builder()
+        .with_env_filter(env_filter)
+        .with_span_events(span_events)
+        .with_line_number(true)
+        .init();
+
+    // Forward requests to the running instance.
+    let mut shared_text = None;
+    match env::args().nth(1).as_deref() {
+        Some("show") => {
+            let token = env::var("XDG_ACTIVATION_TOKEN").ok();
+            match ipc::send(&Message::Show { token }) {
+                Ok(()) => return,
+                Err(err) => info!("No running instance found ({err}), starting Pinax"),
+            }
+        },
+        Some(command @ ("archive" | "save" | "fullscreen" | "maximize" | "paste-items")) => {
+            let message = command.parse::<Message>().unwrap();
+            if let Err(err) = ipc::send(&message) {
+                eprintln!("No running instance found: {err}");
+                process::exit(1);
+            }
+            return;
+        },
+        Some("print") => {
+            // Resolve relative paths before passing them to the running instance.
+            let path = match env::args_os().nth(2).map(path::absolute).transpose() {
+                Ok(path) => path,
+                Err(err) => {
+                    eprintln!("Invalid export path: {err}");
+                    process::exit(1);
+                },
+            };
+
+            if let Err(err) = ipc::send(&Message::Action(Action::Print { path })) {
+                eprintln!("No running instance found: {err}");
+                process::exit(1);
+            }
+            return;
+        },
+        Some("ipc") => {
+            let args: Vec<_> = env::args().skip(2).collect();
+            let message = match args.split_first() {
+                Some((subcommand, args)) if subcommand == "action" => Message::from_args(args),
+                Some((subcommand, [])) if subcommand == "get" => Ok(Message::Get { key: None }),
+                Some((subcommand, [key])) if subcommand == "get" => {
+                    Ok(Message::Get { key: Some(key.clone()) })
+                },
+                _ => Err("Usage: pinax ipc [action <ACTION> [ARGS...]|get [KEY]]".into()),
+            };
+            let message = message.unwrap_or_else(|err| {
+                eprintln!("Invalid IPC message: {err}");
+                process::exit(1);
+            });
+
+            // Print replies to queries.
+            if let Message::Get { .. } = message {
+                match ipc::request(&message) {
+                    Ok(Ok(value)) => println!("{}", value.trim_end()),
+                    Ok(Err(err)) => {
+                        eprintln!("{err}");
+                        process::exit(1);
+                    },
+                    Err(err) => {
+                        eprintln!("No running instance found: {err}");
+                        process::exit(1);
+                    },
+                }
+            } else if let Err(err) = ipc::send(&message) {
+                eprintln!("No running instance found: {err}");
+                process::exit(1);
+            }
+            return;
+        },
+        Some("append") => {
+            let text = match shared_arg_text() {
+                Ok(text) => text,
+                Err(err) => {
+                    eprintln!("Failed to read text from stdin: {err}");
+                    process::exit(1);
+                },
+            };
+
+            match share(text) {
+                Ok(()) => return,
+                Err(text) => shared_text = Some(text),
+            }
+        },
+        Some(uri) if uri.starts_with(SHARE_URI) => {
+            let text = uri[SHARE_URI.len()..]
+                .split('&')
+                .find_map(|param| param.strip_prefix("text="))
+                .and_then(ipc::percent_decode);
+            let text = match text {
+                Some(text) => text,
+                None => {
+                    eprintln!("Invalid share URI: {uri}");
+                    process::exit(1);
+                },
+            };
+
+            match share(text) {
+                Ok(()) => return,
+                Err(text) => shared_text = Some(text),
+            }
+        },
+        Some("import") => {
+            let source = match env::args_os().nth(2) {
+                Some(source) => PathBuf::from(source),
+                None => {
+                    eprintln!("Usage: pinax import <PATH>");
+                    process::exit(1);
+                },
+            };
+
+            match import_notes(&source) {
+                Ok(imported) => {
+                    for path in &imported {
+                        println!("Imported {}", path.display());
+                    }
+                    println!("Imported {} notes", imported.len());
+                },
+                Err(err) => {
+                    eprintln!("Failed to import notes: {err}");
+                    process::exit(1);
+                },
+            }
+            return;
+        },
+        Some("--quick") => {
+            if let Err(err) = quick::run() {
+                eprintln!("Quick capture failed: {err}");
+                process::exit(1);
+            }
+            return;
+        },
+        Some("--profile") => (),
+        Some(arg) => {
+            eprintln!("Unknown argument: {arg}");
+            eprintln!(
+                "Usage: pinax [show|archive|save|fullscreen|maximize|paste-items|print [PATH]|append [TEXT]|import <PATH>|ipc [action <ACTION> [ARGS...]|get [KEY]]|--quick|--profile]"
+            );
+            process::exit(1);
+        },
+        None => (),
+    }
 
     info!("Started Pinax");
 
-    if let Err(err) = run() {
+    if let Err(err) = prompt_import() {
+        error!("First-run import failed: {err}");
+    }
+
+    if let Err(err) = run(shared_text, profile) {
         error!("[CRITICAL] {err}");
         process::exit(1);
     }
 }
 
-fn run() -> Result<(), Error> {
+/// Get the text passed to `pinax append`, reading stdin without arguments.
+fn shared_arg_text() -> io::Result<String> {
+    let args: Vec<_> = env::args().skip(2).collect();
+    if !args.is_empty() {
+        return Ok(args.join(" "));
+    }
+
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Import notes from other applications into the note storage.
+fn import_notes(source: &Path) -> Result<Vec<PathBuf>, Error> {
+    let config = Config::load();
+    let storage_path = config.general.storage_path();
+    let storage_dir = storage_path.parent().ok_or(Error::InvalidStoragePath)?;
+    fs::create_dir_all(storage_dir)?;
+
+    let storage = storage::new(&config, storage_dir)?;
+    import::import(&*storage, storage_dir, source)
+}
+
+/// Offer importing notes from other applications on the first start.
+///
+/// The prompt is only shown once, when Pinax is started from a terminal.
+fn prompt_import() -> Result<(), Error> {
+    let marker = match import::prompted_marker() {
+        Some(marker) if io::stdin().is_terminal() => marker,
+        _ => return Ok(()),
+    };
+
+    let config = Config::load();
+    let storage_path = config.general.storage_path();
+    let storage_dir = storage_path.parent().ok_or(Error::InvalidStoragePath)?;
+    fs::create_dir_all(storage_dir)?;
+    let storage = storage::new(&config, storage_dir)?;
+    if !import::is_first_run(&*storage, storage_dir, &marker) {
+        return Ok(());
+    }
+
+    // Never prompt again, even if the import is skipped.
+    if let Some(marker_dir) = marker.parent() {
+        fs::create_dir_all(marker_dir)?;
+    }
+    fs::write(&marker, "")?;
+
+    println!("No notes found. Import Google Keep takeout, Markdown or text files?");
+    print!("Path to import (leave empty to skip): ");
+    io::stdout().flush()?;
+
+    let mut source = String::new();
+    io::stdin().read_line(&mut source)?;
+    let source = source.trim();
+    if source.is_empty() {
+        return Ok(());
+    }
+
+    let imported = import_notes(Path::new(source))?;
+    println!("Imported {} notes", imported.len());
+
+    Ok(())
+}
+
+/// Append shared text in the running instance and raise its window.
+///
+/// Returns the text if no instance is running, to add it after startup.
+fn share(text: String) -> Result<(), String> {
+    if let Err(err) = ipc::send(&Message::Action(Action::Append { text: text.clone() })) {
+        info!("No running instance found ({err}), starting Pinax");
+        return Err(text);
+    }
+
+    let token = env::var("XDG_ACTIVATION_TOKEN").ok();
+    if let Err(err) = ipc::send(&Message::Show { token }) {
+        error!("Failed to raise Pinax window: {err}");
+    }
+
+    Ok(())
+}
+
+fn run(shared_text: Option<String>, profile: bool) -> Result<(), Error> {
+    // Write unsaved changes to a recovery file if Pinax crashes.
+    recovery::install_panic_hook();
+
+    // Read unsaved changes of a previous instance, before new edits replace them.
+    let recovered = recovery::read();
+    let replay = Replay::read();
+
     // Initialize Wayland connection.
     let connection = Connection::connect_to_env()?;
     let (globals, queue) = globals::registry_queue_init(&connection)?;
 
     let mut event_loop = EventLoop::try_new()?;
+
+    // Block termination signals before any threads are spawned, since the
+    // signalfd only receives signals which are blocked in every thread.
+    let signals = Signals::new(&[Signal::SIGTERM, Signal::SIGINT])?;
+
     let mut state = State::new(event_loop.handle(), connection.clone(), &globals, queue.handle())?;
 
+    if profile {
+        state.window.enable_profiling();
+    }
+
+    // Restore unsaved changes from a previous crash.
+    //
+    // The recovery file is written after the last edit, so it takes precedence.
+    match (recovered, replay) {
+        (Some((path, text)), _) => state.window.restore_recovered(path, text),
+        (None, Some(replay)) => state.window.replay_journal(replay),
+        (None, None) => (),
+    }
+
+    // Add text shared by another application before Pinax was running.
+    if let Some(text) = shared_text {
+        state.window.append_item(&text);
+    }
+
     // Insert wayland source into calloop loop.
     let wayland_source = WaylandSource::new(connection, queue);
     wayland_source.insert(event_loop.handle())?;
 
+    // Shut down gracefully on termination signals, to save pending changes.
+    event_loop.handle().insert_source(signals, |event, _, state| {
+        info!("Received {:?}, shutting down", event.signal());
+        state.terminated = true;
+    })?;
+
     // Start event loop.
     while !state.terminated {
         event_loop.dispatch(None, &mut state)?;
     }
 
-    // Ensure text is saved on shutdown.
-    state.window.persist_text();
+    // Ensure text and session state are saved before the event loop is dropped.
+    state.window.flush();
+    drop(event_loop);
+
+    // Print frame timing summary.
+    if profile {
+        println!("{}", state.window.profiler());
+    }
 
     Ok(())
 }
@@ -78,19 +401,18 @@ struct State {
     event_loop: LoopHandle<'static, Self>,
     protocol_states: ProtocolStates,
 
-    keyboard: Option<KeyboardState>,
-    pointer: Option<WlPointer>,
-    text_input: Vec<TextInput>,
+    seats: Seats,
     clipboard: ClipboardState,
-    touch: Option<WlTouch>,
 
     window: Window,
+    notifier: Notifier,
 
     config: Config,
 
     terminated: bool,
 
     _config_manager: ConfigManager<ConfigEventHandler>,
+    _ipc_server: Option<IpcServer>,
 }
 
 impl State {
@@ -116,20 +438,92 @@ impl State {
         // Create the Wayland window.
         let window = Window::new(event_loop.clone(), &protocol_states, connection, queue, &config)?;
 
+        // Request focus if we were launched with an activation token.
+        if let Ok(token) = env::var("XDG_ACTIVATION_TOKEN") {
+            window.activate(&protocol_states, token);
+        }
+
+        // Listen for IPC messages.
+        let ipc_server = IpcServer::new(&event_loop)?;
+
+        // Save changes before the system suspends or the session is locked.
+        logind::watch(&event_loop);
+
+        // Follow the desktop's text scaling preference.
+        text_scale::watch(&event_loop);
+
+        // Connect to the desktop notification daemon.
+        let notifier = Notifier::new(&event_loop);
+
         Ok(Self {
             protocol_states,
             event_loop,
             config,
             window,
+            notifier,
             _config_manager: config_manager,
+            _ipc_server: ipc_server,
             terminated: Default::default(),
-            text_input: Default::default(),
             clipboard: Default::default(),
-            keyboard: Default::default(),
-            pointer: Default::default(),
-            touch: Default::default(),
+            seats: Default::default(),
+        })
+    }
+}
+
+/// Input state of all seats.
+#[derive(Default)]
+pub struct Seats {
+    seats: HashMap<WlSeat, Seat>,
+}
+
+impl Seats {
+    /// Get a seat's input state, adding it if it is unknown.
+    fn seat_mut(&mut self, seat: WlSeat) -> &mut Seat {
+        self.seats.entry(seat).or_default()
+    }
+
+    /// Remove a seat and release all its input devices.
+    fn remove(&mut self, event_loop: &LoopHandle<'static, State>, seat: &WlSeat) {
+        let keyboard = self.seats.remove(seat).and_then(|seat| seat.keyboard);
+        if let Some(mut keyboard) = keyboard {
+            keyboard.cancel_repeat(event_loop);
+        }
+    }
+
+    /// Get the state of a keyboard.
+    fn keyboard_mut(&mut self, wl_keyboard: &WlKeyboard) -> Option<&mut KeyboardState> {
+        self.seats
+            .values_mut()
+            .filter_map(|seat| seat.keyboard.as_mut())
+            .find(|keyboard| keyboard.wl_keyboard == *wl_keyboard)
+    }
+
+    /// Get the state of a pointer, with the modifiers of its seat's keyboard.
+    fn pointer_mut(&mut self, wl_pointer: &WlPointer) -> Option<(&mut PointerState, Modifiers)> {
+        self.seats.values_mut().find_map(|seat| {
+            let pointer =
+                seat.pointer.as_mut().filter(|pointer| pointer.wl_pointer == *wl_pointer)?;
+            let modifiers = seat.keyboard.as_ref().map(|keyboard| keyboard.modifiers);
+            Some((pointer, modifiers.unwrap_or_default()))
         })
     }
+
+    /// Get the state of a touch device.
+    fn touch_mut(&mut self, wl_touch: &WlTouch) -> Option<&mut TouchState> {
+        self.seats
+            .values_mut()
+            .filter_map(|seat| seat.touch.as_mut())
+            .find(|touch| touch.wl_touch == *wl_touch)
+    }
+}
+
+/// Input state of a single seat.
+#[derive(Default)]
+pub struct Seat {
+    keyboard: Option<KeyboardState>,
+    pointer: Option<PointerState>,
+    touch: Option<TouchState>,
+    text_input: Option<TextInput>,
 }
 
 /// Key status tracking for WlKeyboard.
@@ -158,16 +552,20 @@ impl KeyboardState {
     }
 
     /// Handle new key press.
+    ///
+    /// The `focus` is the element receiving the key press, which stops
+    /// repetition once the focus moves elsewhere.
     fn press_key(
         &mut self,
         event_loop: &LoopHandle<'static, State>,
-        time: u32,
+        focus: KeyboardFocus,
         raw: u32,
         keysym: Keysym,
+        text: Option<String>,
     ) {
         // Update key repeat timers.
         if !keysym.is_modifier_key() {
-            self.request_repeat(event_loop, time, raw, keysym);
+            self.request_repeat(event_loop, focus, raw, keysym, text);
         }
     }
 
@@ -179,42 +577,99 @@ impl KeyboardState {
         }
     }
 
+    /// Update the key repetition rate and delay.
+    ///
+    /// Active repetition is restarted using the new delay and rate.
+    fn set_repeat_info(&mut self, event_loop: &LoopHandle<'static, State>, info: RepeatInfo) {
+        self.repeat_info = info;
+
+        let mut repeat = match self.current_repeat.take() {
+            Some(repeat) => repeat,
+            None => return,
+        };
+        event_loop.remove(repeat.repeat_source);
+
+        let repeat_source = match self.repeat_timing() {
+            Some((delay, _)) => self.stage_repeat(event_loop, delay),
+            None => return,
+        };
+
+        if let Some(repeat_source) = repeat_source {
+            repeat.repeat_source = repeat_source;
+            self.current_repeat = Some(repeat);
+        }
+    }
+
     /// Stage new key repetition.
     fn request_repeat(
         &mut self,
         event_loop: &LoopHandle<'static, State>,
-        time: u32,
+        focus: KeyboardFocus,
         raw: u32,
         keysym: Keysym,
+        text: Option<String>,
     ) {
         // Ensure all previous events are cleared.
         self.cancel_repeat(event_loop);
 
-        let (delay_ms, rate) = match self.repeat_info {
-            RepeatInfo::Repeat { delay, rate } => (delay, rate),
-            _ => return,
+        // Stage timer for initial delay.
+        let repeat_source = match self.repeat_timing() {
+            Some((delay, _)) => self.stage_repeat(event_loop, delay),
+            None => return,
         };
 
-        // Stage timer for initial delay.
-        let delay = Duration::from_millis(delay_ms as u64);
-        let interval = Duration::from_millis(1000 / rate.get() as u64);
+        if let Some(repeat_source) = repeat_source {
+            self.current_repeat = Some(CurrentRepeat { repeat_source, focus, raw, keysym, text });
+        }
+    }
+
+    /// Insert a timer repeating the current key after `delay`.
+    fn stage_repeat(
+        &self,
+        event_loop: &LoopHandle<'static, State>,
+        delay: Duration,
+    ) -> Option<RegistrationToken> {
         let timer = Timer::from_duration(delay);
-        let repeat_source = event_loop.insert_source(timer, move |_, _, state| {
-            let keyboard = match state.keyboard.as_mut() {
-                Some(keyboard) => keyboard,
-                None => return TimeoutAction::Drop,
-            };
+        let wl_keyboard = self.wl_keyboard.clone();
+        event_loop
+            .insert_source(timer, move |_, _, state| Self::repeat_key(state, &wl_keyboard))
+            .inspect_err(|err| error!("Failed to stage key repeat timer: {err}"))
+            .ok()
+    }
 
-            state.window.press_key(raw, keysym, keyboard.modifiers);
+    /// Repeat the key held down on a keyboard.
+    fn repeat_key(state: &mut State, wl_keyboard: &WlKeyboard) -> TimeoutAction {
+        let keyboard = match state.seats.keyboard_mut(wl_keyboard) {
+            Some(keyboard) => keyboard,
+            None => return TimeoutAction::Drop,
+        };
+        let (repeat, interval) = match (&keyboard.current_repeat, keyboard.repeat_timing()) {
+            (Some(repeat), Some((_, interval))) => (repeat, interval),
+            _ => return TimeoutAction::Drop,
+        };
 
-            TimeoutAction::ToDuration(interval)
-        });
+        // Stop repetition once the key press is no longer handled by the same element.
+        if repeat.focus != state.window.keyboard_focus() {
+            keyboard.current_repeat = None;
+            return TimeoutAction::Drop;
+        }
+
+        let (raw, keysym, text) = (repeat.raw, repeat.keysym, repeat.text.clone());
+        let modifiers = keyboard.modifiers;
+        state.window.press_key(&state.config, raw, keysym, text.as_deref(), modifiers);
+
+        TimeoutAction::ToDuration(interval)
+    }
 
-        match repeat_source {
-            Ok(repeat_source) => {
-                self.current_repeat = Some(CurrentRepeat::new(repeat_source, raw, time, delay_ms));
+    /// Get the initial delay and interval of key repetition.
+    fn repeat_timing(&self) -> Option<(Duration, Duration)> {
+        match self.repeat_info {
+            RepeatInfo::Repeat { delay, rate } => {
+                let delay = Duration::from_millis(delay as u64);
+                let interval = Duration::from_millis(1000 / rate.get() as u64);
+                Some((delay, interval))
             },
-            Err(err) => error!("Failed to stage key repeat timer: {err}"),
+            RepeatInfo::Disable => None,
         }
     }
 
@@ -226,32 +681,114 @@ impl KeyboardState {
     }
 }
 
-/// Active keyboard repeat state.
-pub struct CurrentRepeat {
-    repeat_source: RegistrationToken,
-    interval: u32,
-    time: u32,
-    raw: u32,
+/// Pointer status tracking for WlPointer.
+pub struct PointerState {
+    wl_pointer: WlPointer,
+    shape_device: Option<WpCursorShapeDeviceV1>,
+    seat: WlSeat,
+
+    enter_serial: u32,
+    shape: Option<Shape>,
+}
+
+impl Drop for PointerState {
+    fn drop(&mut self) {
+        if let Some(shape_device) = &self.shape_device {
+            shape_device.destroy();
+        }
+        self.wl_pointer.release();
+    }
 }
 
-impl CurrentRepeat {
-    pub fn new(repeat_source: RegistrationToken, raw: u32, time: u32, interval: u32) -> Self {
-        Self { repeat_source, time, interval, raw }
+impl PointerState {
+    pub fn new(
+        wl_pointer: WlPointer,
+        shape_device: Option<WpCursorShapeDeviceV1>,
+        seat: WlSeat,
+    ) -> Self {
+        Self {
+            wl_pointer,
+            shape_device,
+            seat,
+            enter_serial: Default::default(),
+            shape: Default::default(),
+        }
+    }
+
+    /// Handle pointer entering the surface.
+    fn enter(&mut self, serial: u32) {
+        self.enter_serial = serial;
+        self.shape = None;
     }
 
-    /// Get the next key event timestamp.
-    pub fn next_time(&mut self) -> u32 {
-        self.time += self.interval;
-        self.time
+    /// Update the pointer's cursor shape.
+    fn set_shape(&mut self, shape: Shape) {
+        if self.shape == Some(shape) {
+            return;
+        }
+        self.shape = Some(shape);
+
+        if let Some(shape_device) = &self.shape_device {
+            shape_device.set_shape(self.enter_serial, shape);
+        }
     }
 }
 
+/// Touch point tracking for WlTouch.
+pub struct TouchState {
+    wl_touch: WlTouch,
+    seat: WlSeat,
+
+    /// IDs and positions of all active touch points, starting with the primary point.
+    points: Vec<(i32, Logical<Position<f64>>)>,
+    /// Whether the first active touch point started at the window's top edge.
+    top_edge: bool,
+    /// Whether the active touch points are handled as two-finger gesture.
+    gesture: bool,
+}
+
+impl Drop for TouchState {
+    fn drop(&mut self) {
+        self.wl_touch.release();
+    }
+}
+
+impl TouchState {
+    pub fn new(wl_touch: WlTouch, seat: WlSeat) -> Self {
+        Self {
+            wl_touch,
+            seat,
+            points: Default::default(),
+            top_edge: Default::default(),
+            gesture: Default::default(),
+        }
+    }
+
+    /// Get the positions of the first two touch points.
+    fn gesture_points(&self) -> Option<[Logical<Position<f64>>; 2]> {
+        match &self.points[..] {
+            [(_, first), (_, second), ..] => Some([*first, *second]),
+            _ => None,
+        }
+    }
+}
+
+/// Active keyboard repeat state.
+pub struct CurrentRepeat {
+    repeat_source: RegistrationToken,
+    focus: KeyboardFocus,
+    text: Option<String>,
+    keysym: Keysym,
+    raw: u32,
+}
+
 /// Clipboard content cache.
 #[derive(Default)]
 struct ClipboardState {
     serial: u32,
-    text: String,
+    content: Vec<(&'static str, Vec<u8>)>,
     source: Option<CopyPasteSource>,
+    control_source: Option<ZwlrDataControlSourceV1>,
 }
 
 impl ClipboardState {
@@ -259,6 +796,19 @@ impl ClipboardState {
         self.serial += 1;
         self.serial
     }
+
+    /// Get all offered MIME types.
+    fn mime_types(&self) -> Vec<&'static str> {
+        self.content.iter().map(|(mime_type, _)| *mime_type).collect()
+    }
+
+    /// Get the payload for a MIME type.
+    ///
+    /// This falls back to the first payload for unknown MIME types.
+    fn payload(&self, mime_type: &str) -> &[u8] {
+        let payload = self.content.iter().find(|(offered, _)| *offered == mime_type);
+        payload.or(self.content.first()).map_or(&[], |(_, payload)| payload.as_slice())
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -278,7 +828,17 @@ enum Error {
     #[error("{0}")]
     Glutin(#[from] glutin::error::Error),
     #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
     Notify(#[from] calloop_notify::notify::Error),
+    #[error("{0}")]
+    ShmPool(#[from] smithay_client_toolkit::shm::CreatePoolError),
+    #[error("{0}")]
+    Vulkan(#[from] ash::vk::Result),
+    #[error("{0}")]
+    VulkanLoading(#[from] ash::LoadingError),
+    #[error("{0}")]
+    Sqlite(#[from] rusqlite::Error),
     #[error("invalid storage path")]
     InvalidStoragePath,
 }
@@ -0,0 +1,157 @@
+//! Popup menu.
+
+use skia_safe::textlayout::{Paragraph, ParagraphBuilder, ParagraphStyle, TextStyle};
+use skia_safe::{Canvas as SkiaCanvas, Color4f, Paint, Point, Rect};
+
+use crate::config::{Colors, Config};
+use crate::geometry::Size;
+use crate::skia;
+
+/// Height of a menu entry at scale 1.
+const ENTRY_HEIGHT: f64 = 40.;
+
+/// Horizontal padding of menu entries at scale 1.
+const ENTRY_PADDING: f64 = 15.;
+
+/// Popup menu with a list of text entries.
+pub struct Menu {
+    paragraphs: Vec<Paragraph>,
+    rect: Rect,
+
+    background: Paint,
+    separator: Paint,
+    highlight: Color4f,
+
+    selected: Option<usize>,
+    entry_height: f32,
+    padding: f32,
+    scale: f64,
+}
+
+impl Menu {
+    /// Create a new menu.
+    ///
+    /// The menu is placed below the `anchor` rectangle, while staying within
+    /// `bounds`.
+    pub fn new(
+        config: &Config,
+        colors: &Colors,
+        entries: &[&str],
+        anchor: Rect,
+        bounds: Size,
+        scale: f64,
+    ) -> Self {
+        let font_collection = skia::font_collection(config.font.directory.as_deref());
+
+        let mut text_paint = Paint::default();
+        text_paint.set_color4f(colors.foreground.as_color4f(), None);
+        text_paint.set_anti_alias(true);
+
+        let mut text_style = TextStyle::new();
+        text_style.set_foreground_paint(&text_paint);
+        text_style.set_font_size((config.font.size * scale) as f32);
+        text_style.set_font_families(&config.font.family);
+
+        let mut paragraph_style = ParagraphStyle::new();
+        paragraph_style.set_text_style(&text_style);
+        paragraph_style.set_max_lines(1);
+        paragraph_style.set_ellipsis("…");
+
+        // Layout entries, limiting them to the available width.
+        let padding = (ENTRY_PADDING * scale).round() as f32;
+        let max_width = (bounds.width as f32 - 2. * padding).max(0.);
+        let mut width = 0f32;
+        let paragraphs: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                let mut builder = ParagraphBuilder::new(&paragraph_style, &font_collection);
+                builder.add_text(entry);
+                let mut paragraph = builder.build();
+                paragraph.layout(max_width);
+                width = width.max(paragraph.max_intrinsic_width().ceil().min(max_width));
+                paragraph
+            })
+            .collect();
+
+        // Position menu below the anchor, flipping it above if there's no space.
+        let entry_height = (ENTRY_HEIGHT * scale).round() as f32;
+        let size = (width + 2. * padding, entry_height * paragraphs.len() as f32);
+        let x = anchor.left.min(bounds.width as f32 - size.0).max(0.);
+        let mut y = anchor.bottom;
+        if y + size.1 > bounds.height as f32 {
+            y = (anchor.top - size.1).max(0.);
+        }
+        let rect = Rect::from_xywh(x, y, size.0, size.1);
+
+        let mut background = Paint::default();
+        background.set_color4f(colors.header.as_color4f(), None);
+
+        let mut separator = Paint::default();
+        separator.set_color4f(colors.background.color().as_color4f(), None);
+
+        Self {
+            paragraphs,
+            rect,
+            background,
+            separator,
+            entry_height,
+            padding,
+            scale,
+            highlight: colors.highlight.as_color4f(),
+            selected: None,
+        }
+    }
+
+    /// Render the menu to the canvas.
+    pub fn draw(&self, canvas: &SkiaCanvas) {
+        canvas.draw_rect(self.rect, &self.background);
+
+        for (i, paragraph) in self.paragraphs.iter().enumerate() {
+            let top = self.rect.top + i as f32 * self.entry_height;
+
+            // Separate entries with a thin line.
+            if i > 0 {
+                let separator = Rect::from_xywh(self.rect.left, top, self.rect.width(), 1.);
+                canvas.draw_rect(separator, &self.separator);
+            }
+
+            let y = top + ((self.entry_height - paragraph.height()) / 2.).round();
+            paragraph.paint(canvas, Point::new(self.rect.left + self.padding, y));
+
+            // Outline the entry selected by keyboard navigation.
+            if self.selected == Some(i) {
+                let width = self.rect.width();
+                let rect = Rect::from_xywh(self.rect.left, top, width, self.entry_height);
+                skia::draw_focus_outline(canvas, rect, self.highlight, self.scale);
+            }
+        }
+    }
+
+    /// Move the keyboard selection by `delta` entries, wrapping around at the ends.
+    ///
+    /// Without selection, this selects the first or last entry.
+    pub fn move_selection(&mut self, delta: isize) {
+        let len = self.paragraphs.len() as isize;
+        let index = match self.selected {
+            Some(selected) => (selected as isize + delta).rem_euclid(len),
+            None if delta >= 0 => 0,
+            None => len - 1,
+        };
+        self.selected = (len > 0).then_some(index as usize);
+    }
+
+    /// Get the index of the entry selected by keyboard navigation.
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Get the index of the entry at a physical position.
+    pub fn entry_at(&self, position: Point) -> Option<usize> {
+        if !self.rect.contains(position) {
+            return None;
+        }
+
+        let index = ((position.y - self.rect.top) / self.entry_height) as usize;
+        (index < self.paragraphs.len()).then_some(index)
+    }
+}
@@ -0,0 +1,112 @@
+//! Per-note metadata.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use toml::Table;
+use tracing::error;
+
+use crate::config::{Background, Color, Colors};
+use crate::storage::Storage;
+
+/// Note metadata.
+///
+/// This is stored in the TOML format by the storage backend, as a sidecar file
+/// next to the note for flat-file storage.
+#[derive(Deserialize, Default, PartialEq, Eq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Metadata {
+    /// Title shown in the header bar.
+    pub title: Option<String>,
+    /// Note-specific color overrides.
+    pub colors: ColorOverrides,
+}
+
+impl Metadata {
+    /// Load the metadata for the note at `storage_path`.
+    ///
+    /// This will return the default metadata if the note has no metadata or
+    /// it could not be parsed.
+    pub fn load(storage: &dyn Storage, storage_path: &Path) -> Self {
+        let content = match storage.read_metadata(storage_path) {
+            Ok(Some(content)) => content,
+            Ok(None) => return Self::default(),
+            Err(err) => {
+                error!("Failed to read note metadata for {storage_path:?}: {err}");
+                return Self::default();
+            },
+        };
+
+        toml::from_str(&content)
+            .inspect_err(|err| error!("Invalid note metadata for {storage_path:?}: {err}"))
+            .unwrap_or_default()
+    }
+
+    /// Update the title in the metadata of the note at `storage_path`.
+    ///
+    /// All other metadata content is preserved.
+    pub fn save_title(storage: &dyn Storage, storage_path: &Path, title: &str) {
+        let mut table = match storage.read_metadata(storage_path) {
+            Ok(Some(content)) => match toml::from_str::<Table>(&content) {
+                Ok(table) => table,
+                Err(err) => {
+                    error!(
+                        "Refusing to overwrite invalid note metadata for {storage_path:?}: {err}"
+                    );
+                    return;
+                },
+            },
+            Ok(None) => Table::new(),
+            Err(err) => {
+                error!("Failed to read note metadata for {storage_path:?}: {err}");
+                return;
+            },
+        };
+        table.insert("title".into(), title.into());
+
+        if let Err(err) = storage.write_metadata(storage_path, &table.to_string()) {
+            error!("Failed to write note metadata for {storage_path:?}: {err}");
+        }
+    }
+
+    /// Get the note's title.
+    ///
+    /// Falls back to the storage file's name if no title was set.
+    pub fn title(&self, storage_path: &Path) -> String {
+        match &self.title {
+            Some(title) => title.clone(),
+            None => storage_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Note-specific color palette.
+///
+/// Every color which is not set falls back to the configured color.
+#[derive(Deserialize, Default, Clone, PartialEq, Eq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorOverrides {
+    #[serde(alias = "fg")]
+    pub foreground: Option<Color>,
+    #[serde(alias = "bg")]
+    pub background: Option<Background>,
+    #[serde(alias = "hl")]
+    pub highlight: Option<Color>,
+    pub header: Option<Color>,
+}
+
+impl ColorOverrides {
+    /// Apply the overrides to a color palette.
+    pub fn apply(&self, colors: &Colors) -> Colors {
+        Colors {
+            foreground: self.foreground.unwrap_or(colors.foreground),
+            background: self.background.clone().unwrap_or_else(|| colors.background.clone()),
+            highlight: self.highlight.unwrap_or(colors.highlight),
+            header: self.header.unwrap_or(colors.header),
+            ..colors.clone()
+        }
+    }
+}
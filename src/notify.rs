@@ -0,0 +1,146 @@
+//! Desktop notifications.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+
+use calloop::LoopHandle;
+use calloop::channel::{self, Event, Sender};
+use tracing::{error, warn};
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::Value;
+
+use crate::State;
+
+/// Notification D-Bus service name.
+const NOTIFICATIONS_DESTINATION: &str = "org.freedesktop.Notifications";
+
+/// Desktop notification sender.
+///
+/// Activating a notification raises the window.
+pub struct Notifier {
+    tx: mpsc::Sender<Notification>,
+}
+
+impl Notifier {
+    pub fn new(event_loop: &LoopHandle<'static, State>) -> Self {
+        // Create calloop channel to show the window on the main thread.
+        let (activation_tx, activation_rx) = channel::channel();
+        let _ = event_loop
+            .insert_source(activation_rx, |event, _, state| {
+                if let Event::Msg(token) = event {
+                    state.window.show();
+
+                    match token {
+                        Some(token) => state.window.activate(&state.protocol_states, token),
+                        None => warn!("Ignoring notification activation without activation token"),
+                    }
+                }
+            })
+            .inspect_err(|err| error!("Failed to insert notification source: {err}"));
+
+        let (tx, rx) = mpsc::channel();
+        let _ = thread::Builder::new()
+            .name("notifications".into())
+            .spawn(move || {
+                if let Err(err) = send_notifications(rx, activation_tx) {
+                    warn!("Failed to send desktop notifications: {err}");
+                }
+            })
+            .inspect_err(|err| error!("Failed to spawn notification thread: {err}"));
+
+        Self { tx }
+    }
+
+    /// Send a desktop notification.
+    pub fn notify(&self, summary: impl Into<String>, body: impl Into<String>) {
+        let _ = self.tx.send(Notification { summary: summary.into(), body: body.into() });
+    }
+}
+
+/// Desktop notification content.
+struct Notification {
+    summary: String,
+    body: String,
+}
+
+/// Forward notifications to the notification daemon.
+fn send_notifications(
+    rx: mpsc::Receiver<Notification>,
+    activation_tx: Sender<Option<String>>,
+) -> zbus::Result<()> {
+    let connection = Connection::session()?;
+    let proxy = Proxy::new(
+        &connection,
+        NOTIFICATIONS_DESTINATION,
+        "/org/freedesktop/Notifications",
+        "org.freedesktop.Notifications",
+    )?;
+
+    // Watch for activation of the notifications we've sent.
+    let ids = Arc::new(Mutex::new(HashSet::new()));
+    let (watched_ids, watched_proxy) = (ids.clone(), proxy.clone());
+    let _ = thread::Builder::new()
+        .name("notification-actions".into())
+        .spawn(move || {
+            if let Err(err) = watch_actions(&watched_proxy, &watched_ids, activation_tx) {
+                warn!("Failed to watch notification actions: {err}");
+            }
+        })
+        .inspect_err(|err| error!("Failed to spawn notification actions thread: {err}"));
+
+    for notification in rx {
+        let actions = ["default", "Open"].as_slice();
+        let hints = HashMap::<&str, Value>::new();
+        let id: u32 = proxy.call(
+            "Notify",
+            &("Pinax", 0u32, "", notification.summary, notification.body, actions, hints, -1i32),
+        )?;
+
+        if let Ok(mut ids) = ids.lock() {
+            ids.insert(id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Forward activation of our notifications.
+fn watch_actions(
+    proxy: &Proxy<'_>,
+    ids: &Mutex<HashSet<u32>>,
+    activation_tx: Sender<Option<String>>,
+) -> zbus::Result<()> {
+    // Activation tokens are sent immediately before the action itself.
+    let mut token = None;
+
+    for message in proxy.receive_all_signals()? {
+        let header = message.header();
+        let Some(member) = header.member() else { continue };
+        let Ok(mut ids) = ids.lock() else { break };
+
+        match member.as_str() {
+            "ActivationToken" => {
+                let (id, activation_token): (u32, String) = message.body().deserialize()?;
+                if ids.contains(&id) {
+                    token = Some(activation_token);
+                }
+            },
+            "ActionInvoked" => {
+                let (id, _action): (u32, String) = message.body().deserialize()?;
+                if ids.remove(&id) && activation_tx.send(token.take()).is_err() {
+                    break;
+                }
+            },
+            "NotificationClosed" => {
+                let (id, _reason): (u32, u32) = message.body().deserialize()?;
+                if ids.remove(&id) {
+                    token = None;
+                }
+            },
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,182 @@
+//! PDF export and printing.
+
+use std::io::{self, Write};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+
+use calloop::LoopHandle;
+use calloop::channel::{self, Event, Sender};
+use skia_safe::textlayout::{FontCollection, ParagraphBuilder, ParagraphStyle, TextStyle};
+use skia_safe::{Color4f, Paint, Rect, pdf};
+use tracing::{error, info};
+
+use crate::State;
+use crate::config::{self, Config, Layout, ListStyle};
+use crate::editor::{item_numbers, list_items};
+use crate::skia;
+
+/// PDF points per millimeter.
+const POINTS_PER_MM: f64 = 72. / 25.4;
+
+/// Runner for the configured print command.
+pub struct Printer {
+    tx: Sender<io::Result<ExitStatus>>,
+    command: Vec<String>,
+}
+
+impl Printer {
+    pub fn new(event_loop: &LoopHandle<'static, State>, config: &Config) -> Self {
+        // Create calloop channel to report print results on the main thread.
+        let (tx, rx) = channel::channel();
+        let _ = event_loop
+            .insert_source(rx, |event, _, state| {
+                if let Event::Msg(result) = event {
+                    Self::handle_result(state, result);
+                }
+            })
+            .inspect_err(|err| error!("Failed to insert print source: {err}"));
+
+        Self { tx, command: config.print.command.clone() }
+    }
+
+    /// Pass a PDF document to the print command in the background.
+    pub fn print(&self, document: Vec<u8>) {
+        let Some((program, args)) = self.command.split_first() else {
+            error!("Failed to print note: no print command configured");
+            return;
+        };
+
+        let mut command = Command::new(program);
+        command.args(args).stdin(Stdio::piped());
+
+        let tx = self.tx.clone();
+        let _ = thread::Builder::new()
+            .name("print".into())
+            .spawn(move || {
+                let status = command.spawn().and_then(|mut child| {
+                    if let Some(mut stdin) = child.stdin.take() {
+                        stdin.write_all(&document)?;
+                    }
+                    child.wait()
+                });
+                let _ = tx.send(status);
+            })
+            .inspect_err(|err| error!("Failed to spawn print thread: {err}"));
+    }
+
+    /// Handle a finished print command on the main thread.
+    fn handle_result(state: &mut State, status: io::Result<ExitStatus>) {
+        match status {
+            Ok(status) if status.success() => {
+                info!("Successfully printed note");
+                state.window.toast("Note sent to printer".into());
+            },
+            Ok(status) => {
+                error!("Print command failed: {status}");
+                state.window.toast(format!("Printing failed ({status})"));
+            },
+            Err(err) => {
+                error!("Failed to run print command: {err}");
+                state.window.toast(format!("Failed to print: {err}"));
+            },
+        }
+    }
+
+    /// Handle config updates.
+    pub fn update_config(&mut self, config: &Config) {
+        self.command.clone_from(&config.print.command);
+    }
+}
+
+/// Render text into a multi-page PDF document.
+///
+/// The entire text is laid out at the page width, with lines moved to the
+/// next page rather than splitting them at the page boundary.
+pub fn render_pdf(
+    font_collection: &FontCollection,
+    text_style: &TextStyle,
+    text: &str,
+    print: &config::Print,
+    layout: &Layout,
+) -> Vec<u8> {
+    let (page_width, page_height) = print.paper.size();
+    let margin = (print.margin * POINTS_PER_MM) as f32;
+    let content_width = (page_width - 2. * margin).max(1.);
+    let content_height = (page_height - 2. * margin).max(1.);
+
+    // Print black text, independent of the configured colors.
+    let mut paint = Paint::new(Color4f::new(0., 0., 0., 1.), None);
+    paint.set_anti_alias(true);
+    let mut text_style = text_style.clone();
+    text_style.set_foreground_paint(&paint);
+
+    let mut paragraph_style = ParagraphStyle::new();
+    paragraph_style.set_text_style(&text_style);
+    let mut builder = ParagraphBuilder::new(&paragraph_style, font_collection);
+    builder.add_text(text);
+    let mut paragraph = builder.build();
+    paragraph.layout(content_width);
+
+    // Find the vertical offsets at which each page starts.
+    let mut page_tops = vec![0.];
+    for metrics in paragraph.get_line_metrics() {
+        let top = (metrics.baseline - metrics.ascent) as f32;
+        let bottom = (metrics.baseline + metrics.descent) as f32;
+        let page_top = *page_tops.last().unwrap();
+        if bottom - page_top > content_height && top > page_top {
+            page_tops.push(top);
+        }
+    }
+
+    // Get bullet point positions for all list items.
+    let bullet_padding = layout.bullet_padding() as f32;
+    let items = list_items(text);
+    let numbers = item_numbers(text, &items);
+    let bullets: Vec<_> = items
+        .into_iter()
+        .zip(numbers)
+        .filter_map(|(item, number)| {
+            let line = paragraph.get_line_number_at(item.start)?;
+            let metrics = paragraph.get_line_metrics_at(line)?;
+            let y = metrics.baseline - metrics.ascent / 2. + metrics.descent / 2.;
+            let x = metrics.left as f32 - bullet_padding;
+            Some((x, y as f32, metrics.baseline as f32, number))
+        })
+        .collect();
+
+    let mut output = Vec::new();
+    let mut document = pdf::new_document(&mut output, None);
+    for (i, page_top) in page_tops.iter().enumerate() {
+        let page_bottom = page_tops.get(i + 1).copied().unwrap_or(f32::MAX);
+
+        let mut page = document.begin_page((page_width, page_height), None);
+        let canvas = page.canvas();
+
+        // Hide lines belonging to the neighboring pages.
+        let clip_height = (page_bottom - page_top).min(content_height);
+        canvas.clip_rect(Rect::new(0., margin, page_width, margin + clip_height), None, None);
+        canvas.translate((margin, margin - page_top));
+
+        paragraph.paint(canvas, (0., 0.));
+
+        let (shape, size) = (layout.bullet_shape, layout.bullet_size as f32);
+        let bullet_width = shape.width(layout.bullet_size) as f32;
+        for &(x, y, baseline, number) in
+            bullets.iter().filter(|(_, y, ..)| (*page_top..page_bottom).contains(y))
+        {
+            match layout.list_style {
+                ListStyle::Bullets => skia::draw_bullet(canvas, shape, x, y, size, &paint),
+                ListStyle::Numbers => {
+                    let (fonts, style) = (font_collection, &text_style);
+                    let right = x + bullet_width;
+                    skia::draw_item_number(canvas, fonts, style, number, right, baseline, &paint);
+                },
+            }
+        }
+
+        document = page.end_page();
+    }
+    document.close();
+
+    output
+}
@@ -0,0 +1,234 @@
+//! Frame timing instrumentation.
+
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+use skia_safe::textlayout::{FontCollection, ParagraphBuilder, ParagraphStyle, TextStyle};
+use skia_safe::{Canvas as SkiaCanvas, Color4f, Paint, Point, Rect};
+use tracing::debug_span;
+use tracing::span::EnteredSpan;
+
+use crate::skia;
+
+/// Upper bounds of the histogram buckets in milliseconds.
+const BUCKETS: [f64; 8] = [0.5, 1., 2., 4., 8., 16., 33., 66.];
+
+/// Width of the largest histogram bar in characters.
+const BAR_WIDTH: usize = 40;
+
+/// Interval over which the overlay's frame rate is averaged.
+const FPS_WINDOW: Duration = Duration::from_secs(1);
+
+/// Overlay font size at scale 1.
+const OVERLAY_FONT_SIZE: f64 = 12.;
+
+/// Overlay padding and distance from the window edge at scale 1.
+const OVERLAY_PADDING: f64 = 5.;
+
+/// Frame rendering phase.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Phase {
+    /// Text layout and damage calculation.
+    Layout,
+    /// Recording of draw commands.
+    Paint,
+    /// Submission of draw commands to the GPU and buffer swap.
+    Submit,
+    /// Wayland surface commit.
+    Flush,
+    /// The entire frame.
+    Frame,
+}
+
+impl Phase {
+    const ALL: [Self; 5] = [Self::Layout, Self::Paint, Self::Submit, Self::Flush, Self::Frame];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Layout => "layout",
+            Self::Paint => "paint",
+            Self::Submit => "submit",
+            Self::Flush => "flush",
+            Self::Frame => "frame",
+        }
+    }
+
+    fn span(&self) -> EnteredSpan {
+        let span = match self {
+            Self::Layout => debug_span!("layout"),
+            Self::Paint => debug_span!("paint"),
+            Self::Submit => debug_span!("submit"),
+            Self::Flush => debug_span!("flush"),
+            Self::Frame => debug_span!("frame"),
+        };
+        span.entered()
+    }
+}
+
+/// Frame timing recorder.
+///
+/// Frame times for the overlay are always tracked, while histograms are only
+/// recorded in profiling mode.
+#[derive(Default)]
+pub struct Profiler {
+    histograms: [Histogram; Phase::ALL.len()],
+    frames: VecDeque<Instant>,
+    last_frame: Duration,
+
+    font_collection: Option<FontCollection>,
+    overlay: bool,
+    enabled: bool,
+}
+
+impl Profiler {
+    /// Enable recording of frame timing histograms.
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    /// Start timing a frame phase.
+    pub fn start(&self, phase: Phase) -> Timing {
+        Timing { start: Instant::now(), _span: phase.span() }
+    }
+
+    /// Record the duration of a frame phase.
+    pub fn record(&mut self, phase: Phase, duration: Duration) {
+        if phase == Phase::Frame {
+            let now = Instant::now();
+            while self.frames.front().is_some_and(|frame| now - *frame > FPS_WINDOW) {
+                self.frames.pop_front();
+            }
+            self.frames.push_back(now);
+            self.last_frame = duration;
+        }
+
+        if self.enabled {
+            let index = Phase::ALL.iter().position(|p| *p == phase).unwrap();
+            self.histograms[index].record(duration);
+        }
+    }
+
+    /// Toggle the frame time overlay.
+    pub fn toggle_overlay(&mut self) {
+        self.overlay = !self.overlay;
+    }
+
+    /// Check if the frame time overlay is visible.
+    pub fn overlay(&self) -> bool {
+        self.overlay
+    }
+
+    /// Render the frame time overlay in the top right, below `top`.
+    pub fn draw_overlay(&mut self, canvas: &SkiaCanvas, width: u32, top: f32, scale: f64) {
+        if !self.overlay {
+            return;
+        }
+
+        let fps = self.frames.len() as f64 / FPS_WINDOW.as_secs_f64();
+        let frame_time = self.last_frame.as_secs_f64() * 1000.;
+        let text = format!("{fps:.0} FPS · {frame_time:.2} ms");
+
+        let mut paint = Paint::default();
+        paint.set_color4f(Color4f::new(1., 1., 1., 1.), None);
+        paint.set_anti_alias(true);
+
+        let mut text_style = TextStyle::new();
+        text_style.set_foreground_paint(&paint);
+        text_style.set_font_size((OVERLAY_FONT_SIZE * scale) as f32);
+        text_style.set_font_families(&["monospace"]);
+
+        let mut paragraph_style = ParagraphStyle::new();
+        paragraph_style.set_text_style(&text_style);
+
+        let font_collection =
+            self.font_collection.get_or_insert_with(|| skia::font_collection(None));
+        let mut builder = ParagraphBuilder::new(&paragraph_style, font_collection);
+        builder.add_text(text);
+        let mut paragraph = builder.build();
+        paragraph.layout(f32::MAX);
+
+        let padding = (OVERLAY_PADDING * scale).round() as f32;
+        let size = (paragraph.max_intrinsic_width().ceil(), paragraph.height());
+        let size = (size.0 + 2. * padding, size.1 + 2. * padding);
+        let x = width as f32 - size.0 - padding;
+        let rect = Rect::from_xywh(x, top + padding, size.0, size.1);
+
+        let mut background = Paint::default();
+        background.set_color4f(Color4f::new(0., 0., 0., 0.7), None);
+        canvas.draw_rect(rect, &background);
+        paragraph.paint(canvas, Point::new(rect.left + padding, rect.top + padding));
+    }
+}
+
+/// Print a histogram for every frame phase.
+impl Display for Profiler {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let frames = self.histograms[Phase::ALL.len() - 1].count;
+        writeln!(f, "Frame timings ({frames} frames):")?;
+
+        for (phase, histogram) in Phase::ALL.iter().zip(&self.histograms) {
+            writeln!(f)?;
+            writeln!(f, "{}: {histogram}", phase.name())?;
+            histogram.fmt_buckets(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Running timer for a frame phase.
+pub struct Timing {
+    start: Instant,
+    _span: EnteredSpan,
+}
+
+impl Timing {
+    /// Stop the timer, returning the elapsed time.
+    pub fn finish(self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Distribution of a phase's durations.
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len() + 1],
+    count: u64,
+    total: Duration,
+    max: Duration,
+}
+
+impl Histogram {
+    fn record(&mut self, duration: Duration) {
+        let millis = duration.as_secs_f64() * 1000.;
+        let index = BUCKETS.iter().position(|bound| millis < *bound).unwrap_or(BUCKETS.len());
+        self.buckets[index] += 1;
+
+        self.count += 1;
+        self.total += duration;
+        self.max = self.max.max(duration);
+    }
+
+    /// Print one bar for every bucket.
+    fn fmt_buckets(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let max_count = self.buckets.iter().copied().max().unwrap_or(0).max(1);
+        for (i, count) in self.buckets.iter().enumerate() {
+            let label = match BUCKETS.get(i) {
+                Some(bound) => format!("< {bound} ms"),
+                None => format!(">= {} ms", BUCKETS[BUCKETS.len() - 1]),
+            };
+            let bar = "#".repeat((*count as usize * BAR_WIDTH).div_ceil(max_count as usize));
+            writeln!(f, "  {label:>9} | {bar:<BAR_WIDTH$} {count}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Histogram {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mean = self.total.as_secs_f64() * 1000. / self.count.max(1) as f64;
+        let max = self.max.as_secs_f64() * 1000.;
+        write!(f, "mean {mean:.2} ms, max {max:.2} ms")
+    }
+}
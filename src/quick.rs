@@ -0,0 +1,458 @@
+//! Quick-capture popup for adding a single list item.
+//!
+//! The popup is a small layer shell overlay, which can be bound to a global
+//! compositor shortcut. Pressing Enter appends the typed line to the note and
+//! exits, while Escape discards it.
+
+use std::fmt::Write as _;
+use std::fs;
+
+use chrono::Local;
+use skia_safe::textlayout::{FontCollection, ParagraphBuilder, ParagraphStyle, TextStyle};
+use skia_safe::{Color4f, Paint, Point};
+use smithay_client_toolkit::compositor::{CompositorHandler, CompositorState};
+use smithay_client_toolkit::output::{OutputHandler, OutputState};
+use smithay_client_toolkit::reexports::client::globals::{self, GlobalList};
+use smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard;
+use smithay_client_toolkit::reexports::client::protocol::wl_output::{Transform, WlOutput};
+use smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat;
+use smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface;
+use smithay_client_toolkit::reexports::client::{Connection, QueueHandle};
+use smithay_client_toolkit::registry::{ProvidesRegistryState, RegistryState};
+use smithay_client_toolkit::seat::keyboard::{
+    KeyEvent, KeyboardHandler, Keysym, Modifiers, RawModifiers,
+};
+use smithay_client_toolkit::seat::{Capability, SeatHandler, SeatState};
+use smithay_client_toolkit::shell::WaylandSurface;
+use smithay_client_toolkit::shell::wlr_layer::{
+    Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface,
+    LayerSurfaceConfigure,
+};
+use smithay_client_toolkit::shm::{Shm, ShmHandler};
+use smithay_client_toolkit::{
+    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_registry,
+    delegate_seat, delegate_shm, registry_handlers,
+};
+use tracing::{info, warn};
+
+use crate::background::Background;
+use crate::config::Config;
+use crate::editor::{self, normalize_paste};
+use crate::geometry::{Logical, Size};
+use crate::ipc::{self, Message};
+use crate::renderer::Renderer;
+use crate::window::Action;
+use crate::{Error, skia, storage};
+
+/// Popup size at scale 1.
+const SIZE: Size = Size { width: 360, height: 48 };
+
+/// Distance between the popup and the top of the screen at scale 1.
+const TOP_MARGIN: i32 = 64;
+
+/// Text shown while nothing was typed yet.
+const PLACEHOLDER: &str = "New item";
+
+/// Placeholder text alpha.
+const PLACEHOLDER_ALPHA: f32 = 0.5;
+
+/// Show the quick-capture popup until an item was added or discarded.
+pub fn run() -> Result<(), Error> {
+    let connection = Connection::connect_to_env()?;
+    let (globals, mut queue) = globals::registry_queue_init(&connection)?;
+
+    let config = Config::load();
+    let mut state = QuickCapture::new(connection, &globals, &queue.handle(), config)?;
+
+    while !state.terminated {
+        queue.blocking_dispatch(&mut state)?;
+    }
+
+    // Submit the item after the popup was closed.
+    match state.item.take() {
+        Some(item) => append(&state.config, item),
+        None => Ok(()),
+    }
+}
+
+/// Append an item to the running instance's note, or directly to storage.
+fn append(config: &Config, item: String) -> Result<(), Error> {
+    match ipc::send(&Message::Action(Action::Append { text: item.clone() })) {
+        Ok(()) => return Ok(()),
+        Err(err) => info!("No running instance found ({err}), writing to storage"),
+    }
+
+    let storage_path = config.general.storage_path();
+    let storage_dir = storage_path.parent().ok_or(Error::InvalidStoragePath)?;
+    fs::create_dir_all(storage_dir)?;
+    let storage = storage::new(config, storage_dir)?;
+
+    let mut text = storage.read(&storage_path)?.unwrap_or_default().trim_end().to_owned();
+    if !text.is_empty() {
+        text.push_str("\n\n");
+    }
+    if config.general.auto_timestamp {
+        let format = &config.general.timestamp_format;
+        let start = text.len();
+        match write!(text, "{} ", Local::now().format(format)) {
+            Ok(()) => (),
+            Err(_) => {
+                warn!("Invalid timestamp format: {format:?}");
+                text.truncate(start);
+            },
+        }
+    }
+    text.push_str(&item);
+
+    storage.write(&storage_path, &mut [text.as_str()].into_iter())
+}
+
+/// Quick-capture popup state.
+struct QuickCapture {
+    registry: RegistryState,
+    output: OutputState,
+    seat: SeatState,
+    shm: Shm,
+
+    layer: LayerSurface,
+    renderer: Renderer,
+    keyboard: Option<WlKeyboard>,
+
+    font_collection: FontCollection,
+    background: Background,
+    config: Config,
+
+    /// Text typed so far.
+    text: String,
+    /// Item which should be appended after exit.
+    item: Option<String>,
+
+    configured: bool,
+    terminated: bool,
+    scale: i32,
+}
+
+impl QuickCapture {
+    fn new(
+        connection: Connection,
+        globals: &GlobalList,
+        queue: &QueueHandle<Self>,
+        config: Config,
+    ) -> Result<Self, Error> {
+        let compositor = CompositorState::bind(globals, queue)
+            .map_err(|err| Error::WaylandProtocol("wl_compositor", err))?;
+        let layer_shell = LayerShell::bind(globals, queue)
+            .map_err(|err| Error::WaylandProtocol("zwlr_layer_shell_v1", err))?;
+        let shm = Shm::bind(globals, queue).map_err(|err| Error::WaylandProtocol("wl_shm", err))?;
+
+        // Create a borderless overlay at the top of the screen.
+        let surface = compositor.create_surface(queue);
+        let layer = layer_shell.create_layer_surface(
+            queue,
+            surface.clone(),
+            Layer::Overlay,
+            Some("pinax-quick"),
+            None,
+        );
+        layer.set_anchor(Anchor::TOP);
+        layer.set_margin(TOP_MARGIN, 0, 0, 0);
+        layer.set_size(SIZE.width, SIZE.height);
+        layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
+        layer.commit();
+
+        let renderer = Renderer::new(config.general.renderer, &connection, &shm, surface)?;
+
+        Ok(Self {
+            renderer,
+            layer,
+            shm,
+            font_collection: skia::font_collection(config.font.directory.as_deref()),
+            background: Background::new(&config.colors.background),
+            registry: RegistryState::new(globals),
+            output: OutputState::new(globals, queue),
+            seat: SeatState::new(globals, queue),
+            config,
+            scale: 1,
+            configured: Default::default(),
+            terminated: Default::default(),
+            keyboard: Default::default(),
+            text: Default::default(),
+            item: Default::default(),
+        })
+    }
+
+    /// Render the popup.
+    fn draw(&mut self) {
+        if !self.configured {
+            return;
+        }
+
+        let scale = self.scale as f64;
+        let size = *Logical(SIZE).to_physical(scale);
+        let padding = (self.config.layout.padding * scale).round() as f32;
+
+        // Show the placeholder until text was typed.
+        let mut paint = Paint::default();
+        paint.set_color4f(self.config.colors.foreground.as_color4f(), None);
+        paint.set_anti_alias(true);
+        let text = if self.text.is_empty() {
+            paint.set_color4f(Color4f { a: PLACEHOLDER_ALPHA, ..paint.color4f() }, None);
+            PLACEHOLDER.into()
+        } else {
+            format!("{}_", self.text)
+        };
+
+        let mut text_style = TextStyle::new();
+        text_style.set_foreground_paint(&paint);
+        text_style.set_font_size((self.config.font.size * scale) as f32);
+        text_style.set_font_families(&self.config.font.family);
+
+        let mut paragraph_style = ParagraphStyle::new();
+        paragraph_style.set_text_style(&text_style);
+        paragraph_style.set_max_lines(1);
+        paragraph_style.set_ellipsis("…");
+
+        let mut builder = ParagraphBuilder::new(&paragraph_style, &self.font_collection);
+        builder.add_text(&text);
+        let mut paragraph = builder.build();
+        paragraph.layout((size.width as f32 - 2. * padding).max(0.));
+
+        self.renderer.draw(size, None, |canvas, _| {
+            self.background.draw(canvas, size);
+
+            let y = ((size.height as f32 - paragraph.height()) / 2.).round();
+            paragraph.paint(canvas, Point::new(padding, y));
+        });
+
+        self.layer.wl_surface().commit();
+    }
+
+    /// Handle a key press.
+    fn press_key(&mut self, keysym: Keysym, text: Option<&str>) {
+        match keysym {
+            Keysym::Return | Keysym::KP_Enter => {
+                let item = normalize_paste(&self.text, false);
+                let item = item.trim();
+                if !item.is_empty() {
+                    self.item = Some(item.into());
+                }
+                self.terminated = true;
+            },
+            Keysym::Escape => self.terminated = true,
+            Keysym::BackSpace => {
+                let start = editor::previous_grapheme(&self.text, self.text.len());
+                self.text.truncate(start);
+                self.draw();
+            },
+            _ => {
+                if let Some(text) = text {
+                    self.text.push_str(text);
+                    self.draw();
+                }
+            },
+        }
+    }
+}
+
+impl ShmHandler for QuickCapture {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+delegate_shm!(QuickCapture);
+
+impl CompositorHandler for QuickCapture {
+    fn scale_factor_changed(
+        &mut self,
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+        surface: &WlSurface,
+        factor: i32,
+    ) {
+        if self.scale != factor {
+            self.scale = factor;
+            surface.set_buffer_scale(factor);
+            self.draw();
+        }
+    }
+
+    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlSurface, _: u32) {}
+
+    fn transform_changed(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &WlSurface,
+        _: Transform,
+    ) {
+    }
+
+    fn surface_enter(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &WlSurface,
+        _: &WlOutput,
+    ) {
+    }
+
+    fn surface_leave(
+        &mut self,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+        _: &WlSurface,
+        _: &WlOutput,
+    ) {
+    }
+}
+delegate_compositor!(QuickCapture);
+
+impl OutputHandler for QuickCapture {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output
+    }
+
+    fn new_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlOutput) {}
+
+    fn update_output(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlOutput) {}
+
+    fn output_destroyed(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlOutput) {}
+}
+delegate_output!(QuickCapture);
+
+impl LayerShellHandler for QuickCapture {
+    fn closed(&mut self, _connection: &Connection, _queue: &QueueHandle<Self>, _: &LayerSurface) {
+        self.terminated = true;
+    }
+
+    fn configure(
+        &mut self,
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+        _layer: &LayerSurface,
+        _configure: LayerSurfaceConfigure,
+        _serial: u32,
+    ) {
+        self.configured = true;
+        self.draw();
+    }
+}
+delegate_layer!(QuickCapture);
+
+impl SeatHandler for QuickCapture {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat
+    }
+
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlSeat) {}
+
+    fn new_capability(
+        &mut self,
+        _connection: &Connection,
+        queue: &QueueHandle<Self>,
+        seat: WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Keyboard && self.keyboard.is_none() {
+            self.keyboard = self.seat.get_keyboard(queue, &seat, None).ok();
+        }
+    }
+
+    fn remove_capability(
+        &mut self,
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+        _seat: WlSeat,
+        capability: Capability,
+    ) {
+        if capability == Capability::Keyboard
+            && let Some(keyboard) = self.keyboard.take()
+        {
+            keyboard.release();
+        }
+    }
+
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlSeat) {}
+}
+delegate_seat!(QuickCapture);
+
+impl KeyboardHandler for QuickCapture {
+    fn enter(
+        &mut self,
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _surface: &WlSurface,
+        _serial: u32,
+        _raws: &[u32],
+        _keysyms: &[Keysym],
+    ) {
+    }
+
+    fn leave(
+        &mut self,
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _surface: &WlSurface,
+        _serial: u32,
+    ) {
+    }
+
+    fn press_key(
+        &mut self,
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _serial: u32,
+        event: KeyEvent,
+    ) {
+        // Ignore control characters, which are handled through their keysym.
+        let text = event.utf8.filter(|text| !text.contains(char::is_control));
+        self.press_key(event.keysym, text.as_deref());
+    }
+
+    fn release_key(
+        &mut self,
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _serial: u32,
+        _event: KeyEvent,
+    ) {
+    }
+
+    fn repeat_key(
+        &mut self,
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _serial: u32,
+        event: KeyEvent,
+    ) {
+        let text = event.utf8.filter(|text| !text.contains(char::is_control));
+        self.press_key(event.keysym, text.as_deref());
+    }
+
+    fn update_modifiers(
+        &mut self,
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+        _keyboard: &WlKeyboard,
+        _serial: u32,
+        _modifiers: Modifiers,
+        _raw_modifiers: RawModifiers,
+        _layout: u32,
+    ) {
+    }
+}
+delegate_keyboard!(QuickCapture);
+
+impl ProvidesRegistryState for QuickCapture {
+    registry_handlers![OutputState];
+
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry
+    }
+}
+delegate_registry!(QuickCapture);
@@ -0,0 +1,341 @@
+//! Recovery of unsaved text after a crash.
+//!
+//! Unsaved text is written to a recovery file when Pinax panics. To also
+//! survive crashes which do not unwind, every edit is appended to a journal,
+//! which is replayed onto the persisted note on the next start.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::{mem, panic, thread};
+
+use tracing::{error, info, warn};
+
+use crate::ipc::{percent_decode, percent_encode};
+use crate::text::{Text, TextEdit};
+use crate::text_box::content_hash;
+
+/// Unsaved text of the open note, written to the recovery file on panic.
+static UNSAVED: Mutex<Option<(PathBuf, Text)>> = Mutex::new(None);
+
+/// Install a panic hook writing unsaved text to the recovery file.
+///
+/// The previous panic hook is called afterwards.
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        // Ignore background threads, since the text remains editable.
+        if thread::current().name() != Some("main") {
+            return default_hook(info);
+        }
+
+        // Avoid deadlocks if the panic occurred while holding the lock.
+        let unsaved = match UNSAVED.try_lock() {
+            Ok(unsaved) => unsaved,
+            Err(_) => return default_hook(info),
+        };
+
+        if let Some((note_path, text)) = &*unsaved {
+            match write(note_path, text) {
+                Ok(path) => eprintln!("Unsaved notes were written to {path:?}"),
+                Err(err) => eprintln!("Failed to write unsaved notes: {err}"),
+            }
+        }
+
+        default_hook(info);
+    }));
+}
+
+/// Update the unsaved text of the note at `path`.
+///
+/// This is cheap, since cloning the text does not copy its content.
+pub fn update(path: &Path, text: &Text) {
+    if let Ok(mut unsaved) = UNSAVED.lock() {
+        *unsaved = Some((path.into(), text.clone()));
+    }
+}
+
+/// Clear the unsaved text once it was written to storage.
+pub fn clear() {
+    if let Ok(mut unsaved) = UNSAVED.lock() {
+        *unsaved = None;
+    }
+}
+
+/// Read the unsaved text of a previous instance, with the path of its note.
+///
+/// The recovery file is kept until [`discard`] is called, so the text is not
+/// lost if Pinax fails before the text was saved again.
+pub fn read() -> Option<(PathBuf, String)> {
+    let path = recovery_path()?;
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            error!("Failed to read recovery file {path:?}: {err}");
+            return None;
+        },
+    };
+
+    let (note_path, text) = content.split_once('\n')?;
+    info!("Recovering unsaved changes of {note_path:?}");
+
+    Some((PathBuf::from(note_path), text.into()))
+}
+
+/// Remove the recovery file, once its text was saved.
+pub fn discard() {
+    if let Some(path) = recovery_path()
+        && let Err(err) = fs::remove_file(&path)
+        && err.kind() != io::ErrorKind::NotFound
+    {
+        error!("Failed to remove recovery file {path:?}: {err}");
+    }
+}
+
+/// Write unsaved text to the recovery file.
+///
+/// The file starts with the path of the note on its own line, followed by
+/// the note's text.
+fn write(note_path: &Path, text: &Text) -> io::Result<PathBuf> {
+    let path = recovery_path().ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut file = File::create(&path)?;
+    writeln!(file, "{}", note_path.display())?;
+    for chunk in text.chunks() {
+        file.write_all(chunk.as_bytes())?;
+    }
+    file.sync_all()?;
+
+    Ok(path)
+}
+
+/// Append-only log of edits made since the note was last saved.
+///
+/// The journal starts with the note's path and the hash of its persisted
+/// text, followed by one line per edit.
+pub struct Journal {
+    file: Option<File>,
+    revision: u64,
+    len: usize,
+    saved_revision: u64,
+    saved_hash: u64,
+    saved_len: usize,
+    fsync: bool,
+    unsynced: bool,
+}
+
+impl Journal {
+    /// Create a journal for the persisted `text` of the open note.
+    ///
+    /// The journal file is only created once the first edit is recorded.
+    pub fn new(text: &Text, fsync: bool) -> Self {
+        Self {
+            fsync,
+            saved_hash: content_hash(text.chunks()),
+            saved_revision: text.revision(),
+            revision: text.revision(),
+            saved_len: text.len(),
+            len: text.len(),
+            unsynced: Default::default(),
+            file: Default::default(),
+        }
+    }
+
+    /// Record all edits made to the `text` of the note at `path`.
+    pub fn record(&mut self, path: &Path, text: &Text) {
+        if text.revision() == self.revision {
+            return;
+        }
+
+        // Write the entire text if the individual edits are unknown, or if edits
+        // since the last save were lost with a journal which could not be written.
+        let edits = text.edits_since(self.revision);
+        let lost_edits = self.file.is_none() && self.revision != self.saved_revision;
+        let edits = match edits {
+            Some(edits) if !lost_edits => edits.cloned().collect(),
+            _ => {
+                let len = if self.file.is_some() { self.len } else { self.saved_len };
+                vec![TextEdit { range: 0..len, text: text.chunks().collect() }]
+            },
+        };
+
+        if let Err(err) = self.append(path, &edits) {
+            error!("Failed to write edit journal: {err}");
+            self.file = None;
+        }
+
+        self.revision = text.revision();
+        self.len = text.len();
+    }
+
+    /// Flush recorded edits to the storage device.
+    ///
+    /// This is a noop unless edits were recorded since the last sync.
+    pub fn sync(&mut self) {
+        if !mem::take(&mut self.unsynced) {
+            return;
+        }
+
+        if let Some(file) = &self.file
+            && let Err(err) = file.sync_data()
+        {
+            error!("Failed to sync edit journal: {err}");
+        }
+    }
+
+    /// Check if recorded edits still need to be flushed to the storage device.
+    pub fn unsynced(&self) -> bool {
+        self.unsynced
+    }
+
+    /// Reset the journal after the note's `text` was persisted.
+    pub fn reset(&mut self, text: &Text) {
+        self.saved_hash = content_hash(text.chunks());
+        self.saved_revision = text.revision();
+        self.revision = text.revision();
+        self.saved_len = text.len();
+        self.len = text.len();
+        self.unsynced = false;
+
+        // Remove the journal, since all its edits are persisted now.
+        if self.file.take().is_some() {
+            Replay::discard();
+        }
+    }
+
+    /// Update the journal's configuration.
+    pub fn set_fsync(&mut self, fsync: bool) {
+        self.fsync = fsync;
+    }
+
+    /// Write edits to the journal file, creating it if necessary.
+    fn append(&mut self, path: &Path, edits: &[TextEdit]) -> io::Result<()> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let journal_path =
+                    journal_path().ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+                if let Some(parent) = journal_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                let mut file = File::create(journal_path)?;
+                writeln!(file, "{}", percent_encode(&path.to_string_lossy()))?;
+                writeln!(file, "{}", self.saved_hash)?;
+                self.file.insert(file)
+            },
+        };
+
+        for TextEdit { range, text } in edits {
+            writeln!(file, "{} {} {}", range.start, range.end, percent_encode(text))?;
+        }
+
+        // Syncing is batched by the caller, to avoid blocking on every keystroke.
+        self.unsynced = self.fsync;
+
+        Ok(())
+    }
+}
+
+/// Edits of a previous instance, read from its journal.
+pub struct Replay {
+    pub path: PathBuf,
+    hash: u64,
+    edits: Vec<(Range<usize>, String)>,
+}
+
+impl Replay {
+    /// Read the journal of a previous instance.
+    ///
+    /// The journal file is kept until [`Self::discard`] is called, so its edits
+    /// are not lost if Pinax fails before they were saved again.
+    pub fn read() -> Option<Self> {
+        let path = journal_path()?;
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+            Err(err) => {
+                error!("Failed to read edit journal {path:?}: {err}");
+                return None;
+            },
+        };
+
+        // Ignore the last line if it was not completely written.
+        let mut lines = content.split_inclusive('\n').filter_map(|line| line.strip_suffix('\n'));
+        let note_path = PathBuf::from(percent_decode(lines.next()?)?);
+        let hash = lines.next()?.parse().ok()?;
+
+        let mut edits = Vec::new();
+        for line in lines {
+            match parse_edit(line) {
+                Some(edit) => edits.push(edit),
+                None => {
+                    warn!("Ignoring invalid edit journal entry {line:?}");
+                    break;
+                },
+            }
+        }
+
+        Some(Self { path: note_path, hash, edits })
+    }
+
+    /// Remove the journal file, once its edits were saved.
+    pub fn discard() {
+        if let Some(path) = journal_path()
+            && let Err(err) = fs::remove_file(&path)
+            && err.kind() != io::ErrorKind::NotFound
+        {
+            error!("Failed to remove edit journal {path:?}: {err}");
+        }
+    }
+
+    /// Apply the journaled edits to the persisted text of the note.
+    ///
+    /// Returns `None` if the note was modified since the journal was started.
+    pub fn apply(&self, text: &str) -> Option<String> {
+        if content_hash([text]) != self.hash {
+            return None;
+        }
+
+        let mut text = text.to_owned();
+        for (range, replacement) in &self.edits {
+            if !text.is_char_boundary(range.start)
+                || !text.is_char_boundary(range.end)
+                || range.start > range.end
+            {
+                warn!("Stopping edit journal replay at invalid range {range:?}");
+                break;
+            }
+            text.replace_range(range.clone(), replacement);
+        }
+
+        info!("Replayed {} journaled edits of {:?}", self.edits.len(), self.path);
+
+        Some(text)
+    }
+}
+
+/// Parse a journal line of the format `START END TEXT`.
+fn parse_edit(line: &str) -> Option<(Range<usize>, String)> {
+    let mut fields = line.splitn(3, ' ');
+    let start = fields.next()?.parse().ok()?;
+    let end = fields.next()?.parse().ok()?;
+    let text = percent_decode(fields.next()?)?;
+    Some((start..end, text))
+}
+
+/// Location of the edit journal.
+pub fn journal_path() -> Option<PathBuf> {
+    Some(dirs::state_dir()?.join("pinax/notes.journal"))
+}
+
+/// Location of the recovery file.
+pub fn recovery_path() -> Option<PathBuf> {
+    Some(dirs::state_dir()?.join("pinax/notes.recover"))
+}
@@ -0,0 +1,301 @@
+//! Reminder alarms for list items.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use calloop::timer::{TimeoutAction, Timer};
+use calloop::{LoopHandle, RegistrationToken};
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeDelta, TimeZone};
+use tracing::{error, info};
+
+use crate::State;
+use crate::editor::{is_completed, item_content, list_items};
+use crate::storage::Storage;
+
+/// Prefix of reminder times inside a list item.
+const REMINDER_MARKER: char = '@';
+
+/// Format of reminders due once.
+const DATE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Format of daily reminders.
+const TIME_FORMAT: &str = "%H:%M";
+
+/// Maximum time between two checks for due reminders.
+///
+/// Timers do not advance while the system is suspended, so this limits how
+/// late reminders can fire after resuming.
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Scheduler for the reminders of all notes.
+pub struct Reminders {
+    event_loop: LoopHandle<'static, State>,
+    notes: HashMap<PathBuf, Vec<Reminder>>,
+    timer: Option<RegistrationToken>,
+    last_check: NaiveDateTime,
+}
+
+impl Reminders {
+    /// Load reminders from all notes in the storage directory.
+    ///
+    /// Reminders which were due since `last_check` are fired immediately, so
+    /// no reminders are missed while Pinax is not running.
+    pub fn new(
+        event_loop: LoopHandle<'static, State>,
+        storage: &dyn Storage,
+        storage_dir: &Path,
+        last_check: Option<i64>,
+    ) -> Self {
+        let paths = storage
+            .list(storage_dir)
+            .inspect_err(|err| error!("Failed to list notes for reminders: {err}"))
+            .unwrap_or_default();
+
+        let mut notes = HashMap::new();
+        for path in paths {
+            let text = match storage.read(&path) {
+                Ok(Some(text)) => text,
+                Ok(None) => continue,
+                Err(err) => {
+                    error!("Failed to read reminders from {path:?}: {err}");
+                    continue;
+                },
+            };
+
+            let reminders = parse(&text);
+            if !reminders.is_empty() {
+                notes.insert(path, reminders);
+            }
+        }
+
+        let last_check = last_check
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+            .map(|time| time.with_timezone(&Local).naive_local())
+            .unwrap_or_else(|| Local::now().naive_local());
+
+        let mut reminders = Self { event_loop, notes, last_check, timer: Default::default() };
+        reminders.schedule();
+        reminders
+    }
+
+    /// Replace the reminders of a note.
+    pub fn update(&mut self, path: PathBuf, reminders: Vec<Reminder>) {
+        if self.notes.get(&path).map_or(reminders.is_empty(), |old| *old == reminders) {
+            return;
+        }
+
+        // Avoid firing reminders added after they were due.
+        if self.timer.is_none() {
+            self.last_check = Local::now().naive_local();
+        }
+
+        if reminders.is_empty() {
+            self.notes.remove(&path);
+        } else {
+            self.notes.insert(path, reminders);
+        }
+
+        self.schedule();
+    }
+
+    /// Time of the last check for due reminders, as Unix timestamp.
+    pub fn last_check(&self) -> Option<i64> {
+        Local.from_local_datetime(&self.last_check).earliest().map(|time| time.timestamp())
+    }
+
+    /// Schedule a timer for the next due reminder.
+    fn schedule(&mut self) {
+        if let Some(token) = self.timer.take() {
+            self.event_loop.remove(token);
+        }
+
+        let next_due = self
+            .notes
+            .values()
+            .flatten()
+            .filter_map(|reminder| reminder.time.next_after(self.last_check))
+            .min();
+        let Some(next_due) = next_due else { return };
+
+        let now = Local::now().naive_local();
+        let delay = (next_due - now).to_std().unwrap_or_default().min(MAX_CHECK_INTERVAL);
+
+        self.timer = self
+            .event_loop
+            .insert_source(Timer::from_deadline(Instant::now() + delay), |_, _, state| {
+                state.window.reminders.timer = None;
+                Self::fire_due(state);
+                TimeoutAction::Drop
+            })
+            .inspect_err(|err| error!("Failed to schedule reminder: {err}"))
+            .ok();
+    }
+
+    /// Notify about all reminders which became due since the last check.
+    fn fire_due(state: &mut State) {
+        let reminders = &mut state.window.reminders;
+
+        let now = Local::now().naive_local();
+        let last_check = reminders.last_check;
+        reminders.last_check = now;
+
+        for reminder in reminders.notes.values().flatten() {
+            if reminder.time.next_after(last_check).is_some_and(|due| due <= now) {
+                info!("Reminder due: {:?}", reminder.text);
+                state.notifier.notify("Reminder", reminder.text.as_str());
+            }
+        }
+
+        reminders.schedule();
+    }
+}
+
+/// Reminder attached to a list item.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Reminder {
+    pub time: ReminderTime,
+    pub text: String,
+}
+
+/// Time at which a reminder is due.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ReminderTime {
+    /// Due every day, like `@15:30`.
+    Daily(NaiveTime),
+    /// Due once, like `@2026-10-15 15:30`.
+    Once(NaiveDateTime),
+}
+
+impl ReminderTime {
+    /// Get the first time the reminder is due after `time`.
+    fn next_after(&self, time: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self {
+            Self::Daily(daily) => {
+                let due = time.date().and_time(*daily);
+                if due > time { Some(due) } else { Some(due + TimeDelta::days(1)) }
+            },
+            Self::Once(once) => (*once > time).then_some(*once),
+        }
+    }
+
+    /// Parse a reminder time at the start of `text`.
+    ///
+    /// Returns the time and the length of its text representation.
+    fn parse(text: &str) -> Option<(Self, usize)> {
+        let parse_time = |len: usize| {
+            // Ensure the time isn't directly followed by more digits or letters.
+            let time = text.get(..len)?;
+            match text[len..].chars().next() {
+                Some(c) if c.is_alphanumeric() => None,
+                _ => Some(time),
+            }
+        };
+
+        if let Some(time) = parse_time(16)
+            && let Ok(time) = NaiveDateTime::parse_from_str(time, DATE_TIME_FORMAT)
+        {
+            return Some((Self::Once(time), 16));
+        }
+
+        let time = NaiveTime::parse_from_str(parse_time(5)?, TIME_FORMAT).ok()?;
+        Some((Self::Daily(time), 5))
+    }
+}
+
+/// Parse all reminders in a note.
+///
+/// Completed list items are ignored.
+pub fn parse(text: &str) -> Vec<Reminder> {
+    let mut reminders = Vec::new();
+
+    for item in list_items(text) {
+        let content = &text[item_content(text, item)];
+        if is_completed(content) {
+            continue;
+        }
+
+        for (i, _) in content.match_indices(REMINDER_MARKER) {
+            // Only consider markers at the start of a word.
+            if content[..i].chars().next_back().is_some_and(|c| !c.is_whitespace()) {
+                continue;
+            }
+
+            let start = i + REMINDER_MARKER.len_utf8();
+            let Some((time, len)) = ReminderTime::parse(&content[start..]) else { continue };
+
+            // Use the item without its reminder as notification text.
+            let text = format!("{}{}", &content[..i], &content[start + len..]);
+            let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            reminders.push(Reminder { time, text });
+        }
+    }
+
+    reminders
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn date_time(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn daily(hour: u32, minute: u32) -> ReminderTime {
+        ReminderTime::Daily(NaiveTime::from_hms_opt(hour, minute, 0).unwrap())
+    }
+
+    #[test]
+    fn parse_reminders() {
+        let text = "Call Bob @15:30\n\nDentist @2026-10-15 09:00 tomorrow\n\n~~Done @10:00~~\n\n\
+                    Lunch @12:00.";
+        assert_eq!(
+            parse(text),
+            [
+                Reminder { time: daily(15, 30), text: "Call Bob".into() },
+                Reminder {
+                    time: ReminderTime::Once(date_time(15, 9, 0)),
+                    text: "Dentist tomorrow".into()
+                },
+                Reminder { time: daily(12, 0), text: "Lunch .".into() },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for text in [
+            "@25:00",
+            "@15:60",
+            "@2026-13-01 10:00",
+            "@2026-10-15",
+            "mail@15:30",
+            "@15:300",
+            "@15:30x",
+            "@1530",
+            "@",
+            "15:30",
+        ] {
+            assert!(parse(text).is_empty(), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn daily_reminders() {
+        // Times later in the day are due today, earlier times tomorrow.
+        assert_eq!(daily(15, 30).next_after(date_time(15, 10, 0)), Some(date_time(15, 15, 30)));
+        assert_eq!(daily(15, 30).next_after(date_time(15, 16, 0)), Some(date_time(16, 15, 30)));
+        assert_eq!(daily(15, 30).next_after(date_time(15, 15, 30)), Some(date_time(16, 15, 30)));
+    }
+
+    #[test]
+    fn past_reminders() {
+        let once = ReminderTime::Once(date_time(15, 15, 30));
+        assert_eq!(once.next_after(date_time(15, 10, 0)), Some(date_time(15, 15, 30)));
+        assert_eq!(once.next_after(date_time(15, 15, 30)), None);
+        assert_eq!(once.next_after(date_time(16, 10, 0)), None);
+    }
+}
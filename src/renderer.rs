@@ -1,44 +1,150 @@
-//! OpenGL renderer.
+//! Window renderers.
 
+use std::collections::VecDeque;
 use std::ffi::CString;
 use std::num::NonZeroU32;
 use std::ptr::NonNull;
 
 use glutin::config::{Api, Config, ConfigTemplateBuilder};
-use glutin::context::{ContextApi, ContextAttributesBuilder, PossiblyCurrentContext, Version};
-use glutin::display::Display;
+use glutin::context::{
+    ContextApi, ContextAttributes, ContextAttributesBuilder, PossiblyCurrentContext, Version,
+};
+use glutin::display::{Display, DisplayApiPreference};
+use glutin::error::{Error as GlutinError, ErrorKind as GlutinErrorKind};
 use glutin::prelude::*;
-use glutin::surface::{Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface};
-use raw_window_handle::{RawWindowHandle, WaylandWindowHandle};
-use smithay_client_toolkit::reexports::client::Proxy;
+use glutin::surface::{
+    Rect as DamageRect, Surface, SurfaceAttributesBuilder, SwapInterval, WindowSurface,
+};
+use raw_window_handle::{
+    RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle,
+};
+use skia_safe::{AlphaType, Canvas as SkiaCanvas, ColorType, IRect, ImageInfo, surfaces};
+use smithay_client_toolkit::reexports::client::protocol::wl_shm::Format;
 use smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface;
+use smithay_client_toolkit::reexports::client::{Connection, Proxy};
+use smithay_client_toolkit::shm::Shm;
+use smithay_client_toolkit::shm::slot::SlotPool;
+use tracing::{error, info, warn};
 
+use crate::config::RendererBackend;
 use crate::geometry::Size;
-use crate::gl;
-use crate::skia::GlConfig as SkiaGlConfig;
+use crate::skia::{Canvas, GlConfig as SkiaGlConfig};
+use crate::vulkan::VulkanRenderer;
+use crate::{Error, gl};
+
+/// Maximum buffer age for which damage is tracked.
+const MAX_BUFFER_AGE: usize = 4;
+
+/// Window renderer.
+#[derive(Debug)]
+pub enum Renderer {
+    Gl(GlRenderer),
+    Vulkan(VulkanRenderer),
+    Software(SoftwareRenderer),
+}
+
+impl Renderer {
+    /// Initialize the configured renderer.
+    ///
+    /// With [`RendererBackend::Auto`], Vulkan is preferred over OpenGL, with
+    /// software rendering used when neither is available.
+    pub fn new(
+        backend: RendererBackend,
+        connection: &Connection,
+        shm: &Shm,
+        surface: WlSurface,
+    ) -> Result<Self, Error> {
+        match backend {
+            RendererBackend::Gl => return Ok(Self::Gl(GlRenderer::new(connection, surface)?)),
+            RendererBackend::Vulkan => {
+                info!("Using Vulkan renderer");
+                return Ok(Self::Vulkan(VulkanRenderer::new(connection, &surface)?));
+            },
+            RendererBackend::Auto => {
+                match VulkanRenderer::new(connection, &surface) {
+                    Ok(renderer) => {
+                        info!("Using Vulkan renderer");
+                        return Ok(Self::Vulkan(renderer));
+                    },
+                    Err(err) => warn!("Vulkan unavailable, trying OpenGL: {err}"),
+                }
+
+                match GlRenderer::new(connection, surface.clone()) {
+                    Ok(renderer) => return Ok(Self::Gl(renderer)),
+                    Err(err) => warn!("OpenGL unavailable, using software rendering: {err}"),
+                }
+            },
+            RendererBackend::Software => (),
+        }
+
+        info!("Using software renderer");
+
+        Ok(Self::Software(SoftwareRenderer::new(shm, surface)?))
+    }
+
+    /// Perform drawing with this renderer mapped.
+    ///
+    /// The `damage` is the region which changed since the last frame, with
+    /// `None` indicating that the entire surface was damaged. The region
+    /// passed to `fun` must be redrawn, to bring the current buffer up to
+    /// date.
+    pub fn draw<F>(&mut self, size: Size, damage: Option<IRect>, fun: F)
+    where
+        F: FnOnce(&SkiaCanvas, Option<IRect>),
+    {
+        match self {
+            Self::Gl(renderer) => renderer.draw(size, damage, fun),
+            Self::Vulkan(renderer) => renderer.draw(size, damage, fun),
+            Self::Software(renderer) => renderer.draw(size, damage, fun),
+        }
+    }
+
+    /// Release all GPU buffers.
+    pub fn release(&mut self) {
+        match self {
+            Self::Gl(renderer) => renderer.release(),
+            Self::Vulkan(renderer) => renderer.release(),
+            Self::Software(_) => (),
+        }
+    }
+}
 
 /// OpenGL renderer.
 #[derive(Debug)]
-pub struct Renderer {
+pub struct GlRenderer {
     sized: Option<SizedRenderer>,
     surface: WlSurface,
     display: Display,
 }
 
-impl Renderer {
-    /// Initialize a new renderer.
-    pub fn new(display: Display, surface: WlSurface) -> Self {
+impl GlRenderer {
+    /// Initialize a new OpenGL renderer.
+    fn new(connection: &Connection, surface: WlSurface) -> Result<Self, GlutinError> {
+        // Get EGL display.
+        let display = NonNull::new(connection.backend().display_ptr().cast()).unwrap();
+        let wayland_display = WaylandDisplayHandle::new(display);
+        let raw_display = RawDisplayHandle::Wayland(wayland_display);
+        let display = unsafe { Display::new(raw_display, DisplayApiPreference::Egl)? };
+
+        // Ensure a GLES context can be created, since its creation is deferred.
+        let egl_config = SizedRenderer::find_config(&display)?;
+        let context_attributes = SizedRenderer::context_attributes();
+        unsafe { display.create_context(&egl_config, &context_attributes)? };
+
         // Setup OpenGL symbol loader.
         gl::load_with(|symbol| {
             let symbol = CString::new(symbol).unwrap();
             display.get_proc_address(symbol.as_c_str()).cast()
         });
 
-        Renderer { surface, display, sized: Default::default() }
+        Ok(Self { surface, display, sized: Default::default() })
     }
 
     /// Perform drawing with this renderer mapped.
-    pub fn draw<F: FnOnce(&SizedRenderer)>(&mut self, size: Size, fun: F) {
+    fn draw<F>(&mut self, size: Size, damage: Option<IRect>, fun: F)
+    where
+        F: FnOnce(&SkiaCanvas, Option<IRect>),
+    {
         let sized = self.sized(size);
         sized.make_current();
 
@@ -47,15 +153,26 @@ impl Renderer {
         // This isn't done in `Self::resize` since the renderer must be current.
         unsafe { gl::Viewport(0, 0, size.width as i32, size.height as i32) };
 
-        fun(sized);
+        let repaint_region = sized.repaint_region(damage);
+        let skia_config = sized.skia_config();
+        sized.canvas.draw(skia_config, size, |canvas| fun(canvas, repaint_region));
 
         unsafe { gl::Flush() };
 
-        sized.swap_buffers();
+        sized.swap_buffers(damage);
+    }
+
+    /// Release all GPU buffers.
+    fn release(&mut self) {
+        if let Some(sized) = self.sized.take() {
+            // Free Skia's resources while its context is still current.
+            sized.make_current();
+            drop(sized);
+        }
     }
 
     /// Get render state requiring a size.
-    fn sized(&mut self, size: Size) -> &SizedRenderer {
+    fn sized(&mut self, size: Size) -> &mut SizedRenderer {
         // Initialize or resize sized state.
         match &mut self.sized {
             // Resize renderer.
@@ -66,7 +183,7 @@ impl Renderer {
             },
         }
 
-        self.sized.as_ref().unwrap()
+        self.sized.as_mut().unwrap()
     }
 }
 
@@ -75,11 +192,16 @@ impl Renderer {
 /// This state is initialized on-demand, to avoid Mesa's issue with resizing
 /// before the first draw.
 #[derive(Debug)]
-pub struct SizedRenderer {
+struct SizedRenderer {
+    // NOTE: The canvas must be dropped before the EGL context.
+    canvas: Canvas,
+
     egl_surface: Surface<WindowSurface>,
     egl_context: PossiblyCurrentContext,
     egl_config: Config,
 
+    damage_history: DamageHistory,
+
     size: Size,
 }
 
@@ -89,11 +211,18 @@ impl SizedRenderer {
         // Create EGL surface and context and make it current.
         let (egl_surface, egl_context, egl_config) = Self::create_surface(display, surface, size);
 
-        Self { egl_surface, egl_context, egl_config, size }
+        Self {
+            egl_surface,
+            egl_context,
+            egl_config,
+            size,
+            damage_history: Default::default(),
+            canvas: Default::default(),
+        }
     }
 
     /// Get Skia OpenGL configuration.
-    pub fn skia_config(&self) -> SkiaGlConfig {
+    fn skia_config(&self) -> SkiaGlConfig {
         SkiaGlConfig {
             stencil_size: self.egl_config.stencil_size() as usize,
             sample_count: self.egl_config.num_samples() as usize,
@@ -113,16 +242,38 @@ impl SizedRenderer {
             NonZeroU32::new(size.height).unwrap(),
         );
 
+        // Buffer content is invalid after resize.
+        self.damage_history.clear();
+
         self.size = size;
     }
 
+    /// Get the region which needs to be redrawn for the next frame.
+    fn repaint_region(&mut self, damage: Option<IRect>) -> Option<IRect> {
+        let age = self.egl_surface.buffer_age() as usize;
+        self.damage_history.repaint_region(damage, age)
+    }
+
     /// Make EGL surface current.
     fn make_current(&self) {
         self.egl_context.make_current(&self.egl_surface).unwrap();
     }
 
     /// Perform OpenGL buffer swap.
-    fn swap_buffers(&self) {
+    ///
+    /// This will only submit the damaged region to the compositor, if
+    /// `EGL_KHR_swap_buffers_with_damage` is supported.
+    fn swap_buffers(&self, damage: Option<IRect>) {
+        if let Some(damage) = damage {
+            // Convert from Skia's top-left to EGL's bottom-left origin.
+            let y = self.size.height as i32 - damage.bottom;
+            let rect = DamageRect::new(damage.left, y, damage.width(), damage.height());
+
+            if self.egl_surface.swap_buffers_with_damage(&self.egl_context, &[rect]).is_ok() {
+                return;
+            }
+        }
+
         self.egl_surface.swap_buffers(&self.egl_context).unwrap();
     }
 
@@ -135,19 +286,10 @@ impl SizedRenderer {
         assert!(size.width > 0 && size.height > 0);
 
         // Create EGL config.
-        let config_template = ConfigTemplateBuilder::new().with_api(Api::GLES2).build();
-        let egl_config = unsafe {
-            display
-                .find_configs(config_template)
-                .ok()
-                .and_then(|mut configs| configs.next())
-                .unwrap()
-        };
+        let egl_config = Self::find_config(display).unwrap();
 
         // Create EGL context.
-        let context_attributes = ContextAttributesBuilder::new()
-            .with_context_api(ContextApi::Gles(Some(Version::new(2, 0))))
-            .build(None);
+        let context_attributes = Self::context_attributes();
         let egl_context =
             unsafe { display.create_context(&egl_config, &context_attributes).unwrap() };
         let egl_context = egl_context.treat_as_possibly_current();
@@ -170,4 +312,111 @@ impl SizedRenderer {
 
         (egl_surface, egl_context, egl_config)
     }
+
+    /// Find a GLES2 EGL config with an alpha channel.
+    fn find_config(display: &Display) -> Result<Config, GlutinError> {
+        let config_template =
+            ConfigTemplateBuilder::new().with_api(Api::GLES2).with_alpha_size(8).build();
+        let mut configs = unsafe { display.find_configs(config_template)? };
+        configs.next().ok_or_else(|| GlutinErrorKind::BadConfig.into())
+    }
+
+    /// Get EGL context attributes.
+    fn context_attributes() -> ContextAttributes {
+        ContextAttributesBuilder::new()
+            .with_context_api(ContextApi::Gles(Some(Version::new(2, 0))))
+            .build(None)
+    }
+}
+
+/// Damage of the last frames, for redrawing only outdated parts of a buffer.
+#[derive(Default, Debug)]
+pub struct DamageHistory {
+    /// Damage of the last frames, with the most recent one at the front.
+    frames: VecDeque<Option<IRect>>,
+}
+
+impl DamageHistory {
+    /// Add a frame's damage and get the region which needs to be redrawn.
+    ///
+    /// This combines the frame's damage with the damage of all frames since the
+    /// current buffer was last used, based on its buffer `age`. If the buffer
+    /// age is unknown, the entire surface must be redrawn.
+    pub fn repaint_region(&mut self, damage: Option<IRect>, age: usize) -> Option<IRect> {
+        self.frames.push_front(damage);
+        self.frames.truncate(MAX_BUFFER_AGE);
+
+        // Buffer content is undefined.
+        if age == 0 || age > self.frames.len() {
+            return None;
+        }
+
+        let mut region = IRect::new_empty();
+        for damage in self.frames.iter().take(age) {
+            region.join(damage.as_ref()?);
+        }
+        Some(region)
+    }
+
+    /// Forget all damage, after buffer content was invalidated.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+/// CPU renderer using shared memory buffers.
+#[derive(Debug)]
+pub struct SoftwareRenderer {
+    surface: WlSurface,
+    pool: SlotPool,
+}
+
+impl SoftwareRenderer {
+    /// Initialize a new software renderer.
+    fn new(shm: &Shm, surface: WlSurface) -> Result<Self, Error> {
+        // Start with an empty pool, it is resized on demand.
+        let pool = SlotPool::new(1, shm)?;
+        Ok(Self { surface, pool })
+    }
+
+    /// Perform drawing into a new shared memory buffer.
+    fn draw<F>(&mut self, size: Size, damage: Option<IRect>, fun: F)
+    where
+        F: FnOnce(&SkiaCanvas, Option<IRect>),
+    {
+        let (width, height) = (size.width as i32, size.height as i32);
+        let stride = width * 4;
+        let (buffer, pixels) =
+            match self.pool.create_buffer(width, height, stride, Format::Argb8888) {
+                Ok(buffer) => buffer,
+                Err(err) => {
+                    error!("Failed to create shm buffer: {err}");
+                    return;
+                },
+            };
+
+        // Wrap buffer memory in a Skia surface, ARGB8888 is little-endian BGRA.
+        let image_info =
+            ImageInfo::new((width, height), ColorType::BGRA8888, AlphaType::Premul, None);
+        let mut skia_surface =
+            match surfaces::wrap_pixels(&image_info, pixels, stride as usize, None) {
+                Some(skia_surface) => skia_surface,
+                None => {
+                    error!("Failed to create Skia raster surface");
+                    return;
+                },
+            };
+
+        // Buffers are not reused, so their entire content must be redrawn.
+        fun(skia_surface.canvas(), None);
+        drop(skia_surface);
+
+        if let Err(err) = buffer.attach_to(&self.surface) {
+            error!("Failed to attach shm buffer: {err}");
+            return;
+        }
+
+        let damage = damage.unwrap_or_else(|| IRect::from_wh(width, height));
+        self.surface.damage_buffer(damage.left, damage.top, damage.width(), damage.height());
+    }
 }
@@ -0,0 +1,194 @@
+//! Full-text search across all notes.
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use tracing::error;
+
+use crate::storage::Storage;
+
+/// Maximum number of results returned for a query.
+const MAX_RESULTS: usize = 50;
+
+/// Score bonus for consecutive matching characters.
+const CONSECUTIVE_BONUS: i64 = 5;
+
+/// Score bonus for matches at the start of a word.
+const WORD_START_BONUS: i64 = 3;
+
+/// Searchable content of all notes in the storage directory.
+pub struct SearchIndex {
+    notes: Vec<IndexedNote>,
+}
+
+impl SearchIndex {
+    /// Scan all notes in the storage directory.
+    ///
+    /// The content of the currently open note is taken from `current_text`
+    /// instead of the storage, to include unsaved changes.
+    pub fn scan(storage: &dyn Storage, current_path: &Path, current_text: &str) -> Self {
+        let mut notes = vec![IndexedNote::new(current_path.into(), current_text)];
+
+        let storage_dir = match current_path.parent() {
+            Some(storage_dir) => storage_dir,
+            None => return Self { notes },
+        };
+        let paths = match storage.list(storage_dir) {
+            Ok(paths) => paths,
+            Err(err) => {
+                error!("Failed to list notes in {storage_dir:?}: {err}");
+                return Self { notes };
+            },
+        };
+
+        for path in paths.into_iter().filter(|path| path != current_path) {
+            match storage.read(&path) {
+                Ok(Some(text)) => notes.push(IndexedNote::new(path, &text)),
+                Ok(None) => (),
+                Err(err) => error!("Failed to read note at {path:?}: {err}"),
+            }
+        }
+
+        Self { notes }
+    }
+
+    /// Find all lines matching a query, ordered by relevance.
+    pub fn search(&self, query: &str) -> Vec<SearchResult> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut results = Vec::new();
+        for note in &self.notes {
+            for (line_index, line) in note.lines.iter().enumerate() {
+                if let Some((score, spans)) = fuzzy_match(query, line) {
+                    results.push(SearchResult {
+                        path: note.path.clone(),
+                        line: line_index,
+                        text: line.clone(),
+                        spans,
+                        score,
+                    });
+                }
+            }
+        }
+
+        // Prefer higher scores and shorter lines.
+        results.sort_by(|a, b| b.score.cmp(&a.score).then(a.text.len().cmp(&b.text.len())));
+        results.truncate(MAX_RESULTS);
+
+        results
+    }
+}
+
+/// Note content split into lines.
+struct IndexedNote {
+    path: PathBuf,
+    lines: Vec<String>,
+}
+
+impl IndexedNote {
+    fn new(path: PathBuf, text: &str) -> Self {
+        Self { path, lines: text.lines().map(String::from).collect() }
+    }
+}
+
+/// Line matching a search query.
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    /// Path of the note containing the line.
+    pub path: PathBuf,
+    /// Line index within the note.
+    pub line: usize,
+    /// Content of the line.
+    pub text: String,
+    /// Byte ranges of the line matching the query.
+    pub spans: Vec<Range<usize>>,
+    score: i64,
+}
+
+/// Fuzzy match a query against text.
+///
+/// All query characters must appear in order, ignoring case. Returns the match
+/// score and the matching byte ranges.
+fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<Range<usize>>)> {
+    let mut query_chars = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    let mut score = 0;
+
+    let mut previous_char = None;
+    for (i, c) in text.char_indices() {
+        let query_char = match query_chars.peek() {
+            Some(query_char) => *query_char,
+            None => break,
+        };
+
+        if c.to_lowercase().eq([query_char]) {
+            query_chars.next();
+            score += 1;
+
+            // Reward matches at word boundaries.
+            if previous_char.is_none_or(|previous: char| !previous.is_alphanumeric()) {
+                score += WORD_START_BONUS;
+            }
+
+            // Extend the previous span for consecutive matches.
+            match spans.last_mut() {
+                Some(span) if span.end == i => {
+                    span.end = i + c.len_utf8();
+                    score += CONSECUTIVE_BONUS;
+                },
+                _ => spans.push(i..i + c.len_utf8()),
+            }
+        }
+
+        previous_char = Some(c);
+    }
+
+    query_chars.peek().is_none().then_some((score, spans))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_match_spans() {
+        assert_eq!(
+            fuzzy_match("abc", "abc"),
+            Some((3 + WORD_START_BONUS + 2 * CONSECUTIVE_BONUS, vec![0..3]))
+        );
+        assert_eq!(fuzzy_match("ac", "a-b-c").map(|(_, spans)| spans), Some(vec![0..1, 4..5]));
+        assert_eq!(fuzzy_match("ÄB", "xäb").map(|(_, spans)| spans), Some(vec![1..4]));
+        assert_eq!(fuzzy_match("ba", "ab"), None);
+        assert_eq!(fuzzy_match("abcd", "abc"), None);
+    }
+
+    #[test]
+    fn fuzzy_match_scoring() {
+        let score = |query, text| fuzzy_match(query, text).unwrap().0;
+
+        // Consecutive matches beat scattered ones.
+        assert!(score("note", "notes") > score("note", "n o t e"));
+
+        // Word starts beat matches within words.
+        assert!(score("ta", "tax") > score("ta", "stax"));
+    }
+
+    #[test]
+    fn search_ordering() {
+        let index = SearchIndex {
+            notes: vec![
+                IndexedNote::new("a".into(), "xtodox\ntodo list\ntodo\nt o d o"),
+                IndexedNote::new("b".into(), "nothing here"),
+            ],
+        };
+
+        let results = index.search("todo");
+        let lines: Vec<_> = results.iter().map(|result| result.text.as_str()).collect();
+        assert_eq!(lines, ["todo", "todo list", "xtodox", "t o d o"]);
+
+        assert!(index.search("").is_empty());
+        assert!(index.search("missing").is_empty());
+    }
+}
@@ -0,0 +1,238 @@
+//! Search view for finding lines across all notes.
+
+use std::path::Path;
+
+use skia_safe::textlayout::{
+    FontCollection, Paragraph, ParagraphBuilder, ParagraphStyle, TextStyle,
+};
+use skia_safe::{Canvas as SkiaCanvas, Color4f, Paint, Point, Rect};
+
+use crate::config::{Colors, Config, FontFamily};
+use crate::editor::{TAG_MARKER, TagIndex};
+use crate::search::{SearchIndex, SearchResult};
+use crate::skia;
+
+/// Height of the query input and each result at scale 1.
+const ROW_HEIGHT: f64 = 40.;
+
+/// Note name text alpha.
+const NOTE_NAME_ALPHA: f32 = 0.6;
+
+/// Results list with a query input.
+pub struct SearchView {
+    font_collection: FontCollection,
+    index: SearchIndex,
+
+    results: Vec<SearchResult>,
+    query: String,
+    selected: usize,
+    scroll_offset: usize,
+
+    font_family: FontFamily,
+    font_size: f64,
+    padding: f64,
+
+    colors: Colors,
+
+    rect: Rect,
+    scale: f64,
+}
+
+impl SearchView {
+    pub fn new(config: &Config, colors: &Colors, index: SearchIndex) -> Self {
+        Self {
+            index,
+            font_collection: skia::font_collection(config.font.directory.as_deref()),
+            font_family: config.font.family.clone(),
+            font_size: config.font.size,
+            padding: config.layout.padding,
+            colors: colors.clone(),
+            scale: 1.,
+            scroll_offset: Default::default(),
+            selected: Default::default(),
+            results: Default::default(),
+            query: Default::default(),
+            rect: Rect::new_empty(),
+        }
+    }
+
+    /// Render the search view into the specified physical area.
+    pub fn draw(&mut self, canvas: &SkiaCanvas, rect: Rect, scale: f64) {
+        self.rect = rect;
+        self.scale = scale;
+
+        let mut paint = Paint::default();
+        paint.set_color4f(self.colors.background.color().as_color4f(), None);
+        canvas.draw_rect(rect, &paint);
+
+        // Draw query input.
+        let row_height = self.row_height();
+        let query_rect = Rect::from_xywh(rect.left, rect.top, rect.width(), row_height);
+        paint.set_color4f(self.colors.header.as_color4f(), None);
+        canvas.draw_rect(query_rect, &paint);
+        let query = format!("{}_", self.query);
+        let paragraph = self.paragraph(&[(query.as_str(), None)]);
+        self.paint_row(canvas, paragraph, query_rect);
+
+        // Keep selection within the visible results.
+        let visible_rows = ((rect.height() - row_height) / row_height).max(1.) as usize;
+        if self.selected < self.scroll_offset {
+            self.scroll_offset = self.selected;
+        } else if self.selected >= self.scroll_offset + visible_rows {
+            self.scroll_offset = self.selected + 1 - visible_rows;
+        }
+
+        // Draw visible results.
+        let results = self.results.iter().enumerate().skip(self.scroll_offset).take(visible_rows);
+        for (row, (i, result)) in results.enumerate() {
+            let top = rect.top + row_height * (row + 1) as f32;
+            let row_rect = Rect::from_xywh(rect.left, top, rect.width(), row_height);
+
+            if i == self.selected {
+                paint.set_color4f(self.colors.header.as_color4f(), None);
+                canvas.draw_rect(row_rect, &paint);
+            }
+
+            let paragraph = self.result_paragraph(result);
+            self.paint_row(canvas, paragraph, row_rect);
+        }
+    }
+
+    /// Append text to the query.
+    pub fn push_str(&mut self, text: &str) {
+        self.query.push_str(text);
+        self.update_results();
+    }
+
+    /// Delete the last character of the query.
+    pub fn pop_char(&mut self) {
+        self.query.pop();
+        self.update_results();
+    }
+
+    /// Move the selection by `delta` results.
+    pub fn move_selection(&mut self, delta: isize) {
+        let max = self.results.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(max);
+    }
+
+    /// Get the currently selected result.
+    pub fn selected_result(&self) -> Option<&SearchResult> {
+        self.results.get(self.selected)
+    }
+
+    /// Get the tag name, if the query consists of a single tag like `#groceries`.
+    pub fn tag_query(&self) -> Option<&str> {
+        let query = self.query.trim();
+        match TagIndex::new(query).ranges() {
+            [tag] if *tag == (0..query.len()) => Some(&query[TAG_MARKER.len_utf8()..]),
+            _ => None,
+        }
+    }
+
+    /// Get the result at a physical position.
+    pub fn result_at(&self, position: Point) -> Option<&SearchResult> {
+        if !self.rect.contains(position) {
+            return None;
+        }
+
+        let row = ((position.y - self.rect.top) / self.row_height()) as usize;
+        self.results.get(row.checked_sub(1)? + self.scroll_offset)
+    }
+
+    /// Rerun the search for the current query.
+    fn update_results(&mut self) {
+        self.results = self.index.search(&self.query);
+        self.scroll_offset = 0;
+        self.selected = 0;
+    }
+
+    /// Create the paragraph for a search result.
+    ///
+    /// Matching spans are highlighted and the note's name is appended.
+    fn result_paragraph(&self, result: &SearchResult) -> Paragraph {
+        let mut segments = Vec::new();
+        let mut start = 0;
+        for span in &result.spans {
+            segments.push((&result.text[start..span.start], None));
+            segments.push((&result.text[span.clone()], Some(Highlight::Background)));
+            start = span.end;
+        }
+        segments.push((&result.text[start..], None));
+
+        let note_name = note_name(&result.path);
+        segments.push((" — ", None));
+        segments.push((note_name.as_str(), Some(Highlight::Dim)));
+
+        self.paragraph(&segments)
+    }
+
+    /// Create a single-line paragraph from styled text segments.
+    fn paragraph(&self, segments: &[(&str, Option<Highlight>)]) -> Paragraph {
+        let mut paint = Paint::default();
+        paint.set_color4f(self.colors.foreground.as_color4f(), None);
+        paint.set_anti_alias(true);
+
+        let mut text_style = TextStyle::new();
+        text_style.set_foreground_paint(&paint);
+        text_style.set_font_size((self.font_size * self.scale) as f32);
+        text_style.set_font_families(&self.font_family);
+
+        let mut paragraph_style = ParagraphStyle::new();
+        paragraph_style.set_text_style(&text_style);
+        paragraph_style.set_max_lines(1);
+        paragraph_style.set_ellipsis("…");
+
+        let mut builder = ParagraphBuilder::new(&paragraph_style, &self.font_collection);
+        for (text, highlight) in segments {
+            match highlight {
+                Some(Highlight::Background) => {
+                    let mut style = text_style.clone();
+                    let mut background = Paint::default();
+                    background.set_color4f(self.colors.highlight.as_color4f(), None);
+                    style.set_background_paint(&background);
+                    builder.push_style(&style);
+                },
+                Some(Highlight::Dim) => {
+                    let mut style = text_style.clone();
+                    let color = Color4f { a: NOTE_NAME_ALPHA, ..paint.color4f() };
+                    let mut dim_paint = paint.clone();
+                    dim_paint.set_color4f(color, None);
+                    style.set_foreground_paint(&dim_paint);
+                    builder.push_style(&style);
+                },
+                None => builder.push_style(&text_style),
+            };
+            builder.add_text(text);
+            builder.pop();
+        }
+
+        builder.build()
+    }
+
+    /// Layout a paragraph and paint it vertically centered within a row.
+    fn paint_row(&self, canvas: &SkiaCanvas, mut paragraph: Paragraph, rect: Rect) {
+        let padding = (self.padding * self.scale).round() as f32;
+        paragraph.layout((rect.width() - 2. * padding).max(0.));
+
+        let y = rect.top + ((rect.height() - paragraph.height()) / 2.).round();
+        paragraph.paint(canvas, Point::new(rect.left + padding, y));
+    }
+
+    /// Physical height of a single row.
+    fn row_height(&self) -> f32 {
+        (ROW_HEIGHT * self.scale).round() as f32
+    }
+}
+
+/// Text segment styling.
+#[derive(Copy, Clone)]
+enum Highlight {
+    Background,
+    Dim,
+}
+
+/// Get a note's display name.
+fn note_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
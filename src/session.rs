@@ -0,0 +1,104 @@
+//! Session state persistence.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{ErrorKind as IoErrorKind, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+use tracing::{error, info};
+
+use crate::geometry::{Logical, Size};
+
+/// Transient UI state restored on startup.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct Session {
+    pub scroll_offset: f32,
+    pub cursor_index: usize,
+    pub selection: Option<Range<usize>>,
+    pub window_size: Option<Logical<Size>>,
+    pub reminder_check: Option<i64>,
+    pub zoom: Option<f64>,
+    /// Headers of the collapsed sections of each note.
+    pub collapsed_sections: BTreeMap<PathBuf, BTreeSet<String>>,
+    /// Headers of the sections with switched list style of each note.
+    pub toggled_list_styles: BTreeMap<PathBuf, BTreeSet<String>>,
+}
+
+impl Session {
+    /// Load the last session state.
+    ///
+    /// This will return the default session if no previous session exists.
+    pub fn load() -> Self {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Self::default(),
+        };
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == IoErrorKind::NotFound => return Self::default(),
+            Err(err) => {
+                error!("Failed to read session file at {path:?}: {err}");
+                return Self::default();
+            },
+        };
+
+        toml::from_str(&content)
+            .inspect_err(|err| error!("Invalid session file at {path:?}: {err}"))
+            .unwrap_or_default()
+    }
+
+    /// Atomically write the session state to disk.
+    pub fn write(&self) {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let content = match toml::to_string(self) {
+            Ok(content) => content,
+            Err(err) => {
+                error!("Failed to serialize session: {err}");
+                return;
+            },
+        };
+
+        // Ensure state directory exists.
+        let target_dir = path.parent().unwrap();
+        if let Err(err) = fs::create_dir_all(target_dir) {
+            error!("Could not create session file directories: {err}");
+            return;
+        }
+
+        // Create a tempfile "next to" the target path, to avoid persisting across
+        // filesystems.
+        let mut tempfile = match NamedTempFile::new_in(target_dir) {
+            Ok(tempfile) => tempfile,
+            Err(err) => {
+                error!("Failed to create temporary session file: {err}");
+                return;
+            },
+        };
+
+        if let Err(err) = tempfile.write_all(content.as_bytes()) {
+            error!("Failed to write to temporary session file: {err}");
+            return;
+        }
+
+        if let Err(err) = tempfile.persist(&path) {
+            error!("Failed move of temporary session file: {err}");
+            return;
+        }
+
+        info!("Successfully saved session");
+    }
+
+    /// Location of the session state file.
+    fn path() -> Option<PathBuf> {
+        Some(dirs::state_dir()?.join("pinax/session.toml"))
+    }
+}
@@ -1,15 +1,29 @@
 //! Skia graphics rendering.
 
+use std::fs;
+use std::path::Path;
+
 use skia_safe::gpu::gl::{Format, FramebufferInfo, Interface};
 use skia_safe::gpu::{
     DirectContext, SurfaceOrigin, backend_render_targets, direct_contexts, surfaces,
 };
-use skia_safe::{Canvas as SkiaCanvas, ColorType, Surface as SkiaSurface};
+use skia_safe::textlayout::{
+    FontCollection, ParagraphBuilder, ParagraphStyle, TextStyle, TypefaceFontProvider,
+};
+use skia_safe::{
+    Canvas as SkiaCanvas, Color4f, ColorType, FontMgr, Paint, PaintStyle, Rect,
+    Surface as SkiaSurface,
+};
+use tracing::{error, warn};
 
+use crate::config::BulletShape;
 use crate::geometry::Size;
 use crate::gl;
 use crate::gl::types::GLint;
 
+/// Stroke width of keyboard focus outlines at scale 1.
+const FOCUS_OUTLINE_WIDTH: f64 = 2.;
+
 /// OpenGL-based Skia render target.
 #[derive(Default)]
 pub struct Canvas {
@@ -106,3 +120,118 @@ pub struct GlConfig {
     pub stencil_size: usize,
     pub sample_count: usize,
 }
+
+/// Create a font collection with the system fonts and all fonts in `font_dir`.
+pub fn font_collection(font_dir: Option<&Path>) -> FontCollection {
+    let font_mgr = FontMgr::new();
+    let mut font_collection = FontCollection::new();
+
+    if let Some(font_dir) = font_dir {
+        let font_provider = load_fonts(&font_mgr, font_dir);
+        font_collection.set_asset_font_manager(Some(font_provider.into()));
+    }
+    font_collection.set_default_font_manager(font_mgr, None);
+
+    font_collection
+}
+
+/// Draw a keyboard focus outline along the inside of `rect`.
+pub fn draw_focus_outline(canvas: &SkiaCanvas, rect: Rect, color: Color4f, scale: f64) {
+    let width = (FOCUS_OUTLINE_WIDTH * scale).round() as f32;
+
+    let mut paint = Paint::default();
+    paint.set_color4f(color, None);
+    paint.set_style(PaintStyle::Stroke);
+    paint.set_stroke_width(width);
+    paint.set_anti_alias(true);
+
+    canvas.draw_rect(rect.with_inset((width / 2., width / 2.)), &paint);
+}
+
+/// Draw a list item bullet point, vertically centered on `center_y`.
+pub fn draw_bullet(
+    canvas: &SkiaCanvas,
+    shape: BulletShape,
+    x: f32,
+    center_y: f32,
+    size: f32,
+    paint: &Paint,
+) {
+    match shape {
+        BulletShape::Square => {
+            let y = center_y - size / 2.;
+            canvas.draw_rect(Rect::new(x, y, x + size, y + size), paint);
+        },
+        BulletShape::Circle => {
+            let mut paint = paint.clone();
+            paint.set_anti_alias(true);
+            canvas.draw_circle((x + size / 2., center_y), size / 2., &paint);
+        },
+        BulletShape::Dash => {
+            let height = (size / 2.).max(1.);
+            let y = center_y - height / 2.;
+            canvas.draw_rect(Rect::new(x, y, x + size * 2., y + height), paint);
+        },
+    }
+}
+
+/// Draw a list item number, ending at `right` and sitting on `baseline`.
+pub fn draw_item_number(
+    canvas: &SkiaCanvas,
+    font_collection: &FontCollection,
+    text_style: &TextStyle,
+    number: usize,
+    right: f32,
+    baseline: f32,
+    paint: &Paint,
+) {
+    let mut text_style = text_style.clone();
+    text_style.set_foreground_paint(paint);
+    let mut paragraph_style = ParagraphStyle::new();
+    paragraph_style.set_text_style(&text_style);
+
+    let mut builder = ParagraphBuilder::new(&paragraph_style, font_collection);
+    builder.add_text(format!("{number}."));
+    let mut paragraph = builder.build();
+    paragraph.layout(f32::INFINITY);
+
+    let x = right - paragraph.max_intrinsic_width();
+    let y = baseline - paragraph.alphabetic_baseline();
+    paragraph.paint(canvas, (x, y));
+}
+
+/// Register all font files in a directory.
+fn load_fonts(font_mgr: &FontMgr, font_dir: &Path) -> TypefaceFontProvider {
+    let mut font_provider = TypefaceFontProvider::new();
+
+    let entries = match fs::read_dir(font_dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            error!("Failed to read font directory {font_dir:?}: {err}");
+            return font_provider;
+        },
+    };
+
+    for path in entries.filter_map(|entry| Some(entry.ok()?.path())) {
+        if !path.is_file() {
+            continue;
+        }
+
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(err) => {
+                error!("Failed to read font file {path:?}: {err}");
+                continue;
+            },
+        };
+
+        match font_mgr.new_from_data(&data, None) {
+            Some(typeface) => {
+                font_provider.register_typeface(typeface, None);
+            },
+            None => warn!("Ignoring invalid font file {path:?}"),
+        }
+    }
+
+    font_provider
+}
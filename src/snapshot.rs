@@ -0,0 +1,76 @@
+//! Rendering of text into shareable images.
+
+use skia_safe::textlayout::{FontCollection, ParagraphBuilder, ParagraphStyle, TextStyle};
+use skia_safe::{Color4f, EncodedImageFormat, Paint, surfaces};
+
+use crate::config::{Layout, ListStyle};
+use crate::editor::{item_numbers, list_items};
+use crate::skia;
+
+/// Render text into a PNG image sized to its content.
+///
+/// The layout's padding is added around the text and bullet points, with
+/// `width` limiting the length of lines in physical pixels.
+pub fn render_png(
+    font_collection: &FontCollection,
+    text_style: &TextStyle,
+    text: &str,
+    background: Color4f,
+    layout: &Layout,
+    width: f32,
+    scale: f32,
+) -> Option<Vec<u8>> {
+    let mut paragraph_style = ParagraphStyle::new();
+    paragraph_style.set_text_style(text_style);
+    let mut builder = ParagraphBuilder::new(&paragraph_style, font_collection);
+    builder.add_text(text);
+    let mut paragraph = builder.build();
+    paragraph.layout(width);
+
+    // Shrink the image to the longest line.
+    let padding = (layout.padding as f32 * scale).round();
+    let bullet_padding = layout.bullet_padding() as f32 * scale;
+    let text_width = paragraph.longest_line().ceil().min(width);
+    let image_width = (text_width + 2. * padding + bullet_padding).ceil() as i32;
+    let image_height = (paragraph.height() + 2. * padding).ceil() as i32;
+    let mut surface = surfaces::raster_n32_premul((image_width, image_height))?;
+    let canvas = surface.canvas();
+
+    let origin = (padding + bullet_padding, padding);
+    canvas.clear(background);
+    paragraph.paint(canvas, origin);
+
+    // Draw bullet points in front of list items.
+    let color = layout.bullet_color.map_or(text_style.foreground().color4f(), |c| c.as_color4f());
+    let paint = Paint::new(color, None);
+    let size = layout.bullet_size as f32 * scale;
+    let bullet_width = layout.bullet_shape.width(layout.bullet_size) as f32 * scale;
+    let items = list_items(text);
+    let numbers = item_numbers(text, &items);
+    for (item, number) in items.into_iter().zip(numbers) {
+        let Some(line) = paragraph.get_line_number_at(item.start) else { continue };
+        let Some(metrics) = paragraph.get_line_metrics_at(line) else { continue };
+
+        let x = origin.0 + metrics.left as f32 - bullet_padding;
+        if layout.list_style == ListStyle::Numbers {
+            let baseline = origin.1 + metrics.baseline as f32;
+            let right = x + bullet_width;
+            skia::draw_item_number(
+                canvas,
+                font_collection,
+                text_style,
+                number,
+                right,
+                baseline,
+                &paint,
+            );
+            continue;
+        }
+
+        let y = metrics.baseline - metrics.ascent / 2. + metrics.descent / 2.;
+        skia::draw_bullet(canvas, layout.bullet_shape, x, origin.1 + y as f32, size, &paint);
+    }
+
+    let data = surface.image_snapshot().encode(None, EncodedImageFormat::PNG, None)?;
+    Some(data.as_bytes().to_vec())
+}
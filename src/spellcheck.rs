@@ -0,0 +1,529 @@
+//! Dictionary-based spellchecking.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind as IoErrorKind, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::{cmp, mem};
+
+use tracing::{error, info};
+
+/// Maximum number of suggestions offered for a misspelled word.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Maximum edit distance for spelling suggestions.
+const MAX_DISTANCE: usize = 2;
+
+/// Spellchecker.
+pub struct Spellcheck {
+    dictionary_path: PathBuf,
+    /// Dictionary stems, with their affix flags.
+    words: HashMap<String, Vec<u32>>,
+    personal_words: HashSet<String>,
+    affixes: Affixes,
+}
+
+impl Spellcheck {
+    /// Load the dictionary at `dictionary_path`.
+    ///
+    /// Hunspell affix rules are read from the `.aff` file next to the
+    /// dictionary, if it exists.
+    ///
+    /// Returns `None` if the dictionary could not be read.
+    pub fn new(dictionary_path: &Path) -> Option<Self> {
+        let dictionary = match fs::read_to_string(dictionary_path) {
+            Ok(dictionary) => dictionary,
+            Err(err) => {
+                error!("Failed to read dictionary at {dictionary_path:?}: {err}");
+                return None;
+            },
+        };
+
+        // Affix files of some dictionaries are not UTF-8, even if their words are.
+        let affix_path = dictionary_path.with_extension("aff");
+        let affixes = match fs::read(&affix_path) {
+            Ok(affixes) => Affixes::parse(&String::from_utf8_lossy(&affixes)),
+            Err(err) if err.kind() == IoErrorKind::NotFound => Affixes::default(),
+            Err(err) => {
+                error!("Failed to read affix rules at {affix_path:?}: {err}");
+                Affixes::default()
+            },
+        };
+
+        // Split hunspell affix flags from the stems, skipping the leading word count.
+        let mut words: HashMap<String, Vec<u32>> = HashMap::new();
+        for line in dictionary.lines() {
+            let entry = line.split(['\t', ' ']).next().unwrap_or_default();
+            let (word, flags) = entry.split_once('/').unwrap_or((entry, ""));
+            if word.is_empty() || word.bytes().all(|b| b.is_ascii_digit()) {
+                continue;
+            }
+
+            words.entry(word.into()).or_default().extend(affixes.flag_type.parse(flags));
+        }
+
+        let personal_words = match Self::personal_dictionary_path() {
+            Some(path) => match fs::read_to_string(&path) {
+                Ok(words) => words.lines().map(String::from).collect(),
+                Err(err) if err.kind() == IoErrorKind::NotFound => HashSet::new(),
+                Err(err) => {
+                    error!("Failed to read personal dictionary at {path:?}: {err}");
+                    HashSet::new()
+                },
+            },
+            None => HashSet::new(),
+        };
+
+        info!("Loaded dictionary {dictionary_path:?}");
+
+        Some(Self { words, personal_words, affixes, dictionary_path: dictionary_path.into() })
+    }
+
+    /// Location of the loaded dictionary.
+    pub fn dictionary_path(&self) -> &Path {
+        &self.dictionary_path
+    }
+
+    /// Get byte ranges of all misspelled words in `text`.
+    pub fn misspellings(&self, text: &str) -> Vec<Range<usize>> {
+        words(text).filter(|range| !self.check(&text[range.clone()])).collect()
+    }
+
+    /// Check if a word is spelled correctly.
+    pub fn check(&self, word: &str) -> bool {
+        // Ignore single letters.
+        if word.chars().nth(1).is_none() {
+            return true;
+        }
+
+        let lowercase = word.to_lowercase();
+        let known = |word: &str| {
+            self.words.contains_key(word)
+                || self.personal_words.contains(word)
+                || self.check_affixed(word)
+        };
+        known(word) || known(&lowercase)
+    }
+
+    /// Check if a word is a dictionary stem with affixes allowed by its flags.
+    fn check_affixed(&self, word: &str) -> bool {
+        let has_flags = |root: &str, required: &[u32]| {
+            self.words.get(root).is_some_and(|flags| required.iter().all(|f| flags.contains(f)))
+        };
+
+        let suffixed = |word: &str, prefix: Option<&Affix>| {
+            self.affixes
+                .suffixes
+                .iter()
+                .filter(|suffix| prefix.is_none() || suffix.cross_product)
+                .any(|suffix| match (suffix.suffix_root(word), prefix) {
+                    (Some(root), Some(prefix)) => has_flags(&root, &[suffix.flag, prefix.flag]),
+                    (Some(root), None) => has_flags(&root, &[suffix.flag]),
+                    (None, _) => false,
+                })
+        };
+
+        suffixed(word, None)
+            || self.affixes.prefixes.iter().any(|prefix| match prefix.prefix_root(word) {
+                Some(root) => {
+                    has_flags(&root, &[prefix.flag])
+                        || (prefix.cross_product && suffixed(&root, Some(prefix)))
+                },
+                None => false,
+            })
+    }
+
+    /// Get replacement suggestions for a misspelled word.
+    pub fn suggestions(&self, word: &str) -> Vec<String> {
+        let lowercase = word.to_lowercase();
+        let len = lowercase.chars().count();
+
+        let mut candidates: Vec<_> = self
+            .words
+            .keys()
+            .chain(&self.personal_words)
+            .filter(|candidate| candidate.chars().count().abs_diff(len) <= MAX_DISTANCE)
+            .filter_map(|candidate| {
+                let distance = edit_distance(&lowercase, &candidate.to_lowercase());
+                (distance <= MAX_DISTANCE).then_some((distance, candidate))
+            })
+            .collect();
+        candidates.sort_unstable();
+
+        // Preserve capitalization of the first letter.
+        let capitalized = word.starts_with(char::is_uppercase);
+        let mut suggestions = Vec::new();
+        for (_, candidate) in candidates {
+            let suggestion = if capitalized { capitalize(candidate) } else { candidate.clone() };
+            if !suggestions.contains(&suggestion) {
+                suggestions.push(suggestion);
+            }
+
+            if suggestions.len() >= MAX_SUGGESTIONS {
+                break;
+            }
+        }
+
+        suggestions
+    }
+
+    /// Add a word to the personal dictionary.
+    pub fn add_word(&mut self, word: &str) {
+        if !self.personal_words.insert(word.into()) {
+            return;
+        }
+
+        let path = match Self::personal_dictionary_path() {
+            Some(path) => path,
+            None => return,
+        };
+
+        // Ensure data directory exists.
+        if let Err(err) = fs::create_dir_all(path.parent().unwrap()) {
+            error!("Could not create personal dictionary directory: {err}");
+            return;
+        }
+
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| writeln!(file, "{word}"));
+        if let Err(err) = result {
+            error!("Failed to write to personal dictionary at {path:?}: {err}");
+        }
+    }
+
+    /// Location of the user's personal dictionary.
+    pub fn personal_dictionary_path() -> Option<PathBuf> {
+        Some(dirs::state_dir()?.join("pinax/dictionary.txt"))
+    }
+}
+
+/// Affix rules of a hunspell dictionary.
+#[derive(Default)]
+struct Affixes {
+    flag_type: FlagType,
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+}
+
+impl Affixes {
+    /// Parse the prefix and suffix rules of a hunspell `.aff` file.
+    ///
+    /// Other options, like compounding or replacement tables, are ignored.
+    fn parse(aff: &str) -> Self {
+        let mut affixes = Self::default();
+        let mut cross_products = HashMap::new();
+        for line in aff.lines() {
+            let fields: Vec<_> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", flag_type, ..] => {
+                    affixes.flag_type = match *flag_type {
+                        "long" => FlagType::Long,
+                        "num" => FlagType::Numeric,
+                        _ => FlagType::Char,
+                    };
+                },
+                // Header of an affix class, like `SFX D Y 4`.
+                [kind @ ("PFX" | "SFX"), flag, cross_product @ ("Y" | "N"), count]
+                    if count.parse::<usize>().is_ok() =>
+                {
+                    cross_products.insert((*kind, *flag), *cross_product == "Y");
+                },
+                // Affix rule, like `SFX D y ied [^aeiou]y`.
+                [kind @ ("PFX" | "SFX"), flag_name, strip, add, rest @ ..] => {
+                    let Some(flag) = affixes.flag_type.parse(flag_name).next() else { continue };
+                    let cross_product = cross_products.get(&(*kind, *flag_name)) == Some(&true);
+
+                    // Ignore continuation flags of the affix itself.
+                    let add = add.split('/').next().unwrap_or_default();
+                    let empty = |text: &str| if text == "0" { String::new() } else { text.into() };
+
+                    let affix = Affix {
+                        condition: Condition::parse(rest.first().unwrap_or(&".")),
+                        strip: empty(strip),
+                        add: empty(add),
+                        cross_product,
+                        flag,
+                    };
+
+                    if *kind == "PFX" {
+                        affixes.prefixes.push(affix);
+                    } else {
+                        affixes.suffixes.push(affix);
+                    }
+                },
+                _ => (),
+            }
+        }
+        affixes
+    }
+}
+
+/// Encoding of hunspell affix flags.
+#[derive(Default, Copy, Clone)]
+enum FlagType {
+    /// Every character is a flag.
+    #[default]
+    Char,
+    /// Every pair of characters is a flag.
+    Long,
+    /// Flags are comma-separated numbers.
+    Numeric,
+}
+
+impl FlagType {
+    /// Parse a list of flags.
+    fn parse(self, flags: &str) -> impl Iterator<Item = u32> {
+        let flags: Vec<_> = match self {
+            Self::Char => flags.chars().map(u32::from).collect(),
+            Self::Long => {
+                let chars: Vec<_> = flags.chars().map(u32::from).collect();
+                chars.chunks_exact(2).map(|pair| (pair[0] << 21) | pair[1]).collect()
+            },
+            Self::Numeric => flags.split(',').filter_map(|flag| flag.parse().ok()).collect(),
+        };
+        flags.into_iter()
+    }
+}
+
+/// Prefix or suffix rule of a hunspell dictionary.
+struct Affix {
+    /// Flag allowing this affix for a stem.
+    flag: u32,
+    /// Whether the affix can be combined with affixes of the other kind.
+    cross_product: bool,
+    /// Text removed from the stem before adding the affix.
+    strip: String,
+    /// Text added to the stem.
+    add: String,
+    /// Pattern the stem must match, at its start for prefixes or end for suffixes.
+    condition: Vec<Condition>,
+}
+
+impl Affix {
+    /// Get the stem of a word ending with this suffix.
+    fn suffix_root(&self, word: &str) -> Option<String> {
+        let stem = word.strip_suffix(self.add.as_str()).filter(|stem| !stem.is_empty())?;
+        let root = format!("{stem}{}", self.strip);
+        Condition::matches_all(self.condition.iter().rev(), root.chars().rev()).then_some(root)
+    }
+
+    /// Get the stem of a word starting with this prefix.
+    fn prefix_root(&self, word: &str) -> Option<String> {
+        let stem = word.strip_prefix(self.add.as_str()).filter(|stem| !stem.is_empty())?;
+        let root = format!("{}{stem}", self.strip);
+        Condition::matches_all(self.condition.iter(), root.chars()).then_some(root)
+    }
+}
+
+/// Single character of an affix condition.
+enum Condition {
+    /// Any character, written as `.`.
+    Any,
+    /// One of the characters, like `[aeiou]` or `y`.
+    OneOf(Vec<char>),
+    /// None of the characters, like `[^aeiou]`.
+    NoneOf(Vec<char>),
+}
+
+impl Condition {
+    /// Parse an affix condition, like `[^aeiou]y`.
+    fn parse(condition: &str) -> Vec<Self> {
+        let mut conditions = Vec::new();
+        let mut chars = condition.chars();
+        while let Some(c) = chars.next() {
+            let condition = match c {
+                '.' => Self::Any,
+                '[' => {
+                    let class: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                    match class.strip_prefix('^') {
+                        Some(negated) => Self::NoneOf(negated.chars().collect()),
+                        None => Self::OneOf(class.chars().collect()),
+                    }
+                },
+                c => Self::OneOf(vec![c]),
+            };
+            conditions.push(condition);
+        }
+        conditions
+    }
+
+    /// Check if every condition is satisfied by the corresponding character.
+    fn matches_all<'a>(
+        mut conditions: impl Iterator<Item = &'a Self>,
+        mut chars: impl Iterator<Item = char>,
+    ) -> bool {
+        conditions.all(|condition| chars.next().is_some_and(|c| condition.matches(c)))
+    }
+
+    /// Check if a character satisfies the condition.
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Any => true,
+            Self::OneOf(chars) => chars.contains(&c),
+            Self::NoneOf(chars) => !chars.contains(&c),
+        }
+    }
+}
+
+/// Get byte ranges of all words in `text`.
+///
+/// Words which are part of URLs or email addresses are ignored.
+fn words(text: &str) -> impl Iterator<Item = Range<usize>> {
+    let mut offset = 0;
+    text.split_inclusive(char::is_whitespace).flat_map(move |token| {
+        let token_start = offset;
+        offset += token.len();
+
+        let mut words = Vec::new();
+        if token.contains("://") || token.contains('@') {
+            return words;
+        }
+
+        let mut word_start = None;
+        for (i, c) in token.char_indices() {
+            let is_word_char = c.is_alphabetic() || (c == '\'' && word_start.is_some());
+            match (is_word_char, word_start) {
+                (true, None) => word_start = Some(i),
+                (false, Some(start)) => {
+                    words.push(token_start + start..token_start + i);
+                    word_start = None;
+                },
+                _ => (),
+            }
+        }
+        if let Some(start) = word_start {
+            words.push(token_start + start..token_start + token.len());
+        }
+
+        // Remove trailing apostrophes.
+        for word in &mut words {
+            word.end = token_start + token[..word.end - token_start].trim_end_matches('\'').len();
+        }
+
+        words
+    })
+}
+
+/// Levenshtein distance between two words.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = cmp::min(substitution, cmp::min(previous[j + 1], current[j]) + 1);
+        }
+        mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Uppercase the first letter of a word.
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spellcheck(words: &[&str]) -> Spellcheck {
+        Spellcheck {
+            dictionary_path: PathBuf::new(),
+            words: words.iter().map(|word| (word.to_string(), Vec::new())).collect(),
+            personal_words: HashSet::new(),
+            affixes: Affixes::default(),
+        }
+    }
+
+    #[test]
+    fn word_splitting() {
+        let text = "Don't worry, it's well-known: 'quoted' rock'n'roll naïve Straße 日本語 42x \
+                    https://example.org mail@example.org";
+        let words: Vec<_> = words(text).map(|range| &text[range]).collect();
+        assert_eq!(
+            words,
+            [
+                "Don't",
+                "worry",
+                "it's",
+                "well",
+                "known",
+                "quoted",
+                "rock'n'roll",
+                "naïve",
+                "Straße",
+                "日本語",
+                "x",
+            ]
+        );
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("abc", "abc"), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("flaw", "lawn"), 2);
+        assert_eq!(edit_distance("ab", "ba"), 2);
+        assert_eq!(edit_distance("café", "cafe"), 1);
+    }
+
+    #[test]
+    fn affix_rules() {
+        let aff = "SET UTF-8\n\
+                   PFX U Y 1\n\
+                   PFX U 0 un .\n\
+                   SFX D Y 4\n\
+                   SFX D 0 d e\n\
+                   SFX D y ied [^aeiou]y\n\
+                   SFX D 0 ed [^ey]\n\
+                   SFX D 0 ed [aeiou]y\n\
+                   SFX S N 1\n\
+                   SFX S 0 s .";
+        let mut spellcheck = spellcheck(&[]);
+        spellcheck.affixes = Affixes::parse(aff);
+        for (word, flags) in [("try", "D"), ("play", "DS"), ("bake", "DU"), ("lock", "UDS")] {
+            spellcheck.words.insert(word.into(), flags.chars().map(u32::from).collect());
+        }
+
+        for word in ["tried", "played", "baked", "unbake", "unbaked", "unlocked", "locks"] {
+            assert!(spellcheck.check(word), "{word}");
+        }
+        for word in ["tryed", "plaied", "bakeed", "unplay", "tries", "unlocks"] {
+            assert!(!spellcheck.check(word), "{word}");
+        }
+    }
+
+    #[test]
+    fn long_and_numeric_flags() {
+        assert_eq!(FlagType::Long.parse("AaBb").count(), 2);
+        assert_eq!(FlagType::Numeric.parse("1,20,300").collect::<Vec<_>>(), [1, 20, 300]);
+        assert_eq!(FlagType::Char.parse("ab").collect::<Vec<_>>(), ['a' as u32, 'b' as u32]);
+    }
+
+    #[test]
+    fn suggestion_ranking() {
+        let spellcheck =
+            spellcheck(&["heron", "hero", "help", "hello", "hell", "halo", "yellow", "world"]);
+
+        // Closest words first, alphabetically within the same distance.
+        assert_eq!(spellcheck.suggestions("helo"), ["halo", "hell", "hello", "help", "hero"]);
+        assert_eq!(spellcheck.suggestions("Helo"), ["Halo", "Hell", "Hello", "Help", "Hero"]);
+        assert_eq!(spellcheck.suggestions("herons"), ["heron", "hero"]);
+        assert!(spellcheck.suggestions("xyz").is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+//! Storage of notes as individual files.
+
+use std::cell::Cell;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Write};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tempfile::NamedTempFile;
+use tracing::{error, info};
+
+use crate::Error;
+use crate::backup::MAX_BACKUPS;
+use crate::config::Config;
+use crate::recovery;
+use crate::spellcheck::Spellcheck;
+use crate::storage::{Storage, TrashedNote, unix_time};
+
+/// Directory inside the storage directory containing deleted notes.
+const TRASH_DIR: &str = ".trash";
+
+/// Maximum number of same-named notes trashed within one second.
+const MAX_TRASH_COLLISIONS: usize = 1000;
+
+/// Maximum number of chained symlinks followed, matching Linux's limit.
+const MAX_SYMLINK_DEPTH: usize = 40;
+
+/// Flat-file storage.
+///
+/// Every note is a plain text file in the storage directory, with its
+/// metadata in a TOML sidecar file using a `.meta.toml` suffix.
+pub struct FileStorage {
+    fsync: Cell<bool>,
+}
+
+impl FileStorage {
+    pub fn new(config: &Config) -> Self {
+        Self { fsync: Cell::new(config.general.fsync) }
+    }
+
+    /// Get the sidecar metadata path for the note at `path`.
+    pub fn metadata_path(path: &Path) -> PathBuf {
+        let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+        file_name.push(".meta.toml");
+        path.with_file_name(file_name)
+    }
+
+    /// Resolve the file a symlinked note points to.
+    ///
+    /// Dangling symlinks resolve to their missing target, which allows writing
+    /// the note to create it. Paths which are no symlinks are returned as-is.
+    pub fn resolve_symlink(path: &Path) -> PathBuf {
+        let mut path = path.to_path_buf();
+        for _ in 0..MAX_SYMLINK_DEPTH {
+            match fs::read_link(&path) {
+                // Relative targets are relative to the symlink's directory.
+                Ok(target) => match path.parent() {
+                    Some(parent) => path = parent.join(target),
+                    None => path = target,
+                },
+                Err(_) => break,
+            }
+        }
+        path
+    }
+
+    /// Move a file, ignoring it if it does not exist.
+    fn rename_existing(from: &Path, to: &Path) -> Result<(), Error> {
+        match fs::rename(from, to) {
+            Err(err) if err.kind() != IoErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Atomically replace the file at `path`.
+    fn write_atomic(
+        &self,
+        path: &Path,
+        chunks: &mut dyn Iterator<Item = &str>,
+    ) -> Result<(), Error> {
+        // Replace the symlink's target, rather than the symlink itself.
+        let path = &Self::resolve_symlink(path);
+
+        // Create a tempfile "next to" the target path.
+        //
+        // Creating this in the same directory as the target path should avoid errors
+        // due to persisting across filesystems.
+        let target_dir = path.parent().ok_or(Error::InvalidStoragePath)?;
+        let mut tempfile = NamedTempFile::new_in(target_dir)?;
+
+        for chunk in chunks {
+            tempfile.write_all(chunk.as_bytes())?;
+        }
+
+        // Ensure file content is on disk before it replaces the previous version.
+        if self.fsync.get() {
+            tempfile.as_file().sync_all()?;
+        }
+
+        tempfile.persist(path).map_err(|err| err.error)?;
+
+        // Ensure the rename itself is on disk.
+        if self.fsync.get() {
+            File::open(target_dir)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+
+    /// Find an unused trash location for the note at `path`.
+    ///
+    /// Notes are prefixed with their deletion time, with a counter appended if
+    /// another note with the same name was deleted within the same second.
+    fn trash_path(trash_dir: &Path, path: &Path) -> Result<PathBuf, Error> {
+        let name = path.file_name().ok_or(Error::InvalidStoragePath)?;
+        let time = unix_time();
+
+        for counter in 0..MAX_TRASH_COLLISIONS {
+            let mut file_name = match counter {
+                0 => OsString::from(format!("{time}-")),
+                counter => OsString::from(format!("{time}.{counter}-")),
+            };
+            file_name.push(name);
+            let trash_path = trash_dir.join(file_name);
+
+            let metadata_path = Self::metadata_path(&trash_path);
+            if trash_path.symlink_metadata().is_err() && metadata_path.symlink_metadata().is_err() {
+                return Ok(trash_path);
+            }
+        }
+
+        Err(IoError::from(IoErrorKind::AlreadyExists).into())
+    }
+
+    /// Location of the snapshots replaced by external changes.
+    fn snapshot_dir() -> Option<PathBuf> {
+        Some(dirs::state_dir()?.join("pinax/backups"))
+    }
+}
+
+impl Storage for FileStorage {
+    fn read(&self, path: &Path) -> Result<Option<String>, Error> {
+        let mut content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            // Ignore file removal, since it might be done for replacement.
+            Err(err) if err.kind() == IoErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        // Strip trailing newline, commonly inserted by text editors.
+        if content.ends_with('\n') {
+            content.truncate(content.len() - 1);
+        }
+
+        Ok(Some(content))
+    }
+
+    fn write(&self, path: &Path, chunks: &mut dyn Iterator<Item = &str>) -> Result<(), Error> {
+        // Write text with newline appended at the end.
+        self.write_atomic(path, &mut chunks.chain(iter::once("\n")))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn list(&self, storage_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        let entries = fs::read_dir(storage_dir)?;
+        Ok(entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| is_note(path))
+            .collect())
+    }
+
+    fn read_metadata(&self, path: &Path) -> Result<Option<String>, Error> {
+        match fs::read_to_string(Self::metadata_path(path)) {
+            Ok(content) => Ok(Some(content)),
+            Err(err) if err.kind() == IoErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn write_metadata(&self, path: &Path, metadata: &str) -> Result<(), Error> {
+        self.write_atomic(&Self::metadata_path(path), &mut iter::once(metadata))
+    }
+
+    fn trash(&self, path: &Path) -> Result<(), Error> {
+        let storage_dir = path.parent().ok_or(Error::InvalidStoragePath)?;
+        let trash_dir = storage_dir.join(TRASH_DIR);
+        fs::create_dir_all(&trash_dir)?;
+
+        let trash_path = Self::trash_path(&trash_dir, path)?;
+
+        fs::rename(path, &trash_path)?;
+        Self::rename_existing(&Self::metadata_path(path), &Self::metadata_path(&trash_path))?;
+
+        info!("Moved note {path:?} to {trash_path:?}");
+
+        Ok(())
+    }
+
+    fn list_trash(&self, storage_dir: &Path) -> Result<Vec<TrashedNote>, Error> {
+        let entries = match fs::read_dir(storage_dir.join(TRASH_DIR)) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == IoErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut notes: Vec<_> = entries
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| is_note(path))
+            .filter_map(|path| {
+                let id = path.file_name()?.to_str()?.to_owned();
+                let (prefix, name) = id.split_once('-')?;
+                // Strip collision counter from the deletion time.
+                let deleted = prefix.split_once('.').map_or(prefix, |(time, _)| time);
+                let deleted = deleted.parse().ok()?;
+                Some(TrashedNote { name: name.into(), deleted, id })
+            })
+            .collect();
+        notes.sort_unstable_by_key(|note| -note.deleted);
+
+        Ok(notes)
+    }
+
+    fn restore(&self, note: &TrashedNote, path: &Path) -> Result<(), Error> {
+        let storage_dir = path.parent().ok_or(Error::InvalidStoragePath)?;
+        let trash_path = storage_dir.join(TRASH_DIR).join(&note.id);
+
+        fs::rename(&trash_path, path)?;
+        Self::rename_existing(&Self::metadata_path(&trash_path), &Self::metadata_path(path))?;
+
+        info!("Restored note {trash_path:?} to {path:?}");
+
+        Ok(())
+    }
+
+    fn purge(&self, storage_dir: &Path, note: &TrashedNote) -> Result<(), Error> {
+        let trash_path = storage_dir.join(TRASH_DIR).join(&note.id);
+
+        fs::remove_file(&trash_path)?;
+        match fs::remove_file(Self::metadata_path(&trash_path)) {
+            Err(err) if err.kind() != IoErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_snapshot(&self, text: &str) -> Result<(), Error> {
+        let dir = match Self::snapshot_dir() {
+            Some(dir) => dir,
+            None => return Ok(()),
+        };
+        fs::create_dir_all(&dir)?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let path = dir.join(format!("{}.txt", timestamp.as_millis()));
+        fs::write(&path, text)?;
+
+        info!("Saved replaced notes to {path:?}");
+
+        // Timestamp names sort chronologically, so the first entries are the oldest.
+        let mut snapshots: Vec<_> =
+            fs::read_dir(&dir)?.filter_map(|entry| Some(entry.ok()?.path())).collect();
+        snapshots.sort_unstable();
+        for path in snapshots.iter().rev().skip(MAX_BACKUPS) {
+            if let Err(err) = fs::remove_file(path) {
+                error!("Failed to remove old backup {path:?}: {err}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_shared(&self) -> bool {
+        true
+    }
+
+    fn update_config(&self, config: &Config) {
+        self.fsync.set(config.general.fsync);
+    }
+}
+
+/// Check if a file in the storage directory is a note.
+fn is_note(path: &Path) -> bool {
+    let file_name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(file_name) => file_name,
+        None => return false,
+    };
+
+    // Ignore hidden/temporary files, note metadata and conflict copies.
+    path.is_file()
+        && !file_name.starts_with('.')
+        && !file_name.ends_with(".meta.toml")
+        && !file_name.ends_with(".conflict")
+        && !internal_files().any(|internal| internal == path)
+}
+
+/// Files written by Pinax itself, which are never notes.
+///
+/// These are stored outside the default storage directory, but might still be
+/// listed if it is configured to contain them.
+fn internal_files() -> impl Iterator<Item = PathBuf> {
+    [recovery::journal_path(), recovery::recovery_path(), Spellcheck::personal_dictionary_path()]
+        .into_iter()
+        .flatten()
+}
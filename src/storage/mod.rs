@@ -0,0 +1,114 @@
+//! Note persistence.
+
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::{error, info};
+
+use crate::Error;
+use crate::config::{Config, StorageBackend};
+use crate::storage::file::FileStorage;
+use crate::storage::sqlite::SqliteStorage;
+
+pub mod file;
+mod sqlite;
+
+/// Persistence backend for notes and their associated data.
+///
+/// Notes are identified by their path inside the storage directory, even if
+/// the backend does not store them as individual files.
+pub trait Storage {
+    /// Read a note's text.
+    ///
+    /// Returns `None` if the note does not exist.
+    fn read(&self, path: &Path) -> Result<Option<String>, Error>;
+
+    /// Replace a note's text.
+    fn write(&self, path: &Path, chunks: &mut dyn Iterator<Item = &str>) -> Result<(), Error>;
+
+    /// Check if a note exists.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Get the paths of all notes in the storage directory.
+    fn list(&self, storage_dir: &Path) -> Result<Vec<PathBuf>, Error>;
+
+    /// Read a note's metadata in the TOML format.
+    ///
+    /// Returns `None` if the note has no metadata.
+    fn read_metadata(&self, path: &Path) -> Result<Option<String>, Error>;
+
+    /// Replace a note's metadata.
+    fn write_metadata(&self, path: &Path, metadata: &str) -> Result<(), Error>;
+
+    /// Move a note and its metadata to the trash.
+    fn trash(&self, path: &Path) -> Result<(), Error>;
+
+    /// Get all notes in the trash.
+    fn list_trash(&self, storage_dir: &Path) -> Result<Vec<TrashedNote>, Error>;
+
+    /// Move a note from the trash back to `path`.
+    fn restore(&self, note: &TrashedNote, path: &Path) -> Result<(), Error>;
+
+    /// Permanently delete a note from the trash.
+    fn purge(&self, storage_dir: &Path, note: &TrashedNote) -> Result<(), Error>;
+
+    /// Store a snapshot of text replaced by an external change.
+    fn write_snapshot(&self, text: &str) -> Result<(), Error>;
+
+    /// Check if notes can be modified by other applications.
+    ///
+    /// External changes are only monitored if this returns `true`.
+    fn is_shared(&self) -> bool;
+
+    /// Handle config updates.
+    fn update_config(&self, _config: &Config) {}
+}
+
+/// Note moved to the trash.
+#[derive(Clone, Debug)]
+pub struct TrashedNote {
+    /// File name of the note before it was deleted.
+    pub name: String,
+    /// Deletion time as Unix timestamp.
+    pub deleted: i64,
+    /// Backend-specific identifier of the trash entry.
+    id: String,
+}
+
+/// Permanently delete notes which have been in the trash for too long.
+pub fn purge_expired(storage: &dyn Storage, storage_dir: &Path, retention_days: u32) {
+    if retention_days == 0 {
+        return;
+    }
+
+    let notes = match storage.list_trash(storage_dir) {
+        Ok(notes) => notes,
+        Err(err) => {
+            error!("Failed to list trashed notes: {err}");
+            return;
+        },
+    };
+
+    let cutoff = unix_time() - i64::from(retention_days) * 24 * 60 * 60;
+    for note in notes.iter().filter(|note| note.deleted < cutoff) {
+        match storage.purge(storage_dir, note) {
+            Ok(()) => info!("Purged expired note {:?} from trash", note.name),
+            Err(err) => error!("Failed to purge note {:?} from trash: {err}", note.name),
+        }
+    }
+}
+
+/// Get the current time as Unix timestamp.
+fn unix_time() -> i64 {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    time.as_secs() as i64
+}
+
+/// Create the storage backend selected in the config.
+pub fn new(config: &Config, storage_dir: &Path) -> Result<Rc<dyn Storage>, Error> {
+    match config.general.backend {
+        StorageBackend::File => Ok(Rc::new(FileStorage::new(config))),
+        StorageBackend::Sqlite => Ok(Rc::new(SqliteStorage::new(config, storage_dir)?)),
+    }
+}
@@ -0,0 +1,218 @@
+//! Storage of all notes in a single SQLite database.
+//!
+//! Attachments are not stored, since notes cannot reference attachments yet.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{Connection, OptionalExtension, params};
+use tracing::{error, info};
+
+use crate::Error;
+use crate::backup::MAX_BACKUPS;
+use crate::config::Config;
+use crate::storage::{Storage, TrashedNote, unix_time};
+
+/// Database file name inside the storage directory.
+const DATABASE_NAME: &str = "pinax.sqlite";
+
+/// Database schema, created on startup.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS notes (
+        name TEXT PRIMARY KEY,
+        text TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS metadata (
+        name TEXT PRIMARY KEY,
+        content TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS trash (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        deleted INTEGER NOT NULL,
+        text TEXT NOT NULL,
+        metadata TEXT
+    );
+    CREATE TABLE IF NOT EXISTS snapshots (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        created INTEGER NOT NULL,
+        text TEXT NOT NULL
+    );
+";
+
+/// SQLite database storage.
+///
+/// Notes are keyed by their file name, so paths map to the same notes as with
+/// the flat-file storage.
+pub struct SqliteStorage {
+    connection: Connection,
+}
+
+impl SqliteStorage {
+    pub fn new(config: &Config, storage_dir: &Path) -> Result<Self, Error> {
+        fs::create_dir_all(storage_dir)?;
+
+        let path = storage_dir.join(DATABASE_NAME);
+        info!("Opening note database at {path:?}");
+
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+
+        let storage = Self { connection };
+        storage.set_fsync(config.general.fsync)?;
+
+        Ok(storage)
+    }
+
+    /// Set whether transactions wait for their changes to be written to disk.
+    fn set_fsync(&self, fsync: bool) -> Result<(), Error> {
+        let synchronous = if fsync { "FULL" } else { "OFF" };
+        self.connection.pragma_update(None, "synchronous", synchronous)?;
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn read(&self, path: &Path) -> Result<Option<String>, Error> {
+        let text = self
+            .connection
+            .query_row("SELECT text FROM notes WHERE name = ?1", [note_name(path)], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(text)
+    }
+
+    fn write(&self, path: &Path, chunks: &mut dyn Iterator<Item = &str>) -> Result<(), Error> {
+        let mut text = String::new();
+        for chunk in chunks {
+            text.push_str(chunk);
+        }
+
+        self.connection.execute(
+            "INSERT INTO notes (name, text) VALUES (?1, ?2)
+             ON CONFLICT (name) DO UPDATE SET text = excluded.text",
+            params![note_name(path), text],
+        )?;
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.read(path).is_ok_and(|text| text.is_some())
+    }
+
+    fn list(&self, storage_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut statement = self.connection.prepare("SELECT name FROM notes ORDER BY name")?;
+        let names = statement.query_map([], |row| row.get::<_, String>(0))?;
+        names.map(|name| Ok(storage_dir.join(name?))).collect()
+    }
+
+    fn read_metadata(&self, path: &Path) -> Result<Option<String>, Error> {
+        let content = self
+            .connection
+            .query_row("SELECT content FROM metadata WHERE name = ?1", [note_name(path)], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(content)
+    }
+
+    fn write_metadata(&self, path: &Path, metadata: &str) -> Result<(), Error> {
+        self.connection.execute(
+            "INSERT INTO metadata (name, content) VALUES (?1, ?2)
+             ON CONFLICT (name) DO UPDATE SET content = excluded.content",
+            params![note_name(path), metadata],
+        )?;
+        Ok(())
+    }
+
+    fn trash(&self, path: &Path) -> Result<(), Error> {
+        let name = note_name(path);
+
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute(
+            "INSERT INTO trash (name, deleted, text, metadata)
+             SELECT notes.name, ?2, notes.text, metadata.content FROM notes
+             LEFT JOIN metadata ON metadata.name = notes.name WHERE notes.name = ?1",
+            params![name, unix_time()],
+        )?;
+        transaction.execute("DELETE FROM notes WHERE name = ?1", [&name])?;
+        transaction.execute("DELETE FROM metadata WHERE name = ?1", [&name])?;
+        transaction.commit()?;
+
+        info!("Moved note {name:?} to the trash");
+
+        Ok(())
+    }
+
+    fn list_trash(&self, _storage_dir: &Path) -> Result<Vec<TrashedNote>, Error> {
+        let mut statement =
+            self.connection.prepare("SELECT id, name, deleted FROM trash ORDER BY deleted DESC")?;
+        let notes = statement.query_map([], |row| {
+            let id: i64 = row.get(0)?;
+            Ok(TrashedNote { id: id.to_string(), name: row.get(1)?, deleted: row.get(2)? })
+        })?;
+        Ok(notes.collect::<Result<_, _>>()?)
+    }
+
+    fn restore(&self, note: &TrashedNote, path: &Path) -> Result<(), Error> {
+        let name = note_name(path);
+
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute(
+            "INSERT INTO notes (name, text) SELECT ?2, text FROM trash WHERE id = ?1",
+            params![note.id, name],
+        )?;
+        transaction.execute(
+            "INSERT INTO metadata (name, content)
+             SELECT ?2, metadata FROM trash WHERE id = ?1 AND metadata IS NOT NULL",
+            params![note.id, name],
+        )?;
+        transaction.execute("DELETE FROM trash WHERE id = ?1", [&note.id])?;
+        transaction.commit()?;
+
+        info!("Restored note {:?} from the trash to {name:?}", note.name);
+
+        Ok(())
+    }
+
+    fn purge(&self, _storage_dir: &Path, note: &TrashedNote) -> Result<(), Error> {
+        self.connection.execute("DELETE FROM trash WHERE id = ?1", [&note.id])?;
+        Ok(())
+    }
+
+    fn write_snapshot(&self, text: &str) -> Result<(), Error> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.connection.execute(
+            "INSERT INTO snapshots (created, text) VALUES (?1, ?2)",
+            params![timestamp.as_millis() as i64, text],
+        )?;
+
+        // Remove the oldest snapshots beyond the limit.
+        self.connection.execute(
+            "DELETE FROM snapshots WHERE id NOT IN
+             (SELECT id FROM snapshots ORDER BY id DESC LIMIT ?1)",
+            [MAX_BACKUPS as i64],
+        )?;
+
+        info!("Saved replaced notes to the note database");
+
+        Ok(())
+    }
+
+    fn is_shared(&self) -> bool {
+        false
+    }
+
+    fn update_config(&self, config: &Config) {
+        if let Err(err) = self.set_fsync(config.general.fsync) {
+            error!("Failed to update database synchronization: {err}");
+        }
+    }
+}
+
+/// Get the database key of a note.
+fn note_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
+}
@@ -0,0 +1,314 @@
+//! Editable text storage.
+
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ropey::Rope;
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete};
+
+/// Source of unique text revisions.
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+/// Maximum number of recorded edits.
+///
+/// The oldest half of the edits is forgotten once this limit is reached.
+const MAX_EDITS: usize = 1024;
+
+/// Rope-backed text buffer.
+///
+/// All offsets are UTF-8 byte offsets. Operations which require the entire
+/// text as a `&str` use a contiguous copy, which is created on demand and
+/// discarded on every edit.
+#[derive(Default)]
+pub struct Text {
+    rope: Rope,
+    contiguous: OnceCell<String>,
+    revision: u64,
+
+    /// Recent edits, with the revision of the text after each edit.
+    edits: Vec<(u64, TextEdit)>,
+    /// Revision of the text before the first recorded edit.
+    edits_base: u64,
+}
+
+impl Text {
+    /// Get the entire text as a string slice.
+    pub fn as_str(&self) -> &str {
+        self.contiguous.get_or_init(|| self.rope.to_string())
+    }
+
+    /// Convert the text into a string.
+    pub fn into_string(self) -> String {
+        match self.contiguous.into_inner() {
+            Some(text) => text,
+            None => self.rope.to_string(),
+        }
+    }
+
+    /// Iterate over the text's chunks, without creating a contiguous copy.
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.rope.chunks()
+    }
+
+    /// Iterate over the chunks of a byte range, without creating a contiguous copy.
+    pub fn chunks_in(&self, range: Range<usize>) -> impl Iterator<Item = &str> {
+        self.rope.byte_slice(range).chunks()
+    }
+
+    /// Identifier of the text's content.
+    ///
+    /// The revision changes with every edit, so texts with equal revisions
+    /// always have the same content.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Get the edits made since the text had revision `since`, in order.
+    ///
+    /// Returns `None` if the edits since that revision are unknown, like after
+    /// the entire text was replaced or when too many edits were made since.
+    pub fn edits_since(&self, since: u64) -> Option<impl Iterator<Item = &TextEdit>> {
+        let start = if since == self.edits_base {
+            0
+        } else {
+            self.edits.binary_search_by_key(&since, |(revision, _)| *revision).ok()? + 1
+        };
+        Some(self.edits[start..].iter().map(|(_, edit)| edit))
+    }
+
+    /// Length in bytes.
+    pub fn len(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    /// Check if the text is empty.
+    pub fn is_empty(&self) -> bool {
+        self.rope.len_bytes() == 0
+    }
+
+    /// Get the text in a byte range.
+    ///
+    /// Returns `None` if the range is out of bounds or not on char boundaries.
+    pub fn get(&self, range: Range<usize>) -> Option<Cow<'_, str>> {
+        if range.start > range.end
+            || !self.is_char_boundary(range.start)
+            || !self.is_char_boundary(range.end)
+        {
+            return None;
+        }
+
+        Some(self.slice(range))
+    }
+
+    /// Get the text in a byte range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or not on char boundaries, like
+    /// indexing a `str`.
+    pub fn slice(&self, range: Range<usize>) -> Cow<'_, str> {
+        match self.contiguous.get() {
+            Some(text) => Cow::Borrowed(&text[range]),
+            None => self.rope.byte_slice(range).into(),
+        }
+    }
+
+    /// Get the byte at an offset.
+    pub fn byte(&self, offset: usize) -> Option<u8> {
+        self.rope.get_byte(offset)
+    }
+
+    /// Check if a byte offset is on a char boundary.
+    pub fn is_char_boundary(&self, offset: usize) -> bool {
+        match self.rope.try_byte_to_char(offset) {
+            Ok(index) => self.rope.char_to_byte(index) == offset,
+            Err(_) => false,
+        }
+    }
+
+    /// Get the byte offset of the start of a line.
+    ///
+    /// Lines past the end of the text map to the text's length.
+    pub fn line_to_byte(&self, line: usize) -> usize {
+        self.rope.try_line_to_byte(line).unwrap_or(self.len())
+    }
+
+    /// Get the index of the line containing an offset.
+    pub fn line_at(&self, offset: usize) -> usize {
+        self.rope.byte_to_line(offset)
+    }
+
+    /// Get the byte range of the line containing an offset.
+    ///
+    /// The range excludes the line's terminating newline.
+    pub fn line_range(&self, offset: usize) -> Range<usize> {
+        let line = self.rope.byte_to_line(offset);
+        let start = self.rope.line_to_byte(line);
+
+        let mut end = self.line_to_byte(line + 1);
+        if end > start && self.byte(end - 1) == Some(b'\n') {
+            end -= 1;
+        }
+
+        start..end
+    }
+
+    /// Get the start of the grapheme cluster before `offset`.
+    pub fn previous_grapheme(&self, offset: usize) -> usize {
+        let (mut chunk, mut chunk_start, ..) = self.rope.chunk_at_byte(offset);
+        let mut cursor = GraphemeCursor::new(offset, self.len(), true);
+        loop {
+            match cursor.prev_boundary(chunk, chunk_start) {
+                Ok(boundary) => return boundary.unwrap_or(0),
+                Err(GraphemeIncomplete::PrevChunk) => {
+                    (chunk, chunk_start, ..) = self.rope.chunk_at_byte(chunk_start - 1);
+                },
+                Err(GraphemeIncomplete::PreContext(end)) => {
+                    let (context, ..) = self.rope.chunk_at_byte(end - 1);
+                    cursor.provide_context(context, end - context.len());
+                },
+                Err(_) => return 0,
+            }
+        }
+    }
+
+    /// Get the end of the grapheme cluster after `offset`.
+    pub fn next_grapheme(&self, offset: usize) -> usize {
+        let (mut chunk, mut chunk_start, ..) = self.rope.chunk_at_byte(offset);
+        let mut cursor = GraphemeCursor::new(offset, self.len(), true);
+        loop {
+            match cursor.next_boundary(chunk, chunk_start) {
+                Ok(boundary) => return boundary.unwrap_or(self.len()),
+                Err(GraphemeIncomplete::NextChunk) => {
+                    chunk_start += chunk.len();
+                    (chunk, ..) = self.rope.chunk_at_byte(chunk_start);
+                },
+                Err(GraphemeIncomplete::PreContext(end)) => {
+                    let (context, ..) = self.rope.chunk_at_byte(end - 1);
+                    cursor.provide_context(context, end - context.len());
+                },
+                Err(_) => return self.len(),
+            }
+        }
+    }
+
+    /// Get the char before a byte offset.
+    pub fn char_before(&self, offset: usize) -> Option<char> {
+        let index = self.rope.try_byte_to_char(offset).ok()?.checked_sub(1)?;
+        Some(self.rope.char(index))
+    }
+
+    /// Get the char starting at a byte offset.
+    pub fn char_at(&self, offset: usize) -> Option<char> {
+        let index = self.rope.try_byte_to_char(offset).ok()?;
+        self.rope.get_char(index)
+    }
+
+    /// Get the lengths of the common prefix and suffix of two texts in bytes.
+    ///
+    /// The suffix never overlaps the prefix in either text.
+    pub fn common_affixes(&self, other: &Self) -> (usize, usize) {
+        let prefix = self.rope.bytes().zip(other.rope.bytes()).take_while(|(a, b)| a == b).count();
+
+        let max_suffix = self.len().min(other.len()) - prefix;
+        let suffix = self
+            .rope
+            .bytes_at(self.len())
+            .reversed()
+            .zip(other.rope.bytes_at(other.len()).reversed())
+            .take(max_suffix)
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        (prefix, suffix)
+    }
+
+    /// Insert text at a byte offset.
+    pub fn insert(&mut self, offset: usize, text: &str) {
+        self.rope.insert(self.rope.byte_to_char(offset), text);
+        self.changed(offset..offset, text);
+    }
+
+    /// Remove a byte range.
+    pub fn remove(&mut self, range: Range<usize>) {
+        let start = self.rope.byte_to_char(range.start);
+        let end = self.rope.byte_to_char(range.end);
+        self.rope.remove(start..end);
+        self.changed(range, "");
+    }
+
+    /// Replace a byte range with new text.
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        self.remove(range.clone());
+        self.insert(range.start, text);
+    }
+
+    /// Record an edit and discard state derived from the previous content.
+    fn changed(&mut self, range: Range<usize>, text: &str) {
+        self.contiguous.take();
+        self.revision = next_revision();
+
+        if self.edits.len() >= MAX_EDITS {
+            self.edits_base = self.edits[MAX_EDITS / 2 - 1].0;
+            self.edits.drain(..MAX_EDITS / 2);
+        }
+        self.edits.push((self.revision, TextEdit { range, text: text.into() }));
+    }
+}
+
+/// Clone the text, without copying its contiguous representation.
+///
+/// Edits are only recorded starting from the clone's current revision.
+impl Clone for Text {
+    fn clone(&self) -> Self {
+        Self {
+            rope: self.rope.clone(),
+            revision: self.revision,
+            edits_base: self.revision,
+            contiguous: Default::default(),
+            edits: Default::default(),
+        }
+    }
+}
+
+impl From<String> for Text {
+    fn from(text: String) -> Self {
+        let rope = Rope::from_str(&text);
+        let revision = next_revision();
+        Self {
+            rope,
+            revision,
+            contiguous: OnceCell::from(text),
+            edits_base: revision,
+            edits: Default::default(),
+        }
+    }
+}
+
+impl PartialEq for Text {
+    fn eq(&self, other: &Self) -> bool {
+        self.revision == other.revision || self.rope == other.rope
+    }
+}
+
+impl PartialEq<str> for Text {
+    fn eq(&self, other: &str) -> bool {
+        self.rope == other
+    }
+}
+
+/// Replacement of a byte range.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TextEdit {
+    /// Replaced range, in the text before the edit.
+    pub range: Range<usize>,
+    /// Replacement text.
+    pub text: String,
+}
+
+/// Get a new unique text revision.
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
@@ -1,31 +1,58 @@
 //! Text input area.
 
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::f32::consts::SQRT_2;
-use std::io::{ErrorKind as IoErrorKind, Read, Write};
-use std::ops::{Bound, Range, RangeBounds};
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::fmt::Write as _;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::ops::{Range, RangeBounds, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::time::{Duration, Instant};
-use std::{cmp, fs, mem};
+use std::{cmp, fs, iter, mem};
 
 use calloop::timer::{TimeoutAction, Timer};
 use calloop::{LoopHandle, RegistrationToken};
 use calloop_notify::NotifySource;
 use calloop_notify::notify::{EventKind, RecursiveMode, Watcher};
+use chrono::Local;
 use skia_safe::textlayout::{
-    FontCollection, LineMetrics, Paragraph, ParagraphBuilder, ParagraphStyle, TextDecoration,
-    TextStyle,
+    FontCollection, LineMetrics, Paragraph, ParagraphBuilder, ParagraphStyle, RectHeightStyle,
+    RectWidthStyle, TextDecoration, TextDecorationStyle, TextDirection, TextStyle,
 };
 use skia_safe::{
-    Canvas as SkiaCanvas, Color4f, Font, FontMetrics, FontMgr, Paint, Path, Point, Rect,
+    Canvas as SkiaCanvas, Color4f, FilterMode, Font, FontMetrics, FontStyle, MipmapMode, Paint,
+    Path as SkiaPath, Point, Rect, SamplingOptions,
 };
 use smithay_client_toolkit::seat::keyboard::{Keysym, Modifiers};
-use tempfile::NamedTempFile;
 use tracing::{error, info, warn};
+use unicode_segmentation::UnicodeSegmentation;
 
-use crate::config::Config;
+use crate::analysis::{TextAnalysis, TextStats};
+use crate::backup::Backups;
+use crate::config::{
+    self, Accents, Align, Anchor, Colors, Config, Direction, Editing, FontFamily, Layout,
+    ListStyle, MIN_SELECTION_CONTRAST, SaveMode,
+};
+use crate::editor::{
+    self, COLOR_END_MARKER, COMPLETED_MARKER, ColorSpan, LayoutMetrics, LineKind, ListItem,
+    MIN_SCROLL_VELOCITY, PIN_MARKER, Scroll, Section, TagIndex, TextChange, content_prefix_len,
+    html_list, is_completed, item_content, list_items, markdown_list, normalize_paste,
+};
 use crate::geometry::{Position, Size};
-use crate::window::{BULLET_POINT_PADDING, BULLET_POINT_SIZE};
-use crate::{Error, State};
+use crate::hooks::{HookKind, HookRunner};
+use crate::layout::{BlockCache, BlockSize, DisplayMap, TextLayout};
+use crate::recovery::{self, Journal};
+use crate::session::Session;
+use crate::spellcheck::Spellcheck;
+use crate::storage::Storage;
+use crate::storage::file::FileStorage;
+use crate::text::Text;
+use crate::thumbnail::{THUMBNAIL_HEIGHT, THUMBNAIL_PADDING, Thumbnail, Thumbnails};
+use crate::{Error, State, print, reminder, skia, snapshot};
 
 // Selection caret size at scale 1.
 const CARET_SIZE: f64 = 5.;
@@ -33,6 +60,73 @@ const CARET_SIZE: f64 = 5.;
 // Caret outline width at scale 1.
 const CARET_STROKE: f64 = 3.;
 
+/// File name of the note receiving archived list items.
+const ARCHIVE_NOTE: &str = "archive";
+
+/// Date format of the suffix added to archived list items.
+const ARCHIVE_DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Text opacity of completed list items.
+const COMPLETED_ALPHA: f32 = 0.5;
+
+/// Size of section chevrons at scale 1.
+const CHEVRON_SIZE: f64 = 8.;
+
+/// Space between section chevrons and bullet points at scale 1.
+const CHEVRON_SPACING: f64 = 4.;
+
+/// Time for list items to make room for a dragged item.
+const REORDER_DURATION: Duration = Duration::from_millis(150);
+
+/// Opacity of the highlight behind dragged list items.
+const DRAGGED_ITEM_ALPHA: f32 = 0.2;
+
+/// Distance from the viewport's edges which scrolls while dragging items at scale 1.
+const AUTOSCROLL_EDGE: f64 = 48.;
+
+/// Maximum scroll velocity while dragging items at scale 1, in pixels per second.
+const AUTOSCROLL_VELOCITY: f64 = 600.;
+
+/// Opacity of the background behind code blocks.
+const CODE_BACKGROUND_ALPHA: f32 = 0.1;
+
+/// Horizontal padding of the background behind code blocks at scale 1.
+const CODE_BACKGROUND_PADDING: f64 = 4.;
+
+/// Space between the pinned section and its divider at scale 1.
+const PINNED_DIVIDER_SPACING: f64 = 10.;
+
+/// Opacity of the pinned section's divider.
+const PINNED_DIVIDER_ALPHA: f32 = 0.3;
+
+/// Opacity of the block cursor in overwrite mode.
+const OVERWRITE_CURSOR_ALPHA: f32 = 0.5;
+
+/// Underline color for misspelled words.
+const MISSPELLING_COLOR: Color4f = Color4f::new(0.9, 0.25, 0.25, 1.);
+
+/// Maximum time between the last touch motion and release for kinetic
+/// scrolling.
+const MAX_FLING_DELAY: u32 = 50;
+
+/// Horizontal gap between text columns at scale 1.
+const COLUMN_GAP: f64 = 30.;
+
+/// Minimum and maximum text zoom factor.
+const ZOOM_RANGE: RangeInclusive<f64> = 0.5..=3.;
+
+/// Number of consecutive failed saves before a desktop notification is sent.
+const SAVE_FAILURE_NOTIFICATION_COUNT: usize = 3;
+
+/// Maximum time journaled edits are kept unsynced, when `general.fsync` is enabled.
+const JOURNAL_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Delay after the last change of a watched file before it is reloaded.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Maximum delay before a continuously changing file is reloaded.
+const RELOAD_MAX_DELAY: Duration = Duration::from_secs(1);
+
 /// Maximum number of surrounding bytes submitted to IME.
 ///
 /// The value `4000` is chosen to match the maximum Wayland protocol message
@@ -48,64 +142,152 @@ pub struct TextBox {
     selection_style: TextStyle,
     text_style: TextStyle,
     selection_paint: Paint,
+    background: Color4f,
+    tag_paint: Paint,
+    accents: Accents,
     paint: Paint,
 
-    last_paragraph: Option<Paragraph>,
+    last_paragraph: Option<TextLayout>,
     last_cursor_rect: Option<Rect>,
     last_paragraph_height: f32,
+    last_render_state: RenderState,
+    rendered_text: Text,
+    text_point: Point,
 
+    pinned_paragraph: Option<Paragraph>,
+    pinned_items: Vec<PinnedItem>,
+    pinned_text: String,
+    pinned_point: Point,
+    pinned_height: f32,
+
+    preedit_cursor: Option<Range<usize>>,
     preedit_text: String,
-    text: String,
+    text: Text,
 
     selection: Option<Range<usize>>,
     cursor_index: usize,
 
     size: Size,
     scale: f64,
-
-    font_family: String,
+    zoom: f64,
+    text_scale: f64,
+
+    font_family: FontFamily,
+    monospace_family: FontFamily,
+    font_directory: Option<PathBuf>,
+    font_features: Vec<String>,
+    font_style: FontStyle,
     font_size: f64,
+    line_height: f64,
+    paragraph_spacing: f64,
+
+    anchor: Anchor,
+    align: Align,
+    direction: Direction,
+    text_direction: TextDirection,
+    column_width: Option<u32>,
+
+    auto_timestamp: bool,
+    layout: Layout,
+    timestamp_format: String,
+    date_format: String,
+    editing: Editing,
+
+    normalize_paste: bool,
+    paste_lines_as_items: bool,
 
     touch_state: TouchState,
-    scroll_offset: f32,
+    item_drag: Option<ItemDrag>,
+    scroll: Scroll,
+    scroll_anchor: Option<(usize, f32)>,
 
     keyboard_focused: bool,
     ime_focused: bool,
+    overwrite: bool,
 
     persist_token: Option<RegistrationToken>,
+    journal_sync_token: Option<RegistrationToken>,
+    reload_timers: HashMap<PathBuf, (Instant, RegistrationToken)>,
+    symlink_watch: Option<(PathBuf, RegistrationToken)>,
     persist_start: Option<Instant>,
+    save_mode: SaveMode,
+    save_debounce: Duration,
+    save_max_delay: Duration,
+    unsaved: bool,
+    save_failures: usize,
+    storage: Rc<dyn Storage>,
+    hooks: HookRunner,
     storage_path: PathBuf,
+    backups: Backups,
+    journal: Journal,
+
+    spellcheck: Option<Spellcheck>,
+    analysis: TextAnalysis,
+    text_blocks: Vec<Range<usize>>,
+    list_items: Vec<ListItem>,
+    misspellings: Vec<Range<usize>>,
+    selection_stats: Option<(Range<usize>, u64, TextStats)>,
+    completed_items: Vec<Range<usize>>,
+    completed_contents: Vec<Range<usize>>,
+    hidden_markup: Vec<Range<usize>>,
+    tags: TagIndex,
+    tag_filter: Option<String>,
+    color_spans: Vec<ColorSpan>,
+    code_blocks: Vec<Range<usize>>,
+    sections: Vec<Section>,
+    collapsed_sections: BTreeMap<PathBuf, BTreeSet<String>>,
+    toggled_list_styles: BTreeMap<PathBuf, BTreeSet<String>>,
+    thumbnails: Thumbnails,
+    tapped_misspelling: Option<Range<usize>>,
+    disk_hash: u64,
 
     focus_cursor: bool,
 
     text_input_dirty: bool,
+    full_damage: bool,
     dirty: bool,
 }
 
 impl TextBox {
-    pub fn new(event_loop: LoopHandle<'static, State>, config: &Config) -> Result<Self, Error> {
+    pub fn new(
+        event_loop: LoopHandle<'static, State>,
+        config: &Config,
+        colors: &Colors,
+        storage: Rc<dyn Storage>,
+    ) -> Result<Self, Error> {
         let font_family = config.font.family.clone();
         let font_size = config.font.size;
+        let line_height = config.font.line_height;
+        let font_style = config.font.skia_style();
+        let font_features = config.font.features.clone();
 
         let mut paint = Paint::default();
-        paint.set_color4f(config.colors.foreground.as_color4f(), None);
+        paint.set_color4f(colors.foreground.as_color4f(), None);
         paint.set_anti_alias(true);
 
         let mut text_style = TextStyle::new();
         text_style.set_foreground_paint(&paint);
         text_style.set_font_size(font_size as f32);
-        text_style.set_font_families(&[&font_family]);
+        text_style.set_font_families(&font_family);
+        text_style.set_height(line_height as f32);
+        text_style.set_height_override(true);
+        text_style.set_half_leading(true);
+        set_font_options(&mut text_style, font_style, &font_features);
 
         let mut selection_paint = paint.clone();
         selection_paint.set_stroke_width(CARET_STROKE as f32);
         let mut selection_style = text_style.clone();
-        selection_paint.set_color4f(config.colors.background.as_color4f(), None);
+        let (selection_foreground, selection_background) = colors.selection();
+        selection_paint.set_color4f(selection_foreground.as_color4f(), None);
         selection_style.set_foreground_paint(&selection_paint);
-        selection_paint.set_color4f(config.colors.highlight.as_color4f(), None);
+        selection_paint.set_color4f(selection_background.as_color4f(), None);
         selection_style.set_background_paint(&selection_paint);
+        warn_selection_contrast(colors);
+
+        let mut tag_paint = paint.clone();
+        tag_paint.set_color4f(colors.highlight.as_color4f(), None);
 
-        let mut font_collection = FontCollection::new();
-        font_collection.set_default_font_manager(FontMgr::new(), None);
+        let font_collection = skia::font_collection(config.font.directory.as_deref());
 
         // Ensure storage directory exists.
         let storage_path = config.general.storage_path();
@@ -115,48 +297,157 @@ impl TextBox {
             return Err(Error::InvalidStoragePath);
         }
 
-        // Read initial text from file.
-        let text = Self::read_to_string(&storage_path).unwrap_or_default();
+        // Read initial text from storage.
+        let hooks = HookRunner::new(&event_loop, config);
+        hooks.run(HookKind::PreLoad, &storage_path);
+        let text = Text::from(Self::read_note(&*storage, &storage_path).unwrap_or_default());
+        let disk_hash = content_hash(text.chunks());
+        let journal = Journal::new(&text, config.general.fsync);
         let cursor_index = text.len();
 
+        let spellcheck = config
+            .spellcheck
+            .enabled
+            .then(|| Spellcheck::new(&config.spellcheck.dictionary))
+            .flatten();
+
         // Update text box on file change.
-        Self::monitor_file(&event_loop, storage_path.clone())?;
+        if storage.is_shared() {
+            Self::monitor_file(&event_loop, parent_dir)?;
+        }
 
-        Ok(Self {
+        let mut text_box = Self {
             font_collection,
             selection_paint,
             selection_style,
+            tag_paint,
+            accents: colors.accents.clone(),
             cursor_index,
             storage_path,
+            storage,
+            hooks,
             font_family,
+            monospace_family: config.font.monospace_family.clone(),
+            paragraph_spacing: config.font.paragraph_spacing,
+            font_features,
+            font_style,
+            spellcheck,
+            font_directory: config.font.directory.clone(),
+            anchor: config.general.anchor,
+            align: config.general.align,
+            direction: config.general.direction,
+            column_width: config.general.column_width,
+            text_direction: TextDirection::LTR,
+            auto_timestamp: config.general.auto_timestamp,
+            layout: config.layout,
+            timestamp_format: config.general.timestamp_format.clone(),
+            date_format: config.general.date_format.clone(),
+            editing: config.editing,
+            normalize_paste: config.input.normalize_paste,
+            paste_lines_as_items: config.input.paste_lines_as_items,
+            disk_hash,
+            journal,
             event_loop,
             text_style,
+            line_height,
             font_size,
             paint,
             text,
+            background: colors.background.color().as_color4f(),
             text_input_dirty: true,
+            full_damage: true,
             dirty: true,
             scale: 1.,
+            zoom: 1.,
+            text_scale: 1.,
+            last_render_state: Default::default(),
+            rendered_text: Default::default(),
+            text_point: Default::default(),
             last_paragraph_height: Default::default(),
+            pinned_paragraph: Default::default(),
+            pinned_items: Default::default(),
+            pinned_text: Default::default(),
+            pinned_point: Default::default(),
+            pinned_height: Default::default(),
             fallback_metrics: Default::default(),
             keyboard_focused: Default::default(),
+            overwrite: Default::default(),
             last_cursor_rect: Default::default(),
             last_paragraph: Default::default(),
+            save_mode: config.general.save_mode,
+            save_debounce: Duration::from_millis(config.general.save_debounce_ms),
+            save_max_delay: Duration::from_millis(config.general.save_max_delay_ms),
             persist_start: Default::default(),
+            unsaved: Default::default(),
+            save_failures: Default::default(),
             persist_token: Default::default(),
-            scroll_offset: Default::default(),
+            journal_sync_token: Default::default(),
+            reload_timers: Default::default(),
+            symlink_watch: Default::default(),
+            scroll: Default::default(),
+            scroll_anchor: Default::default(),
             focus_cursor: Default::default(),
+            preedit_cursor: Default::default(),
             preedit_text: Default::default(),
             ime_focused: Default::default(),
             touch_state: Default::default(),
+            item_drag: Default::default(),
             selection: Default::default(),
+            backups: Default::default(),
+            tapped_misspelling: Default::default(),
+            analysis: Default::default(),
+            text_blocks: Default::default(),
+            list_items: Default::default(),
+            misspellings: Default::default(),
+            selection_stats: Default::default(),
+            completed_items: Default::default(),
+            completed_contents: Default::default(),
+            hidden_markup: Default::default(),
+            tags: Default::default(),
+            tag_filter: Default::default(),
+            color_spans: Default::default(),
+            code_blocks: Default::default(),
+            sections: Default::default(),
+            collapsed_sections: Default::default(),
+            toggled_list_styles: Default::default(),
+            thumbnails: Default::default(),
             size: Default::default(),
-        })
+        };
+
+        // Follow changes to the target of symlinked notes.
+        text_box.monitor_symlink_target();
+
+        Ok(text_box)
     }
 
     /// Check whether the text box requires a redraw.
     pub fn dirty(&self) -> bool {
         self.dirty
+            || self.scroll.velocity != 0.
+            || self.item_drag_autoscroll() != 0.
+            || self.item_drag.as_ref().is_some_and(ItemDrag::is_animating)
+    }
+
+    /// Advance animations by one frame.
+    ///
+    /// The `interval` is the expected time between two presented frames.
+    pub fn animate(&mut self, interval: Duration) {
+        let mut animating = self.scroll.animate(interval);
+
+        // Scroll while dragging items close to the edges, and make room for them.
+        let autoscroll = self.item_drag_autoscroll();
+        if let Some(item_drag) = &mut self.item_drag {
+            self.scroll.offset += autoscroll * interval.as_secs_f32();
+            item_drag.update(self.scroll.offset);
+            animating |= item_drag.animate(interval) || autoscroll != 0.;
+        }
+
+        if !animating {
+            return;
+        }
+
+        self.text_input_dirty = true;
+        self.dirty = true;
     }
 
     /// Retrieve and reset current IME dirtiness state.
@@ -164,63 +455,278 @@ impl TextBox {
         mem::take(&mut self.text_input_dirty)
     }
 
-    /// Render text content to the canvas.
-    pub fn draw(&mut self, canvas: &SkiaCanvas, point: impl Into<Point>) {
+    /// Update the text layout.
+    ///
+    /// Returns the region which changed since the last layout, or `None` if the
+    /// entire text box needs to be redrawn.
+    pub fn layout(&mut self, point: impl Into<Point>) -> Option<Rect> {
         let mut point = point.into();
 
         self.dirty = false;
 
-        // Render text if not empty.
+        // Parse the text's structure, if it was modified.
+        self.update_text_analysis();
+
+        // Update the pinned section above the text.
+        self.update_pinned_paragraph();
+        self.pinned_point = point;
+
         if !self.text.is_empty() || !self.preedit_text.is_empty() {
             // Re-layout paragraph content.
             self.update_paragraph();
 
+            // Keep the line at the top of the viewport in place after text replacement.
+            if let Some((offset, delta)) = self.scroll_anchor.take()
+                && let Some(top) = self.line_top(offset)
+            {
+                self.scroll.offset = delta - top;
+            }
+
             // Scroll to cursor, or clamp offset within maximum bounds.
             if mem::take(&mut self.focus_cursor) {
+                self.scroll.velocity = 0.;
                 unsafe { self.update_scroll_offset() };
             } else {
                 unsafe { self.clamp_scroll_offset() };
             }
 
-            let paragraph = self.last_paragraph.as_ref().unwrap();
+            // Shape blocks which were scrolled into view.
+            let viewport = self.viewport();
+            if self.last_paragraph.as_ref().is_some_and(|layout| !layout.is_shaped(viewport)) {
+                self.update_paragraph();
+                unsafe { self.clamp_scroll_offset() };
+            }
 
-            // Render text.
-            point.y += (self.size.height as f32 - self.last_paragraph_height).max(0.);
-            point.y += self.scroll_offset;
-            paragraph.paint(canvas, point);
+            // Move list items which are being reordered.
+            self.shift_dragged_items();
 
-            // Draw list element bullet points.
-            self.draw_bullet_points(canvas, point);
+            point.y += self.anchor_offset();
+            point.y += self.scroll.offset;
         } else {
             // Reset scroll offset if there is no text.
-            self.scroll_offset = 0.;
+            self.scroll.offset = 0.;
 
             // Calculate approximate line height.
             let metrics = self.fallback_metrics();
-            self.last_paragraph_height = metrics.descent - metrics.ascent;
+            self.last_paragraph_height =
+                (metrics.descent - metrics.ascent) * self.line_height as f32;
             self.last_paragraph = None;
 
-            // Anchor content to the bottom of the window.
-            point.y += (self.size.height as f32 - self.last_paragraph_height).max(0.);
+            point.y += self.anchor_offset();
+        }
+        self.text_point = point;
+
+        // Update cursor or selection position while focused.
+        let last_cursor_rect = self.last_cursor_rect.take();
+        self.last_cursor_rect =
+            (self.keyboard_focused || self.ime_focused).then(|| self.cursor_rect(point));
+
+        // Keep the IME's cursor rectangle in sync with scrolling and preedit changes.
+        if self.last_cursor_rect != last_cursor_rect {
+            self.text_input_dirty = true;
+        }
+
+        self.damage(last_cursor_rect)
+    }
+
+    /// Render text content to the canvas.
+    pub fn draw(&mut self, canvas: &SkiaCanvas) {
+        let point = self.text_point;
+
+        // Highlight code blocks behind their text.
+        self.draw_code_backgrounds(canvas, point);
 
-            // Draw list element bullet points.
-            self.draw_bullet_points(canvas, point);
+        // Render text.
+        if let Some(paragraph) = &self.last_paragraph {
+            paragraph.paint(canvas, point);
         }
 
+        // Draw the dragged list item on top of the other items.
+        self.draw_dragged_item(canvas, point);
+
+        // Draw image thumbnails below their lines.
+        self.draw_thumbnails(canvas, point);
+
+        // Draw list element bullet points.
+        self.draw_bullet_points(canvas, point);
+
+        // Draw collapse indicators next to section headers.
+        self.draw_section_chevrons(canvas, point);
+
         // Draw cursor or selection carets while focused.
-        self.last_cursor_rect =
-            (self.keyboard_focused || self.ime_focused).then(|| self.draw_cursor(canvas, point));
+        if self.last_cursor_rect.is_some() {
+            self.draw_cursor(canvas, point);
+        }
+
+        // Draw pinned items on top of the scrolled text.
+        self.draw_pinned_section(canvas);
+    }
+
+    /// Draw the pinned items and their divider.
+    fn draw_pinned_section(&self, canvas: &SkiaCanvas) {
+        let paragraph = match &self.pinned_paragraph {
+            Some(paragraph) => paragraph,
+            None => return,
+        };
+        let point = self.pinned_point;
+
+        // Hide text scrolled underneath the pinned section.
+        let width = canvas.base_layer_size().width as f32;
+        let rect = Rect::new(0., 0., width, point.y + self.pinned_height);
+        canvas.draw_rect(rect, &Paint::new(self.background, None));
+
+        paragraph.paint(canvas, point);
+
+        // Separate pinned items from the remaining text.
+        let mut divider_paint = self.paint.clone();
+        let color = Color4f { a: PINNED_DIVIDER_ALPHA, ..divider_paint.color4f() };
+        divider_paint.set_color4f(color, None);
+        divider_paint.set_stroke_width(self.scale.round() as f32);
+        let spacing = (PINNED_DIVIDER_SPACING * self.scale) as f32;
+        let y = (point.y + paragraph.height() + spacing).round();
+        let start = Point::new(point.x, y);
+        let end = Point::new(point.x + self.size.width as f32, y);
+        canvas.draw_line(start, end, &divider_paint);
+    }
+
+    /// Calculate the region changed since the last layout.
+    ///
+    /// Returns `None` if the entire text box was damaged.
+    fn damage(&mut self, last_cursor_rect: Option<Rect>) -> Option<Rect> {
+        let render_state = RenderState {
+            preedit_cursor: self.preedit_cursor.clone(),
+            preedit_text: self.preedit_text.clone(),
+            selection: self.selection.clone(),
+            hidden_markup: self.hidden_markup.clone(),
+            pinned_text: self.pinned_text.clone(),
+            paragraph_height: self.last_paragraph_height,
+            scroll_offset: self.scroll.offset,
+            item_drag: self.item_drag.clone(),
+            scale: self.scale,
+            size: self.size,
+        };
+        let last_render_state = mem::replace(&mut self.last_render_state, render_state);
+        let full_damage =
+            mem::take(&mut self.full_damage) || last_render_state != self.last_render_state;
+
+        // Always update text damage, to keep the last rendered text in sync.
+        let text_damage = self.text_damage();
+        if full_damage {
+            return None;
+        }
+        let mut damage = text_damage?;
+
+        // Add the previous and current cursor position.
+        for rect in [last_cursor_rect, self.last_cursor_rect].into_iter().flatten() {
+            damage.join(rect.with_outset((1., 1.)));
+        }
+
+        Some(damage)
+    }
+
+    /// Calculate the region affected by text changes since the last layout.
+    ///
+    /// Returns `None` if the change could not be isolated to specific lines.
+    fn text_damage(&mut self) -> Option<Rect> {
+        if self.text == self.rendered_text {
+            return Some(Rect::new_empty());
+        }
+
+        // Any change can move blocks between columns.
+        if self.columns() > 1 {
+            self.rendered_text = self.text.clone();
+            return None;
+        }
+
+        // Find the range of modified bytes.
+        let (prefix, suffix) = self.rendered_text.common_affixes(&self.text);
+
+        // Extend damage to the start of the logical line, since changes can reflow it,
+        // and to the end of the following line, since its bullet point might change.
+        let start = self.text.line_range(prefix).start;
+        let end_line = self.text.line_at(self.text.len() - suffix);
+        let end = self.text.line_to_byte(end_line + 2);
+
+        self.rendered_text = self.text.clone();
+
+        // Get vertical bounds of all affected lines.
+        let paragraph = self.last_paragraph.as_ref()?;
+        let first_line = paragraph.get_line_number_at(start)?;
+        let last_line = paragraph.get_line_number_at(end.saturating_sub(1).max(start))?;
+        let first_metrics = paragraph.get_line_metrics_at(first_line)?;
+        let last_metrics = paragraph.get_line_metrics_at(last_line)?;
+        let top = self.text_point.y + (first_metrics.baseline - first_metrics.ascent) as f32;
+        let bottom = self.text_point.y + (last_metrics.baseline + last_metrics.descent) as f32;
+
+        // Include bullet points and cursor at the end of the line.
+        let left = self.text_point.x - self.bullet_padding();
+        let right = self.text_point.x + self.size.width as f32 + self.scale.round() as f32;
+
+        Some(Rect::new(left, top, right, bottom))
+    }
+
+    /// Get the bounding box of the input cursor or selection.
+    fn cursor_rect(&mut self, point: Point) -> Rect {
+        match self.selection {
+            Some(Range { start, end }) => {
+                let (start_points, line_height) = self.caret_points(point, start);
+                let (end_points, _) = self.caret_points(point, end);
+
+                // Use entire selection as IME cursor rectangle.
+                let start = start_points[2];
+                let end = end_points[2];
+                Rect::new(start.x, start.y, end.x, end.y + line_height).sorted()
+            },
+            None => {
+                // Place cursor inside the preedit text while composing.
+                let preedit_start = self.text.len();
+                let cursor = match &self.preedit_cursor {
+                    _ if self.preedit_text.is_empty() => self.cursor_index..self.cursor_index,
+                    Some(cursor) => preedit_start + cursor.start..preedit_start + cursor.end,
+                    None => {
+                        let preedit_end = preedit_start + self.preedit_text.len();
+                        preedit_end..preedit_end
+                    },
+                };
+
+                // Get metrics at cursor position.
+                let metrics = self.metrics_at(cursor.end);
+
+                let cursor_width = self.scale.round() as f32;
+                let (x, width) = if self.overwrite && self.preedit_text.is_empty() {
+                    // Cover the next glyph with a block cursor in overwrite mode.
+                    self.block_cursor_bounds(cursor.end, &metrics)
+                } else if !cursor.is_empty() {
+                    // Cover the active preedit segment, if it is on a single line.
+                    let start_metrics = self.metrics_at(cursor.start);
+                    if start_metrics.baseline == metrics.baseline && start_metrics.x < metrics.x {
+                        (start_metrics.x, metrics.x - start_metrics.x)
+                    } else {
+                        (metrics.x, cursor_width)
+                    }
+                } else {
+                    (metrics.x, cursor_width)
+                };
+
+                // Calculate cursor bounding box.
+                let x = point.x + x;
+                let y = point.y + metrics.baseline - metrics.ascent;
+                let height = (metrics.ascent + metrics.descent).round();
+
+                Rect::new(x, y, x + width, y + height)
+            },
+        }
     }
 
     /// Draw input or selection cursors.
-    fn draw_cursor(&mut self, canvas: &SkiaCanvas, point: Point) -> Rect {
+    fn draw_cursor(&mut self, canvas: &SkiaCanvas, point: Point) {
         match self.selection {
             Some(Range { start, end }) => {
                 // Get points required for drawing the triangles.
-                let (start_points, line_height) = self.caret_points(point, start);
-                let start_path = Path::polygon(&start_points, true, None, true);
+                let (start_points, _) = self.caret_points(point, start);
+                let start_path = SkiaPath::polygon(&start_points, true, None, true);
                 let (end_points, _) = self.caret_points(point, end);
-                let end_path = Path::polygon(&end_points, true, None, true);
+                let end_path = SkiaPath::polygon(&end_points, true, None, true);
 
                 // Draw the caret outlines.
                 self.selection_paint.set_stroke(true);
@@ -231,104 +737,471 @@ impl TextBox {
                 // Draw the center/background.
                 canvas.draw_path(&start_path, &self.selection_style.foreground());
                 canvas.draw_path(&end_path, &self.selection_style.foreground());
-
-                // Use entire selection as IME cursor rectangle.
-                let start = start_points[2];
-                let end = end_points[2];
-                Rect::new(start.x, start.y, end.x, end.y + line_height)
             },
             None => {
-                // Get metrics at cursor position.
-                let metrics = self.metrics_at(self.cursor_index);
+                let rect = match self.last_cursor_rect {
+                    Some(rect) => rect,
+                    None => return,
+                };
 
-                // Calculate cursor bounding box.
-                let x = point.x + metrics.x;
-                let y = point.y + metrics.baseline - metrics.ascent;
-                let width = self.scale.round() as f32;
-                let height = (metrics.ascent + metrics.descent).round();
+                // Keep the glyph below the block cursor readable.
+                if self.overwrite {
+                    let mut paint = self.paint.clone();
+                    let color = Color4f { a: OVERWRITE_CURSOR_ALPHA, ..paint.color4f() };
+                    paint.set_color4f(color, None);
+                    canvas.draw_rect(rect, &paint);
+                } else {
+                    canvas.draw_rect(rect, &self.paint);
+                }
+            },
+        }
+    }
 
-                // Render the cursor rectangle.
-                let rect = Rect::new(x, y, x + width, y + height);
-                canvas.draw_rect(rect, &self.paint);
+    /// Get the horizontal position and width of the overwrite block cursor.
+    fn block_cursor_bounds(&mut self, offset: usize, metrics: &GlyphMetrics) -> (f32, f32) {
+        let end = self.text.next_grapheme(offset);
+        let next_metrics = self.metrics_at(end);
 
-                rect
-            },
+        // Use a fixed width at the end of lines, where nothing is overwritten.
+        let at_line_end = end == offset || self.text.byte(offset) == Some(b'\n');
+        if at_line_end || next_metrics.baseline != metrics.baseline || next_metrics.x == metrics.x {
+            let width = ((metrics.ascent + metrics.descent) / 2.).round();
+            return (metrics.x, width);
         }
+
+        (metrics.x.min(next_metrics.x), (next_metrics.x - metrics.x).abs())
     }
 
     /// Draw list bullet points.
     fn draw_bullet_points(&mut self, canvas: &SkiaCanvas, origin: Point) {
+        let size = (self.layout.bullet_size * self.scale) as f32;
+        let bullet_padding = self.bullet_padding();
+        let shape = self.layout.bullet_shape;
+        let bullet_width = (shape.width(self.layout.bullet_size) * self.scale) as f32;
+        let paint = self.bullet_paint();
+
         match self.last_paragraph.as_ref() {
             Some(paragraph) => {
                 // Add bullet points in front of list elements.
-                let mut consecutive_newlines = 2;
-                for (i, c) in self.text.char_indices() {
-                    if c == '\n' {
-                        consecutive_newlines += 1;
-                        continue;
-                    } else if c.is_whitespace() {
-                        continue;
-                    }
+                let items: Vec<_> = self
+                    .list_items
+                    .iter()
+                    .filter(|item| {
+                        !self.code_blocks.iter().any(|code| code.contains(&item.range.start))
+                    })
+                    .collect();
+                let numbers = editor::item_numbers(items.iter().copied());
+                for (item, number) in items.iter().map(|item| &item.range).zip(numbers) {
+                    // Get metrics of the first character in the line.
+                    let metrics = match paragraph
+                        .get_line_number_at(item.start)
+                        .and_then(|line| paragraph.get_line_metrics_at(line))
+                    {
+                        Some(metrics) => metrics,
+                        // Skip items in unshaped blocks.
+                        None => continue,
+                    };
 
-                    // Draw bullet points after at least one empty line.
-                    if consecutive_newlines >= 2 {
-                        // Get metrics of the first character in the line.
-                        let line = paragraph.get_line_number_at(i).unwrap();
-                        let metrics = paragraph.get_line_metrics_at(line).unwrap();
-
-                        // Draw rectangle in the padding area.
-                        let size = BULLET_POINT_SIZE * self.scale as f32;
-                        let y = origin.y + metrics.baseline as f32 - metrics.ascent as f32 / 2.
-                            + metrics.descent as f32 / 2.
-                            - size / 2.;
-                        let x = origin.x - BULLET_POINT_PADDING * self.scale as f32;
-                        let rect = Rect::new(x, y, x + size, y + size);
-                        canvas.draw_rect(rect, &self.paint);
+                    // Draw numbers right-aligned with the bullet, on the line's baseline.
+                    let x = origin.x + metrics.left as f32 - bullet_padding;
+                    if self.list_style_at(item.start) == ListStyle::Numbers {
+                        let baseline = origin.y + metrics.baseline as f32;
+                        self.draw_item_number(canvas, number, x + bullet_width, baseline, &paint);
+                        continue;
                     }
 
-                    consecutive_newlines = 0;
+                    // Draw bullet in the padding area, vertically centered in the line.
+                    //
+                    // Since line height is distributed evenly using half-leading, this
+                    // also centers it on the glyphs.
+                    let y = origin.y + metrics.baseline as f32 - metrics.ascent as f32 / 2.
+                        + metrics.descent as f32 / 2.;
+                    skia::draw_bullet(canvas, shape, x, y, size, &paint);
                 }
             },
             None => {
                 // Handle bullet point drawing without any text.
-                let size = BULLET_POINT_SIZE * self.scale as f32;
-                let y = origin.y + self.last_paragraph_height / 2. - size / 2.;
-                let x = origin.x - BULLET_POINT_PADDING * self.scale as f32;
-                let rect = Rect::new(x, y, x + size, y + size);
-                canvas.draw_rect(rect, &self.paint);
+                let y = origin.y + self.last_paragraph_height / 2.;
+                let x = origin.x + self.empty_line_x() - bullet_padding;
+                if self.layout.list_style == ListStyle::Numbers {
+                    let metrics = self.fallback_metrics();
+                    let baseline = y - (metrics.ascent + metrics.descent) / 2.;
+                    self.draw_item_number(canvas, 1, x + bullet_width, baseline, &paint);
+                } else {
+                    skia::draw_bullet(canvas, shape, x, y, size, &paint);
+                }
             },
         }
     }
 
+    /// Draw a background behind all visible code blocks.
+    fn draw_code_backgrounds(&self, canvas: &SkiaCanvas, origin: Point) {
+        let mut paint = self.paint.clone();
+        paint.set_alpha_f(CODE_BACKGROUND_ALPHA);
+
+        for code in &self.code_blocks {
+            if let Some(rect) = self.code_block_rect(origin, code) {
+                canvas.draw_rect(rect, &paint);
+            }
+        }
+    }
+
+    /// Get the background rectangle of a code block.
+    ///
+    /// Returns `None` if the code block has not been shaped.
+    fn code_block_rect(&self, origin: Point, code: &Range<usize>) -> Option<Rect> {
+        let paragraph = self.last_paragraph.as_ref()?;
+        let first_line = paragraph.get_line_number_at(code.start)?;
+        let last_line = paragraph.get_line_number_at(code.end.saturating_sub(1).max(code.start))?;
+        let first_metrics = paragraph.get_line_metrics_at(first_line)?;
+        let last_metrics = paragraph.get_line_metrics_at(last_line)?;
+        let column_x = paragraph.line_column_x(first_line)?;
+
+        let padding = (CODE_BACKGROUND_PADDING * self.scale) as f32;
+        let top = origin.y + (first_metrics.baseline - first_metrics.ascent) as f32;
+        let bottom = origin.y + (last_metrics.baseline + last_metrics.descent) as f32;
+        let left = origin.x + column_x - padding;
+        let right = origin.x + column_x + self.column_width_px() + padding;
+
+        Some(Rect::new(left, top, right, bottom))
+    }
+
+    /// Draw a list item's number in front of it, ending at `right`.
+    fn draw_item_number(
+        &self,
+        canvas: &SkiaCanvas,
+        number: usize,
+        right: f32,
+        baseline: f32,
+        paint: &Paint,
+    ) {
+        let (font_collection, text_style) = (&self.font_collection, &self.text_style);
+        skia::draw_item_number(canvas, font_collection, text_style, number, right, baseline, paint);
+    }
+
+    /// Get the list style of the item starting at `start`.
+    ///
+    /// Sections can switch their items to the style not used by the note.
+    fn list_style_at(&self, start: usize) -> ListStyle {
+        let toggled = match self.toggled_list_styles.get(&self.storage_path) {
+            Some(toggled) => toggled,
+            None => return self.layout.list_style,
+        };
+
+        let in_toggled_section = self.sections.iter().any(|section| {
+            section.items.contains(&start)
+                && toggled.contains(self.text.slice(section.header.clone()).as_ref())
+        });
+
+        if in_toggled_section { self.layout.list_style.toggled() } else { self.layout.list_style }
+    }
+
+    /// Switch the list style of the innermost section containing the cursor.
+    ///
+    /// Returns `false` if the cursor is not within a section.
+    pub fn toggle_list_style(&mut self) -> bool {
+        // Sections are sorted by their header, so nested sections follow their parents.
+        let cursor_index = self.cursor_index;
+        let section = self.sections.iter().rev().find(|section| {
+            section.header.start <= cursor_index && cursor_index <= section.items.end
+        });
+        let header = match section {
+            Some(section) => self.text.as_str()[section.header.clone()].to_owned(),
+            None => return false,
+        };
+
+        let toggled = self.toggled_list_styles.entry(self.storage_path.clone()).or_default();
+        if !toggled.remove(&header) {
+            toggled.insert(header);
+        }
+        self.toggled_list_styles.retain(|_, toggled| !toggled.is_empty());
+
+        self.full_damage = true;
+        self.dirty = true;
+
+        true
+    }
+
+    /// Update the structure of the text, like its list items and spans.
+    ///
+    /// Only the blocks changed since the last update are parsed again, unless
+    /// the text's styling was modified.
+    fn update_text_analysis(&mut self) {
+        if self.full_damage {
+            self.analysis.invalidate();
+        }
+        if !self.analysis.update(&self.text, self.spellcheck.as_ref()) {
+            return;
+        }
+
+        // Update misspelled words.
+        self.misspellings = self.analysis.misspellings();
+
+        // Update list items and completed list items.
+        self.list_items = self.analysis.list_items();
+        (self.completed_items, self.completed_contents) = self.analysis.completed_items();
+
+        // Update spans with known colors.
+        self.color_spans = self.analysis.color_spans();
+        self.color_spans.retain(|span| self.accents.contains_key(&span.color));
+
+        // Update tags used in the text.
+        self.tags = TagIndex::from_tags(self.analysis.tags());
+
+        // Update code blocks rendered in a monospace font.
+        let fences = self.analysis.code_fences();
+        self.code_blocks = editor::item_code_blocks(&fences, &self.list_items, self.text.len());
+
+        // Update collapsible sections.
+        self.sections = editor::item_sections(&self.list_items);
+
+        // Update images referenced by the text.
+        self.thumbnails.update(self.analysis.image_lines());
+
+        self.text_direction = self.direction.text_direction(self.text.chunks());
+        self.text_blocks = self.analysis.text_blocks();
+    }
+
     /// Update the text paragraph layout.
     fn update_paragraph(&mut self) {
+        // Hide completion markers, except within the items being edited.
+        let (edit_start, edit_end) = match &self.selection {
+            Some(selection) => (selection.start, selection.end),
+            None => (self.cursor_index, self.cursor_index),
+        };
+        let marker_len = COMPLETED_MARKER.len();
+        self.hidden_markup = self
+            .completed_items
+            .iter()
+            .zip(&self.completed_contents)
+            .filter(|(item, _)| item.end < edit_start || item.start > edit_end)
+            .flat_map(|(_, content)| {
+                [content.start..content.start + marker_len, content.end - marker_len..content.end]
+            })
+            .collect();
+
+        // Hide markers of spans with known colors, except within the spans being edited.
+        self.hidden_markup.extend(
+            self.color_spans
+                .iter()
+                .filter(|span| {
+                    span.end_marker.end < edit_start || span.start_marker.start > edit_end
+                })
+                .flat_map(|span| [span.start_marker.clone(), span.end_marker.clone()]),
+        );
+        self.hidden_markup.sort_unstable_by_key(|range| range.start);
+
         // Get selection range, defaulting to an empty selection.
         let selection = match self.selection.as_ref() {
             Some(selection) => selection.start..selection.end,
             None => self.text.len()..usize::MAX,
         };
 
-        // Create paragraph builder with the default text style.
+        // Create paragraph style shared by all text blocks.
         let mut paragraph_style = ParagraphStyle::new();
         paragraph_style.set_text_style(&self.text_style);
-        let mut paragraph_builder = ParagraphBuilder::new(&paragraph_style, &self.font_collection);
+        paragraph_style.set_text_align(self.align.as_text_align());
+        paragraph_style.set_text_direction(self.text_direction);
+
+        // Reuse unchanged blocks, unless the text styling was modified.
+        let mut cache = match self.last_paragraph.take() {
+            Some(layout) if !self.full_damage => layout.into_cache(),
+            _ => BlockCache::default(),
+        };
+
+        // Offsets which always require shaping, to allow cursor positioning.
+        let mut cursor_offsets = vec![self.cursor_index];
+        cursor_offsets
+            .extend(self.selection.iter().flat_map(|selection| [selection.start, selection.end]));
+        cursor_offsets.extend(self.item_drag.as_ref().map(ItemDrag::item_start));
+
+        // Columns are balanced using all block sizes, so blocks are shaped
+        // regardless of their position.
+        let columns = self.columns();
+
+        let mut layout = TextLayout::default();
+        let blocks = self.text_blocks.clone();
+        let last_block = blocks.len() - 1;
+        for (i, block) in blocks.into_iter().enumerate() {
+            let is_last = i == last_block;
+
+            // Skip filtered items and collapsed sections, unless they are being edited.
+            let has_cursor =
+                cursor_offsets.iter().any(|offset| block.contains(offset) || block.end == *offset);
+            let hidden =
+                !self.matches_tag_filter(block.clone()) || self.is_collapsed(block.clone());
+            if !has_cursor && hidden {
+                layout.push_hidden(block);
+                continue;
+            }
+
+            let hash = self.block_hash(block.clone(), &selection, is_last);
+
+            // Get the block's position relative to the viewport.
+            let (size, exact) = match cache.size(hash) {
+                Some(size) => (size, true),
+                None => (self.estimate_block_size(block.clone()), false),
+            };
+            let viewport = self.viewport();
+            let top = layout.height();
+            let bottom = top + size.height;
+
+            // Skip shaping blocks far outside the viewport.
+            let margin = self.text_height();
+            let visible = top < viewport.end + margin && bottom > viewport.start - margin;
+            if !visible && !has_cursor && !is_last && columns == 1 {
+                layout.push_unshaped(block, hash, size, exact);
+                continue;
+            }
+
+            let paragraph = match cache.take_paragraph(hash) {
+                Some(paragraph) => paragraph,
+                None => self.build_block(&paragraph_style, block.clone(), &selection, is_last),
+            };
+
+            // Preedit text is always part of the last block.
+            let end = if is_last { self.text.len() + self.preedit_text.len() } else { block.end };
+            let indent = self.hanging_indent(&paragraph, block.clone());
+            let display = self.display_map(block.clone());
+            layout.push(paragraph, block.start..end, hash, !is_last, indent, display);
+
+            // Keep visible text in place when estimates above it are corrected.
+            if !exact && bottom <= viewport.start && columns == 1 {
+                self.scroll.offset -= layout.height() - bottom;
+            }
+        }
+
+        let gap = (COLUMN_GAP * self.scale) as f32;
+        layout.flow_columns(columns, self.column_width_px() + gap);
+
+        self.last_paragraph_height = layout.height();
+        self.last_paragraph = Some(layout);
+    }
+
+    /// Estimate the size of an unshaped, non-terminal text block.
+    fn estimate_block_size(&mut self, block: Range<usize>) -> BlockSize {
+        let metrics = self.fallback_metrics();
+        let line_height = (metrics.descent - metrics.ascent) * self.line_height as f32;
+
+        // Approximate line wrapping, assuming glyphs are half as wide as they are tall.
+        let line_length = (2. * self.column_width_px() / self.font_size()).max(1.) as usize;
+        let line_count = self
+            .text
+            .slice(block.clone())
+            .split('\n')
+            .map(|line| 1 + line.chars().count() / line_length)
+            .sum::<usize>();
+
+        // Exclude the empty line after the trailing newline, like shaped blocks.
+        let line_count = line_count.saturating_sub(1);
+        let spacing = (self.paragraph_spacing * self.scale) as f32;
+        let thumbnails: f32 = self
+            .thumbnails
+            .in_range(block)
+            .iter()
+            .map(|thumbnail| self.thumbnail_spacing(thumbnail))
+            .sum();
+
+        BlockSize { height: line_count as f32 * line_height + spacing + thumbnails, line_count }
+    }
+
+    /// Physical size of a thumbnail.
+    fn thumbnail_size(&self, thumbnail: &Thumbnail) -> (f32, f32) {
+        let max_height = (THUMBNAIL_HEIGHT * self.scale) as f32;
+        thumbnail.size(self.column_width_px(), max_height)
+    }
+
+    /// Physical space reserved below a line for its thumbnail.
+    fn thumbnail_spacing(&self, thumbnail: &Thumbnail) -> f32 {
+        let (_, height) = self.thumbnail_size(thumbnail);
+        height + (THUMBNAIL_PADDING * self.scale) as f32
+    }
+
+    /// Physical bounds of all visible thumbnails, relative to the text layout.
+    fn thumbnail_rects(&self) -> impl Iterator<Item = (&Thumbnail, Rect)> {
+        let paragraph = self.last_paragraph.as_ref();
+        let column_width = self.column_width_px();
+        self.thumbnails.iter().filter_map(move |thumbnail| {
+            let paragraph = paragraph?;
+            let line = paragraph.get_line_number_at(thumbnail.newline)?;
+            let metrics = paragraph.get_line_metrics_at(line)?;
+            let column_x = paragraph.line_column_x(line)?;
+
+            // Align the thumbnail like the text, at the bottom of its line.
+            let (width, height) = self.thumbnail_size(thumbnail);
+            let x = column_x
+                + match self.align {
+                    Align::Left => 0.,
+                    Align::Center => (column_width - width) / 2.,
+                    Align::Right => column_width - width,
+                };
+            let bottom = (metrics.baseline + metrics.descent) as f32;
+
+            Some((thumbnail, Rect::new(x, bottom - height, x + width, bottom)))
+        })
+    }
+
+    /// Get the thumbnail at the specified position.
+    fn thumbnail_at(&self, point: impl Into<Point>) -> Option<&Thumbnail> {
+        // Get position independent from current scroll offset.
+        let mut point = point.into();
+        point.y -= self.scroll.offset;
+
+        self.thumbnail_rects()
+            .find(|(_, rect)| rect.contains(point))
+            .map(|(thumbnail, _)| thumbnail)
+    }
+
+    /// Draw all image thumbnails below their lines.
+    fn draw_thumbnails(&self, canvas: &SkiaCanvas, point: Point) {
+        let sampling = SamplingOptions::new(FilterMode::Linear, MipmapMode::Linear);
+        let paint = Paint::default();
+        for (thumbnail, rect) in self.thumbnail_rects() {
+            let rect = rect.with_offset(point);
+            let image = &thumbnail.image;
+            canvas.draw_image_rect_with_sampling_options(image, None, rect, sampling, &paint);
+        }
+    }
+
+    /// Vertical range of the text layout which is currently visible.
+    fn viewport(&self) -> Range<f32> {
+        -self.scroll.offset..self.text_height() - self.scroll.offset
+    }
+
+    /// Shape a single text block.
+    fn build_block(
+        &mut self,
+        paragraph_style: &ParagraphStyle,
+        block: Range<usize>,
+        selection: &Range<usize>,
+        is_last: bool,
+    ) -> Paragraph {
+        let mut paragraph_builder = ParagraphBuilder::new(paragraph_style, &self.font_collection);
+
+        // Limit selection to the block.
+        let selection_start = selection.start.clamp(block.start, block.end);
+        let selection_end = selection.end.clamp(selection_start, block.end);
 
         // Draw text before the selection, or entire text without selection.
-        if selection.start > 0 {
-            paragraph_builder.add_text(&self.text[..selection.start]);
+        if selection_start > block.start {
+            let text_start = block.start..selection_start;
+            self.add_styled_text(&mut paragraph_builder, &self.text_style, text_start);
         }
 
         // Draw selection and text after it.
-        if selection.start < self.text.len() {
+        if selection_start < block.end {
             paragraph_builder.push_style(&self.selection_style);
-            paragraph_builder.add_text(&self.text[selection.start..selection.end]);
+            let selection_style = &self.selection_style;
+            let selection = selection_start..selection_end;
+            self.add_styled_text(&mut paragraph_builder, selection_style, selection);
 
             paragraph_builder.pop();
-            paragraph_builder.add_text(&self.text[selection.end..]);
+            let text_end = selection_end..block.end;
+            self.add_styled_text(&mut paragraph_builder, &self.text_style, text_end);
         }
 
         // Add preedit text with underline.
-        if !self.preedit_text.is_empty() {
+        if is_last && !self.preedit_text.is_empty() {
             // Create style with reduced text brightness and underline.
             let color = Color4f { a: 0.6, ..self.paint.color4f() };
             self.paint.set_color4f(color, None);
@@ -336,33 +1209,986 @@ impl TextBox {
             text_style.set_decoration_type(TextDecoration::UNDERLINE);
             text_style.set_foreground_paint(&self.paint);
 
-            // Add styled text to the paragraph.
+            // Add styled text to the paragraph, highlighting the active segment.
             paragraph_builder.push_style(&text_style);
-            paragraph_builder.add_text(&self.preedit_text);
+            match self.preedit_cursor.as_ref().filter(|cursor| !cursor.is_empty()) {
+                Some(cursor) => {
+                    paragraph_builder.add_text(&self.preedit_text[..cursor.start]);
+
+                    let mut highlight_style = text_style.clone();
+                    highlight_style.set_background_paint(&self.selection_style.background());
+                    paragraph_builder.push_style(&highlight_style);
+                    paragraph_builder.add_text(&self.preedit_text[cursor.start..cursor.end]);
+                    paragraph_builder.pop();
+
+                    paragraph_builder.add_text(&self.preedit_text[cursor.end..]);
+                },
+                None => {
+                    paragraph_builder.add_text(&self.preedit_text);
+                },
+            }
         }
 
-        // Build paragraph and calculate its height.
         let mut paragraph = paragraph_builder.build();
-        paragraph.layout(self.size.width as f32);
+        paragraph.layout(self.column_width_px());
 
-        self.last_paragraph_height = paragraph.height();
-        self.last_paragraph = Some(paragraph);
+        // Make room for aligning wrapped lines with the block's content.
+        let indent = self.hanging_indent(&paragraph, block);
+        if indent > 0. {
+            paragraph.layout(self.column_width_px() - indent);
+        }
+
+        paragraph
     }
 
-    /// Set the text box's physical size.
-    pub fn set_size(&mut self, size: Size) {
-        if self.size == size {
-            return;
+    /// Check if a text block contains the tag used for filtering items.
+    ///
+    /// All blocks match while no tag filter is active.
+    fn matches_tag_filter(&self, block: Range<usize>) -> bool {
+        match &self.tag_filter {
+            Some(tag) => {
+                self.tags.occurrences(tag).any(|occurrence| block.contains(&occurrence.start))
+            },
+            None => true,
         }
-        self.size = size;
+    }
 
-        // Ensure cursor is visible after resize.
-        self.focus_cursor = true;
+    /// Check if a text block is part of a collapsed section.
+    fn is_collapsed(&self, block: Range<usize>) -> bool {
+        let collapsed = match self.collapsed_sections.get(&self.storage_path) {
+            Some(collapsed) => collapsed,
+            None => return false,
+        };
 
-        self.dirty = true;
+        let start = block.end - self.text.slice(block).trim_start().len();
+        self.sections.iter().any(|section| {
+            section.items.contains(&start)
+                && collapsed.contains(&*self.text.slice(section.header.clone()))
+        })
     }
 
-    /// Set the text box's font scale.
+    /// Check if the section with the specified header is collapsed.
+    fn is_section_collapsed(&self, header: Range<usize>) -> bool {
+        let collapsed = self.collapsed_sections.get(&self.storage_path);
+        collapsed.is_some_and(|collapsed| collapsed.contains(&*self.text.slice(header)))
+    }
+
+    /// Collapse or expand the items of a section.
+    fn toggle_section(&mut self, section: Section) {
+        let header = self.text.as_str()[section.header.clone()].to_owned();
+        let collapsed = self.collapsed_sections.entry(self.storage_path.clone()).or_default();
+        if !collapsed.remove(&header) {
+            collapsed.insert(header);
+
+            // Move the cursor out of the collapsed items.
+            let selection = self.selection_range();
+            if selection.start <= section.items.end && selection.end >= section.items.start {
+                self.cursor_index = section.header.end;
+                self.clear_selection();
+            }
+        }
+        self.collapsed_sections.retain(|_, collapsed| !collapsed.is_empty());
+
+        self.text_input_dirty = true;
+        self.full_damage = true;
+        self.dirty = true;
+    }
+
+    /// Toggle the section whose chevron is at the specified position.
+    ///
+    /// The chevrons can be tapped anywhere in the padding left of the
+    /// header's bullet point. Returns `false` if there is no chevron at the
+    /// position.
+    pub fn toggle_section_at(&mut self, mut position: Position<f64>) -> bool {
+        // Get position independent from anchoring and current scroll offset.
+        position.y -= self.anchor_offset() as f64;
+        let (x, y) = (position.x as f32, position.y as f32 - self.scroll.offset);
+
+        let section = self
+            .section_chevrons()
+            .find(|(_, chevron, line)| x < chevron.right && line.contains(&y))
+            .map(|(section, ..)| section.clone());
+
+        match section {
+            Some(section) => {
+                self.toggle_section(section);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Physical bounds of all visible section chevrons and their header's
+    /// first line, relative to the text layout.
+    fn section_chevrons(&self) -> impl Iterator<Item = (&Section, Rect, Range<f32>)> {
+        let paragraph = self.last_paragraph.as_ref();
+        let size = (CHEVRON_SIZE * self.scale) as f32;
+        let spacing = (CHEVRON_SPACING * self.scale) as f32;
+        let bullet_padding = self.bullet_padding();
+        self.sections.iter().filter_map(move |section| {
+            let paragraph = paragraph?;
+            let line = paragraph.get_line_number_at(section.header.start)?;
+            let metrics = paragraph.get_line_metrics_at(line)?;
+
+            // Center the chevron on the glyphs, like bullet points.
+            let center_y = (metrics.baseline - metrics.ascent / 2. + metrics.descent / 2.) as f32;
+            let right = metrics.left as f32 - bullet_padding - spacing;
+            let chevron =
+                Rect::new(right - size, center_y - size / 2., right, center_y + size / 2.);
+
+            let top = (metrics.baseline - metrics.ascent) as f32;
+            let bottom = (metrics.baseline + metrics.descent) as f32;
+
+            Some((section, chevron, top..bottom))
+        })
+    }
+
+    /// Draw chevrons indicating the state of collapsible sections.
+    fn draw_section_chevrons(&self, canvas: &SkiaCanvas, origin: Point) {
+        let mut paint = self.bullet_paint();
+        paint.set_stroke(true);
+        paint.set_stroke_width((self.stroke_size() / 2.).max(1.));
+        paint.set_anti_alias(true);
+
+        for (section, chevron, _) in self.section_chevrons() {
+            let chevron = chevron.with_offset(origin);
+            let points = if self.is_section_collapsed(section.header.clone()) {
+                // Point right, towards the hidden items.
+                let x = chevron.left + chevron.width() / 4.;
+                [
+                    Point::new(x, chevron.top),
+                    Point::new(x + chevron.width() / 2., chevron.center_y()),
+                    Point::new(x, chevron.bottom),
+                ]
+            } else {
+                // Point down, towards the visible items.
+                let y = chevron.top + chevron.height() / 4.;
+                [
+                    Point::new(chevron.left, y),
+                    Point::new(chevron.center_x(), y + chevron.height() / 2.),
+                    Point::new(chevron.right, y),
+                ]
+            };
+            canvas.draw_path(&SkiaPath::polygon(&points, false, None, None), &paint);
+        }
+    }
+
+    /// Get the names of all configured accent colors.
+    pub fn accent_names(&self) -> Vec<String> {
+        self.accents.keys().cloned().collect()
+    }
+
+    /// Replace the natural-language date at the cursor with an absolute date.
+    ///
+    /// The change can be reverted using undo. Returns `false` if there is no
+    /// date at the cursor.
+    pub fn expand_date(&mut self) -> bool {
+        let text = self.text.as_str();
+        let today = Local::now().date_naive();
+        let (range, date) = match editor::relative_date_at(text, self.cursor_index, today) {
+            Some(date) => date,
+            None => return false,
+        };
+
+        let mut formatted = String::new();
+        if write!(formatted, "{}", date.format(&self.date_format)).is_err() {
+            warn!("Invalid date format: {:?}", self.date_format);
+            return true;
+        }
+
+        let mut expanded = text.to_owned();
+        expanded.replace_range(range.clone(), &formatted);
+
+        self.backups.stash(text.to_owned());
+        self.replace_text(expanded);
+        self.persist_text();
+
+        self.cursor_index = range.start + formatted.len();
+        self.selection = None;
+
+        true
+    }
+
+    /// Render the selection in an accent color, or remove its color with `None`.
+    ///
+    /// Without selection, this applies to the content of the list item at the
+    /// cursor. Color spans overlapping the text are merged into the new span.
+    ///
+    /// The change can be reverted using undo.
+    pub fn set_span_color(&mut self, color: Option<&str>) {
+        let text = self.text.as_str();
+        let range = match &self.selection {
+            Some(selection) => selection.clone(),
+            None => {
+                let cursor_index = self.cursor_index;
+                let item = match list_items(text)
+                    .into_iter()
+                    .find(|item| item.start <= cursor_index && cursor_index <= item.end)
+                {
+                    Some(item) => item,
+                    None => return,
+                };
+
+                // Keep completion markers around the colored text.
+                let content = item_content(text, item);
+                if is_completed(&text[content.clone()]) {
+                    let marker_len = COMPLETED_MARKER.len();
+                    content.start + marker_len..content.end - marker_len
+                } else {
+                    content
+                }
+            },
+        };
+
+        // Extend the range to all overlapping spans, removing their markers.
+        let overlapping: Vec<_> = editor::color_spans(text)
+            .into_iter()
+            .filter(|span| span.start_marker.start < range.end && span.end_marker.end > range.start)
+            .collect();
+        let start = overlapping.first().map_or(range.start, |span| span.start_marker.start);
+        let end = overlapping.last().map_or(range.end, |span| span.end_marker.end);
+        let start = cmp::min(start, range.start);
+        let end = cmp::max(end, range.end);
+
+        let mut content = String::with_capacity(end - start);
+        let mut content_start = start;
+        for span in &overlapping {
+            for marker in [&span.start_marker, &span.end_marker] {
+                content.push_str(&text[content_start..marker.start]);
+                content_start = marker.end;
+            }
+        }
+        content.push_str(&text[content_start..end]);
+
+        // Wrap the content with the new color's markers.
+        let mut colored = text[..start].to_owned();
+        if let Some(color) = color {
+            colored.push('{');
+            colored.push_str(color);
+            colored.push('}');
+        }
+        colored.push_str(&content);
+        if color.is_some() {
+            colored.push_str(COLOR_END_MARKER);
+        }
+        let cursor_index = colored.len();
+        colored.push_str(&text[end..]);
+
+        if colored == text {
+            return;
+        }
+
+        self.backups.stash(text.to_owned());
+        self.replace_text(colored);
+        self.persist_text();
+
+        self.cursor_index = cursor_index;
+        self.selection = None;
+    }
+
+    /// Get the mapping from a text block's offsets to its rendered text.
+    fn display_map(&self, block: Range<usize>) -> DisplayMap {
+        let hidden = self
+            .hidden_markup
+            .iter()
+            .filter(|range| range.start >= block.start && range.end <= block.end)
+            .map(|range| range.start - block.start..range.end - block.start)
+            .collect();
+        DisplayMap::new(hidden)
+    }
+
+    /// Get the indent of a text block's lines after the first one.
+    ///
+    /// This aligns continuation lines with the start of the block's content,
+    /// past markup like pin and completion markers.
+    fn hanging_indent(&self, paragraph: &Paragraph, block: Range<usize>) -> f32 {
+        if self.align != Align::Left || self.text_direction == TextDirection::RTL {
+            return 0.;
+        }
+
+        let prefix_len = content_prefix_len(&self.text.slice(block.clone()));
+        let prefix_len = self.display_map(block).to_display(prefix_len);
+        if prefix_len == 0 {
+            return 0.;
+        }
+
+        let rects = paragraph.get_rects_for_range(
+            0..prefix_len,
+            RectHeightStyle::Tight,
+            RectWidthStyle::Tight,
+        );
+        let width = rects.iter().map(|text_box| text_box.rect.right).fold(0., f32::max);
+
+        // Ignore excessive prefixes, which would leave no room for the text.
+        if width < self.column_width_px() / 2. { width } else { 0. }
+    }
+
+    /// Number of text columns fitting into the text box.
+    fn columns(&self) -> usize {
+        let column_width = match self.column_width {
+            Some(column_width) => column_width as f64 * self.scale,
+            None => return 1,
+        };
+        let gap = COLUMN_GAP * self.scale;
+
+        ((self.size.width as f64 + gap) / (column_width + gap)).floor().max(1.) as usize
+    }
+
+    /// Physical width of a single text column.
+    fn column_width_px(&self) -> f32 {
+        let columns = self.columns();
+        let gaps = (columns - 1) as f64 * COLUMN_GAP * self.scale;
+        ((self.size.width as f64 - gaps) / columns as f64).max(0.) as f32
+    }
+
+    /// Hash all content affecting the shaping of a text block.
+    fn block_hash(&self, block: Range<usize>, selection: &Range<usize>, is_last: bool) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for chunk in self.text.chunks_in(block.clone()) {
+            hasher.write(chunk.as_bytes());
+        }
+        self.column_width_px().to_bits().hash(&mut hasher);
+        self.scale.to_bits().hash(&mut hasher);
+        (self.text_direction == TextDirection::RTL).hash(&mut hasher);
+        is_last.hash(&mut hasher);
+
+        // Hash styled ranges relative to the block's start.
+        let relative_range = |range: &Range<usize>| {
+            let start = range.start.clamp(block.start, block.end);
+            let end = range.end.clamp(start, block.end);
+            start - block.start..end - block.start
+        };
+        relative_range(selection).hash(&mut hasher);
+        for thumbnail in self.thumbnails.in_range(block.clone()) {
+            (thumbnail.newline - block.start).hash(&mut hasher);
+            thumbnail.image.unique_id().hash(&mut hasher);
+        }
+        for ranges in
+            [&self.misspellings, &self.completed_items, &self.hidden_markup, &self.code_blocks]
+        {
+            let ranges: Vec<_> = ranges
+                .iter()
+                .filter(|range| range.start < block.end && range.end > block.start)
+                .map(relative_range)
+                .collect();
+            ranges.hash(&mut hasher);
+        }
+
+        if is_last {
+            self.preedit_text.hash(&mut hasher);
+            self.preedit_cursor.hash(&mut hasher);
+        }
+
+        hasher.finish()
+    }
+
+    /// Update the layout of the pinned section.
+    fn update_pinned_paragraph(&mut self) {
+        // Collect the content of all pinned list items.
+        self.pinned_items.clear();
+        self.pinned_text.clear();
+        for ListItem { range: item, .. } in &self.list_items {
+            let item_text = self.text.slice(item.clone());
+            let content = match item_text.strip_prefix(PIN_MARKER) {
+                Some(content) => content.trim_start(),
+                None => continue,
+            };
+
+            if !self.pinned_text.is_empty() {
+                self.pinned_text.push('\n');
+            }
+            let start = self.pinned_text.len();
+            self.pinned_text.push_str(content);
+
+            let text_start = item.end - content.len();
+            self.pinned_items.push(PinnedItem { range: start..self.pinned_text.len(), text_start });
+        }
+
+        if self.pinned_text.is_empty() {
+            self.pinned_paragraph = None;
+            self.pinned_height = 0.;
+            return;
+        }
+
+        let mut paragraph_style = ParagraphStyle::new();
+        paragraph_style.set_text_style(&self.text_style);
+        paragraph_style.set_text_align(self.align.as_text_align());
+        paragraph_style
+            .set_text_direction(self.direction.text_direction([self.pinned_text.as_str()]));
+        let mut paragraph_builder = ParagraphBuilder::new(&paragraph_style, &self.font_collection);
+        paragraph_builder.add_text(&self.pinned_text);
+
+        let mut paragraph = paragraph_builder.build();
+        paragraph.layout(self.size.width as f32);
+
+        let spacing = (PINNED_DIVIDER_SPACING * self.scale) as f32;
+        self.pinned_height = paragraph.height() + 2. * spacing;
+        self.pinned_paragraph = Some(paragraph);
+    }
+
+    /// Get the text offset of the pinned item at the specified position.
+    fn pinned_offset_at(&self, point: impl Into<Point>) -> Option<usize> {
+        let point = point.into();
+        if point.y >= self.pinned_height {
+            return None;
+        }
+
+        // Jump to the start of items when tapping the divider.
+        let paragraph = self.pinned_paragraph.as_ref()?;
+        let index = match paragraph.get_closest_glyph_cluster_at(point) {
+            Some(cluster) if point.y <= paragraph.height() => cluster.text_range.start,
+            _ => self.pinned_items.last()?.range.start,
+        };
+
+        let item = self.pinned_items.iter().find(|item| index <= item.range.end)?;
+        Some(item.text_start + index.saturating_sub(item.range.start))
+    }
+
+    /// Toggle the pinned state of the list item containing `offset`.
+    fn toggle_pin(&mut self, offset: usize) {
+        let item = match list_items(self.text.as_str())
+            .into_iter()
+            .find(|item| item.start <= offset && offset <= item.end)
+        {
+            Some(item) => item,
+            None => return,
+        };
+
+        let mut text = self.text.as_str().to_owned();
+        if text[item.start..].starts_with(PIN_MARKER) {
+            text.remove(item.start);
+        } else {
+            text.insert(item.start, PIN_MARKER);
+        }
+        self.replace_text(text);
+        self.persist_text();
+    }
+
+    /// Toggle the completion state of the list item containing `offset`.
+    fn toggle_completion(&mut self, offset: usize) {
+        let item = match list_items(self.text.as_str())
+            .into_iter()
+            .find(|item| item.start <= offset && offset <= item.end)
+        {
+            Some(item) => item,
+            None => return,
+        };
+
+        let mut text = self.text.as_str().to_owned();
+        let content = item_content(&text, item);
+        if is_completed(&text[content.clone()]) {
+            text.replace_range(content.end - COMPLETED_MARKER.len()..content.end, "");
+            text.replace_range(content.start..content.start + COMPLETED_MARKER.len(), "");
+        } else {
+            text.insert_str(content.end, COMPLETED_MARKER);
+            text.insert_str(content.start, COMPLETED_MARKER);
+        }
+        self.replace_text(text);
+        self.persist_text();
+    }
+
+    /// Move the list item containing `offset` past its previous or next item.
+    ///
+    /// The change can be reverted using undo.
+    fn move_item(&mut self, offset: usize, up: bool) {
+        let items = list_items(self.text.as_str());
+        let index = match items.iter().position(|item| item.start <= offset && offset <= item.end) {
+            Some(index) => index,
+            None => return,
+        };
+
+        // Get the two items swapping places, in text order.
+        let (first, second) = match (up, index) {
+            (true, 0) => return,
+            (true, _) => (items[index - 1].clone(), items[index].clone()),
+            (false, _) if index + 1 >= items.len() => return,
+            (false, _) => (items[index].clone(), items[index + 1].clone()),
+        };
+
+        let text = self.text.as_str();
+        let mut swapped = String::with_capacity(text.len());
+        swapped.push_str(&text[..first.start]);
+        swapped.push_str(&text[second.clone()]);
+        swapped.push_str(&text[first.end..second.start]);
+        swapped.push_str(&text[first.clone()]);
+        swapped.push_str(&text[second.end..]);
+
+        // Keep the cursor at the same position within the moved item.
+        let cursor_index = if up {
+            first.start + (offset - second.start)
+        } else {
+            offset + (second.end - first.end)
+        };
+
+        self.backups.stash(text.to_owned());
+        self.replace_text(swapped);
+        self.persist_text();
+
+        self.cursor_index = cursor_index;
+        self.selection = None;
+    }
+
+    /// Start moving the list item touched at the start of the touch sequence.
+    ///
+    /// Returns `false` if there is no list item which can be moved.
+    fn start_item_drag(&mut self, touch_y: f32) -> bool {
+        let layout = match &self.last_paragraph {
+            Some(layout) if self.columns() == 1 && self.touch_state.pinned_offset.is_none() => {
+                layout
+            },
+            _ => return false,
+        };
+
+        let items = list_items(self.text.as_str());
+        let visible: Vec<_> = items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| Some((i, layout.block_bounds(item.start)?)))
+            .collect();
+
+        // Find the touched item, requiring at least one other item to move past.
+        let offset = self.touch_state.start_offset;
+        let index = match visible.iter().position(|(i, _)| {
+            let item = &items[*i];
+            item.start <= offset && offset <= item.end
+        }) {
+            Some(index) if visible.len() > 1 => index,
+            _ => return false,
+        };
+
+        self.item_drag = Some(ItemDrag {
+            shifts: vec![0.; visible.len()],
+            start_y: touch_y - self.scroll.offset,
+            target: index,
+            offset: 0.,
+            touch_y,
+            visible,
+            index,
+            items,
+        });
+        self.dirty = true;
+
+        true
+    }
+
+    /// Move the dragged list item to its new position.
+    ///
+    /// The change can be reverted using undo.
+    fn finish_item_drag(&mut self) {
+        let item_drag = match self.item_drag.take() {
+            Some(item_drag) => item_drag,
+            None => return,
+        };
+        self.dirty = true;
+
+        // Ignore drops in place, or when the text was changed during the drag.
+        let text = self.text.as_str();
+        if item_drag.target == item_drag.index || list_items(text) != item_drag.items {
+            return;
+        }
+
+        let from = item_drag.visible[item_drag.index].0;
+        let (moved, start) =
+            editor::move_list_item(text, &item_drag.items, from, item_drag.item_target());
+
+        // Keep the cursor at the same position within the moved item.
+        let item = &item_drag.items[from];
+        let cursor_index = (item.start <= self.cursor_index && self.cursor_index <= item.end)
+            .then(|| start + (self.cursor_index - item.start));
+
+        self.backups.stash(text.to_owned());
+        self.replace_text(moved);
+        self.persist_text();
+
+        if let Some(cursor_index) = cursor_index {
+            self.cursor_index = cursor_index;
+            self.selection = None;
+        }
+    }
+
+    /// Move the layout blocks of list items which are being reordered.
+    fn shift_dragged_items(&mut self) {
+        let (item_drag, layout) = match (&mut self.item_drag, &mut self.last_paragraph) {
+            (Some(item_drag), Some(layout)) => (item_drag, layout),
+            _ => return,
+        };
+
+        item_drag.update(self.scroll.offset);
+        for (i, (item, _)) in item_drag.visible.iter().enumerate() {
+            let shift = if i == item_drag.index { item_drag.offset } else { item_drag.shifts[i] };
+            layout.shift_block(item_drag.items[*item].start, shift);
+        }
+    }
+
+    /// Scroll velocity while dragging list items close to the viewport's edges.
+    fn item_drag_autoscroll(&self) -> f32 {
+        let touch_y = match &self.item_drag {
+            Some(item_drag) => item_drag.touch_y,
+            None => return 0.,
+        };
+
+        // Scroll faster the closer the touch is to the edge.
+        let edge = (AUTOSCROLL_EDGE * self.scale) as f32;
+        let velocity = (AUTOSCROLL_VELOCITY * self.scale) as f32;
+        let bottom_distance = self.text_height() - touch_y;
+        if touch_y < edge {
+            velocity * (edge - touch_y).min(edge) / edge
+        } else if bottom_distance < edge {
+            -velocity * (edge - bottom_distance).min(edge) / edge
+        } else {
+            0.
+        }
+    }
+
+    /// Draw the dragged list item floating above the other items.
+    fn draw_dragged_item(&self, canvas: &SkiaCanvas, point: Point) {
+        let (item_drag, layout) = match (&self.item_drag, &self.last_paragraph) {
+            (Some(item_drag), Some(layout)) => (item_drag, layout),
+            _ => return,
+        };
+
+        let start = item_drag.item_start();
+        let bounds = match layout.block_bounds(start) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        // Hide the items underneath, including the bullet point padding.
+        let left = point.x - self.bullet_padding();
+        let right = point.x + self.size.width as f32;
+        let rect = Rect::new(left, point.y + bounds.start, right, point.y + bounds.end);
+        canvas.draw_rect(rect, &Paint::new(self.background, None));
+        let mut highlight_paint = self.tag_paint.clone();
+        highlight_paint.set_alpha_f(DRAGGED_ITEM_ALPHA);
+        canvas.draw_rect(rect, &highlight_paint);
+
+        layout.paint_at(canvas, point, start);
+    }
+
+    /// Insert a copy of the list item containing `offset` after it.
+    ///
+    /// The change can be reverted using undo.
+    fn duplicate_item(&mut self, offset: usize) {
+        let item = match list_items(self.text.as_str())
+            .into_iter()
+            .find(|item| item.start <= offset && offset <= item.end)
+        {
+            Some(item) => item,
+            None => return,
+        };
+
+        let text = self.text.as_str();
+        let mut duplicated = text.to_owned();
+        let copy = format!("\n\n{}", &text[item.clone()]);
+        duplicated.insert_str(item.end, &copy);
+
+        self.backups.stash(text.to_owned());
+        self.replace_text(duplicated);
+        self.persist_text();
+
+        // Move the cursor into the new copy.
+        self.cursor_index = offset + copy.len();
+        self.selection = None;
+    }
+
+    /// Move all completed list items to the archive note.
+    ///
+    /// Archived items are suffixed with the current date.
+    pub fn archive_completed(&mut self) {
+        let text = self.text.as_str();
+        let items: Vec<_> = list_items(text)
+            .into_iter()
+            .filter(|item| is_completed(&text[item_content(text, item.clone())]))
+            .collect();
+
+        let archive_path = self.storage_path.with_file_name(ARCHIVE_NOTE);
+        if items.is_empty() || archive_path == self.storage_path {
+            return;
+        }
+
+        let mut archive = match self.storage.read(&archive_path) {
+            Ok(archive) => archive.map(|archive| archive.trim_end().to_owned()).unwrap_or_default(),
+            Err(err) => {
+                error!("Failed to read archive at {archive_path:?}: {err}");
+                return;
+            },
+        };
+
+        // Append items to the archive, separated by empty lines.
+        let date = Local::now().format(ARCHIVE_DATE_FORMAT);
+        for item in &items {
+            if !archive.is_empty() {
+                archive.push_str("\n\n");
+            }
+            let _ = write!(archive, "{} ({date})", &text[item.clone()]);
+        }
+
+        if let Err(err) = self.storage.write(&archive_path, &mut iter::once(archive.as_str())) {
+            error!("Failed to write archive at {archive_path:?}: {err}");
+            return;
+        }
+
+        // Remove archived items together with their separating whitespace.
+        let mut text = text.to_owned();
+        for item in items.iter().rev() {
+            let whitespace = text[item.end..].len() - text[item.end..].trim_start().len();
+            let end = item.end + whitespace;
+            let start =
+                if end == text.len() { text[..item.start].trim_end().len() } else { item.start };
+            text.replace_range(start..end, "");
+        }
+
+        info!("Archived {} completed items to {archive_path:?}", items.len());
+
+        self.replace_text(text);
+        self.persist_text();
+    }
+
+    /// Drop all cached text layouts until the next redraw.
+    pub fn release_layout(&mut self) {
+        self.last_paragraph = None;
+        self.pinned_paragraph = None;
+        self.full_damage = true;
+        self.dirty = true;
+    }
+
+    /// Set the text box's physical size.
+    pub fn set_size(&mut self, size: Size) {
+        if self.size == size {
+            return;
+        }
+        self.size = size;
+
+        // Ensure cursor is visible after resize.
+        self.focus_cursor = true;
+
+        self.dirty = true;
+    }
+
+    /// Add text to a paragraph, striking through completed list items.
+    ///
+    /// Spellchecking is skipped for completed items.
+    fn add_styled_text(
+        &self,
+        builder: &mut ParagraphBuilder,
+        style: &TextStyle,
+        range: Range<usize>,
+    ) {
+        let mut completed_style = style.clone();
+        let mut completed_paint = style.foreground();
+        let color = Color4f { a: COMPLETED_ALPHA, ..completed_paint.color4f() };
+        completed_paint.set_color4f(color, None);
+        completed_style.set_foreground_paint(&completed_paint);
+        completed_style.set_decoration_type(TextDecoration::LINE_THROUGH);
+        completed_style.set_decoration_color(color.to_color());
+
+        let mut start = range.start;
+        for completed in &self.completed_items {
+            let completed_start = cmp::max(completed.start, range.start);
+            let completed_end = cmp::min(completed.end, range.end);
+            if completed_start >= completed_end {
+                continue;
+            }
+
+            self.add_code_text(builder, style, start..completed_start);
+
+            builder.push_style(&completed_style);
+            self.add_spaced_text(builder, &completed_style, completed_start..completed_end);
+            builder.pop();
+
+            start = completed_end;
+        }
+        self.add_code_text(builder, style, start..range.end);
+    }
+
+    /// Add text to a paragraph, rendering code blocks in the monospace font.
+    ///
+    /// Code is added verbatim, without highlighting misspellings, tags or colors.
+    fn add_code_text(
+        &self,
+        builder: &mut ParagraphBuilder,
+        style: &TextStyle,
+        range: Range<usize>,
+    ) {
+        let mut code_style = style.clone();
+        code_style.set_font_families(&self.monospace_family);
+
+        let mut start = range.start;
+        for code in &self.code_blocks {
+            let code_start = cmp::max(code.start, range.start);
+            let code_end = cmp::min(code.end, range.end);
+            if code_start >= code_end {
+                continue;
+            }
+
+            self.add_checked_text(builder, style, start..code_start);
+
+            builder.push_style(&code_style);
+            self.add_spaced_text(builder, &code_style, code_start..code_end);
+            builder.pop();
+
+            start = code_end;
+        }
+        self.add_checked_text(builder, style, start..range.end);
+    }
+
+    /// Add text to a paragraph, underlining misspelled words.
+    fn add_checked_text(
+        &self,
+        builder: &mut ParagraphBuilder,
+        style: &TextStyle,
+        range: Range<usize>,
+    ) {
+        let mut misspelled_style = style.clone();
+        misspelled_style.set_decoration_type(TextDecoration::UNDERLINE);
+        misspelled_style.set_decoration_style(TextDecorationStyle::Wavy);
+        misspelled_style.set_decoration_color(MISSPELLING_COLOR.to_color());
+
+        let mut start = range.start;
+        for misspelling in &self.misspellings {
+            let misspelling_start = cmp::max(misspelling.start, range.start);
+            let misspelling_end = cmp::min(misspelling.end, range.end);
+            if misspelling_start >= misspelling_end {
+                continue;
+            }
+
+            self.add_colored_text(builder, style, start..misspelling_start);
+
+            builder.push_style(&misspelled_style);
+            self.add_colored_text(builder, &misspelled_style, misspelling_start..misspelling_end);
+            builder.pop();
+
+            start = misspelling_end;
+        }
+        self.add_colored_text(builder, style, start..range.end);
+    }
+
+    /// Add text to a paragraph, rendering color spans in their accent color.
+    fn add_colored_text(
+        &self,
+        builder: &mut ParagraphBuilder,
+        style: &TextStyle,
+        range: Range<usize>,
+    ) {
+        let mut start = range.start;
+        for span in &self.color_spans {
+            let content = span.content();
+            let span_start = cmp::max(content.start, range.start);
+            let span_end = cmp::min(content.end, range.end);
+            if span_start >= span_end {
+                continue;
+            }
+            let color = match self.accents.get(&span.color) {
+                Some(color) => color.as_color4f(),
+                None => continue,
+            };
+
+            self.add_tagged_text(builder, style, start..span_start);
+
+            let mut span_style = style.clone();
+            let mut span_paint = style.foreground();
+            span_paint.set_color4f(Color4f { a: span_paint.alpha_f(), ..color }, None);
+            span_style.set_foreground_paint(&span_paint);
+
+            builder.push_style(&span_style);
+            self.add_tagged_text(builder, &span_style, span_start..span_end);
+            builder.pop();
+
+            start = span_end;
+        }
+        self.add_tagged_text(builder, style, start..range.end);
+    }
+
+    /// Add text to a paragraph, highlighting tags with the accent color.
+    fn add_tagged_text(
+        &self,
+        builder: &mut ParagraphBuilder,
+        style: &TextStyle,
+        range: Range<usize>,
+    ) {
+        let mut tag_style = style.clone();
+        let mut tag_paint = self.tag_paint.clone();
+        let color = Color4f { a: style.foreground().alpha_f(), ..tag_paint.color4f() };
+        tag_paint.set_color4f(color, None);
+        tag_style.set_foreground_paint(&tag_paint);
+
+        let mut start = range.start;
+        for tag in self.tags.ranges() {
+            let tag_start = cmp::max(tag.start, range.start);
+            let tag_end = cmp::min(tag.end, range.end);
+            if tag_start >= tag_end {
+                continue;
+            }
+
+            self.add_spaced_text(builder, style, start..tag_start);
+
+            builder.push_style(&tag_style);
+            self.add_spaced_text(builder, &tag_style, tag_start..tag_end);
+            builder.pop();
+
+            start = tag_end;
+        }
+        self.add_spaced_text(builder, style, start..range.end);
+    }
+
+    /// Add text to a paragraph, with extra spacing between list items.
+    ///
+    /// List items are separated by empty lines, so the spacing is applied by
+    /// increasing the height of the newlines terminating empty lines. Space
+    /// for thumbnails is similarly reserved by shifting the newline below the
+    /// referencing line's text.
+    fn add_spaced_text(
+        &self,
+        builder: &mut ParagraphBuilder,
+        style: &TextStyle,
+        range: Range<usize>,
+    ) {
+        let text = self.text.slice(range.clone());
+
+        let mut start = range.start;
+        for (i, _) in text.match_indices('\n') {
+            let index = range.start + i;
+            let mut newline_style = style.clone();
+            if index > 0 && self.text.byte(index - 1) == Some(b'\n') {
+                if self.paragraph_spacing == 0. {
+                    continue;
+                }
+
+                let spacing = self.paragraph_spacing / self.font_size;
+                newline_style.set_height((self.line_height + spacing) as f32);
+            } else if let Some(thumbnail) = self.thumbnails.get(index) {
+                newline_style.set_baseline_shift(self.thumbnail_spacing(thumbnail));
+            } else {
+                continue;
+            }
+
+            self.add_visible_text(builder, start..index);
+            builder.push_style(&newline_style);
+            builder.add_text("\n");
+            builder.pop();
+
+            start = index + 1;
+        }
+        self.add_visible_text(builder, start..range.end);
+    }
+
+    /// Add text to a paragraph, omitting hidden markup.
+    fn add_visible_text(&self, builder: &mut ParagraphBuilder, range: Range<usize>) {
+        let mut start = range.start;
+        for hidden in &self.hidden_markup {
+            let hidden_start = hidden.start.clamp(start, range.end);
+            let hidden_end = hidden.end.clamp(hidden_start, range.end);
+            if hidden_start >= hidden_end {
+                continue;
+            }
+
+            builder.add_text(&*self.text.slice(start..hidden_start));
+            start = hidden_end;
+        }
+        builder.add_text(&*self.text.slice(start..range.end));
+    }
+
+    /// Set the text box's font scale.
     pub fn set_scale_factor(&mut self, scale: f64) {
         if self.scale == scale {
             return;
@@ -376,52 +2202,276 @@ impl TextBox {
         self.fallback_metrics = None;
     }
 
+    /// Set the text zoom factor, applied on top of the configured font size.
+    pub fn set_zoom(&mut self, zoom: f64) {
+        let zoom = zoom.clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end());
+        if self.zoom == zoom {
+            return;
+        }
+        self.zoom = zoom;
+        self.fallback_metrics = None;
+        self.full_damage = true;
+        self.dirty = true;
+
+        self.selection_style.set_font_size(self.font_size());
+        self.text_style.set_font_size(self.font_size());
+    }
+
+    /// Get the text zoom factor.
+    pub fn zoom(&self) -> f64 {
+        self.zoom
+    }
+
+    /// Set the desktop's text scaling factor.
+    ///
+    /// Unlike the surface scale factor, this only affects the note's text.
+    pub fn set_text_scale(&mut self, text_scale: f64) {
+        if self.text_scale == text_scale {
+            return;
+        }
+        self.text_scale = text_scale;
+        self.fallback_metrics = None;
+        self.full_damage = true;
+        self.dirty = true;
+
+        self.selection_style.set_font_size(self.font_size());
+        self.text_style.set_font_size(self.font_size());
+    }
+
     /// Set keyboard focus state.
     pub fn set_keyboard_focus(&mut self, focused: bool) {
+        self.full_damage |= self.keyboard_focused != focused;
         self.dirty |= self.keyboard_focused != focused;
         self.keyboard_focused = focused;
+
+        // Always start typing in insert mode after regaining focus.
+        if !focused {
+            self.dirty |= mem::take(&mut self.overwrite);
+        }
+    }
+
+    /// Check whether overwrite mode is active.
+    pub fn overwrite(&self) -> bool {
+        self.overwrite
     }
 
     /// Set IME focus state.
     pub fn set_ime_focus(&mut self, focused: bool) {
+        self.full_damage |= self.ime_focused != focused;
         self.dirty |= self.ime_focused != focused;
         self.ime_focused = focused;
     }
 
     /// Handle config updates.
     pub fn update_config(&mut self, config: &Config) {
+        self.auto_timestamp = config.general.auto_timestamp;
+        self.timestamp_format.clone_from(&config.general.timestamp_format);
+        self.date_format.clone_from(&config.general.date_format);
+        self.editing = config.editing;
+        self.normalize_paste = config.input.normalize_paste;
+        self.paste_lines_as_items = config.input.paste_lines_as_items;
+        self.save_debounce = Duration::from_millis(config.general.save_debounce_ms);
+        self.save_max_delay = Duration::from_millis(config.general.save_max_delay_ms);
+        self.storage.update_config(config);
+        self.hooks.update_config(config);
+        self.journal.set_fsync(config.general.fsync);
+        if self.save_mode != config.general.save_mode {
+            self.save_mode = config.general.save_mode;
+
+            // Apply the new mode to pending changes.
+            if self.unsaved {
+                self.persist_text();
+            }
+        }
+
+        // Update text layout options.
+        if self.anchor != config.general.anchor
+            || self.align != config.general.align
+            || self.direction != config.general.direction
+            || self.column_width != config.general.column_width
+        {
+            self.anchor = config.general.anchor;
+            self.align = config.general.align;
+            self.direction = config.general.direction;
+            self.column_width = config.general.column_width;
+            self.full_damage = true;
+            self.dirty = true;
+        }
+
+        // Redraw bullet points with the new layout.
+        if self.layout != config.layout {
+            self.layout = config.layout;
+            self.full_damage = true;
+            self.dirty = true;
+        }
+
+        // Reload spellchecker when its dictionary changed.
+        let dictionary =
+            config.spellcheck.enabled.then_some(config.spellcheck.dictionary.as_path());
+        if dictionary != self.spellcheck.as_ref().map(Spellcheck::dictionary_path) {
+            self.spellcheck = dictionary.and_then(Spellcheck::new);
+            self.full_damage = true;
+            self.dirty = true;
+        }
+
         // Check if any text field parameters changed.
         if self.font_size == config.font.size
             && self.font_family == config.font.family
-            && self.paint.color4f() == config.colors.foreground.as_color4f()
+            && self.monospace_family == config.font.monospace_family
+            && self.line_height == config.font.line_height
+            && self.paragraph_spacing == config.font.paragraph_spacing
+            && self.font_directory == config.font.directory
+            && self.font_style == config.font.skia_style()
+            && self.font_features == config.font.features
         {
             return;
         }
+        if self.font_directory != config.font.directory {
+            self.font_collection = skia::font_collection(config.font.directory.as_deref());
+            self.font_directory = config.font.directory.clone();
+        }
         self.font_family = config.font.family.clone();
+        self.monospace_family = config.font.monospace_family.clone();
         self.font_size = config.font.size;
+        self.line_height = config.font.line_height;
+        self.paragraph_spacing = config.font.paragraph_spacing;
+        self.font_style = config.font.skia_style();
+        self.font_features = config.font.features.clone();
         self.fallback_metrics = None;
+        self.full_damage = true;
         self.dirty = true;
 
         // Update font options.
 
-        self.paint.set_color4f(config.colors.foreground.as_color4f(), None);
-        self.text_style.set_foreground_paint(&self.paint);
         self.text_style.set_font_size(self.font_size());
-        self.text_style.set_font_families(&[&self.font_family]);
+        self.text_style.set_font_families(&self.font_family);
+        self.text_style.set_height(self.line_height as f32);
+        set_font_options(&mut self.text_style, self.font_style, &self.font_features);
+
+        self.selection_style.set_font_size(self.font_size());
+        self.selection_style.set_font_families(&self.font_family);
+        self.selection_style.set_height(self.line_height as f32);
+        set_font_options(&mut self.selection_style, self.font_style, &self.font_features);
+    }
 
-        self.selection_paint.set_color4f(config.colors.background.as_color4f(), None);
+    /// Update the text box's color palette.
+    pub fn set_colors(&mut self, colors: &Colors) {
+        let (selection_foreground, selection_background) = colors.selection();
+        if self.paint.color4f() == colors.foreground.as_color4f()
+            && self.background == colors.background.color().as_color4f()
+            && self.selection_style.background().color4f() == selection_background.as_color4f()
+            && self.selection_style.foreground().color4f() == selection_foreground.as_color4f()
+            && self.tag_paint.color4f() == colors.highlight.as_color4f()
+            && self.accents == colors.accents
+        {
+            return;
+        }
+        self.full_damage = true;
+        self.dirty = true;
+
+        warn_selection_contrast(colors);
+
+        self.paint.set_color4f(colors.foreground.as_color4f(), None);
+        self.text_style.set_foreground_paint(&self.paint);
+        self.background = colors.background.color().as_color4f();
+        self.tag_paint.set_color4f(colors.highlight.as_color4f(), None);
+        self.accents = colors.accents.clone();
+
+        self.selection_paint.set_color4f(selection_foreground.as_color4f(), None);
         self.selection_style.set_foreground_paint(&self.selection_paint);
-        self.selection_paint.set_color4f(config.colors.highlight.as_color4f(), None);
+        self.selection_paint.set_color4f(selection_background.as_color4f(), None);
         self.selection_style.set_background_paint(&self.selection_paint);
-        self.selection_style.set_font_size(self.font_size());
-        self.selection_style.set_font_families(&[&self.font_family]);
+    }
+
+    /// Get a summary of the text statistics.
+    ///
+    /// Statistics of the full text are only counted again for changed blocks,
+    /// while selection statistics are cached until the selection changes.
+    pub fn stats(&mut self) -> String {
+        self.update_text_analysis();
+        let stats = self.analysis.stats();
+
+        let Some(selection) = self.selection.clone() else {
+            return stats.to_string();
+        };
+
+        let revision = self.text.revision();
+        let selection_stats = match &self.selection_stats {
+            Some((range, stats_revision, stats))
+                if *range == selection && *stats_revision == revision =>
+            {
+                *stats
+            },
+            _ => {
+                let Some(text) = self.selection_text() else {
+                    return stats.to_string();
+                };
+                let selection_stats = TextStats::new(&text);
+                self.selection_stats = Some((selection, revision, selection_stats));
+                selection_stats
+            },
+        };
+
+        format!(
+            "{stats} ({} chars, {} words selected)",
+            selection_stats.chars, selection_stats.words
+        )
+    }
+
+    /// Show only items containing `tag`, or all items with `None`.
+    ///
+    /// The filter only affects rendering, the stored text is unchanged.
+    /// Returns `false` if the tag is not used in the note.
+    pub fn set_tag_filter(&mut self, tag: Option<&str>) -> bool {
+        let tag = tag.map(editor::normalize_tag);
+        if tag == self.tag_filter {
+            return true;
+        }
+
+        // Ensure the tag is known, before the text is laid out again.
+        self.update_text_analysis();
+        if let Some(tag) = &tag {
+            let first_end = match self.tags.occurrences(tag).next() {
+                Some(occurrence) => occurrence.end,
+                None => return false,
+            };
+
+            // Move the cursor to the first matching item, unless it's already in one.
+            let cursor_index = self.cursor_index;
+            let item = self.list_items.iter().find(|item| item.range.contains(&cursor_index));
+            let in_match = item.is_some_and(|item| {
+                self.tags.occurrences(tag).any(|occurrence| item.range.contains(&occurrence.start))
+            });
+            if !in_match {
+                self.cursor_index = first_end;
+                self.clear_selection();
+            }
+        }
+        self.tag_filter = tag;
+
+        self.focus_cursor = true;
+        self.text_input_dirty = true;
+        self.full_damage = true;
+        self.dirty = true;
+
+        true
+    }
+
+    /// Get the tag used for filtering items.
+    pub fn tag_filter(&self) -> Option<&str> {
+        self.tag_filter.as_deref()
+    }
+
+    /// Get the note's storage path.
+    pub fn storage_path(&self) -> &Path {
+        &self.storage_path
     }
 
     /// Replace the entire text box content.
     pub fn set_text(&mut self, text: String) {
         self.cursor_index = text.len();
         self.focus_cursor = true;
-        self.text = text;
+        self.text = text.into();
 
         self.clear_selection();
 
@@ -429,31 +2479,200 @@ impl TextBox {
         self.dirty = true;
     }
 
+    /// Switch to a different note, moving the cursor to the start of `line`.
+    pub fn open_note(&mut self, path: PathBuf, line: usize) {
+        if path != self.storage_path {
+            // Write pending changes of the current note.
+            self.flush_text();
+
+            info!("Opening note {path:?}");
+
+            self.hooks.run(HookKind::PreLoad, &path);
+            let text = Self::read_note(&*self.storage, &path).unwrap_or_default();
+            self.disk_hash = content_hash([text.as_str()]);
+            self.backups = Backups::default();
+            self.storage_path = path;
+            self.monitor_symlink_target();
+            self.set_text(text);
+            self.journal.reset(&self.text);
+            self.tag_filter = None;
+            self.full_damage = true;
+        }
+
+        self.cursor_index = self.text.line_to_byte(line);
+        self.focus_cursor = true;
+        self.clear_selection();
+
+        self.text_input_dirty = true;
+        self.dirty = true;
+    }
+
+    /// Get the current text content.
+    pub fn text(&self) -> &str {
+        self.text.as_str()
+    }
+
+    /// Replace the text with externally modified content.
+    ///
+    /// The previous text is stashed, allowing the change to be undone.
+    pub fn apply_external_change(&mut self, text: String) {
+        self.disk_hash = content_hash([text.as_str()]);
+        let previous = self.replace_text(text);
+        self.journal.reset(&self.text);
+        self.backups.push(&*self.storage, previous.into_string());
+    }
+
+    /// Replace the text with unsaved changes recovered after a crash.
+    ///
+    /// The previous text is stashed, allowing the recovery to be undone. The
+    /// recovered text is saved immediately.
+    pub fn restore_recovered(&mut self, text: String) {
+        let previous = self.replace_text(text);
+        self.backups.push(&*self.storage, previous.into_string());
+        self.persist_text();
+        self.flush_text();
+    }
+
+    /// Restore the text replaced by the last external change or item edit.
+    ///
+    /// Returns `false` if there is no change to undo.
+    pub fn undo(&mut self) -> bool {
+        let text = match self.backups.pop() {
+            Some(text) => text,
+            None => return false,
+        };
+
+        info!("Restoring replaced notes");
+        self.replace_text(text);
+        self.persist_text();
+
+        true
+    }
+
+    /// Replace the text while preserving the editing position.
+    ///
+    /// The cursor, selection and scroll position are mapped through the changed
+    /// region, so edits elsewhere in the text do not disturb them.
+    ///
+    /// Returns the replaced text.
+    fn replace_text(&mut self, text: String) -> Text {
+        let change = TextChange::new(self.text.as_str(), &text);
+
+        // Remember the line at the top of the viewport, to restore it after layout.
+        let top = -self.scroll.offset;
+        self.scroll_anchor = self.offset_at_point(0., top).and_then(|offset| {
+            let delta = self.line_top(offset)? - top;
+            Some((change.map(offset), delta))
+        });
+
+        self.cursor_index = change.map(self.cursor_index);
+        self.selection = self
+            .selection
+            .take()
+            .map(|selection| change.map_range(selection))
+            .filter(|selection| !selection.is_empty());
+        let previous = mem::replace(&mut self.text, text.into());
+
+        self.text_input_dirty = true;
+        self.dirty = true;
+
+        previous
+    }
+
     /// Handle new key press.
-    pub fn press_key(&mut self, keysym: Keysym, modifiers: Modifiers) {
-        // Ignore input with logo/alt key held.
+    ///
+    /// The `text` is the key's UTF-8 representation, including the results of
+    /// compose sequences.
+    pub fn press_key(&mut self, keysym: Keysym, text: Option<&str>, modifiers: Modifiers) {
+        // Ignore input with logo/alt key held, except for list item shortcuts.
         if modifiers.logo || modifiers.alt {
+            match (keysym, modifiers.logo, modifiers.shift && modifiers.ctrl) {
+                (Keysym::Up, false, false) => self.move_item(self.cursor_index, true),
+                (Keysym::Down, false, false) => self.move_item(self.cursor_index, false),
+                (Keysym::V, false, true) => self.request_paste(true),
+                _ => return,
+            }
+            self.focus_cursor = true;
             return;
         }
 
         // Ensure cursor is visible after keyboard input.
         self.focus_cursor = true;
 
-        match (keysym, modifiers.shift, modifiers.ctrl) {
-            (Keysym::Left, false, false) => {
-                self.cursor_index = match self.selection.take() {
-                    Some(selection) => selection.start,
-                    None => self.cursor_index.saturating_sub(1),
+        match (keysym, modifiers.shift, modifiers.ctrl) {
+            (Keysym::Left, false, false) => {
+                self.cursor_index = match self.selection.take() {
+                    Some(selection) => selection.start,
+                    None => self.text.previous_grapheme(self.cursor_index),
+                };
+
+                self.text_input_dirty = true;
+                self.dirty = true;
+            },
+            (Keysym::Right, false, false) => {
+                self.cursor_index = match self.selection.take() {
+                    Some(selection) => selection.end,
+                    None => self.text.next_grapheme(self.cursor_index),
+                };
+
+                self.text_input_dirty = true;
+                self.dirty = true;
+            },
+            (Keysym::Up, false, false) | (Keysym::Down, false, false) => {
+                // Move to the adjacent visual line, which might be in another column.
+                let x = self.metrics_at(self.cursor_index).x;
+                let up = keysym == Keysym::Up;
+                self.cursor_index = match editor::vertical_motion(self, self.cursor_index, x, up) {
+                    Some(offset) => offset,
+                    None => return,
+                };
+                self.selection = None;
+
+                self.text_input_dirty = true;
+                self.dirty = true;
+            },
+            (Keysym::Home, false, false) | (Keysym::End, false, false) => {
+                self.cursor_index = match (self.line_bounds(self.cursor_index), keysym) {
+                    (Some(line), Keysym::Home) => line.start,
+                    (Some(line), _) => line.end,
+                    (None, _) => return,
                 };
+                self.selection = None;
 
                 self.text_input_dirty = true;
                 self.dirty = true;
             },
-            (Keysym::Right, false, false) => {
-                self.cursor_index = match self.selection.take() {
-                    Some(selection) => selection.end,
-                    None => cmp::min(self.cursor_index + 1, self.text.len()),
+            (Keysym::Home, false, true) => {
+                self.cursor_index = 0;
+                self.selection = None;
+
+                self.text_input_dirty = true;
+                self.dirty = true;
+            },
+            (Keysym::End, false, true) => {
+                self.cursor_index = self.text.len();
+                self.selection = None;
+
+                self.text_input_dirty = true;
+                self.dirty = true;
+            },
+            (Keysym::Page_Up, false, false) | (Keysym::Page_Down, false, false) => {
+                let page_height = match keysym {
+                    Keysym::Page_Up => -self.text_height(),
+                    _ => self.text_height(),
+                };
+
+                // Move the cursor by one page, keeping its horizontal position.
+                let metrics = self.metrics_at(self.cursor_index);
+                let y = metrics.baseline - metrics.ascent / 2. + page_height;
+                let point = Point::new(metrics.x, y + self.scroll.offset);
+                self.cursor_index = match self.offset_at(point) {
+                    Some(offset) if y < 0. => offset.min(self.cursor_index),
+                    Some(offset) => offset,
+                    None => return,
                 };
+                self.scroll.offset -= page_height;
+                self.selection = None;
 
                 self.text_input_dirty = true;
                 self.dirty = true;
@@ -467,20 +2686,12 @@ impl TextBox {
                     Some(selection) => self.delete_selected(selection),
                     None if self.cursor_index == 0 => return,
                     None => {
-                        if self.text.is_empty() || self.cursor_index == 0 {
-                            return;
-                        }
-
-                        // Jump to the previous character.
-                        self.cursor_index = self.cursor_index.saturating_sub(1);
-                        while self.cursor_index > 0
-                            && !self.text.is_char_boundary(self.cursor_index)
-                        {
-                            self.cursor_index -= 1;
-                        }
-
-                        // Pop the character after the cursor.
-                        self.text.remove(self.cursor_index);
+                        // Remove the grapheme before the cursor, with its auto-closed pair.
+                        let end = editor::empty_pair_end(&self.text, self.cursor_index)
+                            .filter(|_| self.editing.auto_pair)
+                            .unwrap_or(self.cursor_index);
+                        self.cursor_index = self.text.previous_grapheme(self.cursor_index);
+                        self.text.remove(self.cursor_index..end);
                         self.persist_text();
                     },
                 }
@@ -492,9 +2703,10 @@ impl TextBox {
                 match self.selection.take() {
                     Some(selection) => self.delete_selected(selection),
                     None if self.cursor_index >= self.text.len() => return,
-                    // Pop character after the cursor.
+                    // Remove the grapheme after the cursor.
                     None => {
-                        self.text.remove(self.cursor_index);
+                        let end = self.text.next_grapheme(self.cursor_index);
+                        self.text.remove(self.cursor_index..end);
                         self.persist_text();
                     },
                 }
@@ -502,62 +2714,44 @@ impl TextBox {
                 self.text_input_dirty = true;
                 self.dirty = true;
             },
-            (Keysym::Return, false, false) => {
-                self.text.insert(self.cursor_index, '\n');
-                self.persist_text();
-                self.cursor_index += 1;
+            (Keysym::Return | Keysym::KP_Enter, false, false) => {
+                self.insert_newline();
 
                 self.text_input_dirty = true;
                 self.dirty = true;
             },
+            (Keysym::Insert, false, false) => {
+                self.overwrite = !self.overwrite;
+                self.dirty = true;
+            },
+            (Keysym::Up, true, true) => self.move_item(self.cursor_index, true),
+            (Keysym::Down, true, true) => self.move_item(self.cursor_index, false),
+            (Keysym::D, true, true) => self.duplicate_item(self.cursor_index),
+            (Keysym::N, true, true) => _ = self.toggle_list_style(),
+            (Keysym::e, false, true) => _ = self.expand_date(),
+            (Keysym::d, false, true) => self.toggle_completion(self.cursor_index),
+            (Keysym::A, true, true) => self.archive_completed(),
+            (Keysym::semicolon, false, true) => {
+                if let Some(timestamp) = self.timestamp() {
+                    self.insert_text(&timestamp);
+                }
+            },
+            (Keysym::Undo, ..) | (Keysym::z, false, true) => {
+                self.undo();
+            },
             (Keysym::XF86_Copy, ..) | (Keysym::C, true, true) => {
-                // Get selected text.
-                let text = match self.selection_text() {
-                    Some(text) => text.to_owned(),
+                let content = match self.clipboard_content() {
+                    Some(content) => content,
                     None => return,
                 };
 
-                self.event_loop.insert_idle(move |state| {
-                    let serial = state.clipboard.next_serial();
-                    let copy_paste_source = state
-                        .protocol_states
-                        .data_device_manager
-                        .create_copy_paste_source(&state.window.queue, ["text/plain"]);
-                    copy_paste_source.set_selection(&state.protocol_states.data_device, serial);
-                    state.clipboard.source = Some(copy_paste_source);
-                    state.clipboard.text = text;
-                });
-            },
-            (Keysym::XF86_Paste, ..) | (Keysym::V, true, true) => {
-                self.event_loop.insert_idle(|state| {
-                    // Get available Wayland text selection.
-                    let selection_offer =
-                        match state.protocol_states.data_device.data().selection_offer() {
-                            Some(selection_offer) => selection_offer,
-                            None => return,
-                        };
-                    let mut pipe = match selection_offer.receive("text/plain".into()) {
-                        Ok(pipe) => pipe,
-                        Err(err) => {
-                            warn!("Clipboard paste failed: {err}");
-                            return;
-                        },
-                    };
-
-                    // Read text from pipe.
-                    let mut text = String::new();
-                    if let Err(err) = pipe.read_to_string(&mut text) {
-                        error!("Failed to read from clipboard pipe: {err}");
-                        return;
-                    }
-
-                    // Paste text into text box.
-                    state.window.paste(&text);
-                });
+                let content = content.into_iter().map(|(mime, text)| (mime, text.into_bytes()));
+                self.copy(content.collect());
             },
-            (keysym, _, false) => {
-                let key_char = match keysym.key_char() {
-                    Some(key_char) => key_char,
+            (Keysym::XF86_Paste, ..) | (Keysym::V, true, true) => self.request_paste(false),
+            (_, _, false) => {
+                let key_text = match text {
+                    Some(key_text) => key_text,
                     None => return,
                 };
 
@@ -565,13 +2759,19 @@ impl TextBox {
                 if let Some(selection) = self.selection.take() {
                     self.delete_selected(selection);
                 }
+                self.delete_overwritten(key_text);
 
-                // Add text at cursor position.
-                self.text.insert(self.cursor_index, key_char);
+                // Add text at cursor position, with timestamp for new list items.
+                let mut text = self.new_item_prefix(key_text);
+                if text.is_empty() && self.insert_transformed(key_text) {
+                    return;
+                }
+                text.push_str(key_text);
+                self.text.insert(self.cursor_index, &text);
                 self.persist_text();
 
-                // Move cursor behind inserted character.
-                self.cursor_index += key_char.len_utf8();
+                // Move cursor behind inserted text.
+                self.cursor_index += text.len();
 
                 self.text_input_dirty = true;
                 self.dirty = true;
@@ -580,26 +2780,39 @@ impl TextBox {
         }
     }
 
+    /// Insert a newline at the cursor position, following list semantics.
+    fn insert_newline(&mut self) {
+        self.cursor_index = editor::insert_newline(&mut self.text, self.cursor_index);
+        self.persist_text();
+    }
+
     /// Handle touch press events.
     pub fn touch_down(&mut self, config: &Config, time: u32, mut position: Position<f64>) {
-        // Adjust for text box being anchored to the bottom.
-        position.y -= (self.size.height as f64 - self.last_paragraph_height as f64).max(0.);
+        let pinned_offset = self.pinned_offset_at(position);
+
+        // Adjust for text box anchoring.
+        position.y -= self.anchor_offset() as f64;
 
         let offset = self.offset_at(position).unwrap_or(0);
         self.touch_state.down(config, time, position, offset);
+        self.touch_state.pinned_offset = pinned_offset;
+
+        // Stop kinetic scrolling.
+        self.scroll.velocity = 0.;
     }
 
-    /// Handle touch release.
-    pub fn touch_motion(&mut self, config: &Config, mut position: Position<f64>) {
-        // Adjust for text box being anchored to the bottom.
-        position.y -= (self.size.height as f64 - self.last_paragraph_height as f64).max(0.);
+    /// Handle touch motion.
+    pub fn touch_motion(&mut self, config: &Config, time: u32, mut position: Position<f64>) {
+        // Adjust for text box anchoring.
+        position.y -= self.anchor_offset() as f64;
 
-        let delta = self.touch_state.motion(config, position, self.selection.as_ref());
+        let selection = self.selection.as_ref();
+        let delta = self.touch_state.motion(config, time, position, selection);
 
         // Handle touch drag actions.
         match self.touch_state.action {
             TouchAction::Drag => {
-                self.scroll_offset += delta.y as f32;
+                self.scroll.offset += delta.y as f32;
 
                 self.text_input_dirty = true;
                 self.dirty = true;
@@ -632,13 +2845,64 @@ impl TextBox {
                 self.text_input_dirty = true;
                 self.dirty = true;
             },
+            TouchAction::Reorder => {
+                // Fall back to scrolling if there is no item which can be moved.
+                if self.item_drag.is_none() && !self.start_item_drag(position.y as f32) {
+                    self.touch_state.action = TouchAction::Drag;
+                    return;
+                }
+
+                if let Some(item_drag) = &mut self.item_drag {
+                    item_drag.touch_y = position.y as f32;
+                }
+                self.dirty = true;
+            },
             // Ignore touch motion for tap actions.
             _ => (),
         }
     }
 
+    /// Cancel the active touch sequence, without triggering its release action.
+    pub fn touch_cancel(&mut self) {
+        self.touch_state.action = TouchAction::Drag;
+        self.touch_state.velocity = 0.;
+
+        // Drop dragged items back into place.
+        if self.item_drag.take().is_some() {
+            self.dirty = true;
+        }
+    }
+
+    /// Scroll the text by a physical distance.
+    pub fn scroll_by(&mut self, delta: f32) {
+        if delta == 0. {
+            return;
+        }
+
+        self.scroll.offset += delta;
+        self.scroll.velocity = 0.;
+
+        self.text_input_dirty = true;
+        self.dirty = true;
+    }
+
     /// Handle touch release.
-    pub fn touch_up(&mut self) {
+    pub fn touch_up(&mut self, config: &Config, time: u32) {
+        self.touch_state.up(config, time);
+
+        // Continue scrolling after flinging the text.
+        if self.touch_state.action == TouchAction::Drag
+            && self.touch_state.velocity.abs() >= MIN_SCROLL_VELOCITY as f64
+        {
+            self.scroll.velocity = self.touch_state.velocity as f32;
+        }
+
+        // Move dragged list items to their new position.
+        if self.touch_state.action == TouchAction::Reorder {
+            self.finish_item_drag();
+            return;
+        }
+
         // Ignore release handling for drag/focus actions.
         if matches!(
             self.touch_state.action,
@@ -653,9 +2917,22 @@ impl TextBox {
         // Handle tap actions.
         match self.touch_state.action {
             TouchAction::Tap => {
-                self.cursor_index = self.offset_at(position).unwrap_or(0);
+                // Open tapped thumbnails instead of moving the cursor.
+                if self.touch_state.pinned_offset.is_none()
+                    && let Some(thumbnail) = self.thumbnail_at(position)
+                {
+                    thumbnail.open();
+                    return;
+                }
+
+                self.cursor_index = match self.touch_state.pinned_offset {
+                    Some(offset) => offset,
+                    None => self.offset_at(position).unwrap_or(0),
+                };
                 self.focus_cursor = true;
 
+                self.tapped_misspelling = self.misspelling_at_offset(self.cursor_index);
+
                 self.clear_selection();
 
                 self.text_input_dirty = true;
@@ -664,47 +2941,233 @@ impl TextBox {
             // Select word at touch position.
             TouchAction::DoubleTap => {
                 let offset = self.offset_at(position).unwrap_or(0);
-
-                let mut word_start = 0;
-                let mut word_end = self.text.len();
-                for (i, c) in self.text.char_indices() {
-                    let c_end = i + c.len_utf8();
-                    if c_end < offset && !c.is_alphanumeric() {
-                        word_start = c_end;
-                    } else if i > offset && !c.is_alphanumeric() {
-                        word_end = i;
-                        break;
-                    }
-                }
-
-                self.select(word_start..word_end);
+                self.select(editor::word_at(self.text.as_str(), offset));
             },
             // Select everything.
             TouchAction::TripleTap => {
                 let offset = self.offset_at(position).unwrap_or(0);
-                let start = self.text[..offset].rfind('\n').map_or(0, |i| i + 1);
-                let end = self.text[offset..].find('\n').map_or(self.text.len(), |i| offset + i);
-                self.select(start..end);
+                self.select(self.text.line_range(offset));
+            },
+            // Pin or unpin the touched list item.
+            TouchAction::LongPress => {
+                let offset = match self.touch_state.pinned_offset {
+                    Some(offset) => offset,
+                    None => self.offset_at(position).unwrap_or(0),
+                };
+                self.toggle_pin(offset);
             },
-            TouchAction::Drag | TouchAction::DragSelectionStart | TouchAction::DragSelectionEnd => {
-                unreachable!()
+            // Complete or restore the swiped list item.
+            TouchAction::Swipe => {
+                let offset =
+                    self.touch_state.pinned_offset.unwrap_or(self.touch_state.start_offset);
+                self.toggle_completion(offset);
             },
+            TouchAction::Drag
+            | TouchAction::DragSelectionStart
+            | TouchAction::DragSelectionEnd
+            | TouchAction::Reorder => unreachable!(),
+        }
+    }
+
+    /// Take the misspelled word selected by the last tap.
+    pub fn take_tapped_misspelling(&mut self) -> Option<Range<usize>> {
+        self.tapped_misspelling.take()
+    }
+
+    /// Get the misspelled word at the specified position.
+    pub fn misspelling_at(&self, mut position: Position<f64>) -> Option<Range<usize>> {
+        // Adjust for text box anchoring.
+        position.y -= self.anchor_offset() as f64;
+
+        let offset = self.offset_at(position)?;
+        self.misspelling_at_offset(offset)
+    }
+
+    /// Get the misspelled word containing the cursor.
+    pub fn misspelling_at_cursor(&self) -> Option<Range<usize>> {
+        self.misspelling_at_offset(self.cursor_index)
+    }
+
+    /// Get the misspelled word containing a byte offset.
+    fn misspelling_at_offset(&self, offset: usize) -> Option<Range<usize>> {
+        self.misspellings.iter().find(|range| range.start <= offset && offset <= range.end).cloned()
+    }
+
+    /// Get the text in the specified range.
+    pub fn text_at(&self, range: Range<usize>) -> Option<Cow<'_, str>> {
+        self.text.get(range)
+    }
+
+    /// Get spelling suggestions for a word.
+    pub fn spelling_suggestions(&self, word: &str) -> Vec<String> {
+        self.spellcheck.as_ref().map_or_else(Vec::new, |spellcheck| spellcheck.suggestions(word))
+    }
+
+    /// Add a word to the personal dictionary.
+    pub fn add_to_dictionary(&mut self, word: &str) {
+        if let Some(spellcheck) = &mut self.spellcheck {
+            spellcheck.add_word(word);
+            self.full_damage = true;
+            self.dirty = true;
+        }
+    }
+
+    /// Replace a word in the text.
+    ///
+    /// This is ignored if the text in `range` no longer matches `word`.
+    pub fn replace_word(&mut self, range: Range<usize>, word: &str, replacement: &str) {
+        if self.text.get(range.clone()).as_deref() != Some(word) {
+            return;
+        }
+
+        self.text.replace(range.clone(), replacement);
+        self.persist_text();
+
+        self.cursor_index = range.start + replacement.len();
+        self.selection = None;
+
+        self.text_input_dirty = true;
+        self.dirty = true;
+    }
+
+    /// Get the physical bounds of a text range.
+    pub fn range_rect(&self, range: Range<usize>) -> Option<Rect> {
+        let paragraph = self.last_paragraph.as_ref()?;
+        let rects =
+            paragraph.get_rects_for_range(range, RectHeightStyle::Tight, RectWidthStyle::Tight);
+
+        let mut bounds = rects.first()?.rect;
+        for text_box in &rects[1..] {
+            bounds.join(text_box.rect);
+        }
+
+        Some(bounds.with_offset(self.text_point))
+    }
+
+    /// Read the clipboard and paste its text.
+    ///
+    /// With `as_items`, every line of the clipboard is appended to the end of
+    /// the note as a separate list item.
+    pub fn request_paste(&self, as_items: bool) {
+        self.event_loop.insert_idle(move |state| {
+            // Get available Wayland text selection.
+            let selection_offer = match state.protocol_states.data_device.data().selection_offer() {
+                Some(selection_offer) => selection_offer,
+                None => return,
+            };
+            let mut pipe = match selection_offer.receive("text/plain".into()) {
+                Ok(pipe) => pipe,
+                Err(err) => {
+                    warn!("Clipboard paste failed: {err}");
+                    return;
+                },
+            };
+
+            // Read text from pipe.
+            let mut text = String::new();
+            if let Err(err) = pipe.read_to_string(&mut text) {
+                error!("Failed to read from clipboard pipe: {err}");
+                return;
+            }
+
+            // Paste text into text box.
+            if as_items {
+                state.window.append_items(&text);
+            } else {
+                state.window.paste(&text);
+            }
+        });
+    }
+
+    /// Append every line of `text` to the end of the note as a new list item.
+    pub fn append_items(&mut self, text: &str) {
+        let normalized = normalize_paste(text, false);
+        self.append(normalized.lines());
+    }
+
+    /// Append `text` to the end of the note as a single new list item.
+    pub fn append_item(&mut self, text: &str) {
+        // Remove blank lines, which would split the text into multiple items.
+        let normalized = normalize_paste(text, false);
+        let lines: Vec<_> = normalized.lines().filter(|line| !line.trim().is_empty()).collect();
+        self.append(iter::once(lines.join("\n").as_str()));
+    }
+
+    /// Append list items to the end of the note.
+    fn append<'a>(&mut self, items: impl Iterator<Item = &'a str>) {
+        let items: Vec<_> = items.map(str::trim).filter(|item| !item.is_empty()).collect();
+        if items.is_empty() {
+            return;
+        }
+
+        let mut appended = self.text.as_str().trim_end().to_owned();
+        for item in items {
+            if !appended.is_empty() {
+                appended.push_str("\n\n");
+            }
+            if self.auto_timestamp
+                && let Some(timestamp) = self.timestamp()
+            {
+                appended.push_str(&timestamp);
+                appended.push(' ');
+            }
+            appended.push_str(item);
+        }
+
+        self.replace_text(appended);
+        self.persist_text();
+
+        // Move the cursor to the last new item.
+        self.cursor_index = self.text.len();
+        self.selection = None;
+        self.focus_cursor = true;
+    }
+
+    /// Start a new empty list item at the end of the note.
+    pub fn start_item(&mut self) {
+        let mut text = self.text.as_str().trim_end().to_owned();
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        if self.auto_timestamp
+            && let Some(timestamp) = self.timestamp()
+        {
+            text.push_str(&timestamp);
+            text.push(' ');
+        }
+
+        if text != self.text.as_str() {
+            self.replace_text(text);
+            self.persist_text();
         }
+
+        self.cursor_index = self.text.len();
+        self.selection = None;
+        self.focus_cursor = true;
+
+        self.text_input_dirty = true;
+        self.dirty = true;
     }
 
-    /// Paste text into the input element.
+    /// Paste clipboard text into the input element.
     pub fn paste(&mut self, text: &str) {
+        if self.normalize_paste {
+            let text = normalize_paste(text, self.paste_lines_as_items);
+            self.insert_text(&text);
+        } else {
+            self.insert_text(text);
+        }
+    }
+
+    /// Insert text at the cursor position, replacing the selection.
+    fn insert_text(&mut self, text: &str) {
         // Delete selection before writing new text.
         if let Some(selection) = self.selection.take() {
             self.delete_selected(selection);
         }
 
         // Add text to input element.
-        if self.cursor_index >= self.text.len() {
-            self.text.push_str(text);
-        } else {
-            self.text.insert_str(self.cursor_index, text);
-        }
+        self.text.insert(self.cursor_index, text);
         self.persist_text();
 
         // Move cursor behind the new characters.
@@ -722,8 +3185,7 @@ impl TextBox {
         let start = self.cursor_index.saturating_sub(before_length as usize);
 
         // Remove all bytes in the range from the text.
-        self.text.truncate(end);
-        self.text = self.text.split_off(start);
+        self.text.remove(start..end);
         self.persist_text();
 
         // Update cursor position.
@@ -736,32 +3198,204 @@ impl TextBox {
 
     /// Insert text at the current cursor position.
     pub fn commit_string(&mut self, text: &str) {
-        self.paste(text);
+        // Delete selection before checking for new list items.
+        if let Some(selection) = self.selection.take() {
+            self.delete_selected(selection);
+        }
+
+        self.delete_overwritten(text);
+
+        let mut prefixed = self.new_item_prefix(text);
+        if prefixed.is_empty() && self.insert_transformed(text) {
+            return;
+        }
+        prefixed.push_str(text);
+        self.insert_text(&prefixed);
+    }
+
+    /// Insert typed text using the enabled smart input transforms.
+    ///
+    /// Transforms are not applied in overwrite mode or inside code blocks.
+    /// Returns `false` if no transform applies to the text.
+    fn insert_transformed(&mut self, input: &str) -> bool {
+        let cursor_index = self.cursor_index;
+        if self.overwrite || self.code_blocks.iter().any(|code| code.contains(&cursor_index)) {
+            return false;
+        }
+
+        let transform =
+            match editor::transform_input(&self.text, cursor_index, input, &self.editing) {
+                Some(transform) => transform,
+                None => return false,
+            };
+
+        // Stash the text before the input, so undo reverts it with its transform.
+        if self.text.slice(transform.range.clone()) != transform.text {
+            self.backups.stash(self.text.chunks().collect());
+        }
+
+        self.text.replace(transform.range.clone(), &transform.text);
+        self.persist_text();
+
+        self.cursor_index = transform.range.start + transform.cursor;
+        self.focus_cursor = true;
+
+        self.text_input_dirty = true;
+        self.dirty = true;
+
+        true
+    }
+
+    /// Delete the text replaced by `text` in overwrite mode.
+    ///
+    /// One grapheme after the cursor is removed for every grapheme in `text`,
+    /// without ever joining the cursor's line with the next one.
+    fn delete_overwritten(&mut self, text: &str) {
+        if !self.overwrite || self.selection.is_some() {
+            return;
+        }
+
+        let line_end = self.text.line_range(self.cursor_index).end;
+        let mut end = self.cursor_index;
+        for _ in text.graphemes(true).filter(|grapheme| *grapheme != "\n") {
+            if end >= line_end {
+                break;
+            }
+            end = self.text.next_grapheme(end);
+        }
+
+        if end > self.cursor_index {
+            self.text.remove(self.cursor_index..end.min(line_end));
+        }
+    }
+
+    /// Get the timestamp prefix for text inserted at the cursor.
+    ///
+    /// This is empty unless automatic timestamps are enabled and `text` starts
+    /// a new list item.
+    fn new_item_prefix(&self, text: &str) -> String {
+        if !self.auto_timestamp || text.trim().is_empty() {
+            return String::new();
+        }
+
+        // Ensure the cursor's line is empty.
+        let line = self.text.line_range(self.cursor_index);
+        if !self.text.slice(line).trim().is_empty() {
+            return String::new();
+        }
+
+        // List items start after at least one empty line.
+        if let Some(previous) = self.text.line_at(self.cursor_index).checked_sub(1) {
+            let previous = self.text.line_range(self.text.line_to_byte(previous));
+            if !self.text.slice(previous).trim().is_empty() {
+                return String::new();
+            }
+        }
+
+        match self.timestamp() {
+            Some(timestamp) => timestamp + " ",
+            None => String::new(),
+        }
+    }
+
+    /// Format the current time.
+    fn timestamp(&self) -> Option<String> {
+        let mut timestamp = String::new();
+        match write!(timestamp, "{}", Local::now().format(&self.timestamp_format)) {
+            Ok(()) => Some(timestamp),
+            Err(_) => {
+                warn!("Invalid timestamp format: {:?}", self.timestamp_format);
+                None
+            },
+        }
+    }
+
+    /// Set preedit text at the current cursor position.
+    ///
+    /// The cursor positions are byte offsets into the preedit text, with
+    /// negative values indicating that the cursor should be hidden.
+    pub fn set_preedit_string(&mut self, text: String, cursor_begin: i32, cursor_end: i32) {
+        // Ignore cursors outside of the preedit text or between UTF-8 code points.
+        let is_valid = |index: i32| index >= 0 && text.is_char_boundary(index as usize);
+        let cursor = (is_valid(cursor_begin) && is_valid(cursor_end))
+            .then(|| cursor_begin.min(cursor_end) as usize..cursor_begin.max(cursor_end) as usize);
+
+        // Ignore if preedit text did not change.
+        if self.preedit_text == text && self.preedit_cursor == cursor {
+            return;
+        }
+
+        // Delete selection as soon as preedit starts.
+        if !text.is_empty()
+            && let Some(selection) = self.selection.take()
+        {
+            self.delete_selected(selection);
+        }
+
+        self.preedit_cursor = cursor;
+        self.preedit_text = text;
+        self.focus_cursor = true;
+
+        self.dirty = true;
+    }
+
+    /// Get physical dimensions of the last rendered cursor.
+    pub fn last_cursor_rect(&self) -> Option<Rect> {
+        self.last_cursor_rect
+    }
+
+    /// Store the text box's transient state in a session.
+    pub fn update_session(&self, session: &mut Session) {
+        session.scroll_offset = self.scroll.offset;
+        session.cursor_index = self.cursor_index;
+        session.selection = self.selection.clone();
+        session.zoom = (self.zoom != 1.).then_some(self.zoom);
+        session.collapsed_sections = self.collapsed_sections.clone();
+        session.toggled_list_styles = self.toggled_list_styles.clone();
+    }
+
+    /// Restore the text box's transient state from a session.
+    ///
+    /// Offsets which are not valid for the current text are ignored.
+    pub fn restore_session(&mut self, session: &Session) {
+        if self.text.is_char_boundary(session.cursor_index) {
+            self.cursor_index = session.cursor_index;
+        }
+
+        if let Some(selection) = &session.selection
+            && selection.start < selection.end
+            && self.text.is_char_boundary(selection.start)
+            && self.text.is_char_boundary(selection.end)
+        {
+            self.selection = Some(selection.clone());
+        }
+
+        self.scroll.offset = session.scroll_offset;
+        self.set_zoom(session.zoom.unwrap_or(1.));
+        self.collapsed_sections = session.collapsed_sections.clone();
+        self.toggled_list_styles = session.toggled_list_styles.clone();
+
+        self.text_input_dirty = true;
+        self.dirty = true;
+    }
+
+    /// Get the selected range, or the cursor position without selection.
+    pub fn selection_range(&self) -> Range<usize> {
+        self.selection.clone().unwrap_or(self.cursor_index..self.cursor_index)
     }
 
-    /// Set preedit text at the current cursor position.
-    pub fn set_preedit_string(&mut self, text: String, _cursor_begin: i32, _cursor_end: i32) {
-        // Ignore if preedit text did not change.
-        if self.preedit_text == text {
+    /// Move the cursor to the end of `range`, selecting the text within it.
+    pub fn set_selection(&mut self, range: Range<usize>) {
+        if !self.text.is_char_boundary(range.start) || !self.text.is_char_boundary(range.end) {
             return;
         }
 
-        // Delete selection as soon as preedit starts.
-        if !text.is_empty()
-            && let Some(selection) = self.selection.take()
-        {
-            self.delete_selected(selection);
-        }
-
-        self.preedit_text = text;
+        self.cursor_index = range.end;
         self.focus_cursor = true;
-
+        self.text_input_dirty = true;
         self.dirty = true;
-    }
 
-    /// Get physical dimensions of the last rendered cursor.
-    pub fn last_cursor_rect(&self) -> Option<Rect> {
-        self.last_cursor_rect
+        self.select(range);
     }
 
     /// Modify text selection.
@@ -769,21 +3403,8 @@ impl TextBox {
     where
         R: RangeBounds<usize>,
     {
-        let mut start = match range.start_bound() {
-            Bound::Included(start) => *start,
-            Bound::Excluded(start) => *start + 1,
-            Bound::Unbounded => usize::MIN,
-        };
-        start = start.max(0);
-        let mut end = match range.end_bound() {
-            Bound::Included(end) => *end + 1,
-            Bound::Excluded(end) => *end,
-            Bound::Unbounded => usize::MAX,
-        };
-        end = end.min(self.text.len());
-
-        if start < end {
-            self.selection = Some(start..end);
+        if let Some(selection) = editor::selection_range(range, self.text.len()) {
+            self.selection = Some(selection);
 
             // Ensure cursor is visible after selection change.
             self.focus_cursor = true;
@@ -808,9 +3429,64 @@ impl TextBox {
     }
 
     /// Get selection text.
-    fn selection_text(&self) -> Option<&str> {
+    fn selection_text(&self) -> Option<Cow<'_, str>> {
         let selection = self.selection.as_ref()?;
-        Some(&self.text[selection.start..selection.end])
+        self.text.get(selection.clone())
+    }
+
+    /// Offer content to the clipboard, with one payload per MIME type.
+    pub fn copy(&self, content: Vec<(&'static str, Vec<u8>)>) {
+        self.event_loop.insert_idle(move |state| {
+            state.clipboard.content = content;
+            let mime_types = state.clipboard.mime_types();
+
+            // Prefer data control, to keep serving the clipboard without focus.
+            let protocol_states = &state.protocol_states;
+            if let (Some(data_control), Some(data_control_device)) =
+                (&protocol_states.data_control, &protocol_states.data_control_device)
+            {
+                let source = data_control.create_source(&state.window.queue, &mime_types);
+                data_control_device.set_selection(Some(&source));
+                if let Some(old_source) = state.clipboard.control_source.replace(source) {
+                    old_source.destroy();
+                }
+                return;
+            }
+
+            let serial = state.clipboard.next_serial();
+            let copy_paste_source = protocol_states
+                .data_device_manager
+                .create_copy_paste_source(&state.window.queue, mime_types);
+            copy_paste_source.set_selection(&protocol_states.data_device, serial);
+            state.clipboard.source = Some(copy_paste_source);
+        });
+    }
+
+    /// Get the selected text in all supported clipboard formats.
+    ///
+    /// Selections consisting only of whole list items are additionally offered
+    /// as Markdown and HTML lists.
+    fn clipboard_content(&self) -> Option<Vec<(&'static str, String)>> {
+        let selection = self.selection.clone()?;
+        let text = self.selection_text()?.into_owned();
+        let mut content = vec![("text/plain;charset=utf-8", text.clone()), ("text/plain", text)];
+
+        let text = self.text.as_str();
+        let items: Vec<_> = list_items(text)
+            .into_iter()
+            .filter(|item| item.start < selection.end && item.end > selection.start)
+            .collect();
+        let whole_items =
+            items.iter().all(|item| selection.start <= item.start && item.end <= selection.end);
+
+        if !items.is_empty() && whole_items {
+            let items: Vec<_> =
+                items.into_iter().map(|item| &text[item_content(text, item)]).collect();
+            content.push(("text/markdown", markdown_list(&items)));
+            content.push(("text/html", html_list(&items)));
+        }
+
+        Some(content)
     }
 
     /// Delete the selected text.
@@ -818,7 +3494,7 @@ impl TextBox {
     /// This automatically places the cursor at the start of the selection.
     fn delete_selected(&mut self, selection: Range<usize>) {
         // Remove selected text from input.
-        self.text.drain(selection.start..selection.end);
+        self.text.remove(selection.clone());
         self.persist_text();
 
         // Update cursor.
@@ -829,19 +3505,41 @@ impl TextBox {
         self.dirty = true;
     }
 
+    /// Render the entire note into a PDF document.
+    pub fn render_pdf(&self, print: &config::Print) -> Vec<u8> {
+        // Use the configured font size, independent of scale and zoom.
+        let mut text_style = self.text_style.clone();
+        text_style.set_font_size(self.font_size as f32);
+
+        let text = self.text.as_str();
+        print::render_pdf(&self.font_collection, &text_style, text, print, &self.layout)
+    }
+
+    /// Render the selection, or the entire note without selection, into a PNG
+    /// image.
+    pub fn render_png(&self) -> Option<Vec<u8>> {
+        let text = self.selection_text().unwrap_or(Cow::Borrowed(self.text.as_str()));
+        let (font_collection, style, layout) =
+            (&self.font_collection, &self.text_style, &self.layout);
+        let (width, scale) = (self.column_width_px(), self.scale as f32);
+        snapshot::render_png(font_collection, style, &text, self.background, layout, width, scale)
+    }
+
     /// Get byte index at the specified position.
     fn offset_at(&self, point: impl Into<Point>) -> Option<usize> {
         // Get position independent from current scroll offset.
         let mut point = point.into();
-        point.y -= self.scroll_offset;
+        point.y -= self.scroll.offset;
 
         // Get glyph cluster at the location.
         let paragraph = self.last_paragraph.as_ref()?;
         let cluster = paragraph.get_closest_glyph_cluster_at(point)?;
 
         // Calculate index based on position within the cluster.
+        // Clusters in right-to-left runs start on their right side.
         let width = cluster.bounds.right - cluster.bounds.left;
-        let index = if point.x - cluster.bounds.left < width / 2. {
+        let is_rtl = cluster.position == TextDirection::RTL;
+        let index = if (point.x - cluster.bounds.left < width / 2.) != is_rtl {
             cluster.text_range.start
         } else {
             cluster.text_range.end
@@ -850,22 +3548,70 @@ impl TextBox {
         Some(index.min(self.text.len()))
     }
 
+    /// Get the byte range of the visual line containing `offset`.
+    ///
+    /// The range excludes trailing whitespace, to place the cursor in front of
+    /// newlines and line wraps.
+    fn line_bounds(&self, offset: usize) -> Option<Range<usize>> {
+        let paragraph = self.last_paragraph.as_ref()?;
+        let line = self.line_at(offset)?;
+        let metrics = paragraph.get_line_metrics_at(line)?;
+
+        let mut start = metrics.start_index.min(self.text.len());
+        let mut end = metrics.end_excluding_whitespaces.clamp(start, self.text.len());
+        while !self.text.is_char_boundary(start) {
+            start -= 1;
+        }
+        while !self.text.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        Some(start..end)
+    }
+
+    /// Get the visual line containing a cursor at `offset`.
+    fn line_at(&self, offset: usize) -> Option<usize> {
+        let paragraph = self.last_paragraph.as_ref()?;
+        match offset {
+            0 => Some(0),
+            // Treat cursors after a newline as part of the following line.
+            _ if self.text.byte(offset - 1) == Some(b'\n') => paragraph.get_line_number_at(offset),
+            _ => paragraph.get_line_number_at(offset - 1),
+        }
+    }
+
+    /// Get the top of the visual line containing the glyph at `offset`.
+    fn line_top(&self, offset: usize) -> Option<f32> {
+        let paragraph = self.last_paragraph.as_ref()?;
+        let metrics = paragraph.get_line_metrics_at(paragraph.get_line_number_at(offset)?)?;
+        Some((metrics.baseline - metrics.ascent) as f32)
+    }
+
     /// Get metrics for the glyph at the specified offset.
     fn metrics_at(&mut self, offset: usize) -> GlyphMetrics {
         match &self.last_paragraph {
             Some(paragraph) if offset > 0 => {
                 let line_number = paragraph.get_line_number_at(offset - 1).unwrap_or(0);
 
+                // Get the previous byte, which might be part of the preedit text.
+                let previous_byte = match self.text.byte(offset - 1) {
+                    Some(byte) => byte,
+                    None => self.preedit_text.as_bytes()[offset - 1 - self.text.len()],
+                };
+
                 // Newlines are zerowidth glyphs at the end of the line, so we have to manually
                 // move the cursor to the start of the following line.
-                let (x, metrics) = if self.text.as_bytes()[offset - 1] == b'\n'
+                let (x, metrics) = if previous_byte == b'\n'
                     && let Some(metrics) = paragraph.get_line_metrics_at(line_number + 1)
                 {
-                    (0., metrics)
+                    (self.line_start_x(&metrics), metrics)
                 } else {
                     let metrics = paragraph.get_line_metrics_at(line_number).unwrap();
                     let cluster = paragraph.get_glyph_cluster_at(offset - 1);
-                    let x = cluster.map_or(0., |cluster| cluster.bounds.right);
+                    let x = cluster.map_or(0., |cluster| match cluster.position {
+                        TextDirection::RTL => cluster.bounds.left,
+                        TextDirection::LTR => cluster.bounds.right,
+                    });
                     (x, metrics)
                 };
 
@@ -873,9 +3619,20 @@ impl TextBox {
             },
             Some(paragraph) => {
                 let metrics = paragraph.get_line_metrics_at(0).unwrap();
-                GlyphMetrics::from_line_metrics(0., metrics)
+                GlyphMetrics::from_line_metrics(self.line_start_x(&metrics), metrics)
+            },
+            None => {
+                let x = self.empty_line_x();
+                GlyphMetrics::from_font_metrics(x, self.fallback_metrics())
             },
-            None => GlyphMetrics::from_font_metrics(0., self.fallback_metrics()),
+        }
+    }
+
+    /// Horizontal position of a line's logical start.
+    fn line_start_x(&self, metrics: &LineMetrics<'_>) -> f32 {
+        match self.text_direction {
+            TextDirection::RTL => (metrics.left + metrics.width) as f32,
+            TextDirection::LTR => metrics.left as f32,
         }
     }
 
@@ -899,34 +3656,53 @@ impl TextBox {
         (points, line_height)
     }
 
+    /// Get the type of content in the line containing the cursor.
+    pub fn cursor_line_kind(&self) -> LineKind {
+        let line = self.text.line_range(self.cursor_index);
+        LineKind::classify(&self.text.get(line).unwrap_or_default())
+    }
+
     /// Get surrounding text for IME.
     ///
-    /// This will return at most `MAX_SURROUNDING_BYTES` bytes plus the current
-    /// cursor positions relative to the surrounding text's origin.
-    pub fn surrounding_text(&self) -> (String, i32, i32) {
-        // Get up to half of `MAX_SURROUNDING_BYTES` after the cursor.
-        let mut end = self.cursor_index + MAX_SURROUNDING_BYTES / 2;
-        if end >= self.text.len() {
-            end = self.text.len();
-        } else {
-            while end > 0 && !self.text.is_char_boundary(end) {
-                end -= 1;
-            }
+    /// This will return at most `MAX_SURROUNDING_BYTES` bytes centered around
+    /// the selection, plus the current cursor positions relative to the
+    /// surrounding text's origin.
+    ///
+    /// Returns `None` if the selection does not fit into the surrounding text.
+    pub fn surrounding_text(&self) -> Option<(String, i32, i32)> {
+        let (selection_start, selection_end) = match &self.selection {
+            Some(selection) => (selection.start, selection.end),
+            None => (self.cursor_index, self.cursor_index),
         };
 
-        // Get as many bytes as available before the cursor.
-        let remaining = MAX_SURROUNDING_BYTES - (end - self.cursor_index);
-        let mut start = self.cursor_index.saturating_sub(remaining);
-        while start < self.text.len() && !self.text.is_char_boundary(start) {
+        let selection_len = selection_end - selection_start;
+        if selection_len > MAX_SURROUNDING_BYTES {
+            return None;
+        }
+
+        // Split the remaining bytes evenly before and after the selection,
+        // giving unused bytes to the other side at the text's boundaries.
+        let remaining = MAX_SURROUNDING_BYTES - selection_len;
+        let after = (self.text.len() - selection_end).min(remaining / 2);
+        let before = selection_start.min(remaining - after);
+        let after = (self.text.len() - selection_end).min(remaining - before);
+
+        let mut start = selection_start - before;
+        while start < selection_start && !self.text.is_char_boundary(start) {
             start += 1;
         }
 
-        let (cursor_start, cursor_end) = match &self.selection {
-            Some(selection) => (selection.start as i32, selection.end as i32),
-            None => (self.cursor_index as i32, self.cursor_index as i32),
-        };
+        let mut end = selection_end + after;
+        while end > selection_end && !self.text.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        // Ensure cursor and anchor are always within the submitted text.
+        let cursor_start = selection_start.clamp(start, end) - start;
+        let cursor_end = selection_end.clamp(start, end) - start;
 
-        (self.text[start..end].into(), cursor_start - start as i32, cursor_end - start as i32)
+        let text = self.text.get(start..end).unwrap_or_default().into_owned();
+        Some((text, cursor_start as i32, cursor_end as i32))
     }
 
     /// Get font metrics for the fallback font.
@@ -941,25 +3717,33 @@ impl TextBox {
 
     /// Persist current text content to disk.
     ///
-    /// This is automatically debounced to avoid excessive write operations.
+    /// Depending on the save mode, this is debounced to avoid excessive write
+    /// operations, or deferred until the next explicit save.
     pub fn persist_text(&mut self) {
-        // Debounce periods before text is persisted to disk.
-        const MIN_DEBOUNCE: Duration = Duration::from_millis(1000);
-        const MAX_DEBOUNCE: Duration = Duration::from_millis(5000);
+        self.unsaved = true;
+        recovery::update(&self.storage_path, &self.text);
+        self.journal_edit();
 
         // Clear pending timers.
         if let Some(token) = self.persist_token.take() {
             self.event_loop.remove(token);
         }
 
-        // Stage new persist timer, or write immediately if `MAX_DEBOUNCE` was reached.
+        match self.save_mode {
+            SaveMode::Debounced => (),
+            SaveMode::Immediate => return self.atomic_write(),
+            SaveMode::Manual => return,
+        }
+
+        // Stage new persist timer, or write immediately if the maximum delay was
+        // reached.
         let start = self.persist_start.get_or_insert_with(Instant::now);
         let elapsed = start.elapsed();
-        if elapsed >= MAX_DEBOUNCE {
+        if elapsed >= self.save_max_delay {
             self.atomic_write();
             self.persist_start = None;
         } else {
-            let debounce = cmp::min(MIN_DEBOUNCE, MAX_DEBOUNCE - elapsed);
+            let debounce = cmp::min(self.save_debounce, self.save_max_delay - elapsed);
             self.persist_token = self
                 .event_loop
                 .insert_source(Timer::from_duration(debounce), move |_, _, state| {
@@ -971,47 +3755,147 @@ impl TextBox {
         }
     }
 
-    /// Attempt to atomically write a file.
+    /// Record edits since the last journaled text in the edit journal.
+    fn journal_edit(&mut self) {
+        self.journal.record(&self.storage_path, &self.text);
+
+        // Batch journal syncs, instead of blocking on every edit.
+        if self.journal.unsynced() && self.journal_sync_token.is_none() {
+            self.journal_sync_token = self
+                .event_loop
+                .insert_source(Timer::from_duration(JOURNAL_SYNC_INTERVAL), |_, _, state| {
+                    let text_box = &mut state.window.text_box;
+                    text_box.journal_sync_token = None;
+                    text_box.journal.sync();
+                    TimeoutAction::Drop
+                })
+                .inspect_err(|err| error!("Failed to register journal sync callback: {err}"))
+                .ok();
+        }
+    }
+
+    /// Immediately write unsaved text changes to disk.
+    pub fn flush_text(&mut self) {
+        if let Some(token) = self.persist_token.take() {
+            self.event_loop.remove(token);
+        }
+
+        if self.unsaved {
+            self.atomic_write();
+        }
+    }
+
+    /// Check if there are text changes which were not written to disk yet.
+    pub fn unsaved(&self) -> bool {
+        self.unsaved
+    }
+
+    /// Get the text persistence strategy.
+    pub fn save_mode(&self) -> SaveMode {
+        self.save_mode
+    }
+
+    /// Write the text to the storage backend.
     fn atomic_write(&mut self) {
         self.persist_start = None;
 
-        // Create a tempfile "next to" the target path.
-        //
-        // Creating this in the same directory as the target path should avoid errors
-        // due to persisting across filesystems.
-        let target_dir = self.storage_path.parent().unwrap();
-        let mut tempfile = match NamedTempFile::new_in(target_dir) {
-            Ok(tempfile) => tempfile,
-            Err(err) => {
-                error!("Failed to create temporary file: {err}");
-                return;
-            },
-        };
+        if let Err(err) = self.storage.write(&self.storage_path, &mut self.text.chunks()) {
+            error!("Failed to save notes: {err}");
 
-        // Write text with newline appended at the end.
-        let result = tempfile.write_all(self.text.as_bytes()).and_then(|_| tempfile.write(b"\n"));
-        if let Err(err) = result {
-            error!("Failed to write to temporary file: {err}");
+            // Notify the desktop once saving keeps failing, even while hidden.
+            self.save_failures += 1;
+            if self.save_failures == SAVE_FAILURE_NOTIFICATION_COUNT {
+                let body = format!("{err}");
+                self.event_loop.insert_idle(move |state| {
+                    state.notifier.notify("Pinax could not save your notes", body);
+                });
+            }
+
+            self.event_loop.insert_idle(|state| state.window.toast("Failed to save note"));
             return;
         }
 
-        if let Err(err) = tempfile.persist(&self.storage_path) {
-            error!("Failed move of temporary file: {err}");
-        }
+        self.disk_hash = content_hash(self.text.chunks());
+        self.save_failures = 0;
+        self.unsaved = false;
+        recovery::clear();
+        self.journal.reset(&self.text);
 
         info!("Successfully saved notes");
+
+        // Reschedule the note's reminders.
+        let reminders = reminder::parse(self.text.as_str());
+        let path = self.storage_path.clone();
+        self.event_loop.insert_idle(move |state| state.window.reminders.update(path, reminders));
+
+        self.hooks.run(HookKind::PostSave, &self.storage_path);
+    }
+
+    /// Handle a change of the storage file's content.
+    ///
+    /// If the change conflicts with unsaved local edits, the external version
+    /// is written to a `.conflict` sibling file instead of replacing the text.
+    fn handle_external_change(state: &mut State, content: String) {
+        let text_box = &mut state.window.text_box;
+
+        // Ignore our own writes and repeated events for the same content.
+        let hash = content_hash([content.as_str()]);
+        if text_box.text == *content || text_box.disk_hash == hash {
+            return;
+        }
+
+        // Replace text if there are no local edits that would be lost.
+        if text_box.disk_hash == content_hash(text_box.text.chunks()) {
+            info!("Reloading updated storage file");
+            state.window.apply_external_change(content);
+            return;
+        }
+
+        text_box.disk_hash = hash;
+
+        let mut file_name =
+            text_box.storage_path.file_name().map(OsString::from).unwrap_or_default();
+        file_name.push(".conflict");
+        let conflict_path = text_box.storage_path.with_file_name(file_name);
+
+        warn!("External change conflicts with local edits, saving it to {conflict_path:?}");
+        match fs::write(&conflict_path, content + "\n") {
+            Ok(()) => {
+                let body = format!("The external version was saved to {}", conflict_path.display());
+                state.notifier.notify("Conflicting note changes", body);
+            },
+            Err(err) => error!("Failed to write conflicting notes to {conflict_path:?}: {err}"),
+        }
+
+        // Ensure the local version is written back to the storage file.
+        text_box.persist_text();
+    }
+
+    /// Reload a note after it was modified by a hook.
+    ///
+    /// This is ignored if the note is no longer open.
+    pub fn reload_note(state: &mut State, path: &Path) {
+        let text_box = &state.window.text_box;
+        if path != text_box.storage_path {
+            return;
+        }
+
+        if let Some(content) = Self::read_note(&*text_box.storage, path) {
+            Self::handle_external_change(state, content);
+        }
     }
 
     /// Monitor storage path for file changes.
+    ///
+    /// This watches the entire storage directory, so changes are tracked for
+    /// whichever note is currently open.
     fn monitor_file(
         event_loop: &LoopHandle<'static, State>,
-        storage_path: PathBuf,
+        storage_dir: &Path,
     ) -> Result<(), Error> {
-        let parent = storage_path.parent().unwrap();
-
-        // Create new monitor for the parent directory.
+        // Create new monitor for the storage directory.
         let mut notify_source = NotifySource::new()?;
-        notify_source.watch(parent, RecursiveMode::Recursive)?;
+        notify_source.watch(storage_dir, RecursiveMode::Recursive)?;
 
         // Watch for changes.
         event_loop.insert_source(notify_source, move |event, _, state| {
@@ -1020,54 +3904,193 @@ impl TextBox {
                 return;
             }
 
-            // Ignore other files in the storage directory.
-            if !event.paths.contains(&storage_path) {
-                return;
-            }
-
-            // Read file content.
-            let content = match Self::read_to_string(&storage_path) {
-                Some(content) => content,
-                None => return,
-            };
+            let text_box = &mut state.window.text_box;
+            let storage_path = text_box.storage_path.clone();
+            let metadata_path = FileStorage::metadata_path(&storage_path);
 
-            // Update input if text changed.
-            if state.window.text_box.text != content {
-                info!("Reloading updated storage file");
-                state.window.text_box.set_text(content);
-                state.window.unstall();
+            // Ignore other files in the storage directory.
+            for path in &event.paths {
+                if *path == storage_path || *path == metadata_path {
+                    text_box.schedule_reload(path.clone());
+                }
             }
         })?;
 
         Ok(())
     }
 
-    /// Read storage file to a string.
+    /// Reload a watched file once its changes have settled.
+    ///
+    /// Bursts of events for the same path are coalesced into a single reload,
+    /// while files which keep changing are still reloaded after
+    /// [`RELOAD_MAX_DELAY`].
+    fn schedule_reload(&mut self, path: PathBuf) {
+        let start = match self.reload_timers.remove(&path) {
+            Some((start, token)) => {
+                self.event_loop.remove(token);
+                start
+            },
+            None => Instant::now(),
+        };
+
+        let delay = cmp::min(RELOAD_DEBOUNCE, RELOAD_MAX_DELAY.saturating_sub(start.elapsed()));
+        let timer_path = path.clone();
+        let token = self
+            .event_loop
+            .insert_source(Timer::from_duration(delay), move |_, _, state| {
+                Self::reload_file(state, &timer_path);
+                TimeoutAction::Drop
+            })
+            .inspect_err(|err| error!("Failed to register reload callback: {err}"));
+
+        if let Ok(token) = token {
+            self.reload_timers.insert(path, (start, token));
+        }
+    }
+
+    /// Reload a watched file after it was modified.
+    fn reload_file(state: &mut State, path: &Path) {
+        let text_box = &mut state.window.text_box;
+        text_box.reload_timers.remove(path);
+
+        // Reload note metadata when its sidecar file changed.
+        let storage_path = text_box.storage_path.clone();
+        if path == FileStorage::metadata_path(&storage_path) {
+            state.window.reload_metadata();
+            return;
+        }
+
+        // Ignore changes to notes which are no longer open.
+        if path != storage_path {
+            return;
+        }
+
+        // Follow the symlink to its new target, if it was replaced.
+        text_box.monitor_symlink_target();
+
+        if let Some(content) = Self::read_note(&*text_box.storage, path) {
+            Self::handle_external_change(state, content);
+        }
+    }
+
+    /// Monitor the target of the open note, if it is a symlink.
     ///
-    /// This will return `None` if the file does not exist or access was denied.
-    fn read_to_string(path: &PathBuf) -> Option<String> {
-        // Read file content.
-        let mut content = match fs::read_to_string(path) {
-            Ok(content) => content,
-            // Ignore file removal, since it might be done for replacement.
-            Err(err) if err.kind() == IoErrorKind::NotFound => return None,
+    /// The storage directory watcher only reports changes to the symlink
+    /// itself, so changes to its target are forwarded to the note's path.
+    fn monitor_symlink_target(&mut self) {
+        let target = FileStorage::resolve_symlink(&self.storage_path);
+
+        // Keep the existing watcher if the target did not change.
+        if self.symlink_watch.as_ref().is_some_and(|(watched, _)| *watched == target) {
+            return;
+        }
+
+        if let Some((_, token)) = self.symlink_watch.take() {
+            self.event_loop.remove(token);
+        }
+
+        // Only file storage is affected by symlinks.
+        if !self.storage.is_shared() || target == self.storage_path {
+            return;
+        }
+        let target_dir = match target.parent() {
+            Some(target_dir) => target_dir,
+            None => return,
+        };
+
+        let mut notify_source = match NotifySource::new() {
+            Ok(notify_source) => notify_source,
             Err(err) => {
-                error!("Failed to read storage file at {path:?}: {err}");
-                return None;
+                error!("Failed to create symlink target watcher: {err}");
+                return;
             },
         };
+        if let Err(err) = notify_source.watch(target_dir, RecursiveMode::NonRecursive) {
+            error!("Failed to watch symlink target {target:?}: {err}");
+            return;
+        }
+
+        info!("Watching symlink target {target:?}");
+
+        let storage_path = self.storage_path.clone();
+        let watched = target.clone();
+        let token = self
+            .event_loop
+            .insert_source(notify_source, move |event, _, state| {
+                // Ignore non-mutable events and other files in the target's directory.
+                if let EventKind::Access(_) = event.kind {
+                    return;
+                }
+
+                if event.paths.contains(&watched) {
+                    state.window.text_box.schedule_reload(storage_path.clone());
+                }
+            })
+            .inspect_err(|err| error!("Failed to register symlink target watcher: {err}"));
 
-        // Strip trailing newline, commonly inserted by text editors.
-        if content.ends_with('\n') {
-            content.truncate(content.len() - 1);
+        if let Ok(token) = token {
+            self.symlink_watch = Some((target, token));
         }
+    }
 
-        Some(content)
+    /// Read a note from the storage backend.
+    ///
+    /// This will return `None` if the note does not exist or access was denied.
+    fn read_note(storage: &dyn Storage, path: &Path) -> Option<String> {
+        storage
+            .read(path)
+            .inspect_err(|err| error!("Failed to read note at {path:?}: {err}"))
+            .ok()
+            .flatten()
+    }
+
+    /// Get the note storage backend.
+    pub fn storage(&self) -> &dyn Storage {
+        &*self.storage
+    }
+
+    /// Vertical offset of the paragraph within the text box.
+    ///
+    /// This places the paragraph below the pinned section and moves text
+    /// shorter than the text box to the bottom when it is anchored there.
+    fn anchor_offset(&self) -> f32 {
+        let anchor_offset = match self.anchor {
+            Anchor::Top => 0.,
+            Anchor::Bottom => (self.text_height() - self.last_paragraph_height).max(0.),
+        };
+        self.pinned_height + anchor_offset
+    }
+
+    /// Height available to the scrollable text below the pinned section.
+    fn text_height(&self) -> f32 {
+        (self.size.height as f32 - self.pinned_height).max(0.)
+    }
+
+    /// Horizontal position of the cursor without any text.
+    fn empty_line_x(&self) -> f32 {
+        match self.align {
+            Align::Left => 0.,
+            Align::Center => self.size.width as f32 / 2.,
+            Align::Right => self.size.width as f32,
+        }
+    }
+
+    /// Get the physical horizontal space reserved for bullet points.
+    fn bullet_padding(&self) -> f32 {
+        (self.layout.bullet_padding() * self.scale) as f32
+    }
+
+    /// Get the paint used for list bullet points.
+    fn bullet_paint(&self) -> Paint {
+        match self.layout.bullet_color {
+            Some(color) => Paint::new(color.as_color4f(), None),
+            None => self.paint.clone(),
+        }
     }
 
     /// Get the current font size.
     fn font_size(&self) -> f32 {
-        (self.font_size * self.scale) as f32
+        (self.font_size * self.zoom * self.text_scale * self.scale) as f32
     }
 
     /// Get the current caret stroke size.
@@ -1107,12 +4130,7 @@ impl TextBox {
         let line_end = metrics.baseline + metrics.descent;
 
         // Scroll cursor back into the visible range.
-        let delta = line_end + self.scroll_offset - self.size.height as f32;
-        if delta > 0. {
-            self.scroll_offset -= delta;
-        } else if line_end + self.scroll_offset < 0. {
-            self.scroll_offset = -line_end;
-        }
+        self.scroll.reveal(line_end, self.text_height());
 
         unsafe { self.clamp_scroll_offset() };
     }
@@ -1125,8 +4143,26 @@ impl TextBox {
     /// calling it when `self.text` does not match the text used for calculating
     /// `self.last_paragraph_height` will lead to invalid scroll offsets.
     unsafe fn clamp_scroll_offset(&mut self) {
-        let min_offset = -(self.last_paragraph_height - self.size.height as f32).max(0.);
-        self.scroll_offset = self.scroll_offset.min(0.).max(min_offset);
+        self.scroll.clamp(self.last_paragraph_height, self.text_height());
+    }
+}
+
+impl LayoutMetrics for TextBox {
+    fn visual_line(&self, offset: usize) -> Option<usize> {
+        self.line_at(offset)
+    }
+
+    fn line_center_y(&self, line: usize) -> Option<f32> {
+        let metrics = self.last_paragraph.as_ref()?.get_line_metrics_at(line)?;
+        Some((metrics.baseline - metrics.ascent / 2.) as f32)
+    }
+
+    fn column_x(&self, line: usize) -> Option<f32> {
+        self.last_paragraph.as_ref()?.line_column_x(line)
+    }
+
+    fn offset_at_point(&self, x: f32, y: f32) -> Option<usize> {
+        self.offset_at(Point::new(x, y + self.scroll.offset))
     }
 }
 
@@ -1137,7 +4173,11 @@ struct TouchState {
     last_time: u32,
     last_position: Position<f64>,
     last_motion_position: Position<f64>,
+    last_motion_time: u32,
+    pinned_offset: Option<usize>,
     start_offset: usize,
+    /// Vertical touch velocity in pixels per second.
+    velocity: f64,
 }
 
 impl TouchState {
@@ -1159,17 +4199,35 @@ impl TouchState {
 
         // Reset touch origin state.
         self.last_motion_position = position;
+        self.last_motion_time = time;
+        self.velocity = 0.;
         self.start_offset = offset;
         self.last_position = position;
         self.last_time = time;
     }
 
+    /// Update state from touch release event.
+    fn up(&mut self, config: &Config, time: u32) {
+        // Convert taps held in place into long-presses.
+        if self.action == TouchAction::Tap
+            && time.saturating_sub(self.last_time) >= config.input.long_press.as_millis() as u32
+        {
+            self.action = TouchAction::LongPress;
+        }
+
+        // Discard velocity if the touch was held in place before release.
+        if time.saturating_sub(self.last_motion_time) > MAX_FLING_DELAY {
+            self.velocity = 0.;
+        }
+    }
+
     /// Update state from touch motion event.
     ///
     /// Returns the distance moved since the last touch down or motion.
     fn motion(
         &mut self,
         config: &Config,
+        time: u32,
         position: Position<f64>,
         selection: Option<&Range<usize>>,
     ) -> Position<f64> {
@@ -1177,6 +4235,14 @@ impl TouchState {
         let delta = position - self.last_motion_position;
         self.last_motion_position = position;
 
+        // Update velocity, smoothing it across motion events.
+        let elapsed = time.saturating_sub(self.last_motion_time);
+        if elapsed > 0 {
+            let velocity = delta.y * 1000. / elapsed as f64;
+            self.velocity = (self.velocity + velocity) / 2.;
+            self.last_motion_time = time;
+        }
+
         // Never transfer out of drag/multi-tap states.
         if self.action != TouchAction::Tap {
             return delta;
@@ -1188,6 +4254,10 @@ impl TouchState {
             return delta;
         }
 
+        // Reorder list items when dragging after a long-press.
+        let long_press =
+            time.saturating_sub(self.last_time) >= config.input.long_press.as_millis() as u32;
+
         // Check if touch motion started on selection caret, with one character leeway.
         self.action = match selection {
             Some(selection) => {
@@ -1198,10 +4268,15 @@ impl TouchState {
                     TouchAction::DragSelectionEnd
                 } else if start_delta < 2 {
                     TouchAction::DragSelectionStart
+                } else if long_press {
+                    TouchAction::Reorder
                 } else {
                     TouchAction::Drag
                 }
             },
+            _ if long_press => TouchAction::Reorder,
+            // Treat mostly horizontal motion to the right as swipe.
+            _ if delta.x > 0. && delta.x > 2. * delta.y.abs() => TouchAction::Swipe,
             _ => TouchAction::Drag,
         };
 
@@ -1216,9 +4291,129 @@ enum TouchAction {
     Tap,
     DoubleTap,
     TripleTap,
+    LongPress,
+    Swipe,
     Drag,
     DragSelectionStart,
     DragSelectionEnd,
+    Reorder,
+}
+
+/// List item being moved by touch.
+#[derive(Clone, PartialEq, Debug)]
+struct ItemDrag {
+    /// Byte ranges of all list items when the drag started.
+    items: Vec<Range<usize>>,
+    /// Index in `items` and vertical layout bounds of every visible item.
+    visible: Vec<(usize, Range<f32>)>,
+    /// Index of the dragged item in `visible`.
+    index: usize,
+    /// Index in `visible` the dragged item would be moved to.
+    target: usize,
+    /// Vertical distance the dragged item was moved by.
+    offset: f32,
+    /// Animated vertical displacement of every visible item.
+    shifts: Vec<f32>,
+    /// Last touch position, relative to the viewport.
+    touch_y: f32,
+    /// Touch position relative to the layout, when the drag started.
+    start_y: f32,
+}
+
+impl ItemDrag {
+    /// Update the dragged item's position, based on the current scroll offset.
+    fn update(&mut self, scroll_offset: f32) {
+        self.offset = self.touch_y - scroll_offset - self.start_y;
+
+        // Move past all items whose center was crossed by the dragged item's center.
+        let center = |bounds: &Range<f32>| (bounds.start + bounds.end) / 2.;
+        let dragged_center = center(&self.visible[self.index].1) + self.offset;
+        self.target = self
+            .visible
+            .iter()
+            .enumerate()
+            .filter(|(i, (_, bounds))| *i != self.index && center(bounds) < dragged_center)
+            .count();
+    }
+
+    /// Advance the animation of items making room for the dragged item.
+    ///
+    /// Returns `true` if any item was moved.
+    fn animate(&mut self, interval: Duration) -> bool {
+        let step = self.height() * interval.as_secs_f32() / REORDER_DURATION.as_secs_f32();
+        let mut animating = false;
+        for i in 0..self.shifts.len() {
+            let delta = self.target_shift(i) - self.shifts[i];
+            if delta != 0. {
+                self.shifts[i] += delta.clamp(-step, step);
+                animating = true;
+            }
+        }
+        animating
+    }
+
+    /// Check if any items are still moving to make room.
+    fn is_animating(&self) -> bool {
+        self.shifts.iter().enumerate().any(|(i, shift)| *shift != self.target_shift(i))
+    }
+
+    /// Final displacement of a visible item, to make room for the dragged item.
+    fn target_shift(&self, index: usize) -> f32 {
+        if index > self.index && index <= self.target {
+            -self.height()
+        } else if index < self.index && index >= self.target {
+            self.height()
+        } else {
+            0.
+        }
+    }
+
+    /// Height of the dragged item.
+    fn height(&self) -> f32 {
+        let bounds = &self.visible[self.index].1;
+        bounds.end - bounds.start
+    }
+
+    /// Start of the dragged item's text.
+    fn item_start(&self) -> usize {
+        self.items[self.visible[self.index].0].start
+    }
+
+    /// Index in `items` the dragged item would be moved to.
+    fn item_target(&self) -> usize {
+        // Get an item's index, after the dragged item was removed.
+        let from = self.visible[self.index].0;
+        let position = |item: usize| if item > from { item - 1 } else { item };
+
+        let mut others = self.visible.iter().enumerate().filter(|(i, _)| *i != self.index);
+        match self.target.checked_sub(1) {
+            Some(previous) => others.nth(previous).map_or(0, |(_, (item, _))| position(*item) + 1),
+            None => others.next().map_or(0, |(_, (item, _))| position(*item)),
+        }
+    }
+}
+
+/// Text box state affecting the entire rendered content.
+#[derive(Default, PartialEq)]
+struct RenderState {
+    preedit_cursor: Option<Range<usize>>,
+    preedit_text: String,
+    selection: Option<Range<usize>>,
+    hidden_markup: Vec<Range<usize>>,
+    pinned_text: String,
+    paragraph_height: f32,
+    scroll_offset: f32,
+    item_drag: Option<ItemDrag>,
+    scale: f64,
+    size: Size,
+}
+
+/// List item shown in the pinned section.
+struct PinnedItem {
+    /// Byte range within the pinned section's text.
+    range: Range<usize>,
+    /// Start of the item's content within the full text.
+    text_start: usize,
 }
 
 /// Glyph position metrics for a paragraph.
@@ -1247,3 +4442,51 @@ impl GlyphMetrics {
         Self { x, baseline: -metrics.ascent, descent: metrics.descent, ascent: -metrics.ascent }
     }
 }
+
+/// Apply font style and OpenType features to a text style.
+///
+/// Features are either a plain tag like `tnum`, or a tag with a value like
+/// `liga=0`.
+fn set_font_options(text_style: &mut TextStyle, font_style: FontStyle, features: &[String]) {
+    text_style.set_font_style(font_style);
+
+    text_style.reset_font_features();
+    for feature in features {
+        let (tag, value) = match feature.split_once('=') {
+            Some((tag, value)) => match value.parse() {
+                Ok(value) => (tag, value),
+                Err(_) => {
+                    warn!("Ignoring invalid font feature {feature:?}");
+                    continue;
+                },
+            },
+            None => (feature.as_str(), 1),
+        };
+        text_style.add_font_feature(tag, value);
+    }
+}
+
+/// Hash text content, to detect changes to the storage file.
+///
+/// The hash only depends on the concatenated content of all chunks.
+pub fn content_hash<'a>(chunks: impl IntoIterator<Item = &'a str>) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for chunk in chunks {
+        hasher.write(chunk.as_bytes());
+    }
+    hasher.finish()
+}
+
+/// Warn about configured selection colors replaced due to low contrast.
+fn warn_selection_contrast(colors: &Colors) {
+    let foreground = colors.selection_foreground.unwrap_or(colors.background.color());
+    let background = colors.selection_background.unwrap_or(colors.highlight);
+
+    let contrast = foreground.contrast(background);
+    if contrast < MIN_SELECTION_CONTRAST {
+        warn!(
+            "Selection color contrast {contrast:.1} is below {MIN_SELECTION_CONTRAST}, using \
+             inverted text colors instead"
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Desktop text scaling preference.
+
+use std::thread;
+
+use calloop::LoopHandle;
+use calloop::channel::{self, Event, Sender};
+use tracing::{error, warn};
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedValue, Value};
+
+use crate::State;
+
+/// Settings portal D-Bus service name.
+const PORTAL_DESTINATION: &str = "org.freedesktop.portal.Desktop";
+
+/// Settings namespace containing the text scaling factor.
+const INTERFACE_NAMESPACE: &str = "org.gnome.desktop.interface";
+
+/// Setting key of the text scaling factor.
+const TEXT_SCALING_KEY: &str = "text-scaling-factor";
+
+/// Watch the desktop's text scaling factor.
+///
+/// The factor is read from the settings portal, which exposes GNOME's text
+/// scaling setting independently of the output scale.
+pub fn watch(event_loop: &LoopHandle<'static, State>) {
+    // Create calloop channel to apply changes on the main thread.
+    let (tx, rx) = channel::channel();
+    let _ = event_loop
+        .insert_source(rx, |event, _, state| {
+            if let Event::Msg(text_scale) = event {
+                state.window.set_text_scale(text_scale);
+            }
+        })
+        .inspect_err(|err| error!("Failed to insert text scale source: {err}"));
+
+    let _ = thread::Builder::new()
+        .name("text-scale".into())
+        .spawn(move || {
+            if let Err(err) = watch_settings(tx) {
+                warn!("Failed to watch text scaling factor: {err}");
+            }
+        })
+        .inspect_err(|err| error!("Failed to spawn text scale thread: {err}"));
+}
+
+/// Forward the initial text scaling factor and all its changes.
+fn watch_settings(tx: Sender<f64>) -> zbus::Result<()> {
+    let connection = Connection::session()?;
+    let settings = Proxy::new(
+        &connection,
+        PORTAL_DESTINATION,
+        "/org/freedesktop/portal/desktop",
+        "org.freedesktop.portal.Settings",
+    )?;
+
+    // Subscribe before reading the initial value, to avoid missing changes.
+    let signals = settings.receive_signal("SettingChanged")?;
+
+    let value: OwnedValue = settings.call("Read", &(INTERFACE_NAMESPACE, TEXT_SCALING_KEY))?;
+    if let Some(text_scale) = text_scale(&value) {
+        let _ = tx.send(text_scale);
+    }
+
+    for message in signals {
+        let (namespace, key, value): (String, String, OwnedValue) = message.body().deserialize()?;
+        if namespace != INTERFACE_NAMESPACE || key != TEXT_SCALING_KEY {
+            continue;
+        }
+
+        if let Some(text_scale) = text_scale(&value)
+            && tx.send(text_scale).is_err()
+        {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Extract the scaling factor from a setting value.
+///
+/// The deprecated `Read` method wraps the value in an additional variant.
+fn text_scale(value: &Value<'_>) -> Option<f64> {
+    match value {
+        Value::F64(text_scale) if *text_scale > 0. => Some(*text_scale),
+        Value::Value(value) => text_scale(value),
+        _ => None,
+    }
+}
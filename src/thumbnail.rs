@@ -0,0 +1,177 @@
+//! Inline image thumbnails.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+use std::{env, fs, thread};
+
+use skia_safe::{Data, Image};
+use tracing::error;
+
+/// Maximum logical thumbnail height.
+pub const THUMBNAIL_HEIGHT: f64 = 120.;
+
+/// Logical spacing between a thumbnail and the text above it.
+pub const THUMBNAIL_PADDING: f64 = 5.;
+
+/// File extensions recognized as images.
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "gif", "webp", "bmp"];
+
+/// Thumbnails for all lines referencing an image.
+#[derive(Default)]
+pub struct Thumbnails {
+    /// Decoded images, keyed by path and modification time.
+    cache: HashMap<PathBuf, CachedImage>,
+    thumbnails: Vec<Thumbnail>,
+    lines: Vec<(usize, PathBuf)>,
+}
+
+impl Thumbnails {
+    /// Update the thumbnails for all lines referencing an image.
+    ///
+    /// The `lines` contain the newline terminating every such line, with its
+    /// image path, see [`image_lines`]. Images are only decoded again after
+    /// their modification time has changed.
+    pub fn update(&mut self, lines: Vec<(usize, PathBuf)>) {
+        if lines == self.lines {
+            return;
+        }
+
+        self.thumbnails.clear();
+
+        let mut referenced = HashSet::new();
+        for (newline, path) in &lines {
+            let modified = match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+                Ok(modified) => modified,
+                Err(_) => continue,
+            };
+
+            if self.cache.get(path).is_none_or(|cached| cached.modified != modified) {
+                let image = load_image(path);
+                self.cache.insert(path.clone(), CachedImage { modified, image });
+            }
+
+            if let Some(image) = self.cache[path].image.clone() {
+                self.thumbnails.push(Thumbnail { newline: *newline, path: path.clone(), image });
+            }
+            referenced.insert(path);
+        }
+
+        // Release images which are no longer referenced.
+        self.cache.retain(|path, _| referenced.contains(path));
+        self.lines = lines;
+    }
+
+    /// Get the thumbnail placed below the line terminated at `newline`.
+    pub fn get(&self, newline: usize) -> Option<&Thumbnail> {
+        let index = self.thumbnails.binary_search_by_key(&newline, |t| t.newline).ok()?;
+        Some(&self.thumbnails[index])
+    }
+
+    /// Get all thumbnails for lines within a byte range.
+    pub fn in_range(&self, range: Range<usize>) -> &[Thumbnail] {
+        let start = self.thumbnails.partition_point(|t| t.newline < range.start);
+        let end = self.thumbnails.partition_point(|t| t.newline < range.end);
+        &self.thumbnails[start..end]
+    }
+
+    /// Iterate over all thumbnails.
+    pub fn iter(&self) -> impl Iterator<Item = &Thumbnail> {
+        self.thumbnails.iter()
+    }
+}
+
+/// Image referenced by a line of text.
+pub struct Thumbnail {
+    /// Byte offset of the newline terminating the referencing line.
+    pub newline: usize,
+    pub path: PathBuf,
+    pub image: Image,
+}
+
+impl Thumbnail {
+    /// Get the thumbnail size, fit into the maximum bounds.
+    pub fn size(&self, max_width: f32, max_height: f32) -> (f32, f32) {
+        let (width, height) = (self.image.width() as f32, self.image.height() as f32);
+        let scale = (max_width / width).min(max_height / height);
+        (width * scale, height * scale)
+    }
+
+    /// Open the image with the default application.
+    pub fn open(&self) {
+        let mut command = Command::new("xdg-open");
+        command.arg(&self.path).stdin(Stdio::null());
+
+        let path = self.path.clone();
+        let _ = thread::Builder::new()
+            .name("xdg-open".into())
+            .spawn(move || match command.status() {
+                Ok(status) if !status.success() => error!("Failed to open {path:?}: {status}"),
+                Ok(_) => (),
+                Err(err) => error!("Failed to run xdg-open for {path:?}: {err}"),
+            })
+            .inspect_err(|err| error!("Failed to spawn xdg-open thread: {err}"));
+    }
+}
+
+/// Decoded image and the modification time of its file.
+struct CachedImage {
+    modified: SystemTime,
+    image: Option<Image>,
+}
+
+/// Find all lines of `text` referencing an image.
+///
+/// Thumbnails are placed below their line, so only lines terminated by a
+/// newline are considered. Returns the offset of every line's newline, with
+/// its image path.
+pub fn image_lines(text: &str) -> Vec<(usize, PathBuf)> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        let newline = offset + line.len() - 1;
+        offset += line.len();
+
+        if let Some(path) = line.strip_suffix('\n').and_then(image_path) {
+            lines.push((newline, path));
+        }
+    }
+    lines
+}
+
+/// Get the image path referenced by a line.
+///
+/// Lines must consist of nothing but an absolute path, optionally relative to
+/// the home directory or prefixed by `file://`.
+fn image_path(line: &str) -> Option<PathBuf> {
+    let line = line.trim_start_matches('!').trim();
+    let line = line.strip_prefix("file://").unwrap_or(line);
+
+    let path = match line.strip_prefix("~/") {
+        Some(relative) => Path::new(&env::var_os("HOME")?).join(relative),
+        None if line.starts_with('/') => PathBuf::from(line),
+        None => return None,
+    };
+
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    IMAGE_EXTENSIONS.contains(&extension.as_str()).then_some(path)
+}
+
+/// Load an image from disk.
+fn load_image(path: &Path) -> Option<Image> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            error!("Failed to read image {path:?}: {err}");
+            return None;
+        },
+    };
+
+    let image = Image::from_encoded(Data::new_copy(&data));
+    if image.is_none() {
+        error!("Failed to decode image {path:?}");
+    }
+    image
+}
@@ -0,0 +1,212 @@
+//! List of recently deleted notes.
+
+use chrono::{DateTime, Local};
+use skia_safe::textlayout::{
+    FontCollection, Paragraph, ParagraphBuilder, ParagraphStyle, TextStyle,
+};
+use skia_safe::{Canvas as SkiaCanvas, Color4f, Paint, Point, Rect};
+
+use crate::config::{Colors, Config, FontFamily};
+use crate::skia;
+use crate::storage::TrashedNote;
+
+/// Height of the title and each deleted note at scale 1.
+const ROW_HEIGHT: f64 = 40.;
+
+/// Title shown above the deleted notes.
+const TITLE: &str = "Recently deleted — Enter to restore, Delete to purge";
+
+/// Label of the purge button.
+const PURGE_LABEL: &str = "✕";
+
+/// Deletion time format.
+const DELETED_FORMAT: &str = "%Y-%m-%d %H:%M";
+
+/// Deletion time text alpha.
+const DELETED_ALPHA: f32 = 0.6;
+
+/// Deleted notes list.
+pub struct TrashView {
+    font_collection: FontCollection,
+
+    notes: Vec<TrashedNote>,
+    selected: usize,
+    scroll_offset: usize,
+
+    font_family: FontFamily,
+    font_size: f64,
+    padding: f64,
+
+    colors: Colors,
+
+    rect: Rect,
+    scale: f64,
+}
+
+impl TrashView {
+    pub fn new(config: &Config, colors: &Colors, notes: Vec<TrashedNote>) -> Self {
+        Self {
+            notes,
+            font_collection: skia::font_collection(config.font.directory.as_deref()),
+            font_family: config.font.family.clone(),
+            font_size: config.font.size,
+            padding: config.layout.padding,
+            colors: colors.clone(),
+            scale: 1.,
+            scroll_offset: Default::default(),
+            selected: Default::default(),
+            rect: Rect::new_empty(),
+        }
+    }
+
+    /// Render the trash view into the specified physical area.
+    pub fn draw(&mut self, canvas: &SkiaCanvas, rect: Rect, scale: f64) {
+        self.rect = rect;
+        self.scale = scale;
+
+        let mut paint = Paint::default();
+        paint.set_color4f(self.colors.background.color().as_color4f(), None);
+        canvas.draw_rect(rect, &paint);
+
+        // Draw title.
+        let row_height = self.row_height();
+        let title_rect = Rect::from_xywh(rect.left, rect.top, rect.width(), row_height);
+        paint.set_color4f(self.colors.header.as_color4f(), None);
+        canvas.draw_rect(title_rect, &paint);
+        let title = if self.notes.is_empty() { "Trash is empty" } else { TITLE };
+        let paragraph = self.paragraph(title, None);
+        self.paint_row(canvas, paragraph, title_rect);
+
+        // Keep selection within the visible notes.
+        let visible_rows = ((rect.height() - row_height) / row_height).max(1.) as usize;
+        if self.selected < self.scroll_offset {
+            self.scroll_offset = self.selected;
+        } else if self.selected >= self.scroll_offset + visible_rows {
+            self.scroll_offset = self.selected + 1 - visible_rows;
+        }
+
+        // Draw visible notes.
+        let notes = self.notes.iter().enumerate().skip(self.scroll_offset).take(visible_rows);
+        for (row, (i, note)) in notes.enumerate() {
+            let top = rect.top + row_height * (row + 1) as f32;
+            let row_rect = Rect::from_xywh(rect.left, top, rect.width(), row_height);
+
+            if i == self.selected {
+                paint.set_color4f(self.colors.header.as_color4f(), None);
+                canvas.draw_rect(row_rect, &paint);
+            }
+
+            let deleted = DateTime::from_timestamp(note.deleted, 0)
+                .map(|time| time.with_timezone(&Local).format(DELETED_FORMAT).to_string())
+                .unwrap_or_default();
+            let paragraph = self.paragraph(&note.name, Some(&deleted));
+            let text_rect = Rect { right: row_rect.right - row_height, ..row_rect };
+            self.paint_row(canvas, paragraph, text_rect);
+
+            // Draw purge button, right-aligned.
+            let mut button = self.paragraph(PURGE_LABEL, None);
+            button.layout(row_height);
+            let x = row_rect.right - row_height;
+            let x = x + ((row_height - button.max_intrinsic_width()) / 2.).round();
+            let y = top + ((row_height - button.height()) / 2.).round();
+            button.paint(canvas, Point::new(x, y));
+        }
+    }
+
+    /// Move the selection by `delta` notes.
+    pub fn move_selection(&mut self, delta: isize) {
+        let max = self.notes.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add_signed(delta).min(max);
+    }
+
+    /// Remove the selected note from the list.
+    pub fn take_selected(&mut self) -> Option<TrashedNote> {
+        if self.selected >= self.notes.len() {
+            return None;
+        }
+
+        let note = self.notes.remove(self.selected);
+        self.selected = self.selected.min(self.notes.len().saturating_sub(1));
+        Some(note)
+    }
+
+    /// Get the note and requested action at a physical position.
+    ///
+    /// The note is removed from the list.
+    pub fn take_at(&mut self, position: Point) -> Option<(TrashedNote, TrashAction)> {
+        if !self.rect.contains(position) {
+            return None;
+        }
+
+        let row_height = self.row_height();
+        let row = ((position.y - self.rect.top) / row_height) as usize;
+        let index = row.checked_sub(1)? + self.scroll_offset;
+        if index >= self.notes.len() {
+            return None;
+        }
+
+        let action = if position.x >= self.rect.right - row_height {
+            TrashAction::Purge
+        } else {
+            TrashAction::Restore
+        };
+
+        self.selected = index;
+        Some((self.take_selected()?, action))
+    }
+
+    /// Create a single-line paragraph, with an optional dimmed suffix.
+    fn paragraph(&self, text: &str, suffix: Option<&str>) -> Paragraph {
+        let mut paint = Paint::default();
+        paint.set_color4f(self.colors.foreground.as_color4f(), None);
+        paint.set_anti_alias(true);
+
+        let mut text_style = TextStyle::new();
+        text_style.set_foreground_paint(&paint);
+        text_style.set_font_size((self.font_size * self.scale) as f32);
+        text_style.set_font_families(&self.font_family);
+
+        let mut paragraph_style = ParagraphStyle::new();
+        paragraph_style.set_text_style(&text_style);
+        paragraph_style.set_max_lines(1);
+        paragraph_style.set_ellipsis("…");
+
+        let mut builder = ParagraphBuilder::new(&paragraph_style, &self.font_collection);
+        builder.add_text(text);
+
+        if let Some(suffix) = suffix {
+            let mut style = text_style.clone();
+            let color = Color4f { a: DELETED_ALPHA, ..paint.color4f() };
+            let mut dim_paint = paint.clone();
+            dim_paint.set_color4f(color, None);
+            style.set_foreground_paint(&dim_paint);
+            builder.push_style(&style);
+            builder.add_text(" — ");
+            builder.add_text(suffix);
+            builder.pop();
+        }
+
+        builder.build()
+    }
+
+    /// Layout a paragraph and paint it vertically centered within a row.
+    fn paint_row(&self, canvas: &SkiaCanvas, mut paragraph: Paragraph, rect: Rect) {
+        let padding = (self.padding * self.scale).round() as f32;
+        paragraph.layout((rect.width() - 2. * padding).max(0.));
+
+        let y = rect.top + ((rect.height() - paragraph.height()) / 2.).round();
+        paragraph.paint(canvas, Point::new(rect.left + padding, y));
+    }
+
+    /// Physical height of a single row.
+    fn row_height(&self) -> f32 {
+        (ROW_HEIGHT * self.scale).round() as f32
+    }
+}
+
+/// Actions for a deleted note.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TrashAction {
+    Restore,
+    Purge,
+}
@@ -0,0 +1,449 @@
+//! Vulkan renderer.
+
+use std::ffi::c_void;
+use std::fmt::{self, Debug, Formatter};
+use std::ptr;
+
+use ash::khr::{
+    incremental_present, surface as khr_surface, swapchain as khr_swapchain, wayland_surface,
+};
+use ash::vk::{self, Handle};
+use ash::{Device, Entry, Instance};
+use skia_safe::gpu::vk::{self as skia_vk, GetProcOf};
+use skia_safe::gpu::{
+    BackendSurfaceAccess, DirectContext, FlushInfo, SurfaceOrigin, SyncCpu, backend_render_targets,
+    direct_contexts, surfaces,
+};
+use skia_safe::{Canvas as SkiaCanvas, ColorType, IRect, Surface as SkiaSurface};
+use smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface;
+use smithay_client_toolkit::reexports::client::{Connection, Proxy};
+use tracing::error;
+
+use crate::Error;
+use crate::geometry::Size;
+use crate::renderer::DamageHistory;
+
+/// Vulkan renderer.
+///
+/// Presentation never blocks on the compositor, frames are paced using the
+/// window's frame callbacks instead.
+pub struct VulkanRenderer {
+    swapchain: Option<Swapchain>,
+    context: Option<DirectContext>,
+
+    swapchain_loader: khr_swapchain::Device,
+    surface_loader: khr_surface::Instance,
+    physical_device: vk::PhysicalDevice,
+    incremental_present: bool,
+    surface: vk::SurfaceKHR,
+    queue: vk::Queue,
+    fence: vk::Fence,
+    device: Device,
+    instance: Instance,
+    _entry: Entry,
+}
+
+impl VulkanRenderer {
+    /// Initialize a new Vulkan renderer.
+    pub fn new(connection: &Connection, wl_surface: &WlSurface) -> Result<Self, Error> {
+        let entry = unsafe { Entry::load()? };
+
+        // Create Vulkan instance with Wayland presentation support.
+        let app_info = vk::ApplicationInfo::default()
+            .application_name(c"Pinax")
+            .api_version(vk::API_VERSION_1_1);
+        let instance_extensions = [khr_surface::NAME.as_ptr(), wayland_surface::NAME.as_ptr()];
+        let instance_info = vk::InstanceCreateInfo::default()
+            .application_info(&app_info)
+            .enabled_extension_names(&instance_extensions);
+        let instance = unsafe { entry.create_instance(&instance_info, None)? };
+
+        // Create Vulkan surface for the window.
+        let wayland_surface_loader = wayland_surface::Instance::new(&entry, &instance);
+        let surface_info = vk::WaylandSurfaceCreateInfoKHR::default()
+            .display(connection.backend().display_ptr().cast())
+            .surface(wl_surface.id().as_ptr().cast());
+        let surface =
+            unsafe { wayland_surface_loader.create_wayland_surface(&surface_info, None)? };
+        let surface_loader = khr_surface::Instance::new(&entry, &instance);
+
+        // Find a queue supporting both graphics and presentation.
+        let (physical_device, queue_family) = unsafe { instance.enumerate_physical_devices()? }
+            .into_iter()
+            .find_map(|physical_device| {
+                let families = unsafe {
+                    instance.get_physical_device_queue_family_properties(physical_device)
+                };
+                let queue_family = families.iter().enumerate().position(|(i, family)| {
+                    family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                        && unsafe {
+                            surface_loader
+                                .get_physical_device_surface_support(
+                                    physical_device,
+                                    i as u32,
+                                    surface,
+                                )
+                                .unwrap_or(false)
+                        }
+                })?;
+                Some((physical_device, queue_family as u32))
+            })
+            .ok_or(vk::Result::ERROR_INITIALIZATION_FAILED)?;
+
+        // Submit damage to the compositor, if supported.
+        let incremental_present =
+            unsafe { instance.enumerate_device_extension_properties(physical_device)? }.iter().any(
+                |extension| extension.extension_name_as_c_str() == Ok(incremental_present::NAME),
+            );
+
+        // Create logical device.
+        let priorities = [1.];
+        let queue_info = [vk::DeviceQueueCreateInfo::default()
+            .queue_family_index(queue_family)
+            .queue_priorities(&priorities)];
+        let mut device_extensions = vec![khr_swapchain::NAME.as_ptr()];
+        if incremental_present {
+            device_extensions.push(incremental_present::NAME.as_ptr());
+        }
+        let device_info = vk::DeviceCreateInfo::default()
+            .queue_create_infos(&queue_info)
+            .enabled_extension_names(&device_extensions);
+        let device = unsafe { instance.create_device(physical_device, &device_info, None)? };
+        let queue = unsafe { device.get_device_queue(queue_family, 0) };
+
+        let swapchain_loader = khr_swapchain::Device::new(&instance, &device);
+        let fence = unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None)? };
+
+        // Create Skia's GPU context.
+        let get_proc = |of: GetProcOf| unsafe {
+            let function = match of {
+                GetProcOf::Instance(instance, name) => {
+                    entry.get_instance_proc_addr(vk::Instance::from_raw(instance as u64), name)
+                },
+                GetProcOf::Device(device, name) => (instance.fp_v1_0().get_device_proc_addr)(
+                    vk::Device::from_raw(device as u64),
+                    name,
+                ),
+            };
+            function.map_or(ptr::null(), |function| function as *const c_void)
+        };
+        let backend_context = unsafe {
+            skia_vk::BackendContext::new(
+                instance.handle().as_raw() as _,
+                physical_device.as_raw() as _,
+                device.handle().as_raw() as _,
+                (queue.as_raw() as _, queue_family as usize),
+                &get_proc,
+            )
+        };
+        let context = direct_contexts::make_vulkan(&backend_context, None)
+            .ok_or(vk::Result::ERROR_INITIALIZATION_FAILED)?;
+
+        Ok(Self {
+            swapchain_loader,
+            physical_device,
+            incremental_present,
+            surface_loader,
+            instance,
+            surface,
+            device,
+            queue,
+            fence,
+            context: Some(context),
+            _entry: entry,
+            swapchain: None,
+        })
+    }
+
+    /// Render and present a new frame.
+    ///
+    /// The `damage` is the region which changed since the last frame, with
+    /// `None` indicating that the entire surface was damaged. The region
+    /// passed to `fun` must be redrawn, to bring the swapchain image up to
+    /// date.
+    pub fn draw<F>(&mut self, size: Size, damage: Option<IRect>, fun: F)
+    where
+        F: FnOnce(&SkiaCanvas, Option<IRect>),
+    {
+        if let Err(err) = self.draw_frame(size, damage, fun) {
+            error!("Vulkan rendering failed: {err}");
+        }
+    }
+
+    /// Release all GPU buffers.
+    pub fn release(&mut self) {
+        self.destroy_swapchain();
+
+        if let Some(context) = &mut self.context {
+            context.free_gpu_resources();
+        }
+    }
+
+    fn draw_frame<F>(&mut self, size: Size, damage: Option<IRect>, fun: F) -> Result<(), Error>
+    where
+        F: FnOnce(&SkiaCanvas, Option<IRect>),
+    {
+        // Recreate swapchain after resize.
+        if self.swapchain.as_ref().is_none_or(|swapchain| swapchain.size != size) {
+            self.create_swapchain(size)?;
+        }
+
+        // Acquire the next image, recreating outdated swapchains once.
+        let index = match self.acquire_image() {
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.create_swapchain(size)?;
+                self.acquire_image()?
+            },
+            result => result?,
+        };
+
+        let swapchain = self.swapchain.as_mut().unwrap();
+        let context = self.context.as_mut().unwrap();
+        let skia_surface = &mut swapchain.surfaces[index as usize];
+
+        // Swapchain images keep their content, so only changes since the
+        // image was last presented need to be redrawn.
+        let age = swapchain.ages[index as usize];
+        for age in swapchain.ages.iter_mut().filter(|age| **age > 0) {
+            *age += 1;
+        }
+        swapchain.ages[index as usize] = 1;
+
+        let repaint_region = swapchain.damage_history.repaint_region(damage, age);
+        fun(skia_surface.canvas(), repaint_region);
+
+        // Transition image for presentation and wait for rendering to complete.
+        context.flush_surface_with_access(
+            skia_surface,
+            BackendSurfaceAccess::Present,
+            &FlushInfo::default(),
+        );
+        context.submit(SyncCpu::Yes);
+
+        let swapchains = [swapchain.swapchain];
+        let indices = [index];
+        let mut present_info =
+            vk::PresentInfoKHR::default().swapchains(&swapchains).image_indices(&indices);
+
+        // Only submit the damaged region to the compositor.
+        let rectangles = damage.map(|damage| {
+            [vk::RectLayerKHR {
+                offset: vk::Offset2D { x: damage.left, y: damage.top },
+                extent: vk::Extent2D {
+                    width: damage.width() as u32,
+                    height: damage.height() as u32,
+                },
+                layer: 0,
+            }]
+        });
+        let regions = rectangles
+            .as_ref()
+            .map(|rectangles| [vk::PresentRegionKHR::default().rectangles(rectangles)]);
+        let mut present_regions = vk::PresentRegionsKHR::default();
+        if let Some(regions) = regions.as_ref().filter(|_| self.incremental_present) {
+            present_regions = present_regions.regions(regions);
+            present_info = present_info.push_next(&mut present_regions);
+        }
+
+        match unsafe { self.swapchain_loader.queue_present(self.queue, &present_info) } {
+            // Outdated swapchains are recreated on the next frame.
+            Ok(_) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Acquire the next swapchain image, waiting until it is available.
+    fn acquire_image(&mut self) -> Result<u32, vk::Result> {
+        let swapchain = self.swapchain.as_ref().unwrap().swapchain;
+        let (index, _) = unsafe {
+            self.swapchain_loader.acquire_next_image(
+                swapchain,
+                u64::MAX,
+                vk::Semaphore::null(),
+                self.fence,
+            )?
+        };
+
+        unsafe {
+            self.device.wait_for_fences(&[self.fence], true, u64::MAX)?;
+            self.device.reset_fences(&[self.fence])?;
+        }
+
+        Ok(index)
+    }
+
+    /// Create a swapchain for the specified size, replacing the existing one.
+    fn create_swapchain(&mut self, size: Size) -> Result<(), Error> {
+        let capabilities = unsafe {
+            self.surface_loader
+                .get_physical_device_surface_capabilities(self.physical_device, self.surface)?
+        };
+        let formats = unsafe {
+            self.surface_loader
+                .get_physical_device_surface_formats(self.physical_device, self.surface)?
+        };
+        let present_modes = unsafe {
+            self.surface_loader
+                .get_physical_device_surface_present_modes(self.physical_device, self.surface)?
+        };
+
+        // Pick a format supported by Skia.
+        let (format, color_type) = formats
+            .iter()
+            .find_map(|format| match format.format {
+                vk::Format::B8G8R8A8_UNORM => Some((*format, ColorType::BGRA8888)),
+                vk::Format::R8G8B8A8_UNORM => Some((*format, ColorType::RGBA8888)),
+                _ => None,
+            })
+            .ok_or(vk::Result::ERROR_FORMAT_NOT_SUPPORTED)?;
+
+        // Avoid blocking presentation, since frames are paced by frame callbacks.
+        let present_mode = [vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::IMMEDIATE]
+            .into_iter()
+            .find(|mode| present_modes.contains(mode))
+            .unwrap_or(vk::PresentModeKHR::FIFO);
+
+        let composite_alpha = if capabilities
+            .supported_composite_alpha
+            .contains(vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED)
+        {
+            vk::CompositeAlphaFlagsKHR::PRE_MULTIPLIED
+        } else {
+            vk::CompositeAlphaFlagsKHR::OPAQUE
+        };
+
+        let mut image_count = capabilities.min_image_count + 1;
+        if capabilities.max_image_count > 0 {
+            image_count = image_count.min(capabilities.max_image_count);
+        }
+
+        // Wait for the old swapchain's images to be released by Skia.
+        let old_swapchain = match self.swapchain.take() {
+            Some(old_swapchain) => {
+                unsafe { self.device.device_wait_idle()? };
+                old_swapchain.swapchain
+            },
+            None => vk::SwapchainKHR::null(),
+        };
+
+        let extent = vk::Extent2D { width: size.width, height: size.height };
+        let swapchain_info = vk::SwapchainCreateInfoKHR::default()
+            .surface(self.surface)
+            .min_image_count(image_count)
+            .image_format(format.format)
+            .image_color_space(format.color_space)
+            .image_extent(extent)
+            .image_array_layers(1)
+            .image_usage(
+                vk::ImageUsageFlags::COLOR_ATTACHMENT
+                    | vk::ImageUsageFlags::TRANSFER_SRC
+                    | vk::ImageUsageFlags::TRANSFER_DST,
+            )
+            .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .pre_transform(capabilities.current_transform)
+            .composite_alpha(composite_alpha)
+            .present_mode(present_mode)
+            .clipped(true)
+            .old_swapchain(old_swapchain);
+        let swapchain = unsafe { self.swapchain_loader.create_swapchain(&swapchain_info, None) };
+
+        // Destroy the old swapchain, even if creating its replacement failed.
+        if old_swapchain != vk::SwapchainKHR::null() {
+            unsafe { self.swapchain_loader.destroy_swapchain(old_swapchain, None) };
+        }
+        let swapchain = swapchain?;
+
+        // Wrap all swapchain images in Skia surfaces.
+        let context = self.context.as_mut().unwrap();
+        let images = unsafe { self.swapchain_loader.get_swapchain_images(swapchain)? };
+        let mut skia_surfaces = Vec::with_capacity(images.len());
+        for image in images {
+            let image_info = unsafe {
+                skia_vk::ImageInfo::new(
+                    image.as_raw() as _,
+                    skia_vk::Alloc::default(),
+                    skia_vk::ImageTiling::OPTIMAL,
+                    skia_vk::ImageLayout::UNDEFINED,
+                    match color_type {
+                        ColorType::BGRA8888 => skia_vk::Format::B8G8R8A8_UNORM,
+                        _ => skia_vk::Format::R8G8B8A8_UNORM,
+                    },
+                    1,
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+            };
+            let dimensions = (size.width as i32, size.height as i32);
+            let target = backend_render_targets::make_vk(dimensions, &image_info);
+            let skia_surface = surfaces::wrap_backend_render_target(
+                context,
+                &target,
+                SurfaceOrigin::TopLeft,
+                color_type,
+                None,
+                None,
+            )
+            .ok_or(vk::Result::ERROR_INITIALIZATION_FAILED)?;
+            skia_surfaces.push(skia_surface);
+        }
+
+        self.swapchain = Some(Swapchain {
+            ages: vec![0; skia_surfaces.len()],
+            surfaces: skia_surfaces,
+            damage_history: Default::default(),
+            swapchain,
+            size,
+        });
+
+        Ok(())
+    }
+
+    /// Destroy the current swapchain.
+    fn destroy_swapchain(&mut self) {
+        if let Some(swapchain) = self.swapchain.take() {
+            unsafe {
+                let _ = self.device.device_wait_idle();
+                drop(swapchain.surfaces);
+                self.swapchain_loader.destroy_swapchain(swapchain.swapchain, None);
+            }
+        }
+    }
+}
+
+impl Drop for VulkanRenderer {
+    fn drop(&mut self) {
+        self.destroy_swapchain();
+
+        // Skia's context must be dropped before the device it is using.
+        if let Some(mut context) = self.context.take() {
+            context.abandon();
+        }
+
+        unsafe {
+            self.device.destroy_fence(self.fence, None);
+            self.device.destroy_device(None);
+            self.surface_loader.destroy_surface(self.surface, None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+impl Debug for VulkanRenderer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VulkanRenderer").finish_non_exhaustive()
+    }
+}
+
+/// Swapchain with a Skia surface for each of its images.
+struct Swapchain {
+    swapchain: vk::SwapchainKHR,
+    surfaces: Vec<SkiaSurface>,
+    damage_history: DamageHistory,
+    size: Size,
+
+    /// Number of frames since each image was last drawn, with zero for images
+    /// which were never drawn.
+    ages: Vec<usize>,
+}
@@ -0,0 +1,70 @@
+//! Handling of the KDE blur protocol.
+
+use _blur::org_kde_kwin_blur::OrgKdeKwinBlur;
+use _blur::org_kde_kwin_blur_manager::OrgKdeKwinBlurManager;
+use smithay_client_toolkit::globals::GlobalData;
+use smithay_client_toolkit::reexports::client::globals::{BindError, GlobalList};
+use smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface;
+use smithay_client_toolkit::reexports::client::{
+    Connection, Dispatch, Proxy, QueueHandle, delegate_dispatch,
+};
+use wayland_protocols_plasma::blur::client as _blur;
+
+use crate::State;
+
+/// Background blur manager.
+#[derive(Clone, Debug)]
+pub struct BlurManager {
+    manager: OrgKdeKwinBlurManager,
+}
+
+impl BlurManager {
+    /// Create new blur manager.
+    pub fn new(globals: &GlobalList, queue_handle: &QueueHandle<State>) -> Result<Self, BindError> {
+        let manager = globals.bind(queue_handle, 1..=1, GlobalData)?;
+        Ok(Self { manager })
+    }
+
+    /// Blur the content behind the entire surface.
+    ///
+    /// Like all surface state, this is applied on the next surface commit.
+    pub fn blur(&self, queue_handle: &QueueHandle<State>, surface: &WlSurface) -> OrgKdeKwinBlur {
+        let blur = self.manager.create(surface, queue_handle, GlobalData);
+        blur.commit();
+        blur
+    }
+
+    /// Remove the blur from a surface.
+    pub fn unblur(&self, surface: &WlSurface, blur: OrgKdeKwinBlur) {
+        self.manager.unset(surface);
+        blur.release();
+    }
+}
+
+impl Dispatch<OrgKdeKwinBlurManager, GlobalData, State> for BlurManager {
+    fn event(
+        _: &mut State,
+        _: &OrgKdeKwinBlurManager,
+        _: <OrgKdeKwinBlurManager as Proxy>::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        // No events.
+    }
+}
+impl Dispatch<OrgKdeKwinBlur, GlobalData, State> for BlurManager {
+    fn event(
+        _: &mut State,
+        _: &OrgKdeKwinBlur,
+        _: <OrgKdeKwinBlur as Proxy>::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        // No events.
+    }
+}
+
+delegate_dispatch!(State: [OrgKdeKwinBlurManager: GlobalData] => BlurManager);
+delegate_dispatch!(State: [OrgKdeKwinBlur: GlobalData] => BlurManager);
@@ -0,0 +1,141 @@
+//! Handling of the wlr data control protocol.
+
+use std::fs::File;
+use std::io::Write;
+
+use _dc::zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1};
+use _dc::zwlr_data_control_manager_v1::ZwlrDataControlManagerV1;
+use _dc::zwlr_data_control_offer_v1::ZwlrDataControlOfferV1;
+use _dc::zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1};
+use smithay_client_toolkit::globals::GlobalData;
+use smithay_client_toolkit::reexports::client::globals::{BindError, GlobalList};
+use smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat;
+use smithay_client_toolkit::reexports::client::{
+    Connection, Dispatch, Proxy, QueueHandle, delegate_dispatch, event_created_child,
+};
+use smithay_client_toolkit::reexports::protocols_wlr::data_control::v1::client as _dc;
+use tracing::error;
+
+use crate::State;
+
+/// Data control manager.
+///
+/// This allows offering clipboard content independent of keyboard focus.
+#[derive(Debug)]
+pub struct DataControlManager {
+    manager: ZwlrDataControlManagerV1,
+}
+
+impl DataControlManager {
+    /// Create new data control manager.
+    pub fn new(globals: &GlobalList, queue_handle: &QueueHandle<State>) -> Result<Self, BindError> {
+        let manager = globals.bind(queue_handle, 1..=2, GlobalData)?;
+        Ok(Self { manager })
+    }
+
+    /// Get the data control device for a seat.
+    pub fn data_device(
+        &self,
+        queue_handle: &QueueHandle<State>,
+        seat: &WlSeat,
+    ) -> ZwlrDataControlDeviceV1 {
+        self.manager.get_data_device(seat, queue_handle, GlobalData)
+    }
+
+    /// Create a new data source offering the specified MIME types.
+    pub fn create_source(
+        &self,
+        queue_handle: &QueueHandle<State>,
+        mime_types: &[&str],
+    ) -> ZwlrDataControlSourceV1 {
+        let source = self.manager.create_data_source(queue_handle, GlobalData);
+        for mime_type in mime_types {
+            source.offer(mime_type.to_string());
+        }
+        source
+    }
+}
+
+impl Dispatch<ZwlrDataControlManagerV1, GlobalData, State> for DataControlManager {
+    fn event(
+        _: &mut State,
+        _: &ZwlrDataControlManagerV1,
+        _: <ZwlrDataControlManagerV1 as Proxy>::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        // No events.
+    }
+}
+
+impl Dispatch<ZwlrDataControlDeviceV1, GlobalData, State> for DataControlManager {
+    event_created_child!(State, ZwlrDataControlDeviceV1, [
+        zwlr_data_control_device_v1::EVT_DATA_OFFER_OPCODE => (ZwlrDataControlOfferV1, GlobalData)
+    ]);
+
+    fn event(
+        _: &mut State,
+        device: &ZwlrDataControlDeviceV1,
+        event: <ZwlrDataControlDeviceV1 as Proxy>::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        match event {
+            // Pasting uses the core data device, so offers can be released immediately.
+            zwlr_data_control_device_v1::Event::Selection { id: Some(offer) }
+            | zwlr_data_control_device_v1::Event::PrimarySelection { id: Some(offer) } => {
+                offer.destroy();
+            },
+            zwlr_data_control_device_v1::Event::Finished => device.destroy(),
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<ZwlrDataControlOfferV1, GlobalData, State> for DataControlManager {
+    fn event(
+        _: &mut State,
+        _: &ZwlrDataControlOfferV1,
+        _: <ZwlrDataControlOfferV1 as Proxy>::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        // Offered MIME types are ignored.
+    }
+}
+
+impl Dispatch<ZwlrDataControlSourceV1, GlobalData, State> for DataControlManager {
+    fn event(
+        state: &mut State,
+        source: &ZwlrDataControlSourceV1,
+        event: <ZwlrDataControlSourceV1 as Proxy>::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        match event {
+            zwlr_data_control_source_v1::Event::Send { mime_type, fd } => {
+                let mut file = File::from(fd);
+                let payload = state.clipboard.payload(&mime_type);
+                if let Err(err) = file.write_all(payload) {
+                    error!("Failed to write clipboard content: {err}");
+                }
+            },
+            zwlr_data_control_source_v1::Event::Cancelled => {
+                if state.clipboard.control_source.as_ref() == Some(source) {
+                    state.clipboard.control_source = None;
+                }
+                source.destroy();
+            },
+            _ => (),
+        }
+    }
+}
+
+delegate_dispatch!(State: [ZwlrDataControlManagerV1: GlobalData] => DataControlManager);
+delegate_dispatch!(State: [ZwlrDataControlDeviceV1: GlobalData] => DataControlManager);
+delegate_dispatch!(State: [ZwlrDataControlOfferV1: GlobalData] => DataControlManager);
+delegate_dispatch!(State: [ZwlrDataControlSourceV1: GlobalData] => DataControlManager);
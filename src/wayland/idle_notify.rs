@@ -0,0 +1,79 @@
+//! Handling of the ext_idle_notify_v1 protocol.
+
+use std::time::Duration;
+
+use _idle_notify::ext_idle_notification_v1::{self, ExtIdleNotificationV1};
+use _idle_notify::ext_idle_notifier_v1::ExtIdleNotifierV1;
+use smithay_client_toolkit::globals::GlobalData;
+use smithay_client_toolkit::reexports::client::globals::{BindError, GlobalList};
+use smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat;
+use smithay_client_toolkit::reexports::client::{
+    Connection, Dispatch, Proxy, QueueHandle, delegate_dispatch,
+};
+use smithay_client_toolkit::reexports::protocols::ext::idle_notify::v1::client as _idle_notify;
+
+use crate::State;
+
+/// User idle notifier.
+///
+/// Idle state is tracked for the seat passed on creation.
+#[derive(Clone, Debug)]
+pub struct IdleNotifier {
+    notifier: ExtIdleNotifierV1,
+    seat: WlSeat,
+}
+
+impl IdleNotifier {
+    /// Create new idle notifier.
+    pub fn new(
+        globals: &GlobalList,
+        queue_handle: &QueueHandle<State>,
+        seat: WlSeat,
+    ) -> Result<Self, BindError> {
+        let notifier = globals.bind(queue_handle, 1..=1, GlobalData)?;
+        Ok(Self { notifier, seat })
+    }
+
+    /// Get notified when the user has been idle for `timeout`.
+    pub fn notification(
+        &self,
+        queue_handle: &QueueHandle<State>,
+        timeout: Duration,
+    ) -> ExtIdleNotificationV1 {
+        let timeout = timeout.as_millis().try_into().unwrap_or(u32::MAX);
+        self.notifier.get_idle_notification(timeout, &self.seat, queue_handle, GlobalData)
+    }
+}
+
+impl Dispatch<ExtIdleNotifierV1, GlobalData, State> for IdleNotifier {
+    fn event(
+        _: &mut State,
+        _: &ExtIdleNotifierV1,
+        _: <ExtIdleNotifierV1 as Proxy>::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        // No events.
+    }
+}
+
+impl Dispatch<ExtIdleNotificationV1, GlobalData, State> for IdleNotifier {
+    fn event(
+        state: &mut State,
+        _: &ExtIdleNotificationV1,
+        event: ext_idle_notification_v1::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        match event {
+            ext_idle_notification_v1::Event::Idled => state.window.set_idle(true),
+            ext_idle_notification_v1::Event::Resumed => state.window.set_idle(false),
+            _ => (),
+        }
+    }
+}
+
+delegate_dispatch!(State: [ExtIdleNotifierV1: GlobalData] => IdleNotifier);
+delegate_dispatch!(State: [ExtIdleNotificationV1: GlobalData] => IdleNotifier);
@@ -3,8 +3,10 @@
 use std::io::Write;
 use std::sync::{Arc, Mutex};
 
+use _data_control::zwlr_data_control_device_v1::ZwlrDataControlDeviceV1;
 use _text_input::zwp_text_input_manager_v3::{self, ZwpTextInputManagerV3};
 use _text_input::zwp_text_input_v3::{self, ZwpTextInputV3};
+use smithay_client_toolkit::activation::{ActivationHandler, ActivationState, RequestData};
 use smithay_client_toolkit::compositor::{CompositorHandler, CompositorState};
 use smithay_client_toolkit::data_device_manager::data_device::{DataDevice, DataDeviceHandler};
 use smithay_client_toolkit::data_device_manager::data_offer::{DataOfferHandler, DragOffer};
@@ -23,41 +25,62 @@ use smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface;
 use smithay_client_toolkit::reexports::client::protocol::wl_touch::WlTouch;
 use smithay_client_toolkit::reexports::client::{Connection, Dispatch, QueueHandle};
 use smithay_client_toolkit::reexports::protocols::wp::text_input::zv3::client as _text_input;
+use smithay_client_toolkit::reexports::protocols_wlr::data_control::v1::client as _data_control;
 use smithay_client_toolkit::registry::{ProvidesRegistryState, RegistryState};
 use smithay_client_toolkit::seat::keyboard::{
     KeyEvent, KeyboardHandler, Keysym, Modifiers, RawModifiers, RepeatInfo,
 };
+use smithay_client_toolkit::seat::pointer::cursor_shape::CursorShapeManager;
 use smithay_client_toolkit::seat::pointer::{
-    BTN_LEFT, PointerEvent, PointerEventKind, PointerHandler,
+    BTN_LEFT, BTN_RIGHT, PointerEvent, PointerEventKind, PointerHandler,
 };
 use smithay_client_toolkit::seat::touch::TouchHandler;
 use smithay_client_toolkit::seat::{Capability, SeatHandler, SeatState};
 use smithay_client_toolkit::shell::xdg::XdgShell;
-use smithay_client_toolkit::shell::xdg::window::{Window, WindowConfigure, WindowHandler};
+use smithay_client_toolkit::shell::xdg::window::{
+    Window, WindowConfigure, WindowHandler, WindowState,
+};
+use smithay_client_toolkit::shm::{Shm, ShmHandler};
 use smithay_client_toolkit::{
-    delegate_compositor, delegate_data_device, delegate_keyboard, delegate_output,
-    delegate_pointer, delegate_registry, delegate_seat, delegate_touch, delegate_xdg_shell,
-    delegate_xdg_window, registry_handlers,
+    delegate_activation, delegate_compositor, delegate_data_device, delegate_keyboard,
+    delegate_output, delegate_pointer, delegate_registry, delegate_seat, delegate_shm,
+    delegate_touch, delegate_xdg_shell, delegate_xdg_window, registry_handlers,
 };
 
-use crate::geometry::Size;
+use crate::geometry::{Logical, Size};
+use crate::wayland::blur::BlurManager;
+use crate::wayland::data_control::DataControlManager;
 use crate::wayland::fractional_scale::{FractionalScaleHandler, FractionalScaleManager};
+use crate::wayland::idle_notify::IdleNotifier;
+use crate::wayland::presentation::Presentation;
 use crate::wayland::viewporter::Viewporter;
-use crate::{Error, KeyboardState, State};
+use crate::{Error, KeyboardState, PointerState, State, TouchState};
 
+pub mod blur;
+pub mod data_control;
 pub mod fractional_scale;
+pub mod idle_notify;
+pub mod presentation;
 pub mod viewporter;
 
 /// Wayland protocol globals.
 #[derive(Debug)]
 pub struct ProtocolStates {
     pub fractional_scale: Option<FractionalScaleManager>,
+    pub activation: Option<ActivationState>,
+    pub cursor_shape: Option<CursorShapeManager>,
+    pub data_control_device: Option<ZwlrDataControlDeviceV1>,
+    pub data_control: Option<DataControlManager>,
     pub data_device_manager: DataDeviceManagerState,
     pub compositor: CompositorState,
     pub registry: RegistryState,
     pub data_device: DataDevice,
+    pub blur: Option<BlurManager>,
+    pub idle_notifier: Option<IdleNotifier>,
+    pub presentation: Option<Presentation>,
     pub viewporter: Viewporter,
     pub xdg_shell: XdgShell,
+    pub shm: Shm,
 
     text_input: TextInputManager,
     output: OutputState,
@@ -73,9 +96,15 @@ impl ProtocolStates {
             .map_err(|err| Error::WaylandProtocol("xdg_shell", err))?;
         let compositor = CompositorState::bind(globals, queue)
             .map_err(|err| Error::WaylandProtocol("wl_compositor", err))?;
+        let shm = Shm::bind(globals, queue).map_err(|err| Error::WaylandProtocol("wl_shm", err))?;
         let viewporter = Viewporter::new(globals, queue)
             .map_err(|err| Error::WaylandProtocol("wp_viewporter", err))?;
         let fractional_scale = FractionalScaleManager::new(globals, queue).ok();
+        let presentation = Presentation::new(globals, queue).ok();
+        let activation = ActivationState::bind(globals, queue).ok();
+        let cursor_shape = CursorShapeManager::bind(globals, queue).ok();
+        let data_control = DataControlManager::new(globals, queue).ok();
+        let blur = BlurManager::new(globals, queue).ok();
         let seat = SeatState::new(globals, queue);
         let data_device_manager = DataDeviceManagerState::bind(globals, queue)
             .map_err(|err| Error::WaylandProtocol("wl_data_device_manager", err))?;
@@ -83,22 +112,43 @@ impl ProtocolStates {
         // Get data device for the default seat.
         let default_seat = seat.seats().next().unwrap();
         let data_device = data_device_manager.get_data_device(queue, &default_seat);
+        let data_control_device = data_control
+            .as_ref()
+            .map(|data_control| data_control.data_device(queue, &default_seat));
+
+        // Track user activity on the default seat.
+        let idle_notifier = IdleNotifier::new(globals, queue, default_seat).ok();
 
         Ok(Self {
             data_device_manager,
             fractional_scale,
             data_device,
+            data_control_device,
+            cursor_shape,
+            data_control,
+            presentation,
+            activation,
             compositor,
             text_input,
             viewporter,
             xdg_shell,
+            blur,
+            idle_notifier,
             registry,
             output,
             seat,
+            shm,
         })
     }
 }
 
+impl ShmHandler for State {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.protocol_states.shm
+    }
+}
+delegate_shm!(State);
+
 impl CompositorHandler for State {
     fn scale_factor_changed(
         &mut self,
@@ -136,8 +186,9 @@ impl CompositorHandler for State {
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
         _surface: &WlSurface,
-        _output: &WlOutput,
+        output: &WlOutput,
     ) {
+        self.window.surface_enter(output.clone());
     }
 
     fn surface_leave(
@@ -145,8 +196,9 @@ impl CompositorHandler for State {
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
         _surface: &WlSurface,
-        _output: &WlOutput,
+        output: &WlOutput,
     ) {
+        self.window.surface_leave(output);
     }
 }
 delegate_compositor!(State);
@@ -171,7 +223,11 @@ impl WindowHandler for State {
         _queue: &QueueHandle<Self>,
         _window: &Window,
     ) {
-        self.terminated = true;
+        if self.config.general.keep_running {
+            self.window.hide();
+        } else {
+            self.terminated = true;
+        }
     }
 
     fn configure(
@@ -182,10 +238,20 @@ impl WindowHandler for State {
         configure: WindowConfigure,
         _serial: u32,
     ) {
-        if let (Some(width), Some(height)) = configure.new_size {
-            let size = Size::new(width.get(), height.get());
-            self.window.set_size(&self.protocol_states.compositor, size);
-        }
+        // Fall back to the current size if the compositor has no preference.
+        let size = match configure.new_size {
+            (Some(width), Some(height)) => Logical(Size::new(width.get(), height.get())),
+            _ => self.window.size(),
+        };
+        self.window.set_size(&self.protocol_states.compositor, size);
+
+        // Reflow text for the new window state.
+        let fullscreen = configure.state.contains(WindowState::FULLSCREEN);
+        let maximized = configure.state.contains(WindowState::MAXIMIZED);
+        self.window.set_window_state(fullscreen, maximized);
+
+        // Stop rendering while the compositor considers the window invisible.
+        self.window.set_suspended(configure.state.contains(WindowState::SUSPENDED));
     }
 }
 delegate_xdg_window!(State);
@@ -203,12 +269,21 @@ impl FractionalScaleHandler for State {
     }
 }
 
+impl ActivationHandler for State {
+    type RequestData = RequestData;
+
+    fn new_token(&mut self, _token: String, _data: &Self::RequestData) {}
+}
+delegate_activation!(State);
+
 impl SeatHandler for State {
     fn seat_state(&mut self) -> &mut SeatState {
         &mut self.protocol_states.seat
     }
 
-    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlSeat) {}
+    fn new_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, seat: WlSeat) {
+        self.seats.seat_mut(seat);
+    }
 
     fn new_capability(
         &mut self,
@@ -217,19 +292,27 @@ impl SeatHandler for State {
         seat: WlSeat,
         capability: Capability,
     ) {
+        let seat_state = self.seats.seat_mut(seat.clone());
         match capability {
-            Capability::Keyboard if self.keyboard.is_none() => {
+            Capability::Keyboard if seat_state.keyboard.is_none() => {
                 let keyboard = self.protocol_states.seat.get_keyboard(queue, &seat, None).ok();
-                self.keyboard = keyboard.map(KeyboardState::new);
+                seat_state.keyboard = keyboard.map(KeyboardState::new);
 
                 // Add new IME handler for this seat.
-                self.text_input.push(self.protocol_states.text_input.text_input(queue, seat));
+                seat_state.text_input =
+                    Some(self.protocol_states.text_input.text_input(queue, seat));
             },
-            Capability::Pointer if self.pointer.is_none() => {
-                self.pointer = self.protocol_states.seat.get_pointer(queue, &seat).ok();
+            Capability::Pointer if seat_state.pointer.is_none() => {
+                let pointer = self.protocol_states.seat.get_pointer(queue, &seat).ok();
+                seat_state.pointer = pointer.map(|pointer| {
+                    let cursor_shape = self.protocol_states.cursor_shape.as_ref();
+                    let shape_device = cursor_shape.map(|cs| cs.get_shape_device(&pointer, queue));
+                    PointerState::new(pointer, shape_device, seat)
+                });
             },
-            Capability::Touch if self.touch.is_none() => {
-                self.touch = self.protocol_states.seat.get_touch(queue, &seat).ok();
+            Capability::Touch if seat_state.touch.is_none() => {
+                let touch = self.protocol_states.seat.get_touch(queue, &seat).ok();
+                seat_state.touch = touch.map(|touch| TouchState::new(touch, seat));
             },
             _ => (),
         }
@@ -242,28 +325,30 @@ impl SeatHandler for State {
         seat: WlSeat,
         capability: Capability,
     ) {
+        let seat_state = self.seats.seat_mut(seat);
         match capability {
             Capability::Keyboard => {
-                self.keyboard = None;
+                // Cancel key repetition before releasing the keyboard.
+                if let Some(mut keyboard) = seat_state.keyboard.take() {
+                    keyboard.cancel_repeat(&self.event_loop);
+                }
 
                 // Remove IME handler for this seat.
-                self.text_input.retain(|text_input| text_input.seat != seat);
+                seat_state.text_input = None;
             },
             Capability::Pointer => {
-                if let Some(pointer) = self.pointer.take() {
-                    pointer.release();
-                }
+                seat_state.pointer = None;
             },
             Capability::Touch => {
-                if let Some(touch) = self.touch.take() {
-                    touch.release();
-                }
+                seat_state.touch = None;
             },
             _ => (),
         }
     }
 
-    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: WlSeat) {}
+    fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, seat: WlSeat) {
+        self.seats.remove(&self.event_loop, &seat);
+    }
 }
 delegate_seat!(State);
 
@@ -285,11 +370,11 @@ impl KeyboardHandler for State {
         &mut self,
         _connection: &Connection,
         _queue: &QueueHandle<Self>,
-        _keyboard: &WlKeyboard,
+        keyboard: &WlKeyboard,
         _surface: &WlSurface,
         _serial: u32,
     ) {
-        let keyboard_state = match &mut self.keyboard {
+        let keyboard_state = match self.seats.keyboard_mut(keyboard) {
             Some(keyboard_state) => keyboard_state,
             None => return,
         };
@@ -304,29 +389,35 @@ impl KeyboardHandler for State {
         &mut self,
         _connection: &Connection,
         _queue: &QueueHandle<Self>,
-        _keyboard: &WlKeyboard,
+        keyboard: &WlKeyboard,
         _serial: u32,
         event: KeyEvent,
     ) {
-        let keyboard_state = match &mut self.keyboard {
+        let keyboard_state = match self.seats.keyboard_mut(keyboard) {
             Some(keyboard_state) => keyboard_state,
             None => return,
         };
-        keyboard_state.press_key(&self.event_loop, event.time, event.raw_code, event.keysym);
+        // Ignore control characters, which are handled through their keysym.
+        let text = event.utf8.filter(|text| !text.contains(|c: char| c.is_control() && c != '\t'));
+
+        let (raw, keysym) = (event.raw_code, event.keysym);
+        let focus = self.window.keyboard_focus();
+        keyboard_state.press_key(&self.event_loop, focus, raw, keysym, text.clone());
 
         // Update pressed keys.
-        self.window.press_key(event.raw_code, event.keysym, keyboard_state.modifiers);
+        let modifiers = keyboard_state.modifiers;
+        self.window.press_key(&self.config, raw, keysym, text.as_deref(), modifiers);
     }
 
     fn release_key(
         &mut self,
         _connection: &Connection,
         _queue: &QueueHandle<Self>,
-        _keyboard: &WlKeyboard,
+        keyboard: &WlKeyboard,
         _serial: u32,
         event: KeyEvent,
     ) {
-        let keyboard_state = match &mut self.keyboard {
+        let keyboard_state = match self.seats.keyboard_mut(keyboard) {
             Some(keyboard_state) => keyboard_state,
             None => return,
         };
@@ -337,31 +428,37 @@ impl KeyboardHandler for State {
         &mut self,
         _connection: &Connection,
         _queue: &QueueHandle<Self>,
-        _keyboard: &WlKeyboard,
+        keyboard: &WlKeyboard,
         _serial: u32,
         event: KeyEvent,
     ) {
-        let keyboard_state = match &mut self.keyboard {
+        let keyboard_state = match self.seats.keyboard_mut(keyboard) {
             Some(keyboard_state) => keyboard_state,
             None => return,
         };
-        keyboard_state.press_key(&self.event_loop, event.time, event.raw_code, event.keysym);
+        // Ignore control characters, which are handled through their keysym.
+        let text = event.utf8.filter(|text| !text.contains(|c: char| c.is_control() && c != '\t'));
+
+        let (raw, keysym) = (event.raw_code, event.keysym);
+        let focus = self.window.keyboard_focus();
+        keyboard_state.press_key(&self.event_loop, focus, raw, keysym, text.clone());
 
         // Update pressed keys.
-        self.window.press_key(event.raw_code, event.keysym, keyboard_state.modifiers);
+        let modifiers = keyboard_state.modifiers;
+        self.window.press_key(&self.config, raw, keysym, text.as_deref(), modifiers);
     }
 
     fn update_modifiers(
         &mut self,
         _connection: &Connection,
         _queue: &QueueHandle<Self>,
-        _keyboard: &WlKeyboard,
+        keyboard: &WlKeyboard,
         _serial: u32,
         modifiers: Modifiers,
         _raw_modifiers: RawModifiers,
         _layout: u32,
     ) {
-        let keyboard_state = match &mut self.keyboard {
+        let keyboard_state = match self.seats.keyboard_mut(keyboard) {
             Some(keyboard_state) => keyboard_state,
             None => return,
         };
@@ -374,16 +471,16 @@ impl KeyboardHandler for State {
         &mut self,
         _connection: &Connection,
         _queue: &QueueHandle<Self>,
-        _keyboard: &WlKeyboard,
+        keyboard: &WlKeyboard,
         repeat_info: RepeatInfo,
     ) {
-        let keyboard_state = match &mut self.keyboard {
+        let keyboard_state = match self.seats.keyboard_mut(keyboard) {
             Some(keyboard_state) => keyboard_state,
             None => return,
         };
 
         // Update keyboard repeat state.
-        keyboard_state.repeat_info = repeat_info;
+        keyboard_state.set_repeat_info(&self.event_loop, repeat_info);
     }
 }
 delegate_keyboard!(State);
@@ -393,41 +490,110 @@ impl TouchHandler for State {
         &mut self,
         _connection: &Connection,
         _queue: &QueueHandle<Self>,
-        _touch: &WlTouch,
-        _serial: u32,
+        touch: &WlTouch,
+        serial: u32,
         time: u32,
         _surface: WlSurface,
-        _id: i32,
+        id: i32,
         position: (f64, f64),
     ) {
-        self.window.touch_down(&self.config, time, position.into());
+        let touch = match self.seats.touch_mut(touch) {
+            Some(touch) => touch,
+            None => return,
+        };
+
+        let position = Logical(position.into());
+        touch.points.push((id, position));
+
+        match touch.points.len() {
+            // Use the first touch point for text interaction.
+            1 => {
+                touch.top_edge = self.window.is_top_edge(position);
+                touch.gesture = false;
+                self.window.touch_down(&self.config, time, position);
+            },
+            // Move the window when dragging two fingers from its top edge.
+            2 if touch.top_edge => self.window.start_move(&touch.seat, serial),
+            // Handle other two-finger touches as pinch and scroll gestures.
+            2 => {
+                touch.gesture = true;
+                if let Some(points) = touch.gesture_points() {
+                    self.window.gesture_start(points);
+                }
+            },
+            _ => (),
+        }
     }
 
     fn motion(
         &mut self,
         _connection: &Connection,
         _queue: &QueueHandle<Self>,
-        _touch: &WlTouch,
-        _time: u32,
-        _id: i32,
+        touch: &WlTouch,
+        time: u32,
+        id: i32,
         position: (f64, f64),
     ) {
-        self.window.touch_motion(&self.config, position.into());
+        let touch = match self.seats.touch_mut(touch) {
+            Some(touch) => touch,
+            None => return,
+        };
+
+        let position = Logical(position.into());
+        let index = touch.points.iter().position(|(point, _)| *point == id);
+        if let Some(index) = index {
+            touch.points[index].1 = position;
+        }
+
+        if touch.gesture {
+            if let Some(points) = touch.gesture_points() {
+                self.window.gesture_motion(points);
+            }
+        } else if index == Some(0) {
+            self.window.touch_motion(&self.config, time, position);
+        }
     }
 
     fn up(
         &mut self,
         _connection: &Connection,
         _queue: &QueueHandle<Self>,
-        _touch: &WlTouch,
+        touch: &WlTouch,
         _serial: u32,
-        _time: u32,
-        _id: i32,
+        time: u32,
+        id: i32,
     ) {
-        self.window.touch_up();
+        let touch = match self.seats.touch_mut(touch) {
+            Some(touch) => touch,
+            None => return,
+        };
+
+        let is_primary = touch.points.first().is_some_and(|(point, _)| *point == id);
+        touch.points.retain(|(point, _)| *point != id);
+
+        if !touch.gesture {
+            if is_primary {
+                self.window.touch_up(&self.config, time);
+            }
+            return;
+        }
+
+        // Restart the gesture with the remaining touch points, ignoring the last
+        // finger until all of them were lifted.
+        match touch.gesture_points() {
+            Some(points) => self.window.gesture_start(points),
+            None => self.window.gesture_end(),
+        }
+        touch.gesture = !touch.points.is_empty();
     }
 
-    fn cancel(&mut self, _connection: &Connection, _queue: &QueueHandle<Self>, _touch: &WlTouch) {}
+    fn cancel(&mut self, _connection: &Connection, _queue: &QueueHandle<Self>, touch: &WlTouch) {
+        if let Some(touch) = self.seats.touch_mut(touch) {
+            touch.points.clear();
+            touch.gesture = false;
+        }
+        self.window.gesture_end();
+    }
 
     fn shape(
         &mut self,
@@ -457,17 +623,43 @@ impl PointerHandler for State {
         &mut self,
         _connection: &Connection,
         _queue: &QueueHandle<Self>,
-        _pointer: &WlPointer,
+        pointer: &WlPointer,
         events: &[PointerEvent],
     ) {
+        let (pointer, modifiers) = match self.seats.pointer_mut(pointer) {
+            Some((pointer, modifiers)) => (pointer, modifiers),
+            None => return,
+        };
+
         for event in events {
+            // Update cursor shape based on the hovered element.
+            match event.kind {
+                PointerEventKind::Enter { serial } => pointer.enter(serial),
+                PointerEventKind::Leave { .. } => continue,
+                _ => (),
+            }
+            pointer.set_shape(self.window.cursor_shape(Logical(event.position.into())));
+
             // Dispatch event to the window.
             match event.kind {
-                PointerEventKind::Press { time, button: BTN_LEFT, .. } => {
-                    self.window.touch_down(&self.config, time, event.position.into());
+                PointerEventKind::Press { time, button: BTN_LEFT, serial } => {
+                    let position = Logical(event.position.into());
+                    match self.window.resize_edge(position) {
+                        // Move the window with Alt+drag.
+                        _ if modifiers.alt => self.window.start_move(&pointer.seat, serial),
+                        // Resize the window when dragging its edges.
+                        Some(edge) => self.window.start_resize(&pointer.seat, serial, edge),
+                        None => self.window.touch_down(&self.config, time, position),
+                    }
+                },
+                PointerEventKind::Release { time, button: BTN_LEFT, .. } => {
+                    self.window.touch_up(&self.config, time);
+                },
+                PointerEventKind::Press { button: BTN_RIGHT, .. } => {
+                    self.window.secondary_click(&self.config, Logical(event.position.into()));
                 },
-                PointerEventKind::Release { button: BTN_LEFT, .. } => {
-                    self.window.touch_up();
+                PointerEventKind::Axis { vertical, .. } => {
+                    self.window.scroll(vertical.absolute, modifiers);
                 },
                 _ => (),
             }
@@ -511,10 +703,10 @@ impl DataSourceHandler for State {
         _: &Connection,
         _: &QueueHandle<Self>,
         _: &WlDataSource,
-        _: String,
+        mime_type: String,
         mut pipe: WritePipe,
     ) {
-        let _ = pipe.write_all(self.clipboard.text.as_bytes());
+        let _ = pipe.write_all(self.clipboard.payload(&mime_type));
     }
 
     fn cancelled(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &WlDataSource) {}
@@ -561,7 +753,7 @@ impl TextInputManager {
     /// Get a new text input handle.
     fn text_input(&self, queue: &QueueHandle<State>, seat: WlSeat) -> TextInput {
         let _text_input = self.manager.get_text_input(&seat, queue, Default::default());
-        TextInput { _text_input, seat }
+        TextInput { _text_input }
     }
 }
 
@@ -590,7 +782,6 @@ struct TextInputState {
 /// Interface for the zwp_text_input_v3 protocol.
 pub struct TextInput {
     _text_input: ZwpTextInputV3,
-    seat: WlSeat,
 }
 
 impl Dispatch<ZwpTextInputV3, Arc<Mutex<TextInputState>>> for State {
@@ -0,0 +1,68 @@
+//! Handling of the presentation time protocol.
+
+use std::time::Duration;
+
+use _presentation::wp_presentation::WpPresentation;
+use _presentation::wp_presentation_feedback::{Event as FeedbackEvent, WpPresentationFeedback};
+use smithay_client_toolkit::globals::GlobalData;
+use smithay_client_toolkit::reexports::client::globals::{BindError, GlobalList};
+use smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface;
+use smithay_client_toolkit::reexports::client::{
+    Connection, Dispatch, Proxy, QueueHandle, delegate_dispatch,
+};
+use smithay_client_toolkit::reexports::protocols::wp::presentation_time::client as _presentation;
+
+use crate::State;
+
+/// Presentation time manager.
+#[derive(Clone, Debug)]
+pub struct Presentation {
+    presentation: WpPresentation,
+}
+
+impl Presentation {
+    /// Create new presentation time manager.
+    pub fn new(globals: &GlobalList, queue_handle: &QueueHandle<State>) -> Result<Self, BindError> {
+        let presentation = globals.bind(queue_handle, 1..=1, GlobalData)?;
+        Ok(Self { presentation })
+    }
+
+    /// Request presentation feedback for the surface's next commit.
+    pub fn feedback(&self, queue_handle: &QueueHandle<State>, surface: &WlSurface) {
+        self.presentation.feedback(surface, queue_handle, GlobalData);
+    }
+}
+
+impl Dispatch<WpPresentation, GlobalData, State> for Presentation {
+    fn event(
+        _: &mut State,
+        _: &WpPresentation,
+        _: <WpPresentation as Proxy>::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        // The clock ID is ignored, since only differences between timestamps
+        // are used.
+    }
+}
+
+impl Dispatch<WpPresentationFeedback, GlobalData, State> for Presentation {
+    fn event(
+        state: &mut State,
+        _: &WpPresentationFeedback,
+        event: FeedbackEvent,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<State>,
+    ) {
+        if let FeedbackEvent::Presented { tv_sec_hi, tv_sec_lo, tv_nsec, refresh, .. } = event {
+            let secs = (u64::from(tv_sec_hi) << 32) | u64::from(tv_sec_lo);
+            let time = Duration::new(secs, tv_nsec);
+            state.window.presented(time, Duration::from_nanos(refresh.into()));
+        }
+    }
+}
+
+delegate_dispatch!(State: [WpPresentation: GlobalData] => Presentation);
+delegate_dispatch!(State: [WpPresentationFeedback: GlobalData] => Presentation);
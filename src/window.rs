@@ -1,65 +1,215 @@
 //! Wayland window rendering.
 
-use std::mem;
-use std::ptr::NonNull;
-
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::{fs, iter, mem};
+
+use _blur::org_kde_kwin_blur::OrgKdeKwinBlur;
+use _cursor_shape::wp_cursor_shape_device_v1::Shape;
+use _idle_notify::ext_idle_notification_v1::ExtIdleNotificationV1;
 use _text_input::zwp_text_input_v3::{ChangeCause, ContentHint, ContentPurpose, ZwpTextInputV3};
-use calloop::LoopHandle;
-use glutin::display::{Display, DisplayApiPreference};
-use raw_window_handle::{RawDisplayHandle, WaylandDisplayHandle};
-use skia_safe::Color4f;
+use accesskit::ActionRequest;
+use calloop::timer::{TimeoutAction, Timer};
+use calloop::{LoopHandle, RegistrationToken};
+use chrono::Local;
+use skia_safe::textlayout::{
+    FontCollection, Paragraph, ParagraphBuilder, ParagraphStyle, TextStyle,
+};
+use skia_safe::{Canvas as SkiaCanvas, Color4f, IRect, Paint, Point, Rect};
 use smithay_client_toolkit::compositor::{CompositorState, Region};
+use smithay_client_toolkit::reexports::client::protocol::wl_output::WlOutput;
+use smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat;
 use smithay_client_toolkit::reexports::client::{Connection, QueueHandle};
+use smithay_client_toolkit::reexports::protocols::ext::idle_notify::v1::client as _idle_notify;
+use smithay_client_toolkit::reexports::protocols::wp::cursor_shape::v1::client as _cursor_shape;
 use smithay_client_toolkit::reexports::protocols::wp::text_input::zv3::client as _text_input;
 use smithay_client_toolkit::reexports::protocols::wp::viewporter::client::wp_viewport::WpViewport;
+use smithay_client_toolkit::reexports::protocols::xdg::shell::client::xdg_toplevel::ResizeEdge;
 use smithay_client_toolkit::seat::keyboard::{Keysym, Modifiers};
 use smithay_client_toolkit::shell::WaylandSurface;
 use smithay_client_toolkit::shell::xdg::window::{Window as XdgWindow, WindowDecorations};
-
-use crate::config::Config;
-use crate::geometry::{Position, Size};
+use tracing::{error, info, warn};
+use unicode_segmentation::UnicodeSegmentation;
+use wayland_protocols_plasma::blur::client as _blur;
+
+use crate::accessibility::{Accessibility, AccessibleAction};
+use crate::background::Background;
+use crate::config::{self, Colors, Config, ContentHints, FontFamily, Layout, SaveMode};
+use crate::editor::{self, LineKind, TAG_MARKER};
+use crate::geometry::{Logical, Physical, Position, Size};
+use crate::header::{Header, HeaderAction};
+use crate::menu::Menu;
+use crate::metadata::Metadata;
+use crate::print::Printer;
+use crate::profile::{Phase, Profiler};
+use crate::recovery::{self, Replay};
+use crate::reminder::{self, Reminders};
 use crate::renderer::Renderer;
-use crate::skia::Canvas;
+use crate::search::SearchIndex;
+use crate::search_view::SearchView;
+use crate::session::Session;
+use crate::storage::TrashedNote;
 use crate::text_box::TextBox;
+use crate::trash_view::{TrashAction, TrashView};
 use crate::wayland::ProtocolStates;
-use crate::{Error, State};
+use crate::wayland::blur::BlurManager;
+use crate::wayland::idle_notify::IdleNotifier;
+use crate::wayland::presentation::Presentation;
+use crate::{Error, State, skia, storage};
 
-/// Horizontal padding reserved for bullet points at scale 1.
-///
-/// This includes the space of the bullet point itself, so it should always be
-/// bigger than `BULLET_POINT_SIZE`.
-///
-/// It is not distributed around the bullet point evenly, but instead the bullet
-/// point is aligned to the left. This helps with balancing this setting and
-/// `PADDING`.
-pub const BULLET_POINT_PADDING: f32 = f32::max(BULLET_POINT_SIZE, 15.);
+/// Debounce period before session state is persisted to disk.
+const SESSION_DEBOUNCE: Duration = Duration::from_millis(1000);
+
+/// Debounce period before the window title follows note changes.
+const TITLE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Maximum number of characters of the note preview in the window title.
+const MAX_TITLE_LENGTH: usize = 50;
+
+/// Opacity of the overlay dimming the window while the user is idle.
+const IDLE_DIM_ALPHA: f32 = 0.5;
+
+/// Window title used for empty notes.
+const DEFAULT_TITLE: &str = "Pinax";
+
+/// Header notice shown after the note was replaced by an external change.
+const EXTERNAL_CHANGE_NOTICE: &str = "External change applied — Ctrl+Z to undo";
+
+/// Header notice shown after restoring unsaved changes from a crash.
+const RECOVERY_NOTICE: &str = "Unsaved changes recovered — Ctrl+Z to undo";
+
+/// Toast shown on startup while there are no notes.
+const FIRST_RUN_NOTICE: &str = "Import existing notes with `pinax import <PATH>`";
+
+/// Duration a toast is shown before it starts fading out.
+const TOAST_DURATION: Duration = Duration::from_millis(3000);
+
+/// Duration of a toast's fade-out animation.
+const TOAST_FADE: Duration = Duration::from_millis(300);
+
+/// Distance between toasts and the top of the text area at scale 1.
+const TOAST_MARGIN: f64 = 10.;
+
+/// Padding around a toast's message at scale 1.
+const TOAST_PADDING: f64 = 10.;
+
+/// Text zoom change per zoom shortcut or scroll step.
+const ZOOM_STEP: f64 = 0.1;
 
-/// Size of the bullet points at scale 1.
-pub const BULLET_POINT_SIZE: f32 = 5.;
+/// Entries of the header bar's overflow menu.
+const OVERFLOW_ENTRIES: [&str; 12] = [
+    "Rename note",
+    "Search notes",
+    "Toggle statistics",
+    "Toggle numbering",
+    "Color text",
+    "Expand date",
+    "Paste as items",
+    "Print note",
+    "Copy as image",
+    "Save as image",
+    "Delete note",
+    "Recently deleted",
+];
 
-/// Padding around the text box at scale 1.
-const PADDING: f64 = 15.;
+/// Menu entry for adding a misspelled word to the dictionary.
+const ADD_TO_DICTIONARY: &str = "Add to dictionary";
+
+/// Menu entry for removing the color of a text span.
+const REMOVE_COLOR: &str = "No color";
+
+/// Minimum window size hint at scale 1.
+const MIN_SIZE: Logical<Size> = Logical(Size { width: 200, height: 200 });
+
+/// Width of the window's pointer resize area at scale 1.
+const RESIZE_BORDER: f64 = 8.;
+
+/// Height of the area starting touch window movement at scale 1.
+const TOUCH_MOVE_EDGE: f64 = 20.;
+
+/// Height of the bottom area starting the quick-add swipe at scale 1.
+const QUICK_ADD_EDGE: f64 = 20.;
+
+/// Upward distance of a bottom edge swipe before a new item is added at scale 1.
+const QUICK_ADD_DISTANCE: f64 = 50.;
+
+/// Multiplier of the padding around the text box while fullscreen.
+const FULLSCREEN_PADDING_FACTOR: f64 = 2.;
 
 /// Wayland window.
 pub struct Window {
     pub queue: QueueHandle<State>,
     pub initial_configure_done: bool,
     pub text_box: TextBox,
+    pub reminders: Reminders,
 
     connection: Connection,
     xdg_window: XdgWindow,
     viewport: WpViewport,
     renderer: Renderer,
 
+    presentation: Option<Presentation>,
+    frame_clock: FrameClock,
+
     ime_cause: Option<ChangeCause>,
     text_input: Option<TextInput>,
 
-    background: Color4f,
-    canvas: Canvas,
+    accessibility: Accessibility,
+
+    event_loop: LoopHandle<'static, State>,
+    session_token: Option<RegistrationToken>,
+    session: Session,
+    title_token: Option<RegistrationToken>,
+    title: String,
+    rollover_token: Option<RegistrationToken>,
+
+    config_colors: Colors,
+    keyboard_height: u32,
+    layout: Layout,
+    content_hints: ContentHints,
+    config: Config,
+    max_text_width: Option<u32>,
+    metadata: Metadata,
+    header: Header,
+    toasts: Toasts,
+    printer: Printer,
+
+    spelling_menu: Option<SpellingMenu>,
+    overflow_menu: Option<Menu>,
+    color_menu: Option<ColorMenu>,
+    show_stats: bool,
+    profiler: Profiler,
+    search: Option<SearchView>,
+    trash: Option<TrashView>,
+    overlay_touch: bool,
+    gesture: Option<Gesture>,
+    quick_add_start: Option<f64>,
+
+    background: Background,
+    opaque_region: Option<Region>,
+    blur_manager: Option<BlurManager>,
+    blur: Option<OrgKdeKwinBlur>,
+    opaque: bool,
+
+    idle_notifier: Option<IdleNotifier>,
+    idle_notification: Option<ExtIdleNotificationV1>,
+    idle_dim_secs: Option<u32>,
+    idle: bool,
+
+    outputs: Vec<WlOutput>,
+    offscreen: bool,
+    suspended: bool,
+    paused: bool,
+
+    fullscreen: bool,
+    maximized: bool,
 
     stalled: bool,
+    hidden: bool,
     dirty: bool,
-    size: Size,
+    size: Logical<Size>,
     scale: f64,
 }
 
@@ -71,12 +221,6 @@ impl Window {
         queue: QueueHandle<State>,
         config: &Config,
     ) -> Result<Self, Error> {
-        // Get EGL display.
-        let display = NonNull::new(connection.backend().display_ptr().cast()).unwrap();
-        let wayland_display = WaylandDisplayHandle::new(display);
-        let raw_display = RawDisplayHandle::Wayland(wayland_display);
-        let egl_display = unsafe { Display::new(raw_display, DisplayApiPreference::Egl)? };
-
         // Create surface's Wayland global handles.
         let surface = protocol_states.compositor.create_surface(&queue);
         if let Some(fractional_scale) = &protocol_states.fractional_scale {
@@ -90,78 +234,283 @@ impl Window {
             WindowDecorations::RequestClient,
             &queue,
         );
-        xdg_window.set_title("Pinax");
+        xdg_window.set_title(DEFAULT_TITLE);
         xdg_window.set_app_id("Pinax");
-        xdg_window.commit();
 
-        // Create OpenGL renderer.
-        let renderer = Renderer::new(egl_display, surface);
+        // Avoid sizes too small for the header and a few lines of text, while
+        // leaving the maximum size unconstrained to allow tiling and maximizing.
+        xdg_window.set_min_size(Some((MIN_SIZE.width, MIN_SIZE.height)));
+        xdg_window.set_max_size(None);
 
-        // Default to a reasonable default size.
-        let size = Size { width: 360, height: 720 };
+        xdg_window.commit();
 
-        Ok(Self {
+        // Create the renderer, falling back to software rendering without OpenGL.
+        let backend = config.general.renderer;
+        let renderer = Renderer::new(backend, &connection, &protocol_states.shm, surface)?;
+
+        // Restore the previous session, or default to the configured size.
+        let session = Session::load();
+        let default_size = Logical(Size::new(config.general.width, config.general.height));
+        let mut size = session.window_size.unwrap_or(default_size);
+        size.width = size.width.max(MIN_SIZE.width);
+        size.height = size.height.max(MIN_SIZE.height);
+
+        // Open the note storage.
+        let storage_path = config.general.storage_path();
+        let storage_dir = storage_path.parent().ok_or(Error::InvalidStoragePath)?;
+        let storage = storage::new(config, storage_dir)?;
+        storage::purge_expired(&*storage, storage_dir, config.general.trash_retention_days);
+        let first_run = storage.list(storage_dir).is_ok_and(|notes| notes.is_empty());
+
+        // Schedule reminders of all notes.
+        let reminders =
+            Reminders::new(event_loop.clone(), &*storage, storage_dir, session.reminder_check);
+
+        // Apply note-specific metadata.
+        let metadata = Metadata::load(&*storage, &storage_path);
+        let colors = metadata.colors.apply(&config.colors);
+        let header = Header::new(config, &colors, metadata.title(&storage_path));
+        let mut toasts = Toasts::new(config, &colors);
+
+        // Point out the importer while there are no notes yet.
+        if first_run {
+            toasts.push(FIRST_RUN_NOTICE.into());
+        }
+        let printer = Printer::new(&event_loop, config);
+
+        let mut text_box = TextBox::new(event_loop.clone(), config, &colors, storage)?;
+        text_box.restore_session(&session);
+
+        // Expose the window content to assistive technologies.
+        let accessibility = Accessibility::new(&event_loop);
+
+        let mut window = Self {
+            event_loop,
+            text_box,
+            reminders,
+            session,
+            metadata,
+            header,
+            toasts,
+            printer,
             connection,
             xdg_window,
             viewport,
             renderer,
             queue,
+            presentation: protocol_states.presentation.clone(),
+            blur_manager: protocol_states.blur.clone(),
+            idle_notifier: protocol_states.idle_notifier.clone(),
+            accessibility,
+            title: DEFAULT_TITLE.into(),
             size,
-            background: config.colors.background.as_color4f(),
-            text_box: TextBox::new(event_loop, config)?,
+            background: Background::new(&colors.background),
+            config_colors: config.colors.clone(),
+            keyboard_height: config.input.keyboard_height,
+            layout: config.layout,
+            content_hints: config.input.content_hints.clone(),
+            config: config.clone(),
+            max_text_width: config.general.max_text_width,
             stalled: true,
+            opaque: true,
             dirty: true,
             scale: 1.,
             initial_configure_done: Default::default(),
+            spelling_menu: Default::default(),
+            overflow_menu: Default::default(),
+            color_menu: Default::default(),
+            show_stats: Default::default(),
+            profiler: Default::default(),
+            search: Default::default(),
+            trash: Default::default(),
+            overlay_touch: Default::default(),
+            gesture: Default::default(),
+            quick_add_start: Default::default(),
+            hidden: Default::default(),
+            outputs: Default::default(),
+            offscreen: Default::default(),
+            suspended: Default::default(),
+            paused: Default::default(),
+            fullscreen: Default::default(),
+            maximized: Default::default(),
             text_input: Default::default(),
+            session_token: Default::default(),
+            title_token: Default::default(),
+            rollover_token: Default::default(),
             ime_cause: Default::default(),
-            canvas: Default::default(),
-        })
+            frame_clock: Default::default(),
+            opaque_region: Default::default(),
+            blur: Default::default(),
+            idle_notification: Default::default(),
+            idle_dim_secs: Default::default(),
+            idle: Default::default(),
+        };
+        window.update_translucency(&colors);
+        window.apply_config();
+        window.schedule_rollover();
+
+        Ok(window)
     }
 
     /// Redraw the window.
     pub fn draw(&mut self) {
-        // Stall rendering if nothing changed since last redraw.
-        if !self.dirty() || !self.initial_configure_done {
+        // Stall rendering if nothing changed since last redraw, or while invisible.
+        if !self.dirty() || !self.initial_configure_done || self.paused {
             self.stalled = true;
             return;
         }
-        self.dirty = false;
+
+        let frame = self.profiler.start(Phase::Frame);
+
+        // Indicate unsaved changes, which persist until the next manual save.
+        let manual = self.text_box.save_mode() == SaveMode::Manual;
+        self.dirty |= self.header.set_unsaved(manual && self.text_box.unsaved());
+        self.dirty |= self.header.set_overwrite(self.text_box.overwrite());
+
+        // Advance toasts, scheduling a redraw once a new toast starts fading.
+        let now = Instant::now();
+        self.dirty |= self.toasts.update(now);
+        if self.toasts.start(now) {
+            self.schedule_toast_fade();
+            self.dirty = true;
+        }
+
+        // Redraw everything while the overlay is visible, to keep it up to date.
+        let full_damage = mem::take(&mut self.dirty) || self.profiler.overlay();
+
+        // Advance animations to the time this frame will be presented.
+        self.text_box.animate(self.frame_clock.interval());
+
+        // Update text box's physical dimensions.
+        self.text_box.set_size(*self.text_size());
+        self.text_box.set_scale_factor(self.scale);
+        let origin = self.text_origin();
+
+        // Update text layout and get the damaged region.
+        let layout = self.profiler.start(Phase::Layout);
+        let physical_size = self.size.to_physical(self.scale);
+        let mut damage = match self.text_box.layout(*origin) {
+            Some(mut damage) if !full_damage => {
+                let bounds =
+                    Rect::from_iwh(physical_size.width as i32, physical_size.height as i32);
+                if !damage.intersect(bounds) {
+                    damage = Rect::new_empty();
+                }
+                Some(damage.round_out())
+            },
+            _ => None,
+        };
+        self.profiler.record(Phase::Layout, layout.finish());
+
+        // Update text statistics, redrawing the header on change.
+        if self.show_stats
+            && self.header.set_stats(Some(self.text_box.stats()))
+            && let Some(damage) = &mut damage
+        {
+            let header_width = physical_size.width as i32;
+            damage.join(IRect::from_wh(header_width, self.header.height() as i32));
+        }
 
         // Update IME state.
         if self.text_box.take_text_input_dirty() {
             self.update_text_input();
         }
 
+        // Skip rendering if nothing visible changed.
+        if damage.is_some_and(|damage| damage.is_empty()) {
+            self.stalled = true;
+            self.stage_session_write();
+            self.stage_title_update();
+            self.update_accessibility();
+            return;
+        }
+
         // Update viewporter logical render size.
         //
         // NOTE: This must be done every time we draw with Sway; it is not
         // persisted when drawing with the same surface multiple times.
         self.viewport.set_destination(self.size.width as i32, self.size.height as i32);
 
-        // Mark entire window as damaged.
-        let wl_surface = self.xdg_window.wl_surface();
-        wl_surface.damage(0, 0, self.size.width as i32, self.size.height as i32);
-
-        // Update text box's physical dimensions.
-        self.text_box.set_size(self.text_size());
-        self.text_box.set_scale_factor(self.scale);
-        let origin = self.text_origin();
-
         // Render the window content.
-        let physical_size = self.size * self.scale;
-        self.renderer.draw(physical_size, |renderer| {
-            self.canvas.draw(renderer.skia_config(), physical_size, |canvas| {
-                canvas.clear(self.background);
-                self.text_box.draw(canvas, origin);
-            });
+        //
+        // Surface damage is submitted by the renderer on buffer swap.
+        let mut paint_time = Duration::ZERO;
+        let submit = self.profiler.start(Phase::Submit);
+        self.renderer.draw(*physical_size, damage, |canvas, repaint_region| {
+            let paint = self.profiler.start(Phase::Paint);
+
+            // Only redraw outdated regions of the buffer.
+            canvas.save();
+            if let Some(repaint_region) = repaint_region {
+                canvas.clip_irect(repaint_region, None);
+            }
+
+            self.background.draw(canvas, *physical_size);
+            self.text_box.draw(canvas);
+            self.header.draw(canvas, physical_size.width);
+            if let Some(spelling_menu) = &self.spelling_menu {
+                spelling_menu.menu.draw(canvas);
+            }
+            if let Some(search) = &mut self.search {
+                let top = self.header.height() as f32;
+                let (width, height) = (physical_size.width as f32, physical_size.height as f32);
+                search.draw(canvas, Rect::new(0., top, width, height), self.scale);
+            }
+            if let Some(trash) = &mut self.trash {
+                let top = self.header.height() as f32;
+                let (width, height) = (physical_size.width as f32, physical_size.height as f32);
+                trash.draw(canvas, Rect::new(0., top, width, height), self.scale);
+            }
+            if let Some(overflow_menu) = &self.overflow_menu {
+                overflow_menu.draw(canvas);
+            }
+            if let Some(color_menu) = &self.color_menu {
+                color_menu.menu.draw(canvas);
+            }
+            let top = self.header.height() as f32;
+            self.toasts.draw(canvas, physical_size.width, top, now);
+
+            // Dim the entire window while the user is idle.
+            if self.idle {
+                let mut paint = Paint::default();
+                paint.set_color4f(Color4f::new(0., 0., 0., IDLE_DIM_ALPHA), None);
+                let (width, height) = (physical_size.width as f32, physical_size.height as f32);
+                canvas.draw_rect(Rect::new(0., 0., width, height), &paint);
+            }
+
+            self.profiler.draw_overlay(canvas, physical_size.width, top, self.scale);
+
+            canvas.restore();
+
+            paint_time = paint.finish();
         });
 
+        // Exclude painting from the submission, since it happens within the renderer.
+        let submit_time = submit.finish().saturating_sub(paint_time);
+        self.profiler.record(Phase::Paint, paint_time);
+        self.profiler.record(Phase::Submit, submit_time);
+
+        let flush = self.profiler.start(Phase::Flush);
+
         // Request a new frame.
+        let wl_surface = self.xdg_window.wl_surface();
         wl_surface.frame(&self.queue, wl_surface.clone());
 
+        // Request presentation time, to time animations.
+        if let Some(presentation) = &self.presentation {
+            presentation.feedback(&self.queue, wl_surface);
+        }
+
         // Apply surface changes.
         wl_surface.commit();
+
+        self.profiler.record(Phase::Flush, flush.finish());
+        self.profiler.record(Phase::Frame, frame.finish());
+
+        // Update persisted session state.
+        self.stage_session_write();
+        self.stage_title_update();
+        self.update_accessibility();
     }
 
     /// Unstall the renderer.
@@ -179,9 +528,141 @@ impl Window {
         let _ = self.connection.flush();
     }
 
+    /// Handle frame presentation feedback.
+    pub fn presented(&mut self, time: Duration, refresh: Duration) {
+        self.frame_clock.presented(time, refresh);
+    }
+
+    /// Unmap the window, while keeping the application running.
+    ///
+    /// This releases all GPU buffers until the window is shown again.
+    pub fn hide(&mut self) {
+        if mem::replace(&mut self.hidden, true) {
+            return;
+        }
+
+        // Free GPU resources.
+        self.renderer.release();
+
+        // Unmap the surface by committing a null buffer.
+        let wl_surface = self.xdg_window.wl_surface();
+        wl_surface.attach(None, 0, 0);
+        wl_surface.commit();
+        let _ = self.connection.flush();
+
+        // Output tracking restarts once the surface is mapped again.
+        self.outputs.clear();
+        self.offscreen = false;
+        self.suspended = false;
+        self.paused = false;
+
+        // Redraw only after the next initial configure.
+        self.initial_configure_done = false;
+        self.stalled = true;
+        self.dirty = true;
+
+        self.persist_session();
+    }
+
+    /// Remap a hidden window.
+    pub fn show(&mut self) {
+        if !mem::take(&mut self.hidden) {
+            return;
+        }
+
+        // Request a new initial configure, which will trigger a redraw.
+        self.xdg_window.commit();
+        let _ = self.connection.flush();
+    }
+
+    /// Handle the surface entering an output.
+    pub fn surface_enter(&mut self, output: WlOutput) {
+        if !self.outputs.contains(&output) {
+            self.outputs.push(output);
+        }
+        self.offscreen = false;
+
+        self.update_paused();
+    }
+
+    /// Handle the surface leaving an output.
+    pub fn surface_leave(&mut self, output: &WlOutput) {
+        let output_count = self.outputs.len();
+        self.outputs.retain(|entered| entered != output);
+
+        // Consider the window off-screen once it left its last output.
+        if self.outputs.len() < output_count && self.outputs.is_empty() {
+            self.offscreen = true;
+        }
+
+        self.update_paused();
+    }
+
+    /// Update the xdg toplevel's suspended state.
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+        self.update_paused();
+    }
+
+    /// Update the window's fullscreen and maximized state.
+    pub fn set_window_state(&mut self, fullscreen: bool, maximized: bool) {
+        self.maximized = maximized;
+
+        // Update padding, which depends on the fullscreen state.
+        if mem::replace(&mut self.fullscreen, fullscreen) != fullscreen {
+            self.dirty = true;
+            self.unstall();
+        }
+    }
+
+    /// Request fullscreen to be enabled or disabled.
+    pub fn toggle_fullscreen(&mut self) {
+        if self.fullscreen {
+            self.xdg_window.unset_fullscreen();
+        } else {
+            self.xdg_window.set_fullscreen(None);
+        }
+        let _ = self.connection.flush();
+    }
+
+    /// Request the window to be maximized or unmaximized.
+    pub fn toggle_maximized(&mut self) {
+        if self.maximized {
+            self.xdg_window.unset_maximized();
+        } else {
+            self.xdg_window.set_maximized();
+        }
+        let _ = self.connection.flush();
+    }
+
+    /// Pause or resume rendering based on the window's visibility.
+    ///
+    /// While paused, no frames are requested and all GPU buffers and cached
+    /// text layouts are released.
+    fn update_paused(&mut self) {
+        let paused = self.suspended || self.offscreen;
+        if mem::replace(&mut self.paused, paused) == paused {
+            return;
+        }
+
+        if paused {
+            info!("Pausing rendering while the window is not visible");
+
+            self.renderer.release();
+            self.text_box.release_layout();
+            self.header.release_layout();
+            self.stalled = true;
+        } else {
+            info!("Resuming rendering");
+
+            self.dirty = true;
+            self.unstall();
+        }
+    }
+
     /// Update the window's logical size.
-    pub fn set_size(&mut self, compositor: &CompositorState, size: Size) {
-        if self.size == size {
+    pub fn set_size(&mut self, compositor: &CompositorState, size: Logical<Size>) {
+        if self.size == size && self.initial_configure_done {
             return;
         }
 
@@ -195,7 +676,8 @@ impl Window {
         // atomically on redraw.
         if let Ok(region) = Region::new(compositor) {
             region.add(0, 0, size.width as i32, size.height as i32);
-            self.xdg_window.wl_surface().set_opaque_region(Some(region.wl_region()));
+            self.opaque_region = Some(region);
+            self.update_opaque_region();
         }
 
         self.unstall();
@@ -209,185 +691,1833 @@ impl Window {
         self.scale = scale;
         self.dirty = true;
 
+        self.header.set_scale_factor(scale);
+        self.toasts.set_scale_factor(scale);
+
         self.unstall();
     }
 
+    /// Get the element receiving keyboard input.
+    pub fn keyboard_focus(&self) -> KeyboardFocus {
+        if self.header.editing() {
+            KeyboardFocus::Title
+        } else if self.overflow_menu.is_some()
+            || self.spelling_menu.is_some()
+            || self.color_menu.is_some()
+        {
+            KeyboardFocus::Menu
+        } else if self.header.focus().is_some() {
+            KeyboardFocus::Header
+        } else if self.trash.is_some() {
+            KeyboardFocus::Trash
+        } else if self.search.is_some() {
+            KeyboardFocus::Search
+        } else {
+            KeyboardFocus::TextBox
+        }
+    }
+
     /// Handle config updates.
     pub fn update_config(&mut self, config: &Config) {
-        let background = config.colors.background.as_color4f();
-        if self.background != background {
-            self.background = background;
-            self.dirty = true;
+        self.config = config.clone();
+        self.apply_config();
+        self.schedule_rollover();
+        self.unstall();
+    }
+
+    /// Schedule switching to the next day's note at midnight.
+    ///
+    /// This only has an effect if the storage path contains placeholders.
+    fn schedule_rollover(&mut self) {
+        if let Some(token) = self.rollover_token.take() {
+            self.event_loop.remove(token);
         }
 
-        self.text_box.update_config(config);
+        if !self.config.general.is_path_template() {
+            return;
+        }
 
-        self.unstall();
+        let now = Local::now();
+        let midnight = now
+            .date_naive()
+            .succ_opt()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .and_then(|midnight| midnight.and_local_timezone(Local).earliest());
+        let Some(midnight) = midnight else { return };
+        let delay = (midnight - now).to_std().unwrap_or_default();
+
+        let daily_path = self.config.general.storage_path_at(now);
+        self.rollover_token = self
+            .event_loop
+            .insert_source(Timer::from_duration(delay), move |_, _, state| {
+                state.window.rollover_token = None;
+                state.window.rollover(&daily_path);
+                TimeoutAction::Drop
+            })
+            .inspect_err(|err| error!("Failed to schedule note rollover: {err}"))
+            .ok();
     }
 
-    /// Check whether UI needs redraw.
-    pub fn dirty(&self) -> bool {
-        self.dirty || self.text_box.dirty()
+    /// Switch from the previous day's note to the current one.
+    fn rollover(&mut self, previous_path: &Path) {
+        // Keep other notes open, since they were opened explicitly.
+        let path = self.config.general.storage_path();
+        if self.text_box.storage_path() == previous_path && path != previous_path {
+            info!("Switching to daily note {path:?}");
+            self.open_note(path, 0);
+        }
+
+        self.schedule_rollover();
     }
 
-    /// Handle touch press.
-    pub fn touch_down(&mut self, config: &Config, time: u32, position: Position<f64>) {
-        self.ime_cause = Some(ChangeCause::Other);
+    /// Apply the configuration, with the overrides of the open note.
+    fn apply_config(&mut self) {
+        let config = &self.config();
 
-        // Clamp padding touch to nearest text box position.
-        let text_size = self.text_size();
-        let mut physical_position = position * self.scale;
-        physical_position -= self.text_origin();
-        physical_position.x = physical_position.x.clamp(0., text_size.width as f64);
-        physical_position.y = physical_position.y.clamp(0., text_size.height as f64);
-        self.text_box.touch_down(config, time, physical_position);
+        self.config_colors = config.colors.clone();
+        self.update_colors();
 
-        self.unstall();
-    }
+        if self.keyboard_height != config.input.keyboard_height {
+            self.keyboard_height = config.input.keyboard_height;
+            self.dirty = true;
+        }
 
-    /// Handle touch release.
-    pub fn touch_motion(&mut self, config: &Config, position: Position<f64>) {
-        self.ime_cause = Some(ChangeCause::Other);
+        if self.max_text_width != config.general.max_text_width {
+            self.max_text_width = config.general.max_text_width;
+            self.dirty = true;
+        }
 
-        // Clamp padding touch to nearest text box position.
-        let text_size = self.text_size();
-        let mut physical_position = position * self.scale;
-        physical_position -= self.text_origin();
-        physical_position.x = physical_position.x.clamp(0., text_size.width as f64);
-        physical_position.y = physical_position.y.clamp(0., text_size.height as f64);
-        self.text_box.touch_motion(config, physical_position);
+        if self.layout != config.layout {
+            self.layout = config.layout;
+            self.dirty = true;
+        }
 
-        self.unstall();
-    }
+        if self.content_hints != config.input.content_hints {
+            self.content_hints = config.input.content_hints.clone();
+            self.update_text_input();
+        }
 
-    /// Handle touch release.
-    pub fn touch_up(&mut self) {
-        self.ime_cause = Some(ChangeCause::Other);
-        self.text_box.touch_up();
-        self.unstall();
-    }
+        self.update_idle_notification(config.general.idle_dim_secs);
 
-    /// Handle keyboard focus.
-    pub fn keyboard_enter(&mut self) {
-        self.text_box.set_keyboard_focus(true);
-        self.unstall();
+        self.dirty |= self.header.update_config(config);
+        self.dirty |= self.toasts.update_config(config);
+        self.printer.update_config(config);
+        self.text_box.update_config(config);
     }
 
-    /// Handle keyboard focus loss.
-    pub fn keyboard_leave(&mut self) {
-        self.text_box.set_keyboard_focus(false);
-        self.unstall();
+    /// Effective configuration of the open note.
+    pub fn config(&self) -> Config {
+        self.config.for_note(&self.note_name())
     }
 
-    /// Handle keyboard key press.
-    pub fn press_key(&mut self, _raw: u32, keysym: Keysym, modifiers: Modifiers) {
-        self.ime_cause = Some(ChangeCause::Other);
-        self.text_box.press_key(keysym, modifiers);
-        self.unstall();
+    /// Name of the open note, used to look up its configuration overrides.
+    fn note_name(&self) -> String {
+        let path = self.text_box.storage_path();
+        path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()
     }
 
-    /// Paste text into the window.
-    pub fn paste(&mut self, text: &str) {
-        self.text_box.paste(text);
+    /// Reload the note's metadata sidecar file.
+    pub fn reload_metadata(&mut self) {
+        let storage_path = self.text_box.storage_path();
+        let metadata = Metadata::load(self.text_box.storage(), storage_path);
+        self.dirty |= self.header.set_title(metadata.title(storage_path));
+        if self.metadata == metadata {
+            return;
+        }
+
+        self.metadata = metadata;
+        self.update_colors();
+
         self.unstall();
     }
 
-    /// Handle IME focus.
-    pub fn text_input_enter(&mut self, text_input: ZwpTextInputV3) {
-        self.text_input = Some(text_input.into());
-        self.text_box.set_ime_focus(true);
-        self.update_text_input();
-        self.unstall();
+    /// Apply the note's colors overrides to the configured color palette.
+    fn update_colors(&mut self) {
+        let colors = self.metadata.colors.apply(&self.config_colors);
+
+        self.dirty |= self.background.set(&colors.background);
+
+        self.dirty |= self.header.set_colors(&colors);
+        self.dirty |= self.toasts.set_colors(&colors);
+        self.text_box.set_colors(&colors);
+
+        self.update_translucency(&colors);
     }
 
-    /// Handle IME focus loss.
-    pub fn text_input_leave(&mut self) {
-        self.text_box.set_ime_focus(false);
-        self.text_input = None;
-        self.unstall();
+    /// Update surface state depending on the background's translucency.
+    fn update_translucency(&mut self, colors: &Colors) {
+        let opaque = self.background.is_opaque();
+        if mem::replace(&mut self.opaque, opaque) != opaque {
+            self.update_opaque_region();
+            self.dirty = true;
+        }
+
+        // Blur content behind translucent backgrounds, if supported.
+        let Some(blur_manager) = &self.blur_manager else { return };
+        let blur = colors.blur && !opaque;
+        if blur == self.blur.is_some() {
+            return;
+        }
+
+        let wl_surface = self.xdg_window.wl_surface();
+        match self.blur.take() {
+            Some(surface_blur) => blur_manager.unblur(wl_surface, surface_blur),
+            None => self.blur = Some(blur_manager.blur(&self.queue, wl_surface)),
+        }
+        self.dirty = true;
     }
 
-    /// Delete text around the current cursor position.
-    pub fn delete_surrounding_text(&mut self, before_length: u32, after_length: u32) {
-        self.text_box.delete_surrounding_text(before_length, after_length);
-        self.unstall();
+    /// Update the idle period before the window is dimmed.
+    fn update_idle_notification(&mut self, idle_dim_secs: Option<u32>) {
+        if self.idle_dim_secs == idle_dim_secs {
+            return;
+        }
+        self.idle_dim_secs = idle_dim_secs;
+
+        if let Some(notification) = self.idle_notification.take() {
+            notification.destroy();
+        }
+        self.set_idle(false);
+
+        if let (Some(idle_notifier), Some(secs)) = (&self.idle_notifier, idle_dim_secs) {
+            let timeout = Duration::from_secs(secs.into());
+            self.idle_notification = Some(idle_notifier.notification(&self.queue, timeout));
+        }
     }
 
-    /// Insert text at the current cursor position.
-    pub fn commit_string(&mut self, text: String) {
-        self.text_box.commit_string(&text);
+    /// Update the user's idle state.
+    pub fn set_idle(&mut self, idle: bool) {
+        if self.idle == idle {
+            return;
+        }
+        self.idle = idle;
+        self.dirty = true;
+
         self.unstall();
     }
 
-    /// Set preedit text at the current cursor position.
-    pub fn set_preedit_string(&mut self, text: String, cursor_begin: i32, cursor_end: i32) {
-        self.text_box.set_preedit_string(text, cursor_begin, cursor_end);
-        self.unstall();
+    /// Update the surface's opaque region.
+    ///
+    /// Translucent backgrounds leave the opaque region empty.
+    fn update_opaque_region(&self) {
+        let region = self.opaque_region.as_ref().filter(|_| self.opaque);
+        self.xdg_window.wl_surface().set_opaque_region(region.map(Region::wl_region));
     }
 
-    /// Persist current text content to disk.
-    pub fn persist_text(&mut self) {
-        self.text_box.persist_text();
+    /// Check whether UI needs redraw.
+    pub fn dirty(&self) -> bool {
+        self.dirty || self.text_box.dirty() || self.toasts.animating(Instant::now())
     }
 
-    /// Apply pending text input changes.
-    fn update_text_input(&mut self) {
-        let origin = self.text_origin();
+    /// Handle touch press.
+    pub fn touch_down(&mut self, config: &Config, time: u32, position: Logical<Position<f64>>) {
+        self.ime_cause = Some(ChangeCause::Other);
 
-        let text_input = match &mut self.text_input {
-            Some(text_input) => text_input,
-            None => return,
-        };
+        // Return keyboard focus to the text box.
+        self.dirty |= self.header.set_focus(None);
 
-        text_input.enable();
+        // Reset overlay state, since interactive moves might swallow the release.
+        self.overlay_touch = false;
 
-        let (text, cursor_start, cursor_end) = self.text_box.surrounding_text();
-        text_input.set_surrounding_text(text, cursor_start, cursor_end);
+        // Track swipes starting at the bottom edge for adding new items.
+        let bottom_edge = self.size.height as f64 - QUICK_ADD_EDGE;
+        self.quick_add_start = (position.y >= bottom_edge).then_some(position.y);
 
-        let cause = self.ime_cause.take().unwrap_or(ChangeCause::InputMethod);
-        text_input.set_text_change_cause(cause);
+        let physical_position = position.to_physical(self.scale);
+        let point = (*physical_position).into();
 
-        let content_hint = ContentHint::Completion
-            | ContentHint::Spellcheck
-            | ContentHint::Multiline
-            | ContentHint::AutoCapitalization;
-        text_input.set_content_type(content_hint, ContentPurpose::Normal);
+        // Handle overflow menu interactions, closing it when tapping elsewhere.
+        if let Some(overflow_menu) = self.overflow_menu.take() {
+            self.dirty = true;
 
-        // Update logical cursor rectangle.
-        if let Some(rect) = self.text_box.last_cursor_rect() {
-            let scale = self.scale as f32;
+            if let Some(index) = overflow_menu.entry_at(point) {
+                self.activate_overflow_entry(config, index);
+                self.overlay_touch = true;
+                self.unstall();
+                return;
+            }
+        }
 
-            let x = ((origin.x as f32 + rect.left) / scale).round() as i32;
-            let y = ((origin.y as f32 + rect.top) / scale).round() as i32;
-            let width = ((rect.left - rect.right) / scale).round() as i32;
-            let height = ((rect.bottom - rect.top) / scale).round() as i32;
+        // Handle color palette interactions, closing it when tapping elsewhere.
+        if let Some(color_menu) = self.color_menu.take() {
+            self.dirty = true;
 
-            text_input.set_cursor_rectangle(x, y, width, height);
+            if let Some(index) = color_menu.menu.entry_at(point) {
+                color_menu.activate(&mut self.text_box, index);
+                self.overlay_touch = true;
+                self.unstall();
+                return;
+            }
         }
 
-        text_input.commit();
-    }
+        // Handle header bar actions.
+        if let Some(action) = self.header.action_at(point) {
+            self.activate_header_action(config, action);
+            self.overlay_touch = true;
+            return;
+        }
 
-    /// Origin point of the text box.
-    fn text_origin(&self) -> Position<f64> {
-        let padding = (PADDING * self.scale).round();
-        let bullet_padding = (BULLET_POINT_PADDING as f64 * self.scale).round();
-        Position::new(padding + bullet_padding, padding)
-    }
+        // Submit title changes when tapping outside the header.
+        self.finish_title_edit();
 
-    /// Size of the text box.
-    fn text_size(&self) -> Size {
-        let physical_size = self.size * self.scale;
-        let padding = (PADDING * self.scale).round() as u32;
-        let bullet_padding = (BULLET_POINT_PADDING as f64 * self.scale).round() as u32;
-        physical_size - Size::new(padding * 2 + bullet_padding, padding * 2)
-    }
-}
+        // Open tapped search results.
+        if let Some(search) = &self.search {
+            if let Some(result) = search.result_at(point) {
+                let (path, line) = (result.path.clone(), result.line);
+                self.open_note(path, line);
+            }
 
-/// Text input with enabled-state tracking.
-#[derive(Debug)]
-pub struct TextInput {
-    text_input: ZwpTextInputV3,
+            self.overlay_touch = true;
+            self.unstall();
+            return;
+        }
+
+        // Restore or purge tapped deleted notes.
+        if let Some(trash) = &mut self.trash {
+            if let Some((note, action)) = trash.take_at(point) {
+                self.apply_trash_action(note, action);
+            }
+
+            self.dirty = true;
+            self.overlay_touch = true;
+            self.unstall();
+            return;
+        }
+
+        // Handle spelling menu interactions, closing it when tapping elsewhere.
+        if let Some(spelling_menu) = self.spelling_menu.take() {
+            self.dirty = true;
+
+            if let Some(index) = spelling_menu.menu.entry_at(point) {
+                spelling_menu.activate(&mut self.text_box, index);
+                self.overlay_touch = true;
+                self.unstall();
+                return;
+            }
+        }
+
+        let text_position = physical_position - self.text_origin();
+
+        // Toggle collapsible sections when tapping their chevron in the padding.
+        if self.text_box.toggle_section_at(*text_position) {
+            self.overlay_touch = true;
+            self.unstall();
+            return;
+        }
+
+        // Clamp padding touch to nearest text box position.
+        let text_position = text_position.clamp(*self.text_size());
+        self.text_box.touch_down(config, time, text_position);
+
+        self.unstall();
+    }
+
+    /// Handle touch release.
+    pub fn touch_motion(&mut self, config: &Config, time: u32, position: Logical<Position<f64>>) {
+        // Ignore touch sequences consumed by overlays.
+        if self.overlay_touch {
+            return;
+        }
+
+        self.ime_cause = Some(ChangeCause::Other);
+
+        // Start a new item once the bottom edge swipe moved far enough.
+        if self.quick_add_start.is_some_and(|start| start - position.y >= QUICK_ADD_DISTANCE) {
+            self.quick_add_start = None;
+            self.text_box.touch_cancel();
+            self.overlay_touch = true;
+            self.quick_add();
+            return;
+        }
+
+        let physical_position = position.to_physical(self.scale);
+
+        // Clamp padding touch to nearest text box position.
+        let text_position = physical_position - self.text_origin();
+        let text_position = text_position.clamp(*self.text_size());
+        self.text_box.touch_motion(config, time, text_position);
+
+        self.unstall();
+    }
+
+    /// Handle touch release.
+    pub fn touch_up(&mut self, config: &Config, time: u32) {
+        // Ignore touch sequences consumed by overlays.
+        if mem::take(&mut self.overlay_touch) {
+            return;
+        }
+
+        self.ime_cause = Some(ChangeCause::Other);
+        self.text_box.touch_up(config, time);
+
+        // Offer spelling suggestions when tapping a misspelled word.
+        if let Some(range) = self.text_box.take_tapped_misspelling() {
+            self.open_spelling_menu(config, range);
+        }
+
+        self.unstall();
+    }
+
+    /// Start a two-finger pinch and scroll gesture.
+    ///
+    /// This cancels the active touch sequence of the primary touch point.
+    pub fn gesture_start(&mut self, positions: [Logical<Position<f64>>; 2]) {
+        self.quick_add_start = None;
+        self.text_box.touch_cancel();
+        self.gesture = Some(Gesture::new(positions, self.text_box.zoom()));
+    }
+
+    /// Handle touch motion of an active two-finger gesture.
+    pub fn gesture_motion(&mut self, positions: [Logical<Position<f64>>; 2]) {
+        let gesture = match &mut self.gesture {
+            Some(gesture) => gesture,
+            None => return,
+        };
+
+        // Zoom text relative to the initial finger distance.
+        let (center, distance) = Gesture::metrics(positions);
+        if gesture.distance > 0. {
+            self.text_box.set_zoom(gesture.zoom * distance / gesture.distance);
+        }
+
+        // Scroll text with the movement of the fingers' center.
+        let delta = (center.y - gesture.center.y) * self.scale;
+        gesture.center = center;
+        self.text_box.scroll_by(delta as f32);
+
+        self.unstall();
+    }
+
+    /// End the active two-finger gesture.
+    pub fn gesture_end(&mut self) {
+        self.gesture = None;
+    }
+
+    /// Start a new item at the end of the note and raise the on-screen keyboard.
+    pub fn quick_add(&mut self) {
+        self.text_box.start_item();
+
+        // Re-enable text input, since the keyboard might have been dismissed.
+        if let Some(text_input) = &mut self.text_input {
+            text_input.force_enable();
+        }
+        self.update_text_input();
+
+        self.unstall();
+    }
+
+    /// Handle secondary click.
+    pub fn secondary_click(&mut self, config: &Config, position: Logical<Position<f64>>) {
+        let text_position = position.to_physical(self.scale) - self.text_origin();
+
+        match self.text_box.misspelling_at(*text_position) {
+            Some(range) => self.open_spelling_menu(config, range),
+            None => self.dirty |= self.spelling_menu.take().is_some(),
+        }
+
+        self.unstall();
+    }
+
+    /// Open spelling suggestions for a misspelled word.
+    fn open_spelling_menu(&mut self, config: &Config, range: Range<usize>) {
+        let (word, anchor) =
+            match (self.text_box.text_at(range.clone()), self.text_box.range_rect(range.clone())) {
+                (Some(word), Some(anchor)) => (word.into_owned(), anchor),
+                _ => return,
+            };
+
+        let suggestions = self.text_box.spelling_suggestions(&word);
+        let mut entries: Vec<_> = suggestions.iter().map(String::as_str).collect();
+        entries.push(ADD_TO_DICTIONARY);
+
+        let colors = self.metadata.colors.apply(&self.config_colors);
+        let bounds = *self.size.to_physical(self.scale);
+        let menu = Menu::new(config, &colors, &entries, anchor, bounds, self.scale);
+
+        self.spelling_menu = Some(SpellingMenu { menu, range, word, suggestions });
+        self.dirty = true;
+    }
+
+    /// Get the cursor shape at the specified logical position.
+    pub fn cursor_shape(&self, position: Logical<Position<f64>>) -> Shape {
+        match self.resize_edge(position) {
+            Some(ResizeEdge::Top) => Shape::NResize,
+            Some(ResizeEdge::Bottom) => Shape::SResize,
+            Some(ResizeEdge::Left) => Shape::WResize,
+            Some(ResizeEdge::Right) => Shape::EResize,
+            Some(ResizeEdge::TopLeft) => Shape::NwResize,
+            Some(ResizeEdge::TopRight) => Shape::NeResize,
+            Some(ResizeEdge::BottomLeft) => Shape::SwResize,
+            Some(ResizeEdge::BottomRight) => Shape::SeResize,
+            _ if position.to_physical(self.scale).y < self.header.height() => Shape::Default,
+            _ => Shape::Text,
+        }
+    }
+
+    /// Get the window edge available for resizing at a position.
+    pub fn resize_edge(&self, position: Logical<Position<f64>>) -> Option<ResizeEdge> {
+        // Size is controlled by the compositor while fullscreen or maximized.
+        if self.fullscreen || self.maximized {
+            return None;
+        }
+
+        let top = position.y < RESIZE_BORDER;
+        let bottom = position.y >= self.size.height as f64 - RESIZE_BORDER;
+        let left = position.x < RESIZE_BORDER;
+        let right = position.x >= self.size.width as f64 - RESIZE_BORDER;
+
+        match (top, bottom, left, right) {
+            (true, _, true, _) => Some(ResizeEdge::TopLeft),
+            (true, _, _, true) => Some(ResizeEdge::TopRight),
+            (_, true, true, _) => Some(ResizeEdge::BottomLeft),
+            (_, true, _, true) => Some(ResizeEdge::BottomRight),
+            (true, ..) => Some(ResizeEdge::Top),
+            (_, true, ..) => Some(ResizeEdge::Bottom),
+            (_, _, true, _) => Some(ResizeEdge::Left),
+            (.., true) => Some(ResizeEdge::Right),
+            _ => None,
+        }
+    }
+
+    /// Check if a touch position is at the window's top edge.
+    pub fn is_top_edge(&self, position: Logical<Position<f64>>) -> bool {
+        position.y < TOUCH_MOVE_EDGE
+    }
+
+    /// Start an interactive window move.
+    pub fn start_move(&mut self, seat: &WlSeat, serial: u32) {
+        self.xdg_window.move_(seat, serial);
+
+        // Ignore the remaining input sequence.
+        self.overlay_touch = true;
+    }
+
+    /// Start an interactive window resize.
+    pub fn start_resize(&mut self, seat: &WlSeat, serial: u32, edge: ResizeEdge) {
+        self.xdg_window.resize(seat, serial, edge);
+
+        // Ignore the remaining input sequence.
+        self.overlay_touch = true;
+    }
+
+    /// Handle keyboard focus.
+    pub fn keyboard_enter(&mut self) {
+        self.text_box.set_keyboard_focus(true);
+        self.accessibility.set_focused(true);
+        self.unstall();
+    }
+
+    /// Handle keyboard focus loss.
+    pub fn keyboard_leave(&mut self) {
+        // Save changes, since losing focus often precedes locking or suspending.
+        self.flush();
+
+        self.text_box.set_keyboard_focus(false);
+        self.accessibility.set_focused(false);
+        self.unstall();
+    }
+
+    /// Handle keyboard key press.
+    pub fn press_key(
+        &mut self,
+        config: &Config,
+        _raw: u32,
+        keysym: Keysym,
+        text: Option<&str>,
+        modifiers: Modifiers,
+    ) {
+        self.ime_cause = Some(ChangeCause::Other);
+
+        // Handle title editing.
+        if self.header.editing() {
+            match keysym {
+                Keysym::Return => self.finish_title_edit(),
+                Keysym::Escape => _ = self.header.finish_editing(),
+                Keysym::BackSpace => self.header.pop_char(),
+                _ if !modifiers.ctrl => {
+                    if let Some(text) = text {
+                        self.header.push_str(text);
+                    }
+                },
+                _ => (),
+            }
+
+            self.dirty = true;
+            self.unstall();
+            return;
+        }
+
+        // Handle keyboard navigation of popup menus and header buttons.
+        if self.press_menu_key(config, keysym) || self.press_focus_key(config, keysym) {
+            self.dirty = true;
+            self.unstall();
+            return;
+        }
+
+        // Handle deleted notes list.
+        if let Some(trash) = &mut self.trash {
+            match keysym {
+                Keysym::Escape => self.trash = None,
+                Keysym::Up => trash.move_selection(-1),
+                Keysym::Down => trash.move_selection(1),
+                Keysym::Return | Keysym::Delete => {
+                    if let Some(note) = trash.take_selected() {
+                        let action = match keysym {
+                            Keysym::Return => TrashAction::Restore,
+                            _ => TrashAction::Purge,
+                        };
+                        self.apply_trash_action(note, action);
+                    }
+                },
+                _ => (),
+            }
+
+            self.dirty = true;
+            self.unstall();
+            return;
+        }
+
+        match (&mut self.search, keysym, modifiers.ctrl) {
+            (None, Keysym::f, true) => self.run_action(config, Action::Search),
+            (None, Keysym::i, true) => self.run_action(config, Action::ToggleStats),
+            (None, Keysym::s, true) => self.run_action(config, Action::Save),
+            (None, Keysym::plus | Keysym::equal | Keysym::KP_Add, true) => {
+                self.run_action(config, Action::ZoomIn);
+            },
+            (None, Keysym::minus | Keysym::KP_Subtract, true) => {
+                self.run_action(config, Action::ZoomOut);
+            },
+            (None, Keysym::_0 | Keysym::KP_0, true) => self.run_action(config, Action::ResetZoom),
+            (_, Keysym::F11, _) => self.run_action(config, Action::Fullscreen),
+            (_, Keysym::F12, _) => self.run_action(config, Action::ToggleProfiler),
+            (None, Keysym::Escape, false) if self.text_box.tag_filter().is_some() => {
+                self.run_action(config, Action::FilterTag { tag: None });
+            },
+            (None, ..) => self.text_box.press_key(keysym, text, modifiers),
+            (Some(search), keysym, ctrl) => {
+                match (keysym, ctrl) {
+                    (Keysym::Escape, _) | (Keysym::f, true) => self.search = None,
+                    (Keysym::Return, _) => {
+                        // Filter the note's items by tag queries, like `#groceries`.
+                        if let Some(tag) = search.tag_query().map(String::from) {
+                            self.search = None;
+                            self.set_tag_filter(Some(&tag));
+                        } else if let Some(result) = search.selected_result() {
+                            let (path, line) = (result.path.clone(), result.line);
+                            self.open_note(path, line);
+                        }
+                    },
+                    (Keysym::Up, _) => search.move_selection(-1),
+                    (Keysym::Down, _) => search.move_selection(1),
+                    (Keysym::BackSpace, _) => search.pop_char(),
+                    (_, false) => {
+                        if let Some(text) = text {
+                            search.push_str(text);
+                        }
+                    },
+                    _ => (),
+                }
+                self.dirty = true;
+            },
+        }
+
+        // Dismiss external change notice and popups on any input.
+        self.dirty |= self.header.set_notice(None);
+        self.dirty |= self.spelling_menu.take().is_some();
+        self.dirty |= self.overflow_menu.take().is_some();
+        self.dirty |= self.color_menu.take().is_some();
+
+        self.unstall();
+    }
+
+    /// Handle keyboard navigation of an open popup menu.
+    ///
+    /// Returns `true` if the key was consumed.
+    fn press_menu_key(&mut self, config: &Config, keysym: Keysym) -> bool {
+        let menu = match (&mut self.overflow_menu, &mut self.spelling_menu, &mut self.color_menu) {
+            (Some(menu), ..) => menu,
+            (None, Some(spelling_menu), _) => &mut spelling_menu.menu,
+            (None, None, Some(color_menu)) => &mut color_menu.menu,
+            (None, None, None) => return false,
+        };
+
+        match keysym {
+            Keysym::Up | Keysym::ISO_Left_Tab => menu.move_selection(-1),
+            Keysym::Down | Keysym::Tab => menu.move_selection(1),
+            Keysym::Return => {
+                if let Some(overflow_menu) = self.overflow_menu.take() {
+                    if let Some(index) = overflow_menu.selected() {
+                        self.activate_overflow_entry(config, index);
+                    }
+                } else if let Some(spelling_menu) = self.spelling_menu.take()
+                    && let Some(index) = spelling_menu.menu.selected()
+                {
+                    spelling_menu.activate(&mut self.text_box, index);
+                } else if let Some(color_menu) = self.color_menu.take()
+                    && let Some(index) = color_menu.menu.selected()
+                {
+                    color_menu.activate(&mut self.text_box, index);
+                }
+            },
+            Keysym::Escape => {
+                self.overflow_menu = None;
+                self.spelling_menu = None;
+                self.color_menu = None;
+            },
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Handle keyboard focus changes and activation of header buttons.
+    ///
+    /// Returns `true` if the key was consumed.
+    fn press_focus_key(&mut self, config: &Config, keysym: Keysym) -> bool {
+        match (keysym, self.header.focus()) {
+            (Keysym::Tab, _) => return self.cycle_focus(false),
+            (Keysym::ISO_Left_Tab, _) => return self.cycle_focus(true),
+            (Keysym::Return | Keysym::space, Some(action)) => {
+                self.header.set_focus(None);
+                self.activate_header_action(config, action);
+                self.select_first_menu_entry();
+            },
+            (Keysym::Escape, Some(_)) => _ = self.header.set_focus(None),
+            (Keysym::Menu, _) => self.open_context_menu(config),
+            // Return focus to the text box on any other input.
+            (_, Some(_)) => {
+                self.header.set_focus(None);
+                return false;
+            },
+            (_, None) => return false,
+        }
+
+        true
+    }
+
+    /// Move keyboard focus to the next or previous header button.
+    ///
+    /// The focus returns to the text box after the last button. Returns
+    /// `false` if the header is hidden.
+    fn cycle_focus(&mut self, reverse: bool) -> bool {
+        const ORDER: [Option<HeaderAction>; 4] = [
+            None,
+            Some(HeaderAction::EditTitle),
+            Some(HeaderAction::NewNote),
+            Some(HeaderAction::Overflow),
+        ];
+
+        if self.header.visible_title().is_none() {
+            return false;
+        }
+
+        let index = ORDER.iter().position(|focus| *focus == self.header.focus()).unwrap_or(0);
+        let delta = if reverse { ORDER.len() - 1 } else { 1 };
+        self.header.set_focus(ORDER[(index + delta) % ORDER.len()]);
+
+        true
+    }
+
+    /// Open the context menu for the cursor position.
+    ///
+    /// This shows spelling suggestions for misspelled words, falling back to
+    /// the overflow menu.
+    fn open_context_menu(&mut self, config: &Config) {
+        match self.text_box.misspelling_at_cursor() {
+            Some(range) => self.open_spelling_menu(config, range),
+            None => self.open_overflow_menu(config),
+        }
+        self.select_first_menu_entry();
+    }
+
+    /// Select the first entry of the open popup menu for keyboard navigation.
+    fn select_first_menu_entry(&mut self) {
+        let spelling_menu = self.spelling_menu.as_mut().map(|menu| &mut menu.menu);
+        if let Some(menu) = self.overflow_menu.as_mut().or(spelling_menu) {
+            menu.move_selection(1);
+        }
+    }
+
+    /// Replace the text with externally modified content.
+    pub fn apply_external_change(&mut self, text: String) {
+        self.text_box.apply_external_change(text);
+        self.dirty |= self.header.set_notice(Some(EXTERNAL_CHANGE_NOTICE));
+        self.unstall();
+    }
+
+    /// Restore unsaved changes of the note at `path` after a crash.
+    pub fn restore_recovered(&mut self, path: PathBuf, text: String) {
+        self.open_note(path, 0);
+        if self.text_box.text() != text {
+            self.text_box.restore_recovered(text);
+            self.dirty |= self.header.set_notice(Some(RECOVERY_NOTICE));
+            self.unstall();
+        }
+
+        // Keep the recovery file until the recovered text was saved.
+        if !self.text_box.unsaved() {
+            recovery::discard();
+        }
+    }
+
+    /// Replay edits journaled by a previous instance onto their note.
+    pub fn replay_journal(&mut self, replay: Replay) {
+        self.open_note(replay.path.clone(), 0);
+        match replay.apply(self.text_box.text()) {
+            Some(text) => self.restore_recovered(replay.path, text),
+            None => warn!("Ignoring edit journal, {:?} was modified since", replay.path),
+        }
+
+        // Keep the journal until the replayed edits were saved.
+        if !self.text_box.unsaved() {
+            Replay::discard();
+        }
+    }
+
+    /// Show a transient message on top of the window.
+    pub fn toast(&mut self, message: impl Into<String>) {
+        self.toasts.push(message.into());
+        self.unstall();
+    }
+
+    /// Redraw the window once the current toast starts fading out.
+    fn schedule_toast_fade(&self) {
+        let _ = self
+            .event_loop
+            .insert_source(Timer::from_duration(TOAST_DURATION), |_, _, state| {
+                state.window.dirty = true;
+                state.window.unstall();
+                TimeoutAction::Drop
+            })
+            .inspect_err(|err| error!("Failed to schedule toast fade: {err}"));
+    }
+
+    /// Paste text into the window.
+    pub fn paste(&mut self, text: &str) {
+        self.text_box.paste(text);
+        self.unstall();
+    }
+
+    /// Append every line of `text` as a new list item.
+    pub fn append_items(&mut self, text: &str) {
+        self.text_box.append_items(text);
+        self.unstall();
+    }
+
+    /// Append `text` as a single new list item.
+    pub fn append_item(&mut self, text: &str) {
+        self.text_box.append_item(text);
+        self.unstall();
+    }
+
+    /// Append the clipboard's lines as new list items.
+    pub fn paste_items(&self) {
+        self.text_box.request_paste(true);
+    }
+
+    /// Handle IME focus.
+    pub fn text_input_enter(&mut self, text_input: ZwpTextInputV3) {
+        self.text_input = Some(text_input.into());
+        self.text_box.set_ime_focus(true);
+        self.update_text_input();
+        self.unstall();
+    }
+
+    /// Handle IME focus loss.
+    pub fn text_input_leave(&mut self) {
+        self.text_box.set_ime_focus(false);
+        self.text_input = None;
+        self.unstall();
+    }
+
+    /// Delete text around the current cursor position.
+    pub fn delete_surrounding_text(&mut self, before_length: u32, after_length: u32) {
+        self.text_box.delete_surrounding_text(before_length, after_length);
+        self.unstall();
+    }
+
+    /// Insert text at the current cursor position.
+    pub fn commit_string(&mut self, text: String) {
+        match &mut self.search {
+            _ if self.header.editing() => {
+                self.header.push_str(&text);
+                self.dirty = true;
+            },
+            Some(search) => {
+                search.push_str(&text);
+                self.dirty = true;
+            },
+            None if self.trash.is_some() => (),
+            None => self.text_box.commit_string(&text),
+        }
+        self.unstall();
+    }
+
+    /// Open the search view for all notes.
+    fn open_search(&mut self, config: &Config) {
+        let storage = self.text_box.storage();
+        let index = SearchIndex::scan(storage, self.text_box.storage_path(), self.text_box.text());
+        let colors = self.metadata.colors.apply(&self.config_colors);
+        self.search = Some(SearchView::new(config, &colors, index));
+        self.spelling_menu = None;
+        self.trash = None;
+        self.dirty = true;
+    }
+
+    /// Open the list of recently deleted notes.
+    fn open_trash(&mut self, config: &Config) {
+        let storage = self.text_box.storage();
+        let storage_dir = self.text_box.storage_path().parent().unwrap();
+        storage::purge_expired(storage, storage_dir, config.general.trash_retention_days);
+        let notes = storage
+            .list_trash(storage_dir)
+            .inspect_err(|err| error!("Failed to list trashed notes: {err}"))
+            .unwrap_or_default();
+
+        let colors = self.metadata.colors.apply(&self.config_colors);
+        self.trash = Some(TrashView::new(config, &colors, notes));
+        self.spelling_menu = None;
+        self.search = None;
+        self.dirty = true;
+    }
+
+    /// Restore or permanently delete a note in the trash.
+    fn apply_trash_action(&mut self, note: TrashedNote, action: TrashAction) {
+        let storage = self.text_box.storage();
+        let storage_dir = self.text_box.storage_path().parent().unwrap().to_path_buf();
+
+        match action {
+            TrashAction::Restore => {
+                // Avoid replacing notes created with the same name after deletion.
+                let path = iter::once(storage_dir.join(&note.name))
+                    .chain((1..).map(|i| storage_dir.join(format!("{}-{i}", note.name))))
+                    .find(|path| !storage.exists(path))
+                    .unwrap();
+
+                if let Err(err) = storage.restore(&note, &path) {
+                    error!("Failed to restore note {:?}: {err}", note.name);
+                    self.toasts.push("Failed to restore note".into());
+                    return;
+                }
+
+                self.open_note(path.clone(), 0);
+                self.reminders.update(path, reminder::parse(self.text_box.text()));
+                self.toasts.push("Note restored".into());
+            },
+            TrashAction::Purge => {
+                if let Err(err) = storage.purge(&storage_dir, &note) {
+                    error!("Failed to purge note {:?}: {err}", note.name);
+                    self.toasts.push("Failed to purge note".into());
+                }
+            },
+        }
+    }
+
+    /// Open the header bar's overflow menu.
+    fn open_overflow_menu(&mut self, config: &Config) {
+        let colors = self.metadata.colors.apply(&self.config_colors);
+        let anchor = self.header.button_rect(HeaderAction::Overflow);
+        let bounds = *self.size.to_physical(self.scale);
+        let menu = Menu::new(config, &colors, &OVERFLOW_ENTRIES, anchor, bounds, self.scale);
+        self.overflow_menu = Some(menu);
+    }
+
+    /// Open the palette of accent colors for the selection or item at the cursor.
+    fn open_color_menu(&mut self, config: &Config) {
+        let colors = self.text_box.accent_names();
+        let mut entries: Vec<_> = colors.iter().map(String::as_str).collect();
+        entries.push(REMOVE_COLOR);
+
+        let theme = self.metadata.colors.apply(&self.config_colors);
+        let anchor = self.header.button_rect(HeaderAction::Overflow);
+        let bounds = *self.size.to_physical(self.scale);
+        let menu = Menu::new(config, &theme, &entries, anchor, bounds, self.scale);
+
+        self.color_menu = Some(ColorMenu { menu, colors });
+        self.dirty = true;
+    }
+
+    /// Apply the overflow menu entry at `index`.
+    fn activate_overflow_entry(&mut self, config: &Config, index: usize) {
+        match index {
+            0 => self.header.start_editing(),
+            1 => self.open_search(config),
+            2 => self.toggle_stats(),
+            3 => self.toggle_list_style(),
+            4 => self.open_color_menu(config),
+            5 => self.expand_date(),
+            6 => self.paste_items(),
+            7 => self.print(config, None),
+            8 => self.copy_image(),
+            9 => self.save_image(),
+            10 => self.delete_note(),
+            11 => self.open_trash(config),
+            _ => (),
+        }
+    }
+
+    /// Save the edited note title.
+    fn finish_title_edit(&mut self) {
+        let title = match self.header.finish_editing() {
+            Some(title) => title.trim().to_owned(),
+            None => return,
+        };
+        self.dirty = true;
+
+        if !title.is_empty() {
+            Metadata::save_title(self.text_box.storage(), self.text_box.storage_path(), &title);
+            self.header.set_title(title);
+        }
+    }
+
+    /// Run an action triggered by a key binding or IPC command.
+    pub fn run_action(&mut self, config: &Config, action: Action) {
+        match action {
+            Action::Archive => self.archive_completed(),
+            Action::Save => self.save(),
+            Action::Fullscreen => self.toggle_fullscreen(),
+            Action::Maximize => self.toggle_maximized(),
+            Action::PasteItems => self.paste_items(),
+            Action::Append { text } => self.append_item(&text),
+            Action::Print { path } => self.print(config, path.as_deref()),
+            Action::NewNote { name } => self.open_named_note(&name),
+            Action::FilterTag { tag } => self.set_tag_filter(tag.as_deref()),
+            Action::Search => self.open_search(config),
+            Action::ToggleStats => self.toggle_stats(),
+            Action::ZoomIn => self.zoom(ZOOM_STEP),
+            Action::ZoomOut => self.zoom(-ZOOM_STEP),
+            Action::ResetZoom => self.reset_zoom(),
+            Action::ToggleProfiler => self.toggle_profiler_overlay(),
+        }
+    }
+
+    /// Toggle text statistics in the header.
+    fn toggle_stats(&mut self) {
+        self.show_stats = !self.show_stats;
+        if !self.show_stats {
+            self.header.set_stats(None);
+        }
+        self.dirty = true;
+    }
+
+    /// Switch between bullets and numbers for the section at the cursor.
+    fn toggle_list_style(&mut self) {
+        if !self.text_box.toggle_list_style() {
+            self.toast("No section at the cursor");
+        }
+    }
+
+    /// Expand the natural-language date at the cursor.
+    fn expand_date(&mut self) {
+        if !self.text_box.expand_date() {
+            self.toast("No date at the cursor");
+        }
+    }
+
+    /// Toggle the frame time overlay.
+    fn toggle_profiler_overlay(&mut self) {
+        self.profiler.toggle_overlay();
+        self.dirty = true;
+    }
+
+    /// Enable recording of frame timings for the profiling summary.
+    pub fn enable_profiling(&mut self) {
+        self.profiler.enable();
+    }
+
+    /// Get the frame timing recorder.
+    pub fn profiler(&self) -> &Profiler {
+        &self.profiler
+    }
+
+    /// Create a new empty note and open it.
+    fn new_note(&mut self) {
+        let storage = self.text_box.storage();
+        let storage_dir = self.text_box.storage_path().parent().unwrap();
+        let path = (1..)
+            .map(|i| storage_dir.join(format!("note-{i}")))
+            .find(|path| !storage.exists(path))
+            .unwrap();
+
+        if let Err(err) = storage.write(&path, &mut iter::empty()) {
+            error!("Failed to create note at {path:?}: {err}");
+            return;
+        }
+
+        self.open_note(path, 0);
+    }
+
+    /// Show only items containing `tag`, or all items with `None`.
+    pub fn set_tag_filter(&mut self, tag: Option<&str>) {
+        if !self.text_box.set_tag_filter(tag) {
+            let tag = tag.map(editor::normalize_tag).unwrap_or_default();
+            self.toast(format!("No items tagged {TAG_MARKER}{tag}"));
+            return;
+        }
+
+        match self.text_box.tag_filter() {
+            Some(tag) => self.toast(format!("Showing items tagged {TAG_MARKER}{tag}")),
+            None => self.unstall(),
+        }
+    }
+
+    /// Open the note called `name`, creating it if it doesn't exist yet.
+    pub fn open_named_note(&mut self, name: &str) {
+        if name.is_empty() || name.starts_with('.') || name.contains('/') {
+            warn!("Ignoring invalid note name {name:?}");
+            return;
+        }
+
+        let storage = self.text_box.storage();
+        let path = self.text_box.storage_path().parent().unwrap().join(name);
+        if !storage.exists(&path)
+            && let Err(err) = storage.write(&path, &mut iter::empty())
+        {
+            error!("Failed to create note at {path:?}: {err}");
+            return;
+        }
+
+        self.open_note(path, 0);
+    }
+
+    /// Print the note, or export it as PDF document to `path`.
+    pub fn print(&mut self, config: &Config, path: Option<&Path>) {
+        let document = self.text_box.render_pdf(&config.print);
+
+        let path = match path {
+            Some(path) => path,
+            None => {
+                self.printer.print(document);
+                return;
+            },
+        };
+
+        match fs::write(path, document) {
+            Ok(()) => {
+                info!("Exported note to {path:?}");
+                self.toast(format!("Exported note to {}", path.display()));
+            },
+            Err(err) => {
+                error!("Failed to export note to {path:?}: {err}");
+                self.toast(format!("Failed to export note: {err}"));
+            },
+        }
+    }
+
+    /// Copy the selection or entire note to the clipboard as PNG image.
+    fn copy_image(&mut self) {
+        match self.text_box.render_png() {
+            Some(png) => {
+                self.text_box.copy(vec![("image/png", png)]);
+                self.toast("Copied note as image");
+            },
+            None => self.toast("Failed to render note as image"),
+        }
+    }
+
+    /// Save the selection or entire note to the pictures directory as PNG image.
+    fn save_image(&mut self) {
+        let png = match self.text_box.render_png() {
+            Some(png) => png,
+            None => {
+                self.toast("Failed to render note as image");
+                return;
+            },
+        };
+
+        let directory = dirs::picture_dir().or_else(dirs::home_dir).unwrap_or_default();
+        let file_name = Local::now().format("pinax-%Y%m%d-%H%M%S.png").to_string();
+        let path = directory.join(file_name);
+        match fs::write(&path, png) {
+            Ok(()) => {
+                info!("Saved note image to {path:?}");
+                self.toast(format!("Saved image to {}", path.display()));
+            },
+            Err(err) => {
+                error!("Failed to save note image to {path:?}: {err}");
+                self.toast(format!("Failed to save image: {err}"));
+            },
+        }
+    }
+
+    /// Move the current note to the trash and switch to another note.
+    fn delete_note(&mut self) {
+        let path = self.text_box.storage_path().to_path_buf();
+        let storage_dir = path.parent().unwrap();
+
+        // Switch notes first, which also writes pending changes of the deleted note.
+        let next = self
+            .text_box
+            .storage()
+            .list(storage_dir)
+            .inspect_err(|err| error!("Failed to list notes in {storage_dir:?}: {err}"))
+            .ok()
+            .and_then(|paths| paths.into_iter().find(|other| *other != path));
+        match next {
+            Some(next) => self.open_note(next, 0),
+            None => self.new_note(),
+        }
+
+        // Keep the note if no other note could be opened.
+        if self.text_box.storage_path() == path {
+            return;
+        }
+
+        if let Err(err) = self.text_box.storage().trash(&path) {
+            error!("Failed to move note {path:?} to trash: {err}");
+            self.toasts.push("Failed to delete note".into());
+            return;
+        }
+
+        self.reminders.update(path, Vec::new());
+        self.toasts.push("Note moved to trash".into());
+    }
+
+    /// Open a note and move the cursor to the start of `line`.
+    fn open_note(&mut self, path: PathBuf, line: usize) {
+        self.search = None;
+        self.trash = None;
+        self.dirty = true;
+
+        self.text_box.open_note(path, line);
+        self.reload_metadata();
+        self.apply_config();
+    }
+
+    /// Set preedit text at the current cursor position.
+    pub fn set_preedit_string(&mut self, text: String, cursor_begin: i32, cursor_end: i32) {
+        self.text_box.set_preedit_string(text, cursor_begin, cursor_end);
+        self.unstall();
+    }
+
+    /// Request keyboard focus for the window.
+    ///
+    /// The `token` is an XDG activation token, which is usually provided by
+    /// the application requesting the activation.
+    pub fn activate(&self, protocol_states: &ProtocolStates, token: String) {
+        match &protocol_states.activation {
+            Some(activation) => {
+                activation.activate::<State>(self.xdg_window.wl_surface(), token);
+                let _ = self.connection.flush();
+            },
+            None => warn!("Ignoring activation request: xdg_activation_v1 is not supported"),
+        }
+    }
+
+    /// Write unsaved text changes to disk.
+    pub fn save(&mut self) {
+        self.text_box.flush_text();
+        self.dirty = true;
+        self.unstall();
+    }
+
+    /// Immediately write all pending text and session changes to disk.
+    pub fn flush(&mut self) {
+        self.text_box.flush_text();
+        self.persist_session();
+    }
+
+    /// Change the text zoom factor by `delta`.
+    fn zoom(&mut self, delta: f64) {
+        self.text_box.set_zoom(self.text_box.zoom() + delta);
+        self.unstall();
+    }
+
+    /// Reset the text zoom to the configured font size.
+    fn reset_zoom(&mut self) {
+        self.text_box.set_zoom(1.);
+        self.unstall();
+    }
+
+    /// Update the desktop's text scaling factor.
+    pub fn set_text_scale(&mut self, text_scale: f64) {
+        self.text_box.set_text_scale(text_scale);
+        self.unstall();
+    }
+
+    /// Handle pointer scrolling.
+    pub fn scroll(&mut self, delta: f64, modifiers: Modifiers) {
+        // Zoom text with Ctrl+scroll.
+        if modifiers.ctrl && delta != 0. {
+            self.zoom(-delta.signum() * ZOOM_STEP);
+        }
+    }
+
+    /// Move all completed list items to the archive note.
+    pub fn archive_completed(&mut self) {
+        self.text_box.archive_completed();
+        self.unstall();
+    }
+
+    /// Immediately persist current session state to disk.
+    pub fn persist_session(&mut self) {
+        if let Some(token) = self.session_token.take() {
+            self.event_loop.remove(token);
+        }
+
+        self.session = self.current_session();
+        self.session.write();
+    }
+
+    /// Get the window's logical size.
+    pub fn size(&self) -> Logical<Size> {
+        self.size
+    }
+
+    /// Stage session state write.
+    ///
+    /// This is automatically debounced to avoid excessive write operations.
+    fn stage_session_write(&mut self) {
+        // Ignore if nothing changed since the last write.
+        let session = self.current_session();
+        if self.session == session {
+            return;
+        }
+        self.session = session;
+
+        // Clear pending timers.
+        if let Some(token) = self.session_token.take() {
+            self.event_loop.remove(token);
+        }
+
+        self.session_token = self
+            .event_loop
+            .insert_source(Timer::from_duration(SESSION_DEBOUNCE), |_, _, state| {
+                state.window.session_token = None;
+                state.window.session.write();
+                TimeoutAction::Drop
+            })
+            .inspect_err(|err| error!("Failed to register session write callback: {err}"))
+            .ok();
+    }
+
+    /// Activate a header bar button.
+    fn activate_header_action(&mut self, config: &Config, action: HeaderAction) {
+        match action {
+            HeaderAction::EditTitle if !self.header.editing() => self.header.start_editing(),
+            HeaderAction::EditTitle => (),
+            HeaderAction::NewNote => self.new_note(),
+            HeaderAction::Overflow => self.open_overflow_menu(config),
+        }
+
+        self.dirty = true;
+        self.unstall();
+    }
+
+    /// Publish changes to the accessibility tree.
+    fn update_accessibility(&mut self) {
+        let selection = self.text_box.selection_range();
+        let title = self.header.visible_title();
+        self.accessibility.update(self.text_box.text(), selection, title);
+    }
+
+    /// Submit the entire accessibility tree.
+    pub fn refresh_accessibility(&mut self) {
+        self.accessibility.invalidate();
+        self.update_accessibility();
+    }
+
+    /// Handle actions requested by assistive technologies.
+    pub fn accessibility_action(&mut self, config: &Config, request: ActionRequest) {
+        match self.accessibility.action(request) {
+            Some(AccessibleAction::Header(action)) => self.activate_header_action(config, action),
+            Some(AccessibleAction::Select(range)) => {
+                self.text_box.set_selection(range);
+                self.unstall();
+            },
+            None => (),
+        }
+    }
+
+    /// Stage window title update with the note's preview.
+    fn stage_title_update(&mut self) {
+        let title = window_title(self.text_box.text());
+        if self.title == title {
+            return;
+        }
+        self.title = title;
+
+        // Clear pending timers.
+        if let Some(token) = self.title_token.take() {
+            self.event_loop.remove(token);
+        }
+
+        self.title_token = self
+            .event_loop
+            .insert_source(Timer::from_duration(TITLE_DEBOUNCE), |_, _, state| {
+                state.window.title_token = None;
+                state.window.xdg_window.set_title(state.window.title.clone());
+                TimeoutAction::Drop
+            })
+            .inspect_err(|err| error!("Failed to register title update callback: {err}"))
+            .ok();
+    }
+
+    /// Get the current session state.
+    fn current_session(&self) -> Session {
+        let mut session = Session {
+            window_size: Some(self.size),
+            reminder_check: self.reminders.last_check(),
+            ..Default::default()
+        };
+        self.text_box.update_session(&mut session);
+        session
+    }
+
+    /// Apply pending text input changes.
+    fn update_text_input(&mut self) {
+        let origin = self.text_origin();
+
+        let text_input = match &mut self.text_input {
+            Some(text_input) => text_input,
+            None => return,
+        };
+
+        text_input.enable();
+
+        // Clear surrounding text when the selection exceeds the protocol limits.
+        let surrounding_text = self.text_box.surrounding_text();
+        let (text, cursor_start, cursor_end) = surrounding_text.unwrap_or_default();
+        text_input.set_surrounding_text(text, cursor_start, cursor_end);
+
+        let cause = self.ime_cause.take().unwrap_or(ChangeCause::InputMethod);
+        text_input.set_text_change_cause(cause);
+
+        // Update content type based on the line containing the cursor.
+        let (content_hint, content_purpose) = match self.text_box.cursor_line_kind() {
+            LineKind::Text => {
+                (content_hint(&self.content_hints) | ContentHint::Multiline, ContentPurpose::Normal)
+            },
+            LineKind::Url => (ContentHint::Multiline, ContentPurpose::Url),
+            LineKind::Number => (ContentHint::Multiline, ContentPurpose::Number),
+        };
+        text_input.set_content_type(content_hint, content_purpose);
+
+        // Update logical cursor rectangle.
+        if let Some(rect) = self.text_box.last_cursor_rect() {
+            let top_left = Physical(Position::new(rect.left as f64, rect.top as f64));
+            let bottom_right = Physical(Position::new(rect.right as f64, rect.bottom as f64));
+            let top_left = (origin + top_left).to_logical(self.scale);
+            let bottom_right = (origin + bottom_right).to_logical(self.scale);
+
+            // Round edges rather than size, to avoid accumulating rounding errors.
+            let (x, y) = (top_left.x.round() as i32, top_left.y.round() as i32);
+            let width = (bottom_right.x.round() as i32 - x).max(1);
+            let height = (bottom_right.y.round() as i32 - y).max(1);
+
+            text_input.set_cursor_rectangle(x, y, width, height);
+        }
+
+        text_input.commit();
+    }
+
+    /// Origin point of the text box.
+    fn text_origin(&self) -> Physical<Position<f64>> {
+        let padding = self.padding();
+        let bullet_padding = (self.layout.bullet_padding() * self.scale).round();
+
+        // Center text limited by the maximum text width.
+        let physical_width = self.size.to_physical(self.scale).width as f64;
+        let available_width = physical_width - padding * 2. - bullet_padding;
+        let centering = ((available_width - self.text_size().width as f64) / 2.).max(0.).round();
+
+        let x = padding + bullet_padding + centering;
+        Physical(Position::new(x, self.header.height() + padding))
+    }
+
+    /// Size of the text box.
+    fn text_size(&self) -> Physical<Size> {
+        let physical_size = self.size.to_physical(self.scale);
+        let padding = self.padding() as u32;
+        let bullet_padding = (self.layout.bullet_padding() * self.scale).round() as u32;
+        let header_height = self.header.height() as u32;
+
+        // Reserve space for the on-screen keyboard while IME is active.
+        let keyboard_height = match self.text_input {
+            Some(_) => (self.keyboard_height as f64 * self.scale).round() as u32,
+            None => 0,
+        };
+
+        let mut width = physical_size.width.saturating_sub(padding * 2 + bullet_padding);
+        if let Some(max_text_width) = self.max_text_width {
+            width = width.min((max_text_width as f64 * self.scale).round() as u32);
+        }
+
+        let height =
+            physical_size.height.saturating_sub(padding * 2 + header_height + keyboard_height);
+        Physical(Size::new(width, height))
+    }
+
+    /// Physical padding around the text box.
+    fn padding(&self) -> f64 {
+        let factor = if self.fullscreen { FULLSCREEN_PADDING_FACTOR } else { 1. };
+        (self.layout.padding * factor * self.scale).round()
+    }
+}
+
+/// Convert configured IME content hints to their protocol representation.
+fn content_hint(hints: &ContentHints) -> ContentHint {
+    hints.iter().fold(ContentHint::None, |hint, configured| {
+        hint | match configured {
+            config::ContentHint::Completion => ContentHint::Completion,
+            config::ContentHint::Spellcheck => ContentHint::Spellcheck,
+            config::ContentHint::AutoCapitalization => ContentHint::AutoCapitalization,
+            config::ContentHint::Lowercase => ContentHint::Lowercase,
+            config::ContentHint::Uppercase => ContentHint::Uppercase,
+            config::ContentHint::Titlecase => ContentHint::Titlecase,
+            config::ContentHint::Latin => ContentHint::Latin,
+        }
+    })
+}
+
+/// Get the window title for a note, based on its first list item's first line.
+///
+/// Long lines are truncated in the middle, keeping both their start and end.
+fn window_title(text: &str) -> String {
+    let line = editor::list_items(text)
+        .into_iter()
+        .next()
+        .and_then(|item| text[editor::item_content(text, item)].lines().next());
+    let line = match line.map(str::trim) {
+        Some(line) if !line.is_empty() => line,
+        _ => return DEFAULT_TITLE.into(),
+    };
+
+    let graphemes: Vec<_> = line.graphemes(true).collect();
+    if graphemes.len() <= MAX_TITLE_LENGTH {
+        return line.into();
+    }
+
+    let half = (MAX_TITLE_LENGTH - 1) / 2;
+    let start = graphemes[..half].concat();
+    let end = graphemes[graphemes.len() - half..].concat();
+    format!("{}…{}", start.trim_end(), end.trim_start())
+}
+
+/// Two-finger touch gesture.
+struct Gesture {
+    /// Logical center between both touch points.
+    center: Position<f64>,
+    /// Initial logical distance between both touch points.
+    distance: f64,
+    /// Text zoom at the start of the gesture.
+    zoom: f64,
+}
+
+impl Gesture {
+    fn new(positions: [Logical<Position<f64>>; 2], zoom: f64) -> Self {
+        let (center, distance) = Self::metrics(positions);
+        Self { center, distance, zoom }
+    }
+
+    /// Get the center and distance between two touch points.
+    fn metrics([start, end]: [Logical<Position<f64>>; 2]) -> (Position<f64>, f64) {
+        let center = (*start + *end) / 2.;
+        let distance = (start.x - end.x).hypot(start.y - end.y);
+        (center, distance)
+    }
+}
+
+/// Action triggered by a key binding or IPC command.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Action {
+    /// Move completed list items to the archive note.
+    Archive,
+    /// Write unsaved changes to disk.
+    Save,
+    /// Toggle the window's fullscreen state.
+    Fullscreen,
+    /// Toggle the window's maximized state.
+    Maximize,
+    /// Append the clipboard's lines as new list items.
+    PasteItems,
+    /// Append text as a new list item.
+    Append { text: String },
+    /// Print the note, or export it as PDF document to `path`.
+    Print { path: Option<PathBuf> },
+    /// Open the note called `name`, creating it if necessary.
+    NewNote { name: String },
+    /// Show only items containing `tag`, or all items without a tag.
+    FilterTag { tag: Option<String> },
+    /// Open the search across all notes.
+    Search,
+    /// Toggle text statistics in the header.
+    ToggleStats,
+    /// Increase the text zoom.
+    ZoomIn,
+    /// Decrease the text zoom.
+    ZoomOut,
+    /// Reset the text zoom.
+    ResetZoom,
+    /// Toggle the frame time overlay.
+    ToggleProfiler,
+}
+
+/// Element receiving keyboard input.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum KeyboardFocus {
+    /// Note title editing.
+    Title,
+    /// Overflow or spelling menu.
+    Menu,
+    /// Header bar buttons.
+    Header,
+    /// Deleted notes list.
+    Trash,
+    /// Note search.
+    Search,
+    /// Note content.
+    TextBox,
+}
+
+/// Spelling suggestions for a misspelled word.
+struct SpellingMenu {
+    menu: Menu,
+    range: Range<usize>,
+    word: String,
+    suggestions: Vec<String>,
+}
+
+impl SpellingMenu {
+    /// Apply the menu entry at `index`.
+    fn activate(self, text_box: &mut TextBox, index: usize) {
+        match self.suggestions.get(index) {
+            Some(suggestion) => text_box.replace_word(self.range, &self.word, suggestion),
+            None => text_box.add_to_dictionary(&self.word),
+        }
+    }
+}
+
+/// Palette of accent colors for text spans.
+struct ColorMenu {
+    menu: Menu,
+    colors: Vec<String>,
+}
+
+impl ColorMenu {
+    /// Apply the menu entry at `index`.
+    fn activate(self, text_box: &mut TextBox, index: usize) {
+        text_box.set_span_color(self.colors.get(index).map(String::as_str));
+    }
+}
+
+/// Queue of transient messages shown below the header.
+struct Toasts {
+    queue: VecDeque<String>,
+    shown: Option<Instant>,
+    paragraph: Option<Paragraph>,
+
+    font_collection: FontCollection,
+    font_family: FontFamily,
+    font_directory: Option<PathBuf>,
+    font_size: f64,
+
+    background: Color4f,
+    foreground: Color4f,
+
+    scale: f64,
+}
+
+impl Toasts {
+    fn new(config: &Config, colors: &Colors) -> Self {
+        Self {
+            font_collection: skia::font_collection(config.font.directory.as_deref()),
+            font_family: config.font.family.clone(),
+            font_directory: config.font.directory.clone(),
+            font_size: config.font.size,
+            background: colors.header.as_color4f(),
+            foreground: colors.foreground.as_color4f(),
+            scale: 1.,
+            paragraph: Default::default(),
+            queue: Default::default(),
+            shown: Default::default(),
+        }
+    }
+
+    /// Add a message to the end of the queue.
+    fn push(&mut self, message: String) {
+        self.queue.push_back(message);
+    }
+
+    /// Remove the current toast once it has faded out.
+    ///
+    /// Returns `true` if the toast's appearance changed.
+    fn update(&mut self, now: Instant) -> bool {
+        let Some(shown) = self.shown else { return false };
+
+        if now >= shown + TOAST_DURATION + TOAST_FADE {
+            self.queue.pop_front();
+            self.paragraph = None;
+            self.shown = None;
+        }
+
+        now >= shown + TOAST_DURATION
+    }
+
+    /// Start showing the next toast.
+    ///
+    /// Returns `false` if a toast is already shown or the queue is empty.
+    fn start(&mut self, now: Instant) -> bool {
+        if self.shown.is_some() || self.queue.is_empty() {
+            return false;
+        }
+
+        self.shown = Some(now);
+
+        true
+    }
+
+    /// Check if toasts require continuous redraws.
+    ///
+    /// This is the case while a toast is fading out, or waiting to be shown.
+    fn animating(&self, now: Instant) -> bool {
+        match self.shown {
+            Some(shown) => now >= shown + TOAST_DURATION,
+            None => !self.queue.is_empty(),
+        }
+    }
+
+    /// Render the current toast, centered horizontally below `top`.
+    fn draw(&mut self, canvas: &SkiaCanvas, width: u32, top: f32, now: Instant) {
+        let Some(shown) = self.shown else { return };
+        let Some(message) = self.queue.front() else { return };
+
+        let margin = (TOAST_MARGIN * self.scale).round() as f32;
+        let padding = (TOAST_PADDING * self.scale).round() as f32;
+        let max_width = (width as f32 - 2. * (margin + padding)).max(0.);
+
+        let paragraph = match self.paragraph.take() {
+            Some(paragraph) => paragraph,
+            None => self.build_paragraph(message),
+        };
+        let paragraph = self.paragraph.insert(paragraph);
+        paragraph.layout(max_width);
+
+        let text_width = paragraph.max_intrinsic_width().ceil().min(max_width);
+        let size = (text_width + 2. * padding, paragraph.height() + 2. * padding);
+        let x = ((width as f32 - size.0) / 2.).round();
+        let rect = Rect::from_xywh(x, top + margin, size.0, size.1);
+
+        // Fade out linearly once the toast has expired.
+        let fade = now.saturating_duration_since(shown + TOAST_DURATION);
+        let alpha = 1. - (fade.as_secs_f32() / TOAST_FADE.as_secs_f32()).min(1.);
+
+        let mut background = Paint::default();
+        background.set_color4f(self.background, None);
+
+        canvas.save_layer_alpha_f(rect, alpha);
+        canvas.draw_rect(rect, &background);
+        paragraph.paint(canvas, Point::new(rect.left + padding, rect.top + padding));
+        canvas.restore();
+    }
+
+    /// Create a paragraph with the toast's text style.
+    fn build_paragraph(&self, text: &str) -> Paragraph {
+        let mut paint = Paint::default();
+        paint.set_color4f(self.foreground, None);
+        paint.set_anti_alias(true);
+
+        let mut text_style = TextStyle::new();
+        text_style.set_foreground_paint(&paint);
+        text_style.set_font_size((self.font_size * self.scale) as f32);
+        text_style.set_font_families(&self.font_family);
+
+        let mut paragraph_style = ParagraphStyle::new();
+        paragraph_style.set_text_style(&text_style);
+        paragraph_style.set_max_lines(2);
+        paragraph_style.set_ellipsis("…");
+
+        let mut builder = ParagraphBuilder::new(&paragraph_style, &self.font_collection);
+        builder.add_text(text);
+        builder.build()
+    }
+
+    /// Update the toast's color palette.
+    ///
+    /// Returns `true` if a redraw is required.
+    fn set_colors(&mut self, colors: &Colors) -> bool {
+        let background = colors.header.as_color4f();
+        let foreground = colors.foreground.as_color4f();
+        if self.background == background && self.foreground == foreground {
+            return false;
+        }
+
+        self.background = background;
+        self.foreground = foreground;
+        self.paragraph = None;
+
+        self.shown.is_some()
+    }
+
+    /// Set the toast's font scale.
+    fn set_scale_factor(&mut self, scale: f64) {
+        if self.scale != scale {
+            self.scale = scale;
+            self.paragraph = None;
+        }
+    }
+
+    /// Handle config updates.
+    ///
+    /// Returns `true` if a redraw is required.
+    fn update_config(&mut self, config: &Config) -> bool {
+        if self.font_size == config.font.size
+            && self.font_family == config.font.family
+            && self.font_directory == config.font.directory
+        {
+            return false;
+        }
+
+        if self.font_directory != config.font.directory {
+            self.font_collection = skia::font_collection(config.font.directory.as_deref());
+            self.font_directory = config.font.directory.clone();
+        }
+
+        self.font_family = config.font.family.clone();
+        self.font_size = config.font.size;
+        self.paragraph = None;
+
+        self.shown.is_some()
+    }
+}
+
+/// Frame timing based on presentation feedback.
+#[derive(Default, Debug)]
+struct FrameClock {
+    last_presentation: Option<Duration>,
+    interval: Option<Duration>,
+}
+
+impl FrameClock {
+    /// Frame interval used without presentation feedback.
+    const DEFAULT_INTERVAL: Duration = Duration::from_nanos(16_666_667);
+    /// Maximum interval between presentations considered continuous animation.
+    const MAX_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Update timing with a presented frame.
+    ///
+    /// The `refresh` duration is zero if the output's refresh rate is unknown.
+    fn presented(&mut self, time: Duration, refresh: Duration) {
+        // Prefer measured intervals, since frames might not be presented every refresh.
+        let measured = self
+            .last_presentation
+            .replace(time)
+            .and_then(|last| time.checked_sub(last))
+            .filter(|interval| !interval.is_zero() && *interval <= Self::MAX_INTERVAL);
+
+        self.interval = measured.or((!refresh.is_zero()).then_some(refresh));
+    }
+
+    /// Expected time between two presented frames.
+    fn interval(&self) -> Duration {
+        self.interval.unwrap_or(Self::DEFAULT_INTERVAL)
+    }
+}
+
+/// Text input with enabled-state tracking.
+#[derive(Debug)]
+pub struct TextInput {
+    text_input: ZwpTextInputV3,
     enabled: bool,
 }
 
@@ -413,6 +2543,14 @@ impl TextInput {
         self.text_input.enable();
     }
 
+    /// Enable text input, even if it is already enabled.
+    ///
+    /// This prompts the input method to show its on-screen keyboard again.
+    pub fn force_enable(&mut self) {
+        self.enabled = true;
+        self.text_input.enable();
+    }
+
     /// Set the surrounding text.
     pub fn set_surrounding_text(&self, text: String, cursor_index: i32, selection_anchor: i32) {
         self.text_input.set_surrounding_text(text, cursor_index, selection_anchor);
@@ -0,0 +1,479 @@
+//! Integration tests against a headless Wayland compositor.
+//!
+//! Every test starts its own headless sway instance and drives Pinax through
+//! the virtual keyboard protocol using `wtype`, or as input method through the
+//! input method protocol. Since both sway and `wtype` must be installed, these
+//! tests are ignored by default and can be run with `cargo test -- --ignored`.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use _input_method::zwp_input_method_manager_v2::ZwpInputMethodManagerV2;
+use _input_method::zwp_input_method_v2::{self, ZwpInputMethodV2};
+use _text_input::zwp_text_input_v3::ContentPurpose;
+use smithay_client_toolkit::reexports::client::globals::{GlobalListContents, registry_queue_init};
+use smithay_client_toolkit::reexports::client::protocol::wl_registry::{self, WlRegistry};
+use smithay_client_toolkit::reexports::client::protocol::wl_seat::WlSeat;
+use smithay_client_toolkit::reexports::client::{
+    Connection, Dispatch, EventQueue, QueueHandle, WEnum, delegate_noop,
+};
+use smithay_client_toolkit::reexports::protocols::wp::text_input::zv3::client as _text_input;
+use tempfile::TempDir;
+use wayland_protocols_misc::zwp_input_method_v2::client as _input_method;
+
+/// Maximum time to wait for an expected state.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval between checks for an expected state.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Time between startup and the window receiving keyboard focus.
+const FOCUS_DELAY: Duration = Duration::from_millis(500);
+
+/// Minimal sway configuration for a phone-sized output.
+const SWAY_CONFIG: &str = "output * resolution 720x1440\ndefault_border none\n";
+
+/// Pinax configuration persisting every edit immediately.
+const PINAX_CONFIG: &str = "[general]\nsave_mode = \"immediate\"\nrenderer = \"software\"\n";
+
+#[test]
+#[ignore = "requires sway and wtype"]
+fn typed_text_is_persisted() {
+    let pinax = Pinax::start();
+
+    pinax.wtype(["hello world"]);
+
+    pinax.wait_for_note("hello world");
+}
+
+#[test]
+#[ignore = "requires sway and wtype"]
+fn newline_on_empty_line_starts_item() {
+    let pinax = Pinax::start();
+
+    pinax.wtype(["one", "-k", "Return", "-k", "Return", "two"]);
+
+    pinax.wait_for_note("one\n\ntwo");
+}
+
+#[test]
+#[ignore = "requires sway and wtype"]
+fn backspace_removes_grapheme() {
+    let pinax = Pinax::start();
+
+    pinax.wtype(["cafe\u{301}", "-k", "BackSpace", "s"]);
+
+    pinax.wait_for_note("cafs");
+}
+
+#[test]
+#[ignore = "requires sway and wtype"]
+fn append_adds_list_item() {
+    let pinax = Pinax::start();
+
+    pinax.wtype(["first"]);
+    pinax.wait_for_note("first");
+
+    let output = pinax.command(["append", "second"]);
+    assert!(output.status.success(), "append failed: {output:?}");
+
+    pinax.wait_for_note("first\n\nsecond");
+}
+
+#[test]
+#[ignore = "requires sway and wtype"]
+fn ipc_opens_named_note() {
+    let pinax = Pinax::start();
+
+    let output = pinax.command(["ipc", "action", "new-note", "groceries"]);
+    assert!(output.status.success(), "new-note failed: {output:?}");
+
+    let path = pinax.data_dir().join("pinax/groceries");
+    wait_for(|| path.exists(), "named note creation");
+
+    pinax.wtype(["milk"]);
+    wait_for(|| fs::read_to_string(&path).is_ok_and(|text| text == "milk"), "named note text");
+}
+
+#[test]
+#[ignore = "requires sway and wtype"]
+fn ipc_reports_effective_config() {
+    let pinax = Pinax::start();
+
+    let output = pinax.command(["ipc", "get", "general.save_mode"]);
+    assert!(output.status.success(), "get failed: {output:?}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "\"immediate\"");
+
+    let output = pinax.command(["ipc", "get", "general.missing"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+#[ignore = "requires sway and wtype"]
+fn input_method_commits_text() {
+    let pinax = Pinax::start();
+    let mut input_method = InputMethod::connect(&pinax.compositor);
+    input_method.wait_for(|state| state.active, "input method activation");
+
+    input_method.commit_string("hello");
+    pinax.wait_for_note("hello");
+    input_method.wait_for(
+        |state| state.surrounding_text == Some((String::from("hello"), 5, 5)),
+        "surrounding text update",
+    );
+
+    input_method.delete_surrounding_text(2, 0);
+    input_method.commit_string("p");
+    pinax.wait_for_note("help");
+}
+
+#[test]
+#[ignore = "requires sway and wtype"]
+fn input_method_preedit_is_replaced() {
+    let pinax = Pinax::start();
+    let mut input_method = InputMethod::connect(&pinax.compositor);
+    input_method.wait_for(|state| state.active, "input method activation");
+
+    input_method.set_preedit_string("wor");
+    input_method.commit_string("world");
+
+    pinax.wait_for_note("world");
+}
+
+#[test]
+#[ignore = "requires sway and wtype"]
+fn input_method_receives_url_content_type() {
+    let pinax = Pinax::start();
+    let mut input_method = InputMethod::connect(&pinax.compositor);
+    input_method.wait_for(|state| state.active, "input method activation");
+
+    input_method.commit_string("https://example.org");
+
+    pinax.wait_for_note("https://example.org");
+    input_method.wait_for(
+        |state| state.purpose == Some(WEnum::Value(ContentPurpose::Url)),
+        "URL content purpose",
+    );
+}
+
+/// Pinax instance running inside a headless compositor.
+struct Pinax {
+    compositor: Compositor,
+    process: Child,
+    home: TempDir,
+}
+
+impl Pinax {
+    /// Start Pinax in a new headless compositor.
+    fn start() -> Self {
+        assert!(installed("wtype"), "wtype is not installed");
+        let compositor = Compositor::start();
+
+        let home = tempfile::tempdir().expect("create home directory");
+        let config_dir = home.path().join("config/pinax");
+        fs::create_dir_all(&config_dir).expect("create config directory");
+        fs::write(config_dir.join("pinax.toml"), PINAX_CONFIG).expect("write config");
+
+        let pinax =
+            Self { process: compositor.spawn(pinax_command(home.path())), compositor, home };
+
+        // Wait for the IPC socket to accept connections.
+        wait_for(|| pinax.command(["ipc", "get", "general"]).status.success(), "Pinax startup");
+
+        // Give the compositor time to map and focus the window.
+        thread::sleep(FOCUS_DELAY);
+
+        pinax
+    }
+
+    /// Run a Pinax subcommand against this instance.
+    fn command<I, S>(&self, args: I) -> Output
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = pinax_command(self.home.path());
+        command.args(args);
+        self.compositor.output(command)
+    }
+
+    /// Simulate keyboard input with `wtype`.
+    fn wtype<I, S>(&self, args: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let mut command = Command::new("wtype");
+        command.args(args);
+        let output = self.compositor.output(command);
+        assert!(output.status.success(), "wtype failed: {output:?}");
+    }
+
+    /// Wait for the default note to contain `expected`.
+    fn wait_for_note(&self, expected: &str) {
+        let path = self.data_dir().join("pinax/notes");
+        let start = Instant::now();
+        loop {
+            let text = fs::read_to_string(&path).unwrap_or_default();
+            if text == expected {
+                return;
+            }
+
+            assert!(start.elapsed() < TIMEOUT, "expected note {expected:?}, found {text:?}");
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Location of the notes' data directory.
+    fn data_dir(&self) -> PathBuf {
+        self.home.path().join("data")
+    }
+}
+
+impl Drop for Pinax {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// Headless sway instance.
+struct Compositor {
+    process: Child,
+    runtime_dir: TempDir,
+    display: String,
+}
+
+impl Compositor {
+    /// Start a headless compositor.
+    fn start() -> Self {
+        assert!(installed("sway"), "sway is not installed");
+
+        let runtime_dir = tempfile::tempdir().expect("create runtime directory");
+        let config = runtime_dir.path().join("sway.conf");
+        fs::write(&config, SWAY_CONFIG).expect("write sway config");
+
+        let process = Command::new("sway")
+            .arg("--config")
+            .arg(&config)
+            .env("XDG_RUNTIME_DIR", runtime_dir.path())
+            .env("WLR_BACKENDS", "headless")
+            .env("WLR_RENDERER", "pixman")
+            .env("WLR_LIBINPUT_NO_DEVICES", "1")
+            .env_remove("WAYLAND_DISPLAY")
+            .env_remove("DISPLAY")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("spawn sway");
+
+        let mut compositor = Self { process, runtime_dir, display: String::new() };
+
+        // Wait for the compositor to create its Wayland socket.
+        let start = Instant::now();
+        compositor.display = loop {
+            if let Some(display) = wayland_socket(compositor.runtime_dir.path()) {
+                break display;
+            }
+
+            if let Ok(Some(status)) = compositor.process.try_wait() {
+                panic!("sway exited unexpectedly: {status}");
+            }
+            assert!(start.elapsed() < TIMEOUT, "timeout waiting for sway startup");
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        compositor
+    }
+
+    /// Spawn a Wayland client connected to this compositor.
+    fn spawn(&self, mut command: Command) -> Child {
+        self.client_env(&mut command);
+        command.stdout(Stdio::null()).spawn().expect("spawn Wayland client")
+    }
+
+    /// Run a Wayland client connected to this compositor to completion.
+    fn output(&self, mut command: Command) -> Output {
+        self.client_env(&mut command);
+        command.output().expect("run Wayland client")
+    }
+
+    /// Connect a client to this compositor.
+    fn client_env(&self, command: &mut Command) {
+        command
+            .env("XDG_RUNTIME_DIR", self.runtime_dir.path())
+            .env("WAYLAND_DISPLAY", &self.display);
+    }
+}
+
+impl Drop for Compositor {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
+/// Input method connected to a headless compositor.
+struct InputMethod {
+    input_method: ZwpInputMethodV2,
+    queue: EventQueue<InputMethodState>,
+    state: InputMethodState,
+}
+
+impl InputMethod {
+    /// Connect a new input method to the compositor's seat.
+    fn connect(compositor: &Compositor) -> Self {
+        let socket = compositor.runtime_dir.path().join(&compositor.display);
+        let stream = UnixStream::connect(socket).expect("connect to compositor");
+        let connection = Connection::from_socket(stream).expect("create Wayland connection");
+
+        let (globals, queue) = registry_queue_init(&connection).expect("get Wayland globals");
+        let queue_handle = queue.handle();
+        let seat: WlSeat = globals.bind(&queue_handle, 1..=1, ()).expect("bind wl_seat");
+        let manager: ZwpInputMethodManagerV2 =
+            globals.bind(&queue_handle, 1..=1, ()).expect("bind zwp_input_method_manager_v2");
+        let input_method = manager.get_input_method(&seat, &queue_handle, ());
+
+        Self { input_method, queue, state: Default::default() }
+    }
+
+    /// Wait for the text input state to satisfy `condition`.
+    fn wait_for(&mut self, condition: impl Fn(&TextInputState) -> bool, description: &str) {
+        let start = Instant::now();
+        loop {
+            self.queue.roundtrip(&mut self.state).expect("dispatch Wayland events");
+            if condition(&self.state.current) {
+                return;
+            }
+
+            assert!(start.elapsed() < TIMEOUT, "timeout waiting for {description}");
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Insert text at the cursor position.
+    fn commit_string(&mut self, text: &str) {
+        self.input_method.commit_string(text.into());
+        self.commit();
+    }
+
+    /// Show uncommitted text at the cursor position.
+    fn set_preedit_string(&mut self, text: &str) {
+        let cursor = text.len() as i32;
+        self.input_method.set_preedit_string(text.into(), cursor, cursor);
+        self.commit();
+    }
+
+    /// Delete text around the cursor position on the next commit.
+    fn delete_surrounding_text(&mut self, before_length: u32, after_length: u32) {
+        self.input_method.delete_surrounding_text(before_length, after_length);
+    }
+
+    /// Apply all pending changes.
+    fn commit(&mut self) {
+        self.input_method.commit(self.state.serial);
+        self.queue.roundtrip(&mut self.state).expect("dispatch Wayland events");
+    }
+}
+
+/// Input method protocol state.
+#[derive(Default)]
+struct InputMethodState {
+    pending: TextInputState,
+    current: TextInputState,
+    /// Number of received `done` events.
+    serial: u32,
+}
+
+/// State of the focused text input, as seen by the input method.
+#[derive(Default, Clone)]
+struct TextInputState {
+    active: bool,
+    surrounding_text: Option<(String, u32, u32)>,
+    purpose: Option<WEnum<ContentPurpose>>,
+}
+
+impl Dispatch<ZwpInputMethodV2, ()> for InputMethodState {
+    fn event(
+        state: &mut Self,
+        _input_method: &ZwpInputMethodV2,
+        event: zwp_input_method_v2::Event,
+        _data: &(),
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_input_method_v2::Event::Activate => {
+                state.pending = TextInputState { active: true, ..Default::default() };
+            },
+            zwp_input_method_v2::Event::Deactivate => state.pending.active = false,
+            zwp_input_method_v2::Event::SurroundingText { text, cursor, anchor } => {
+                state.pending.surrounding_text = Some((text, cursor, anchor));
+            },
+            zwp_input_method_v2::Event::ContentType { purpose, .. } => {
+                state.pending.purpose = Some(purpose);
+            },
+            zwp_input_method_v2::Event::Done => {
+                state.current = state.pending.clone();
+                state.serial += 1;
+            },
+            zwp_input_method_v2::Event::Unavailable => panic!("input method unavailable"),
+            _ => (),
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for InputMethodState {
+    fn event(
+        _state: &mut Self,
+        _registry: &WlRegistry,
+        _event: wl_registry::Event,
+        _data: &GlobalListContents,
+        _connection: &Connection,
+        _queue: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+delegate_noop!(InputMethodState: ignore WlSeat);
+delegate_noop!(InputMethodState: ZwpInputMethodManagerV2);
+
+/// Create a Pinax command using `home` for all its configuration and data.
+fn pinax_command(home: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_pinax"));
+    command
+        .env("HOME", home)
+        .env("XDG_CONFIG_HOME", home.join("config"))
+        .env("XDG_DATA_HOME", home.join("data"))
+        .env("XDG_CACHE_HOME", home.join("cache"))
+        .env("XDG_STATE_HOME", home.join("state"))
+        .env_remove("XDG_ACTIVATION_TOKEN");
+    command
+}
+
+/// Find the name of the Wayland socket in `runtime_dir`.
+fn wayland_socket(runtime_dir: &Path) -> Option<String> {
+    fs::read_dir(runtime_dir).ok()?.flatten().find_map(|entry| {
+        let name = entry.file_name().into_string().ok()?;
+        (name.starts_with("wayland-") && !name.ends_with(".lock")).then_some(name)
+    })
+}
+
+/// Check if an executable is available.
+fn installed(program: &str) -> bool {
+    Command::new(program).arg("--help").stdout(Stdio::null()).stderr(Stdio::null()).status().is_ok()
+}
+
+/// Wait until `condition` is met, panicking after a timeout.
+fn wait_for(mut condition: impl FnMut() -> bool, description: &str) {
+    let start = Instant::now();
+    while !condition() {
+        assert!(start.elapsed() < TIMEOUT, "timeout waiting for {description}");
+        thread::sleep(POLL_INTERVAL);
+    }
+}